@@ -0,0 +1,305 @@
+//! Devnet/localnet test scaffolding, compiled only with the `devnet` feature
+//!
+//! Mainnet builds leave this module and its instructions out entirely
+
+use super::*;
+use anchor_lang::solana_program::program_option::COption;
+
+/// Maximum amount minted per faucet call (1,000,000 tokens at 6 decimals)
+pub const FAUCET_MAX_AMOUNT: u64 = 1_000_000_000_000;
+
+/// Mint test tokens to any token account
+/// The mint's authority must be the instance's faucet PDA
+pub fn process_faucet_mint(ctx: Context<FaucetMint>, amount: u64) -> Result<()> {
+    require!(
+        amount > 0 && amount <= FAUCET_MAX_AMOUNT,
+        ShadeError::InvalidAmount
+    );
+
+    let config_key = ctx.accounts.protocol_config.key();
+    let seeds = &[b"faucet".as_ref(), config_key.as_ref(), &[ctx.bumps.faucet]];
+    let signer_seeds = &[&seeds[..]];
+
+    let mint_ctx = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        MintTo {
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.destination.to_account_info(),
+            authority: ctx.accounts.faucet.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token_interface::mint_to(mint_ctx, amount)?;
+
+    emit_event!(
+        ctx.accounts,
+        FaucetMinted {
+            mint: ctx.accounts.mint.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+        }
+    );
+
+    Ok(())
+}
+
+/// Move an authorization's expiry to any timestamp, including the past
+/// Lets integrators exercise expiry paths without waiting (issuer only)
+pub fn process_set_authorization_expiry(
+    ctx: Context<SetAuthorizationExpiry>,
+    expires_at: i64,
+) -> Result<()> {
+    let authorization = &mut ctx.accounts.authorization;
+    authorization.expires_at = expires_at;
+
+    emit_event!(
+        ctx.accounts,
+        AuthorizationExpirySet {
+            authorization: authorization.key(),
+            expires_at,
+        }
+    );
+
+    Ok(())
+}
+
+/// Create a fog pool whose vault is pre-funded from the faucet
+/// The seeded funds are credited to the caller as LP share tokens, less the
+/// locked minimum liquidity
+pub fn process_seed_demo_pool(
+    ctx: Context<SeedDemoPool>,
+    pool_seed: [u8; 32],
+    amount: u64,
+) -> Result<()> {
+    require!(amount <= FAUCET_MAX_AMOUNT, ShadeError::InvalidAmount);
+
+    let fog_pool = &mut ctx.accounts.fog_pool;
+    fog_pool.set_inner(FogPool {
+        authority: ctx.accounts.authority.key(),
+        vault: ctx.accounts.vault.key(),
+        total_deposited: amount,
+        pool_seed,
+        bump: ctx.bumps.fog_pool,
+        lp_mint: ctx.accounts.lp_mint.key(),
+        mint: ctx.accounts.mint.key(),
+        vault_authority_bump: ctx.bumps.pool_vault_authority,
+        ..FogPool::new(&ctx.accounts.protocol_config)
+    });
+
+    let position = &mut ctx.accounts.lp_position;
+    position.owner = ctx.accounts.authority.key();
+    position.fog_pool = fog_pool.key();
+    position.total_deposited = amount;
+    position.total_withdrawn = 0;
+    position.bump = ctx.bumps.lp_position;
+
+    if amount > 0 {
+        // Priced like a first deposit, so the locked minimum applies
+        let (shares, locked) = lp_shares_for_deposit(amount, 0, 0)?;
+        fog_pool.locked_shares = locked;
+
+        let config_key = ctx.accounts.protocol_config.key();
+        let seeds = &[b"faucet".as_ref(), config_key.as_ref(), &[ctx.bumps.faucet]];
+        let signer_seeds = &[&seeds[..]];
+
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.faucet.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::mint_to(mint_ctx, amount)?;
+
+        let pool_key = fog_pool.key();
+        let pool_seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[fog_pool.vault_authority_bump],
+        ];
+        let pool_signer = &[&pool_seeds[..]];
+
+        let lp_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.lp_mint.to_account_info(),
+                to: ctx.accounts.authority_lp_account.to_account_info(),
+                authority: ctx.accounts.pool_vault_authority.to_account_info(),
+            },
+            pool_signer,
+        );
+        token_interface::mint_to(lp_ctx, shares)?;
+    }
+
+    let registry_index = register_pool(
+        &mut ctx.accounts.protocol_config,
+        &mut ctx.accounts.pool_registry_page,
+        ctx.bumps.pool_registry_page,
+        fog_pool.key(),
+    )?;
+
+    let sequence = fog_pool.next_event_sequence()?;
+    emit_event!(
+        ctx.accounts,
+        FogPoolCreated {
+            pool: fog_pool.key(),
+            sequence,
+            authority: fog_pool.authority,
+            vault: fog_pool.vault,
+            registry_index,
+        }
+    );
+
+    Ok(())
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct FaucetMint<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Faucet PDA, the mint authority of test mints
+    #[account(seeds = [b"faucet", protocol_config.key().as_ref()], bump)]
+    pub faucet: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = mint.mint_authority == COption::Some(faucet.key()) @ ShadeError::Unauthorized
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == mint.key() @ ShadeError::InvalidMint
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetAuthorizationExpiry<'info> {
+    #[account(
+        mut,
+        has_one = issuer @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(pool_seed: [u8; 32])]
+pub struct SeedDemoPool<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = FogPool::LEN,
+        seeds = [b"fog_pool", pool_seed.as_ref()],
+        bump
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Pool registry page with room for this pool
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = PoolRegistryPage::LEN,
+        seeds = [
+            b"pool_registry",
+            protocol_config.key().as_ref(),
+            &protocol_config.next_pool_page().to_le_bytes()
+        ],
+        bump
+    )]
+    pub pool_registry_page: Account<'info, PoolRegistryPage>,
+
+    /// CHECK: Faucet PDA, the mint authority of test mints
+    #[account(seeds = [b"faucet", protocol_config.key().as_ref()], bump)]
+    pub faucet: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = mint.mint_authority == COption::Some(faucet.key()) @ ShadeError::Unauthorized
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA that owns the demo vault and LP mint; holds no data
+    #[account(seeds = [b"pool_vault_authority", fog_pool.key().as_ref()], bump)]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    /// Vault token account - the pool vault authority's associated token account
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = pool_vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"lp_mint", fog_pool.key().as_ref()],
+        bump,
+        mint::decimals = mint.decimals,
+        mint::authority = pool_vault_authority,
+        mint::token_program = token_program
+    )]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    /// Caller's LP token account for the seeded shares
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = lp_mint,
+        associated_token::authority = authority,
+        associated_token::token_program = token_program
+    )]
+    pub authority_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = LpPosition::LEN,
+        seeds = [b"lp_position", fog_pool.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event]
+pub struct FaucetMinted {
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AuthorizationExpirySet {
+    pub authorization: Pubkey,
+    pub expires_at: i64,
+}
@@ -1,11 +1,161 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::memo::{self, BuildMemo, Memo};
+use anchor_spl::token_interface::spl_token_2022::instruction::AuthorityType;
+use anchor_spl::token_interface::{
+    self, Burn, CloseAccount, Mint, MintTo, SetAuthority, TokenAccount, TokenInterface,
+    TransferChecked,
+};
+use solana_instructions_sysvar as instructions_sysvar;
+use solana_sdk_ids::{ed25519_program, sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID};
+use solana_sha256_hasher::hashv;
 
 declare_id!("FgQsc4FZUvZFvBWiNstP9Rf5vRjGX7pcr9gB89QZq3hj");
 
+/// Emit an event from an instruction's accounts
+/// With the `event-cpi` feature the event is a self-CPI through the
+/// `event_authority` PDA, so it survives log truncation under heavy CPI use;
+/// otherwise it is logged
+macro_rules! emit_event {
+    ($accounts:expr, $event:expr) => {
+        #[cfg(feature = "event-cpi")]
+        emit_cpi_event(&$accounts.event_authority, &$event)?;
+        #[cfg(not(feature = "event-cpi"))]
+        emit!($event);
+    };
+}
+
+/// Fail with `Denylisted` when a protocol denylist entry exists, first logging
+/// the blocked attempt
+macro_rules! require_not_denylisted {
+    ($accounts:expr, $entry:expr, $account:expr, $role:expr) => {
+        if !$entry.data_is_empty() {
+            emit_event!(
+                $accounts,
+                DenylistBlocked {
+                    account: $account,
+                    role: $role,
+                }
+            );
+            return err!(ShadeError::Denylisted);
+        }
+    };
+}
+
+#[cfg(feature = "devnet")]
+pub mod devnet;
+#[cfg(feature = "devnet")]
+pub use devnet::*;
+
+#[cfg(test)]
+mod tests;
+
+/// Maximum number of approvers on an approval policy
+pub const MAX_APPROVERS: usize = 10;
+
+/// Maximum number of concurrent velocity windows per authorization
+pub const MAX_VELOCITY_WINDOWS: usize = 3;
+
+/// Delay between announcing a resume and resuming without the guardian (24 hours)
+pub const RESUME_TIMELOCK: i64 = 24 * 60 * 60;
+
+/// Domain separator for off-chain spend approvals
+pub const SPEND_APPROVAL_DOMAIN: &[u8] = b"SHADE_SPEND_APPROVAL";
+
+/// Domain separator for off-chain spend permits
+pub const SPEND_PERMIT_DOMAIN: &[u8] = b"SHADE_SPEND_PERMIT";
+
+/// Permit nonces tracked per nonce bitmap page
+pub const PERMIT_NONCES_PER_PAGE: u64 = 2048;
+
+/// Fixed-point scale for rebase_per_share
+pub const REBASE_PRECISION: u128 = 1_000_000_000_000;
+
+/// Fixed-point scale for acc_fee_per_share
+pub const FEE_PRECISION: u128 = 1_000_000_000_000;
+
+/// Maximum unstake cooldown (30 days)
+pub const MAX_UNSTAKE_COOLDOWN: i64 = 30 * 24 * 60 * 60;
+
+/// Maximum payments in one spend_batch
+pub const MAX_BATCH_PAYMENTS: usize = 20;
+
+/// Maximum keeper reward per expired authorization (1 USDC)
+pub const MAX_KEEPER_REWARD: u64 = 1_000_000;
+
+/// Maximum keeper cut of the fees a sweep_fees crank moves (1%)
+pub const MAX_KEEPER_REWARD_BPS: u16 = 100;
+
+/// Maximum share of a pool's spend fees its owner can take (50%)
+pub const MAX_POOL_FEE_BPS: u16 = 5000;
+
+/// Maximum share of protocol fees that can be routed to rebases (50%)
+pub const MAX_REBASE_BPS: u16 = 5000;
+
+/// Bond prices are quoted in pool-asset base units per whole SHADE (6 decimals)
+pub const BOND_PRICE_SCALE: u128 = 1_000_000;
+
+/// Fixed-point scale for gauge and staking emission reward_per_share
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+/// Authorizations per page of a spender's authorization index
+pub const SPENDER_INDEX_PAGE_SIZE: usize = 32;
+
+/// Authorizations per page of a pool's authorization index
+pub const POOL_INDEX_PAGE_SIZE: usize = 32;
+
+/// Fog pools per page of an instance's pool registry
+pub const POOL_REGISTRY_PAGE_SIZE: usize = 64;
+
+/// Maximum number of pools in a federation
+pub const MAX_FEDERATION_MEMBERS: usize = 16;
+
+/// Longest dispute window an escrowed spend can be held for (90 days)
+pub const MAX_DISPUTE_WINDOW: i64 = 90 * 24 * 60 * 60;
+
+/// Maximum number of recipients in a split template
+pub const MAX_SPLIT_RECIPIENTS: usize = 10;
+
+/// Maximum number of spends a pool's spend ring holds (fits one account
+/// creation or realloc, 10 KiB)
+pub const MAX_SPEND_RING_CAPACITY: u32 = 60;
+
+/// Longest stake lock (2 years)
+pub const MAX_STAKE_LOCK_DURATION: i64 = 2 * 365 * 24 * 60 * 60;
+
+/// Extra effective stake for a maximum-length lock (bps of the locked stake)
+pub const MAX_LOCK_BOOST_BPS: u64 = 10_000;
+
+/// Highest early-exit penalty the admin can set
+pub const MAX_EARLY_EXIT_PENALTY_BPS: u16 = 5_000;
+
+/// Pyth Solana receiver program, owner of pull-oracle price update accounts
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+/// Anchor discriminator of the receiver's PriceUpdateV2 account
+pub const PYTH_PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// Solana Attestation Service program, owner of credential attestations
+pub const SAS_PROGRAM_ID: Pubkey = pubkey!("22zoJMtdu4tQc2PzL74ZUT7FrwgB1Udec8DdW4yw4BdG");
+
+/// Account type byte of a Solana Attestation Service attestation
+pub const SAS_ATTESTATION_DISCRIMINATOR: u8 = 2;
+
+/// Decimals of USD-denominated spending caps (micro-USD)
+pub const USD_DECIMALS: u32 = 6;
+
+/// Spending cap at a 1x tier multiplier for new instances (1000 tokens at 6 decimals)
+pub const DEFAULT_BASE_SPENDING_CAP: u64 = 1_000_000_000;
+
+/// LP shares locked for good by a pool's first deposit, so the share price
+/// can't be inflated by donating to a near-empty vault
+pub const MIN_LP_LIQUIDITY: u64 = 1_000;
+
 /// SHADE Protocol: Authorization-Based Finance
 /// Spend without owning - cryptographic permission to spend from shared liquidity
-/// 
+///
 /// Features:
 /// - Fog Pools: Shared liquidity reservoirs
 /// - Authorizations: Cryptographic spending permissions
@@ -19,9 +169,11 @@ pub mod shade {
     // PROTOCOL CONFIGURATION
     // ========================================================================
 
-    /// Initialize the protocol configuration (one-time setup)
+    /// Initialize a protocol instance (one-time setup per instance seed)
+    /// Each instance has its own config, fee settings, mint and vaults
     pub fn initialize_protocol(
         ctx: Context<InitializeProtocol>,
+        instance_seed: [u8; 32],
         fee_basis_points: u16,
     ) -> Result<()> {
         require!(fee_basis_points <= 1000, ShadeError::FeeTooHigh); // Max 10%
@@ -36,28 +188,73 @@ pub mod shade {
         config.total_fees_collected = 0;
         config.total_fees_distributed = 0;
         config.bump = ctx.bumps.protocol_config;
+        config.instance_seed = instance_seed;
+        config.tier_source = Pubkey::default();
+        config.paused = false;
+        config.resume_guard = ResumeGuard::default();
+        config.pending_authority = Pubkey::default();
+        config.vault_authority_bump = ctx.bumps.vault_authority;
 
         // Tier thresholds (in $SHADE tokens with 6 decimals)
         // Adjusted for pump.fun launch
-        config.bronze_threshold = 10_000_000_000;      // 10,000 $SHADE
-        config.silver_threshold = 100_000_000_000;     // 100,000 $SHADE
-        config.gold_threshold = 500_000_000_000;       // 500,000 $SHADE
+        config.bronze_threshold = 10_000_000_000; // 10,000 $SHADE
+        config.silver_threshold = 100_000_000_000; // 100,000 $SHADE
+        config.gold_threshold = 500_000_000_000; // 500,000 $SHADE
 
         // Spending cap multipliers per tier (in basis points of base cap)
-        config.bronze_cap_multiplier = 100;  // 1x base
-        config.silver_cap_multiplier = 500;  // 5x base
-        config.gold_cap_multiplier = 1000;   // 10x base
-
-        emit!(ProtocolInitialized {
-            config: config.key(),
-            authority: config.authority,
-            fee_basis_points,
-        });
+        config.bronze_cap_multiplier = 100; // 1x base
+        config.silver_cap_multiplier = 500; // 5x base
+        config.gold_cap_multiplier = 1000; // 10x base
+        config.base_spending_cap = DEFAULT_BASE_SPENDING_CAP;
+
+        // Rebases are off until governance sets a fee share
+        config.rebase_bps = 0;
+        config.rebase_epoch_length = 0;
+        config.rebase_fees_pending = 0;
+        config.last_rebase_at = 0;
+        config.rebase_epoch = 0;
+        config.rebase_per_share = 0;
+        config.total_rebased = 0;
+        config.min_stake_age = 0;
+        config.acc_fee_per_share = 0;
+        config.keeper_reward = 0;
+        config.keeper_reward_bps = 0;
+        config.staker_share_bps = 10_000;
+        config.lp_share_bps = 0;
+        config.treasury_share_bps = 0;
+        config.fee_treasury = Pubkey::default();
+        config.bronze_fee_rebate_bps = 0;
+        config.silver_fee_rebate_bps = 0;
+        config.gold_fee_rebate_bps = 0;
+        config.unstake_cooldown = 0;
+        config.version = ProtocolConfig::VERSION;
+        config.pool_count = 0;
+        config.max_price_deviation_bps = 0;
+        config.price_deviation_window = 0;
+        config.total_lock_bonus = 0;
+        config.early_exit_penalty_bps = 0;
+        config.reserved_penalties = 0;
+        config.staking_emissions = EmissionStream::default();
+        config.emissions_updated_at = Clock::get()?.unix_timestamp;
+        config.legacy_fees_collected = 0;
+        config.legacy_total_staked = 0;
+        config.total_warming = 0;
+        config.reserved = [0; 16];
+
+        emit_event!(
+            ctx.accounts,
+            ProtocolInitialized {
+                config: config.key(),
+                authority: config.authority,
+                fee_basis_points,
+            }
+        );
 
         Ok(())
     }
 
     /// Update protocol fee (admin only)
+    /// Pools charge the new rate once synced with sync_pool_config
     pub fn update_fee(ctx: Context<UpdateProtocol>, new_fee_basis_points: u16) -> Result<()> {
         require!(new_fee_basis_points <= 1000, ShadeError::FeeTooHigh);
 
@@ -65,1145 +262,17907 @@ pub mod shade {
         let old_fee = config.fee_basis_points;
         config.fee_basis_points = new_fee_basis_points;
 
-        emit!(FeeUpdated {
-            old_fee,
-            new_fee: new_fee_basis_points,
-        });
+        emit_event!(
+            ctx.accounts,
+            FeeUpdated {
+                old_fee,
+                new_fee: new_fee_basis_points,
+            }
+        );
 
         Ok(())
     }
 
-    /// Update tier thresholds (admin only)
-    pub fn update_tiers(
+    /// Update tier thresholds and spending cap multipliers (admin only)
+    /// Spenders' tiers in this instance are re-evaluated against the new
+    /// thresholds when authorizations are created
+    pub fn update_tier_config(
         ctx: Context<UpdateProtocol>,
         bronze_threshold: u64,
         silver_threshold: u64,
         gold_threshold: u64,
+        bronze_cap_multiplier: u16,
+        silver_cap_multiplier: u16,
+        gold_cap_multiplier: u16,
     ) -> Result<()> {
         // Validate thresholds are in ascending order
-        require!(bronze_threshold < silver_threshold, ShadeError::InvalidTierThresholds);
-        require!(silver_threshold < gold_threshold, ShadeError::InvalidTierThresholds);
+        require!(bronze_threshold > 0, ShadeError::InvalidTierThresholds);
+        require!(
+            bronze_threshold < silver_threshold,
+            ShadeError::InvalidTierThresholds
+        );
+        require!(
+            silver_threshold < gold_threshold,
+            ShadeError::InvalidTierThresholds
+        );
+
+        // A higher tier never gets a smaller cap
+        require!(bronze_cap_multiplier > 0, ShadeError::InvalidCapMultipliers);
+        require!(
+            bronze_cap_multiplier <= silver_cap_multiplier
+                && silver_cap_multiplier <= gold_cap_multiplier,
+            ShadeError::InvalidCapMultipliers
+        );
 
         let config = &mut ctx.accounts.protocol_config;
         config.bronze_threshold = bronze_threshold;
         config.silver_threshold = silver_threshold;
         config.gold_threshold = gold_threshold;
+        config.bronze_cap_multiplier = bronze_cap_multiplier;
+        config.silver_cap_multiplier = silver_cap_multiplier;
+        config.gold_cap_multiplier = gold_cap_multiplier;
 
-        emit!(TiersUpdated {
-            bronze: bronze_threshold,
-            silver: silver_threshold,
-            gold: gold_threshold,
-        });
+        emit_event!(
+            ctx.accounts,
+            TiersUpdated {
+                bronze: bronze_threshold,
+                silver: silver_threshold,
+                gold: gold_threshold,
+                bronze_cap_multiplier,
+                silver_cap_multiplier,
+                gold_cap_multiplier,
+            }
+        );
 
         Ok(())
     }
 
-    // ========================================================================
-    // STAKING
-    // ========================================================================
+    /// Set the share of the protocol fee rebated to spenders of each staking
+    /// tier (admin only); a higher tier never gets a smaller rebate
+    /// Pools apply new rebates once synced with sync_pool_config
+    pub fn set_fee_rebates(
+        ctx: Context<UpdateProtocol>,
+        bronze_fee_rebate_bps: u16,
+        silver_fee_rebate_bps: u16,
+        gold_fee_rebate_bps: u16,
+    ) -> Result<()> {
+        require!(
+            bronze_fee_rebate_bps <= silver_fee_rebate_bps
+                && silver_fee_rebate_bps <= gold_fee_rebate_bps
+                && gold_fee_rebate_bps <= 10_000,
+            ShadeError::InvalidFeeRebates
+        );
 
-    /// Stake $SHADE tokens to earn fees and unlock higher tiers
-    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
-        require!(amount > 0, ShadeError::InvalidAmount);
+        let config = &mut ctx.accounts.protocol_config;
+        config.bronze_fee_rebate_bps = bronze_fee_rebate_bps;
+        config.silver_fee_rebate_bps = silver_fee_rebate_bps;
+        config.gold_fee_rebate_bps = gold_fee_rebate_bps;
 
-        // Transfer $SHADE from user to staking vault
-        let transfer_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.user_shade_account.to_account_info(),
-                to: ctx.accounts.staking_vault.to_account_info(),
-                authority: ctx.accounts.user.to_account_info(),
-            },
+        emit_event!(
+            ctx.accounts,
+            FeeRebatesUpdated {
+                bronze_fee_rebate_bps,
+                silver_fee_rebate_bps,
+                gold_fee_rebate_bps,
+            }
         );
-        token::transfer(transfer_ctx, amount)?;
 
-        // Update or initialize staker account
-        let staker = &mut ctx.accounts.staker;
-        let config = &ctx.accounts.protocol_config;
-        
-        if staker.user == Pubkey::default() {
-            // New staker - initialize
-            staker.user = ctx.accounts.user.key();
-            staker.staked_amount = 0;
-            staker.pending_rewards = 0;
-            staker.last_claim_timestamp = Clock::get()?.unix_timestamp;
-            // Initialize snapshot to current fees so new stakers don't claim old fees
-            staker.last_fees_snapshot = config.total_fees_collected;
-            staker.bump = ctx.bumps.staker;
-        } else if staker.staked_amount > 0 && config.total_staked > 0 {
-            // Existing staker adding more - auto-distribute pending rewards first
-            // This prevents gaming by staking more right before distribution
-            let new_fees = config.total_fees_collected
-                .saturating_sub(staker.last_fees_snapshot);
-            
-            if new_fees > 0 {
-                let share = (new_fees as u128)
-                    .checked_mul(staker.staked_amount as u128)
-                    .ok_or(ShadeError::Overflow)?
-                    .checked_div(config.total_staked as u128)
-                    .ok_or(ShadeError::Overflow)? as u64;
-                
-                if share > 0 {
-                    staker.pending_rewards = staker.pending_rewards
-                        .checked_add(share)
-                        .ok_or(ShadeError::Overflow)?;
-                }
+        Ok(())
+    }
+
+    /// Set the base spending cap that tier multipliers scale (admin only)
+    /// Denominated in the pool asset's base units, so it tracks its decimals
+    pub fn set_base_spending_cap(
+        ctx: Context<UpdateProtocol>,
+        base_spending_cap: u64,
+    ) -> Result<()> {
+        require!(base_spending_cap > 0, ShadeError::InvalidAmount);
+
+        let config = &mut ctx.accounts.protocol_config;
+        let old_cap = config.base_spending_cap;
+        config.base_spending_cap = base_spending_cap;
+
+        emit_event!(
+            ctx.accounts,
+            BaseSpendingCapUpdated {
+                old_cap,
+                new_cap: base_spending_cap,
             }
-            staker.last_fees_snapshot = config.total_fees_collected;
-        }
+        );
 
-        staker.staked_amount = staker
-            .staked_amount
-            .checked_add(amount)
-            .ok_or(ShadeError::Overflow)?;
+        Ok(())
+    }
 
-        // Update tier
-        staker.tier = calculate_tier(staker.staked_amount, config);
+    /// Set how swept fees are split between stakers, pool LPs and the treasury
+    /// (admin only); the shares must add up to 10000 bps. A treasury share
+    /// needs a fee treasury token account owned by the treasury PDA
+    pub fn set_fee_split(
+        ctx: Context<SetFeeSplit>,
+        staker_share_bps: u16,
+        lp_share_bps: u16,
+        treasury_share_bps: u16,
+    ) -> Result<()> {
+        require!(
+            staker_share_bps as u32 + lp_share_bps as u32 + treasury_share_bps as u32 == 10_000,
+            ShadeError::InvalidFeeSplit
+        );
 
-        // Update protocol total
         let config = &mut ctx.accounts.protocol_config;
-        config.total_staked = config
-            .total_staked
-            .checked_add(amount)
-            .ok_or(ShadeError::Overflow)?;
+        if let Some(fee_treasury) = &ctx.accounts.fee_treasury {
+            config.fee_treasury = fee_treasury.key();
+        }
+        require!(
+            treasury_share_bps == 0 || config.fee_treasury != Pubkey::default(),
+            ShadeError::InvalidFeeSplit
+        );
+        config.staker_share_bps = staker_share_bps;
+        config.lp_share_bps = lp_share_bps;
+        config.treasury_share_bps = treasury_share_bps;
 
-        emit!(Staked {
-            user: ctx.accounts.user.key(),
-            amount,
-            new_total: staker.staked_amount,
-            tier: staker.tier,
-        });
+        emit_event!(
+            ctx.accounts,
+            FeeSplitUpdated {
+                staker_share_bps,
+                lp_share_bps,
+                treasury_share_bps,
+                fee_treasury: config.fee_treasury,
+            }
+        );
 
         Ok(())
     }
 
-    /// Unstake $SHADE tokens
-    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<()> {
-        let staker = &ctx.accounts.staker;
-        require!(amount > 0, ShadeError::InvalidAmount);
-        require!(staker.staked_amount >= amount, ShadeError::InsufficientStake);
+    /// Set how long new stake must be held before it earns fees (admin only)
+    /// Fees collected while stake is warming up are not paid to it, so staking
+    /// right before a large fee and unstaking after earns nothing
+    pub fn set_min_stake_age(ctx: Context<UpdateProtocol>, min_stake_age: i64) -> Result<()> {
+        require!(min_stake_age >= 0, ShadeError::InvalidTimestamp);
 
-        let config = &ctx.accounts.protocol_config;
+        let config = &mut ctx.accounts.protocol_config;
+        let old_age = config.min_stake_age;
+        config.min_stake_age = min_stake_age;
 
-        // Auto-distribute pending rewards before reducing stake
-        // This ensures fair distribution based on stake at time fees were earned
-        let staker = &mut ctx.accounts.staker;
-        if staker.staked_amount > 0 && config.total_staked > 0 {
-            let new_fees = config.total_fees_collected
-                .saturating_sub(staker.last_fees_snapshot);
-            
-            if new_fees > 0 {
-                let share = (new_fees as u128)
-                    .checked_mul(staker.staked_amount as u128)
-                    .ok_or(ShadeError::Overflow)?
-                    .checked_div(config.total_staked as u128)
-                    .ok_or(ShadeError::Overflow)? as u64;
-                
-                if share > 0 {
-                    staker.pending_rewards = staker.pending_rewards
-                        .checked_add(share)
-                        .ok_or(ShadeError::Overflow)?;
-                }
+        emit_event!(
+            ctx.accounts,
+            MinStakeAgeUpdated {
+                old_age,
+                new_age: min_stake_age,
             }
-            staker.last_fees_snapshot = config.total_fees_collected;
-        }
+        );
 
-        // Transfer $SHADE from staking vault to user
-        let config = &ctx.accounts.protocol_config;
-        let seeds = &[
-            b"protocol_config".as_ref(),
-            &[config.bump][..],
-        ];
-        let signer_seeds = &[&seeds[..]];
+        Ok(())
+    }
 
-        let transfer_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.staking_vault.to_account_info(),
-                to: ctx.accounts.user_shade_account.to_account_info(),
-                authority: config.to_account_info(),
-            },
-            signer_seeds,
+    /// Set how long requested unstakes wait before they can be withdrawn (admin only)
+    /// Applies to pending requests too, measured from each request time
+    pub fn set_unstake_cooldown(ctx: Context<UpdateProtocol>, unstake_cooldown: i64) -> Result<()> {
+        require!(
+            (0..=MAX_UNSTAKE_COOLDOWN).contains(&unstake_cooldown),
+            ShadeError::InvalidTimestamp
         );
-        token::transfer(transfer_ctx, amount)?;
 
-        // Update staker account
-        let staker = &mut ctx.accounts.staker;
-        staker.staked_amount = staker
-            .staked_amount
-            .checked_sub(amount)
-            .ok_or(ShadeError::Overflow)?;
+        let config = &mut ctx.accounts.protocol_config;
+        let old_cooldown = config.unstake_cooldown;
+        config.unstake_cooldown = unstake_cooldown;
 
-        // Update tier
-        let config = &ctx.accounts.protocol_config;
-        staker.tier = calculate_tier(staker.staked_amount, config);
+        emit_event!(
+            ctx.accounts,
+            UnstakeCooldownUpdated {
+                old_cooldown,
+                new_cooldown: unstake_cooldown,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the share of stake forfeited by unlock_stake_early and
+    /// complete_unstake_early (admin only), at most MAX_EARLY_EXIT_PENALTY_BPS
+    /// Penalties are paid into the fee vault, so only an instance whose fee
+    /// vault holds $SHADE can charge one
+    pub fn set_early_exit_penalty(
+        ctx: Context<SetEarlyExitPenalty>,
+        early_exit_penalty_bps: u16,
+    ) -> Result<()> {
+        require!(
+            early_exit_penalty_bps <= MAX_EARLY_EXIT_PENALTY_BPS,
+            ShadeError::InvalidExitPenalty
+        );
+        require!(
+            early_exit_penalty_bps == 0
+                || ctx.accounts.fee_vault.mint == ctx.accounts.protocol_config.shade_mint,
+            ShadeError::ExitPenaltyMintMismatch
+        );
 
-        // Update protocol total
         let config = &mut ctx.accounts.protocol_config;
-        config.total_staked = config
-            .total_staked
-            .saturating_sub(amount);
+        let old_bps = config.early_exit_penalty_bps;
+        config.early_exit_penalty_bps = early_exit_penalty_bps;
 
-        emit!(Unstaked {
-            user: ctx.accounts.user.key(),
-            amount,
-            remaining: staker.staked_amount,
-            tier: staker.tier,
-        });
+        emit_event!(
+            ctx.accounts,
+            EarlyExitPenaltyUpdated {
+                old_bps,
+                new_bps: early_exit_penalty_bps,
+            }
+        );
 
         Ok(())
     }
 
-    /// Claim accumulated fee rewards
-    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
-        let staker = &ctx.accounts.staker;
-        let pending = staker.pending_rewards;
-        require!(pending > 0, ShadeError::NoRewardsToClaim);
+    /// Set the fee-vault reward paid for each expire_authorization crank (admin only)
+    pub fn set_keeper_reward(ctx: Context<UpdateProtocol>, keeper_reward: u64) -> Result<()> {
+        require!(
+            keeper_reward <= MAX_KEEPER_REWARD,
+            ShadeError::InvalidAmount
+        );
 
-        // Transfer rewards from fee vault to user
-        let config = &ctx.accounts.protocol_config;
-        let seeds = &[
-            b"protocol_config".as_ref(),
-            &[config.bump][..],
-        ];
-        let signer_seeds = &[&seeds[..]];
+        let config = &mut ctx.accounts.protocol_config;
+        let old_reward = config.keeper_reward;
+        config.keeper_reward = keeper_reward;
 
-        let transfer_ctx = CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.fee_vault.to_account_info(),
-                to: ctx.accounts.user_token_account.to_account_info(),
-                authority: config.to_account_info(),
-            },
-            signer_seeds,
+        emit_event!(
+            ctx.accounts,
+            KeeperRewardUpdated {
+                old_reward,
+                new_reward: keeper_reward,
+            }
         );
-        token::transfer(transfer_ctx, pending)?;
 
-        // Update staker
-        let staker = &mut ctx.accounts.staker;
-        staker.pending_rewards = 0;
-        staker.last_claim_timestamp = Clock::get()?.unix_timestamp;
+        Ok(())
+    }
+
+    /// Set the keeper's cut of the fees each sweep_fees crank moves (admin only)
+    pub fn set_keeper_reward_bps(
+        ctx: Context<UpdateProtocol>,
+        keeper_reward_bps: u16,
+    ) -> Result<()> {
+        require!(
+            keeper_reward_bps <= MAX_KEEPER_REWARD_BPS,
+            ShadeError::FeeTooHigh
+        );
 
-        // Update protocol stats
         let config = &mut ctx.accounts.protocol_config;
-        config.total_fees_distributed = config
-            .total_fees_distributed
-            .checked_add(pending)
-            .ok_or(ShadeError::Overflow)?;
+        let old_bps = config.keeper_reward_bps;
+        config.keeper_reward_bps = keeper_reward_bps;
 
-        emit!(RewardsClaimed {
-            user: ctx.accounts.user.key(),
-            amount: pending,
-        });
+        emit_event!(
+            ctx.accounts,
+            KeeperRewardBpsUpdated {
+                old_bps,
+                new_bps: keeper_reward_bps,
+            }
+        );
 
         Ok(())
     }
 
-    /// Distribute fees to a staker (called by anyone, incentivized)
-    /// Uses snapshot pattern to prevent double-distribution
-    pub fn distribute_fees(ctx: Context<DistributeFees>) -> Result<()> {
-        let config = &ctx.accounts.protocol_config;
-        let staker = &ctx.accounts.staker;
-
-        require!(config.total_staked > 0, ShadeError::NoStakers);
-        require!(staker.staked_amount > 0, ShadeError::NotStaking);
+    /// Recognize stakers' tiers from another instance (admin only)
+    /// White-label instances point this at the canonical SHADE config so users
+    /// staked there don't have to stake again; pass no source to stop
+    pub fn set_tier_source(ctx: Context<SetTierSource>) -> Result<()> {
+        let tier_source = ctx
+            .accounts
+            .source_config
+            .as_ref()
+            .map(|source| source.key())
+            .unwrap_or_default();
 
-        // Calculate NEW fees since this staker's last distribution
-        // This prevents double-claiming: each staker can only claim their share of
-        // fees collected AFTER their last distribution call
-        let new_fees = config.total_fees_collected
-            .saturating_sub(staker.last_fees_snapshot);
-        
-        if new_fees == 0 {
-            return Ok(()); // No new fees to distribute to this staker
-        }
+        let config = &mut ctx.accounts.protocol_config;
+        require!(tier_source != config.key(), ShadeError::InvalidTierSource);
+        config.tier_source = tier_source;
 
-        // Proportional share based on stake ratio at time of distribution
-        let share = (new_fees as u128)
-            .checked_mul(staker.staked_amount as u128)
-            .ok_or(ShadeError::Overflow)?
-            .checked_div(config.total_staked as u128)
-            .ok_or(ShadeError::Overflow)? as u64;
+        emit_event!(
+            ctx.accounts,
+            TierSourceUpdated {
+                config: config.key(),
+                tier_source,
+            }
+        );
 
-        if share == 0 {
-            return Ok(());
-        }
+        Ok(())
+    }
 
-        // Update staker's pending rewards and snapshot
-        let staker = &mut ctx.accounts.staker;
-        staker.pending_rewards = staker
-            .pending_rewards
-            .checked_add(share)
-            .ok_or(ShadeError::Overflow)?;
-        
-        // Record the snapshot - this staker has now claimed their share up to this point
-        staker.last_fees_snapshot = config.total_fees_collected;
+    /// Propose a new protocol authority (admin only)
+    /// The handover completes only when the new key calls accept_authority;
+    /// proposing the default pubkey cancels a pending proposal
+    pub fn propose_authority(ctx: Context<UpdateProtocol>, new_authority: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        config.pending_authority = new_authority;
 
-        emit!(FeesDistributed {
-            staker: staker.user,
-            amount: share,
-        });
+        emit_event!(
+            ctx.accounts,
+            AuthorityProposed {
+                authority: config.authority,
+                pending_authority: new_authority,
+            }
+        );
 
         Ok(())
     }
 
-    // ========================================================================
-    // FOG POOLS
-    // ========================================================================
+    /// Accept a pending authority proposal (proposed key only)
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        let old_authority = config.authority;
+        config.authority = ctx.accounts.new_authority.key();
+        config.pending_authority = Pubkey::default();
 
-    /// Initialize a new Fog Pool - shared liquidity reservoir
-    pub fn initialize_fog_pool(
-        ctx: Context<InitializeFogPool>,
-        pool_seed: [u8; 32],
-    ) -> Result<()> {
-        let fog_pool = &mut ctx.accounts.fog_pool;
-        fog_pool.authority = ctx.accounts.authority.key();
-        fog_pool.vault = ctx.accounts.vault.key();
-        fog_pool.total_deposited = 0;
-        fog_pool.total_spent = 0;
-        fog_pool.total_fees_generated = 0;
-        fog_pool.active_authorizations = 0;
-        fog_pool.pool_seed = pool_seed;
-        fog_pool.bump = ctx.bumps.fog_pool;
-
-        emit!(FogPoolCreated {
-            pool: fog_pool.key(),
-            authority: fog_pool.authority,
-            vault: fog_pool.vault,
-        });
+        emit_event!(
+            ctx.accounts,
+            AuthorityTransferred {
+                old_authority,
+                new_authority: config.authority,
+            }
+        );
 
         Ok(())
     }
 
-    /// Deposit funds into the Fog Pool (LP deposit)
-    pub fn deposit_to_fog(ctx: Context<DepositToFog>, amount: u64) -> Result<()> {
-        require!(amount > 0, ShadeError::InvalidAmount);
+    /// Whitelist an AMM program for protocol swaps and liquidity (admin only)
+    pub fn add_amm_program(ctx: Context<AddAmmProgram>) -> Result<()> {
+        let amm = &mut ctx.accounts.amm;
+        amm.program_id = ctx.accounts.amm_program.key();
+        amm.added_at = Clock::get()?.unix_timestamp;
+        amm.bump = ctx.bumps.amm;
 
-        // Transfer tokens from depositor to vault
-        let transfer_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.depositor_token_account.to_account_info(),
-                to: ctx.accounts.vault.to_account_info(),
-                authority: ctx.accounts.depositor.to_account_info(),
-            },
+        emit_event!(
+            ctx.accounts,
+            AmmProgramUpdated {
+                program_id: amm.program_id,
+                whitelisted: true,
+            }
         );
-        token::transfer(transfer_ctx, amount)?;
 
-        // Update fog pool stats
-        let fog_pool = &mut ctx.accounts.fog_pool;
-        fog_pool.total_deposited = fog_pool
-            .total_deposited
-            .checked_add(amount)
-            .ok_or(ShadeError::Overflow)?;
+        Ok(())
+    }
 
-        emit!(DepositMade {
-            pool: fog_pool.key(),
-            depositor: ctx.accounts.depositor.key(),
-            amount,
-        });
+    /// Remove an AMM program from the whitelist (admin only)
+    pub fn remove_amm_program(ctx: Context<RemoveAmmProgram>) -> Result<()> {
+        emit_event!(
+            ctx.accounts,
+            AmmProgramUpdated {
+                program_id: ctx.accounts.amm.program_id,
+                whitelisted: false,
+            }
+        );
 
         Ok(())
     }
 
     // ========================================================================
-    // AUTHORIZATIONS
+    // STAKING
     // ========================================================================
 
-    /// Create a spending authorization - permission to spend from the fog
-    /// The spending cap is validated against the spender's staking tier
-    pub fn create_authorization(
-        ctx: Context<CreateAuthorization>,
-        nonce: u64,
-        spending_cap: u64,
-        expires_at: i64,
-        purpose: String,
-    ) -> Result<()> {
-        require!(spending_cap > 0, ShadeError::InvalidAmount);
-        require!(purpose.len() <= 64, ShadeError::PurposeTooLong);
-        
-        let clock = Clock::get()?;
-        require!(expires_at > clock.unix_timestamp, ShadeError::InvalidExpiry);
+    /// Stake $SHADE tokens to earn fees and unlock higher tiers
+    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            !ctx.accounts.protocol_config.paused,
+            ShadeError::ProtocolPaused
+        );
 
-        // Validate spending cap against staker tier if staker exists
-        if let Some(staker) = &ctx.accounts.staker {
-            let config = &ctx.accounts.protocol_config;
-            let max_cap = get_max_cap_for_tier(staker.tier, config);
-            require!(spending_cap <= max_cap, ShadeError::ExceedsTierLimit);
-        }
+        // Transfer $SHADE from user to staking vault
+        let vault_balance = ctx.accounts.staking_vault.amount;
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.user_shade_account.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.staking_vault.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.shade_mint.decimals)?;
+        let amount = received_amount(&mut ctx.accounts.staking_vault, vault_balance)?;
 
-        let authorization = &mut ctx.accounts.authorization;
-        authorization.fog_pool = ctx.accounts.fog_pool.key();
-        authorization.authorized_spender = ctx.accounts.spender.key();
-        authorization.issuer = ctx.accounts.issuer.key();
-        authorization.spending_cap = spending_cap;
-        authorization.amount_spent = 0;
-        authorization.created_at = clock.unix_timestamp;
-        authorization.expires_at = expires_at;
-        authorization.purpose = purpose.clone();
-        authorization.is_active = true;
-        authorization.bump = ctx.bumps.authorization;
+        // Update or initialize staker account
+        let user = ctx.accounts.user.key();
+        let bump = ctx.bumps.staker;
+        let staker = &mut ctx.accounts.staker;
+        credit_stake(
+            staker,
+            &mut ctx.accounts.protocol_config,
+            user,
+            bump,
+            amount,
+        )?;
 
-        // Update fog pool stats
-        let fog_pool = &mut ctx.accounts.fog_pool;
-        fog_pool.active_authorizations = fog_pool
-            .active_authorizations
-            .checked_add(1)
-            .ok_or(ShadeError::Overflow)?;
+        emit_event!(
+            ctx.accounts,
+            Staked {
+                user: ctx.accounts.user.key(),
+                amount,
+                new_total: staker.staked_amount,
+                tier: staker.tier,
+            }
+        );
 
-        emit!(AuthorizationCreated {
-            authorization: authorization.key(),
-            fog_pool: fog_pool.key(),
-            spender: authorization.authorized_spender,
-            issuer: authorization.issuer,
-            spending_cap,
-            expires_at,
-            purpose,
-        });
+        Ok(())
+    }
+
+    /// Stake $SHADE on behalf of `user` (e.g. an employer or protocol),
+    /// funded from the funder's account and credited to the user's position
+    /// Rent for a new position comes from `payer`, which may be a third party
+    pub fn stake_for(ctx: Context<StakeFor>, user: Pubkey, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            user != Pubkey::default(),
+            ShadeError::InvalidStakeBeneficiary
+        );
+        require!(
+            !ctx.accounts.protocol_config.paused,
+            ShadeError::ProtocolPaused
+        );
+
+        let vault_balance = ctx.accounts.staking_vault.amount;
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.funder_shade_account.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.staking_vault.to_account_info(),
+                authority: ctx.accounts.funder.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.shade_mint.decimals)?;
+        let amount = received_amount(&mut ctx.accounts.staking_vault, vault_balance)?;
+
+        let bump = ctx.bumps.staker;
+        let staker = &mut ctx.accounts.staker;
+        credit_stake(
+            staker,
+            &mut ctx.accounts.protocol_config,
+            user,
+            bump,
+            amount,
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            StakedFor {
+                funder: ctx.accounts.funder.key(),
+                user,
+                amount,
+                new_total: staker.staked_amount,
+                tier: staker.tier,
+            }
+        );
 
         Ok(())
     }
 
-    /// Spend using an authorization - the core of SHADE
-    /// Takes a protocol fee that goes to stakers
-    pub fn spend(ctx: Context<Spend>, amount: u64) -> Result<()> {
-        let authorization = &ctx.accounts.authorization;
-        let clock = Clock::get()?;
+    /// Start unstaking $SHADE tokens
+    /// The amount stops counting toward tier and fees immediately and can be
+    /// withdrawn with complete_unstake once the unstake cooldown has elapsed.
+    /// A new request adds to any pending amount and restarts the cooldown
+    pub fn request_unstake(ctx: Context<RequestUnstake>, amount: u64) -> Result<()> {
+        let staker = &ctx.accounts.staker;
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            staker.staked_amount >= amount,
+            ShadeError::InsufficientStake
+        );
 
-        // Validate authorization
-        require!(authorization.is_active, ShadeError::AuthorizationInactive);
+        // Settle fees and rebase earned at the current stake before reducing it
+        let now = Clock::get()?.unix_timestamp;
+        let staker = &mut ctx.accounts.staker;
+        settle_rebase(staker, &mut ctx.accounts.protocol_config)?;
+        refresh_lock_bonus(staker, &mut ctx.accounts.protocol_config, now)?;
         require!(
-            clock.unix_timestamp < authorization.expires_at,
-            ShadeError::AuthorizationExpired
+            staker.staked_amount - staker.locked_stake(now) >= amount,
+            ShadeError::StakeStillLocked
         );
-        
-        let remaining = authorization
-            .spending_cap
-            .checked_sub(authorization.amount_spent)
+
+        // Update staker account
+        // Still-warming stake is withdrawn first
+        staker.pending_unstake_amount = staker
+            .pending_unstake_amount
+            .checked_add(amount)
             .ok_or(ShadeError::Overflow)?;
-        require!(amount <= remaining, ShadeError::ExceedsSpendingCap);
+        staker.unstake_requested_at = now;
+        staker.staked_amount = staker
+            .staked_amount
+            .checked_sub(amount)
+            .ok_or(ShadeError::Overflow)?;
+        drop_warming(staker, &mut ctx.accounts.protocol_config, amount);
 
-        // Calculate fee
+        // Update tier
         let config = &ctx.accounts.protocol_config;
-        let fee = (amount as u128)
-            .checked_mul(config.fee_basis_points as u128)
-            .ok_or(ShadeError::Overflow)?
-            .checked_div(10000)
-            .ok_or(ShadeError::Overflow)? as u64;
-        
-        let net_amount = amount.checked_sub(fee).ok_or(ShadeError::Overflow)?;
+        staker.rebase_debt = staker.rebase_entitlement(config.rebase_per_share);
+        staker.reward_debt = staker.fee_entitlement(config.acc_fee_per_share);
+        staker.emission_debt =
+            staker.emission_entitlement(config.staking_emissions.reward_per_share);
+        staker.tier = calculate_tier(staker.tier_weight(), config);
 
-        // Transfer net amount from vault to recipient
-        let fog_pool = &ctx.accounts.fog_pool;
-        let seeds = &[
-            b"fog_pool",
-            fog_pool.pool_seed.as_ref(),
-            &[fog_pool.bump],
-        ];
-        let signer_seeds = &[&seeds[..]];
+        // Update protocol total
+        let config = &mut ctx.accounts.protocol_config;
+        config.total_staked = config.total_staked.saturating_sub(amount);
 
-        // Transfer to recipient
-        let transfer_ctx = CpiContext::new_with_signer(
+        emit_event!(
+            ctx.accounts,
+            UnstakeRequested {
+                user: ctx.accounts.user.key(),
+                amount,
+                pending: staker.pending_unstake_amount,
+                available_at: now.saturating_add(config.unstake_cooldown),
+                remaining: staker.staked_amount,
+                tier: staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Withdraw requested $SHADE once the unstake cooldown has elapsed
+    pub fn complete_unstake(ctx: Context<CompleteUnstake>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let staker = &ctx.accounts.staker;
+        let amount = staker.pending_unstake_amount;
+        require!(amount > 0, ShadeError::NoPendingUnstake);
+        require!(
+            Clock::get()?.unix_timestamp
+                >= staker
+                    .unstake_requested_at
+                    .saturating_add(config.unstake_cooldown),
+            ShadeError::UnstakeCooldownActive
+        );
+
+        pay_unstake(ctx.accounts, amount)?;
+
+        emit_event!(
+            ctx.accounts,
+            Unstaked {
+                user: ctx.accounts.user.key(),
+                amount,
+                remaining: ctx.accounts.staker.staked_amount,
+                tier: ctx.accounts.staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Withdraw requested $SHADE before the unstake cooldown has elapsed
+    /// The early-exit penalty goes to the fee vault and is booked as a
+    /// protocol fee for stakers
+    pub fn complete_unstake_early(ctx: Context<CompleteUnstake>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let staker = &ctx.accounts.staker;
+        let amount = staker.pending_unstake_amount;
+        require!(amount > 0, ShadeError::NoPendingUnstake);
+        require!(
+            Clock::get()?.unix_timestamp
+                < staker
+                    .unstake_requested_at
+                    .saturating_add(config.unstake_cooldown),
+            ShadeError::UnstakeCooldownElapsed
+        );
+
+        // The pending amount already left total_staked, so it earns no share
+        let penalty = early_exit_penalty(config, amount);
+        if penalty > 0 {
+            let fee_vault = ctx
+                .accounts
+                .fee_vault
+                .as_ref()
+                .ok_or(ShadeError::InvalidVaultAuthority)?;
+            book_exit_penalty(
+                &mut ctx.accounts.protocol_config,
+                &ctx.accounts.staking_vault,
+                &ctx.accounts.shade_mint,
+                fee_vault,
+                &ctx.accounts.vault_authority,
+                &ctx.accounts.token_program,
+                penalty,
+            )?;
+        }
+        let payout = amount - penalty;
+        pay_unstake(ctx.accounts, payout)?;
+
+        emit_event!(
+            ctx.accounts,
+            UnstakedEarly {
+                user: ctx.accounts.user.key(),
+                amount: payout,
+                penalty,
+                remaining: ctx.accounts.staker.staked_amount,
+                tier: ctx.accounts.staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Rage-quit: withdraw all stake and any pending unstake immediately,
+    /// skipping the cooldown and reward settlement, so funds can always leave
+    /// while the protocol is paused or reward accounting is stuck
+    /// The full principal is returned, locked or not. Pending and unsettled
+    /// fees, rebases and emissions are forfeited to the remaining stakers; if
+    /// that bookkeeping fails they are dropped instead of blocking the exit
+    pub fn emergency_unstake(ctx: Context<CompleteUnstake>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &mut ctx.accounts.protocol_config;
+        let staker = &mut ctx.accounts.staker;
+        let principal = staker
+            .staked_amount
+            .saturating_add(staker.pending_unstake_amount);
+        require!(principal > 0, ShadeError::InsufficientStake);
+
+        // Leave the totals first so the forfeits go to the remaining stakers
+        let leaving = (**staker).clone();
+        config.total_staked = config.total_staked.saturating_sub(staker.staked_amount);
+        config.total_lock_bonus = config.total_lock_bonus.saturating_sub(staker.lock_bonus);
+        let warming = staker.warming_amount;
+        drop_warming(staker, config, warming);
+
+        // Applied to a copy so a failure leaves the config untouched
+        let mut forfeited_config = (**config).clone();
+        let (forfeited_fees, forfeited_rebase, forfeited_emissions) =
+            match forfeit_staker_rewards(&leaving, &mut forfeited_config) {
+                Ok(forfeited) => {
+                    config.set_inner(forfeited_config);
+                    forfeited
+                }
+                Err(_) => (0, 0, 0),
+            };
+
+        staker.staked_amount = 0;
+        staker.locked_amount = 0;
+        staker.lock_end = now.min(staker.lock_end);
+        staker.lock_boost_bps = 0;
+        staker.lock_bonus = 0;
+        staker.pending_rewards = 0;
+        staker.pending_emissions = 0;
+        staker.reward_debt = 0;
+        staker.rebase_debt = 0;
+        staker.emission_debt = 0;
+        staker.tier = calculate_tier(staker.tier_weight(), config);
+
+        pay_unstake(ctx.accounts, principal)?;
+
+        emit_event!(
+            ctx.accounts,
+            EmergencyUnstaked {
+                user: ctx.accounts.user.key(),
+                amount: principal,
+                forfeited_fees,
+                forfeited_rebase,
+                forfeited_emissions,
+                tier: ctx.accounts.staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Stake $SHADE locked for `lock_duration` seconds (vote-escrow style)
+    /// The lock boosts effective stake for tier and fee share by up to
+    /// MAX_LOCK_BOOST_BPS at MAX_STAKE_LOCK_DURATION, and unlocks linearly
+    /// until it ends, the boost shrinking with it. Locking more restarts the
+    /// schedule for the still-locked stake plus the new amount, ending no
+    /// earlier than the current lock
+    pub fn stake_locked(ctx: Context<Stake>, amount: u64, lock_duration: i64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            lock_duration > 0 && lock_duration <= MAX_STAKE_LOCK_DURATION,
+            ShadeError::InvalidLockDuration
+        );
+        require!(
+            !ctx.accounts.protocol_config.paused,
+            ShadeError::ProtocolPaused
+        );
+
+        let vault_balance = ctx.accounts.staking_vault.amount;
+        let transfer_ctx = CpiContext::new(
             ctx.accounts.token_program.to_account_info(),
-            Transfer {
-                from: ctx.accounts.vault.to_account_info(),
-                to: ctx.accounts.recipient_token_account.to_account_info(),
-                authority: fog_pool.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.user_shade_account.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.staking_vault.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
             },
-            signer_seeds,
         );
-        token::transfer(transfer_ctx, net_amount)?;
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.shade_mint.decimals)?;
+        let amount = received_amount(&mut ctx.accounts.staking_vault, vault_balance)?;
 
-        // Transfer fee to fee vault
-        if fee > 0 {
-            let fee_transfer_ctx = CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.fee_vault.to_account_info(),
-                    authority: fog_pool.to_account_info(),
-                },
-                signer_seeds,
-            );
-            token::transfer(fee_transfer_ctx, fee)?;
-        }
+        let user = ctx.accounts.user.key();
+        let bump = ctx.bumps.staker;
+        let now = Clock::get()?.unix_timestamp;
+        let staker = &mut ctx.accounts.staker;
+        let config = &mut ctx.accounts.protocol_config;
+        credit_stake(staker, config, user, bump, amount)?;
 
-        // Update authorization
-        let authorization = &mut ctx.accounts.authorization;
-        authorization.amount_spent = authorization
-            .amount_spent
+        let still_locked = staker.locked_stake(now);
+        let lock_end = staker.lock_end.max(now.saturating_add(lock_duration));
+        staker.locked_amount = still_locked
             .checked_add(amount)
             .ok_or(ShadeError::Overflow)?;
+        staker.lock_start = now;
+        staker.lock_end = lock_end;
+        staker.lock_boost_bps = (MAX_LOCK_BOOST_BPS * (lock_end - now) as u64
+            / MAX_STAKE_LOCK_DURATION as u64)
+            .min(MAX_LOCK_BOOST_BPS) as u16;
+        refresh_lock_bonus(staker, config, now)?;
 
-        // Update fog pool stats
-        let fog_pool = &mut ctx.accounts.fog_pool;
-        fog_pool.total_spent = fog_pool
-            .total_spent
-            .checked_add(amount)
-            .ok_or(ShadeError::Overflow)?;
-        fog_pool.total_fees_generated = fog_pool
-            .total_fees_generated
-            .checked_add(fee)
-            .ok_or(ShadeError::Overflow)?;
+        emit_event!(
+            ctx.accounts,
+            StakeLocked {
+                user,
+                amount,
+                locked_amount: staker.locked_amount,
+                lock_end,
+                lock_boost_bps: staker.lock_boost_bps,
+                tier: staker.tier,
+            }
+        );
+
+        Ok(())
+    }
 
-        // Update protocol fee stats
+    /// Release a stake lock before it ends
+    /// The early-exit penalty on the still-locked stake goes to the fee vault
+    /// as a protocol fee for the other stakers; the rest becomes ordinary
+    /// stake that can be unstaked
+    pub fn unlock_stake_early(ctx: Context<UnlockStakeEarly>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
         let config = &mut ctx.accounts.protocol_config;
-        config.total_fees_collected = config
-            .total_fees_collected
-            .checked_add(fee)
-            .ok_or(ShadeError::Overflow)?;
+        let staker = &mut ctx.accounts.staker;
+        settle_rebase(staker, config)?;
+        let released = staker.locked_stake(now);
+        require!(released > 0, ShadeError::NoActiveLock);
 
-        emit!(SpendExecuted {
-            authorization: authorization.key(),
-            fog_pool: fog_pool.key(),
-            spender: ctx.accounts.spender.key(),
-            recipient: ctx.accounts.recipient_token_account.key(),
-            amount,
-            fee,
-            net_amount,
-            remaining: authorization.spending_cap - authorization.amount_spent,
-        });
+        let penalty = early_exit_penalty(config, released);
+        staker.staked_amount -= penalty;
+        config.total_staked = config.total_staked.saturating_sub(penalty);
+        let excess_warming = staker.warming_amount.saturating_sub(staker.staked_amount);
+        drop_warming(staker, config, excess_warming);
+        staker.rebase_debt = staker.rebase_entitlement(config.rebase_per_share);
+
+        staker.lock_end = now;
+        refresh_lock_bonus(staker, config, now)?;
+
+        // The leaving staker earns nothing from its own penalty
+        book_exit_penalty(
+            config,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.shade_mint,
+            &ctx.accounts.fee_vault,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.token_program,
+            penalty,
+        )?;
+        staker.reward_debt = staker.fee_entitlement(config.acc_fee_per_share);
+
+        emit_event!(
+            ctx.accounts,
+            StakeUnlockedEarly {
+                user: staker.user,
+                released,
+                penalty,
+                remaining: staker.staked_amount,
+                tier: staker.tier,
+            }
+        );
 
         Ok(())
     }
 
-    /// Revoke an authorization
-    pub fn revoke_authorization(ctx: Context<RevokeAuthorization>) -> Result<()> {
-        let authorization = &mut ctx.accounts.authorization;
-        require!(authorization.is_active, ShadeError::AuthorizationInactive);
+    /// Bring a staker's lock bonus in line with its unlock schedule
+    /// (permissionless); the bonus is otherwise only updated when the staker
+    /// locks or unstakes
+    pub fn refresh_stake_lock(ctx: Context<RefreshStakeLock>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let staker = &mut ctx.accounts.staker;
+        refresh_lock_bonus(staker, &mut ctx.accounts.protocol_config, now)?;
 
-        authorization.is_active = false;
+        emit_event!(
+            ctx.accounts,
+            StakeLockRefreshed {
+                user: staker.user,
+                locked: staker.locked_stake(now),
+                lock_bonus: staker.lock_bonus,
+                tier: staker.tier,
+            }
+        );
 
-        // Update fog pool stats
-        let fog_pool = &mut ctx.accounts.fog_pool;
-        fog_pool.active_authorizations = fog_pool
-            .active_authorizations
-            .saturating_sub(1);
+        Ok(())
+    }
 
-        emit!(AuthorizationRevoked {
-            authorization: authorization.key(),
-            fog_pool: fog_pool.key(),
-            revoked_by: ctx.accounts.issuer.key(),
-        });
+    /// Claim accumulated fee rewards
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        let pending = pay_rewards(
+            &mut ctx.accounts.protocol_config,
+            &mut ctx.accounts.staker,
+            &ctx.accounts.fee_vault,
+            &ctx.accounts.fee_mint,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.token_program,
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            RewardsClaimed {
+                user: ctx.accounts.user.key(),
+                amount: pending,
+            }
+        );
 
         Ok(())
     }
-}
 
-// ============================================================================
-// Helper Functions
-// ============================================================================
+    /// Authorize a delegate (e.g. an automation bot) to claim rewards on the
+    /// staker's behalf; proceeds can only go to `reward_destination`, a token
+    /// account owned by the staker. Pass Pubkey::default() to revoke
+    pub fn set_claim_delegate(ctx: Context<SetClaimDelegate>, delegate: Pubkey) -> Result<()> {
+        let staker = &mut ctx.accounts.staker;
+        staker.claim_delegate = delegate;
+        staker.reward_destination = if delegate == Pubkey::default() {
+            Pubkey::default()
+        } else {
+            ctx.accounts.reward_destination.key()
+        };
 
-fn calculate_tier(staked_amount: u64, config: &ProtocolConfig) -> u8 {
-    if staked_amount >= config.gold_threshold {
-        3 // Gold
-    } else if staked_amount >= config.silver_threshold {
-        2 // Silver
-    } else if staked_amount >= config.bronze_threshold {
-        1 // Bronze
-    } else {
-        0 // No tier
+        emit_event!(
+            ctx.accounts,
+            ClaimDelegateSet {
+                user: staker.user,
+                delegate,
+                reward_destination: staker.reward_destination,
+            }
+        );
+
+        Ok(())
     }
-}
 
-fn get_max_cap_for_tier(tier: u8, config: &ProtocolConfig) -> u64 {
-    let base_cap: u64 = 1_000_000_000; // 1000 tokens base
-    let multiplier = match tier {
-        3 => config.gold_cap_multiplier,
-        2 => config.silver_cap_multiplier,
-        1 => config.bronze_cap_multiplier,
-        _ => 50, // Non-stakers get 0.5x base (50 basis points)
-    };
-    
-    (base_cap as u128)
-        .checked_mul(multiplier as u128)
-        .unwrap_or(0)
-        .checked_div(100)
-        .unwrap_or(0) as u64
-}
+    /// Claim a staker's rewards as their delegate
+    /// Rewards are paid to the staker's registered reward destination
+    pub fn claim_rewards_as_delegate(ctx: Context<ClaimRewardsAsDelegate>) -> Result<()> {
+        let pending = pay_rewards(
+            &mut ctx.accounts.protocol_config,
+            &mut ctx.accounts.staker,
+            &ctx.accounts.fee_vault,
+            &ctx.accounts.fee_mint,
+            &ctx.accounts.vault_authority,
+            &ctx.accounts.reward_destination,
+            &ctx.accounts.token_program,
+        )?;
 
-// ============================================================================
-// Account Structures
-// ============================================================================
+        emit_event!(
+            ctx.accounts,
+            RewardsClaimed {
+                user: ctx.accounts.staker.user,
+                amount: pending,
+            }
+        );
 
-/// Protocol configuration - global settings
-#[account]
-#[derive(Default)]
-pub struct ProtocolConfig {
-    /// Protocol admin
-    pub authority: Pubkey,
-    /// $SHADE token mint
-    pub shade_mint: Pubkey,
-    /// Fee vault for collected fees
+        Ok(())
+    }
+
+    /// Delegate the staker's tier, not their stake, to another wallet such as
+    /// a hot spending wallet, which co-signs to accept it. Authorizations and
+    /// spends for the delegate can then use the delegator's tier, while the
+    /// delegator's own wallet counts as tier 0. Each staker has at most one
+    /// delegate and each delegate at most one delegator
+    pub fn set_tier_delegate(ctx: Context<SetTierDelegate>) -> Result<()> {
+        let staker = &mut ctx.accounts.staker;
+        let delegate = ctx.accounts.delegate.key();
+        require!(
+            staker.tier_delegate == Pubkey::default(),
+            ShadeError::TierAlreadyDelegated
+        );
+        require!(delegate != staker.user, ShadeError::InvalidTierDelegation);
+        staker.tier_delegate = delegate;
+
+        let delegation = &mut ctx.accounts.tier_delegation;
+        delegation.delegator = staker.user;
+        delegation.delegate = delegate;
+        delegation.created_at = Clock::get()?.unix_timestamp;
+        delegation.bump = ctx.bumps.tier_delegation;
+
+        emit_event!(
+            ctx.accounts,
+            TierDelegateSet {
+                user: staker.user,
+                delegate,
+                tier: staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Revoke a tier delegation
+    /// Authorizations already issued to the delegate keep their caps
+    pub fn revoke_tier_delegate(ctx: Context<RevokeTierDelegate>) -> Result<()> {
+        let staker = &mut ctx.accounts.staker;
+        let delegate = staker.tier_delegate;
+        staker.tier_delegate = Pubkey::default();
+
+        emit_event!(
+            ctx.accounts,
+            TierDelegateRevoked {
+                user: staker.user,
+                delegate,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Fund SHADE emissions for stakers from the admin's SHADE account (admin only)
+    /// Emissions are paid on top of fee sharing until the funding runs out
+    pub fn fund_staking_emissions(ctx: Context<FundStakingEmissions>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let vault_balance = ctx.accounts.emissions_vault.amount;
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.authority_shade_account.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.emissions_vault.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.shade_mint.decimals)?;
+        let amount = received_amount(&mut ctx.accounts.emissions_vault, vault_balance)?;
+
+        // Accrue first so the new funding only covers emissions from now on
+        let config = &mut ctx.accounts.protocol_config;
+        accrue_staking_emissions(config, Clock::get()?.unix_timestamp)?;
+        let stream = &mut config.staking_emissions;
+        stream.funded = stream
+            .funded
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            StakingEmissionsFunded {
+                amount,
+                funded: stream.funded,
+                allocated: stream.allocated,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the SHADE emitted per second across all stakers (admin only)
+    pub fn set_staking_emission_rate(
+        ctx: Context<UpdateProtocol>,
+        emission_rate: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        accrue_staking_emissions(config, Clock::get()?.unix_timestamp)?;
+        let old_rate = config.staking_emissions.rate;
+        config.staking_emissions.rate = emission_rate;
+
+        emit_event!(
+            ctx.accounts,
+            StakingEmissionRateUpdated {
+                old_rate,
+                new_rate: emission_rate,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Claim accrued SHADE staking emissions
+    /// Emissions are paid out rather than compounded into the stake
+    pub fn claim_staking_emissions(ctx: Context<ClaimStakingEmissions>) -> Result<()> {
+        let staker = &mut ctx.accounts.staker;
+        settle_emissions(staker, &mut ctx.accounts.protocol_config)?;
+        let amount = staker.pending_emissions;
+        require!(amount > 0, ShadeError::NoRewardsToClaim);
+
+        let config_key = ctx.accounts.protocol_config.key();
+        let seeds = &[
+            b"vault_authority".as_ref(),
+            config_key.as_ref(),
+            &[ctx.accounts.protocol_config.vault_authority_bump][..],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.emissions_vault.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.user_shade_account.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.shade_mint.decimals)?;
+
+        let staker = &mut ctx.accounts.staker;
+        staker.pending_emissions = 0;
+
+        emit_event!(
+            ctx.accounts,
+            StakingEmissionsClaimed {
+                user: staker.user,
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // FOG POOLS
+    // ========================================================================
+
+    /// Initialize a new Fog Pool - shared liquidity reservoir
+    pub fn initialize_fog_pool(ctx: Context<InitializeFogPool>, pool_seed: [u8; 32]) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.set_inner(FogPool {
+            authority: ctx.accounts.authority.key(),
+            vault: ctx.accounts.vault.key(),
+            pool_seed,
+            bump: ctx.bumps.fog_pool,
+            lp_mint: ctx.accounts.lp_mint.key(),
+            mint: ctx.accounts.mint.key(),
+            vault_authority_bump: ctx.bumps.pool_vault_authority,
+            ..FogPool::new(&ctx.accounts.protocol_config)
+        });
+
+        let registry_index = register_pool(
+            &mut ctx.accounts.protocol_config,
+            &mut ctx.accounts.pool_registry_page,
+            ctx.bumps.pool_registry_page,
+            fog_pool.key(),
+        )?;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            FogPoolCreated {
+                pool: fog_pool.key(),
+                sequence,
+                authority: fog_pool.authority,
+                vault: fog_pool.vault,
+                registry_index,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Deposit funds into the Fog Pool (LP deposit)
+    /// The depositor receives LP share tokens priced against the current vault
+    /// balance, excluding fees not yet swept
+    pub fn deposit_to_fog(ctx: Context<DepositToFog>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            !ctx.accounts.protocol_config.paused,
+            ShadeError::ProtocolPaused
+        );
+        require_not_denylisted!(
+            ctx.accounts,
+            ctx.accounts.depositor_denylist_entry,
+            ctx.accounts.depositor.key(),
+            DenylistRole::Depositor
+        );
+        require!(
+            !ctx.accounts.fog_pool.depositor_allowlist
+                || ctx.accounts.depositor_allowlist_entry.is_some(),
+            ShadeError::DepositorNotAllowlisted
+        );
+
+        let vault_balance = ctx.accounts.vault.amount;
+        let pool_balance = ctx.accounts.fog_pool.available_balance(vault_balance);
+        let pool_value = ctx.accounts.fog_pool.lp_value(vault_balance);
+
+        // Transfer tokens from depositor to vault
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.depositor_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.depositor.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        // Credit what arrived, net of any Token-2022 transfer fee
+        let amount = received_amount(&mut ctx.accounts.vault, vault_balance)?;
+        let max_total_deposits = ctx.accounts.fog_pool.max_total_deposits;
+        require!(
+            max_total_deposits == 0
+                || pool_balance
+                    .checked_add(amount)
+                    .ok_or(ShadeError::Overflow)?
+                    <= max_total_deposits,
+            ShadeError::DepositCapExceeded
+        );
+        let share_supply = ctx
+            .accounts
+            .fog_pool
+            .share_supply(ctx.accounts.lp_mint.supply)?;
+        let (shares, locked) = lp_shares_for_deposit(amount, pool_value, share_supply)?;
+        require!(shares > 0, ShadeError::InvalidAmount);
+
+        // Update fog pool stats
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.locked_shares = fog_pool
+            .locked_shares
+            .checked_add(locked)
+            .ok_or(ShadeError::Overflow)?;
+        fog_pool.total_deposited = fog_pool
+            .total_deposited
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        // Mint LP share tokens to the depositor
+        let pool_key = fog_pool.key();
+        let seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[fog_pool.vault_authority_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.lp_mint.to_account_info(),
+                to: ctx.accounts.depositor_lp_account.to_account_info(),
+                authority: ctx.accounts.pool_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::mint_to(mint_ctx, shares)?;
+
+        let position = &mut ctx.accounts.lp_position;
+        if position.owner == Pubkey::default() {
+            position.owner = ctx.accounts.depositor.key();
+            position.fog_pool = fog_pool.key();
+            position.bump = ctx.bumps.lp_position;
+        }
+        position.total_deposited = position
+            .total_deposited
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            DepositMade {
+                pool: fog_pool.key(),
+                sequence,
+                depositor: ctx.accounts.depositor.key(),
+                amount,
+                shares,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Burn LP share tokens for their portion of the pool's remaining vault balance
+    /// Spends reduce the balance, so LPs share them pro-rata; open in incident modes
+    pub fn withdraw_from_fog(ctx: Context<WithdrawFromFog>, shares: u64) -> Result<()> {
+        require!(
+            shares <= ctx.accounts.owner_lp_account.amount,
+            ShadeError::InsufficientShares
+        );
+        let fog_pool = &ctx.accounts.fog_pool;
+        let available = fog_pool.available_balance(ctx.accounts.vault.amount);
+        let share_supply = fog_pool.share_supply(ctx.accounts.lp_mint.supply)?;
+        let amount = lp_amount_for_shares(
+            shares,
+            fog_pool.lp_value(ctx.accounts.vault.amount),
+            share_supply,
+        )?;
+        // Liquidity reserved for active authorizations and owed to other pools
+        // stays in the vault
+        let remaining = available
+            .checked_sub(amount)
+            .ok_or(ShadeError::InsufficientPoolBalance)?;
+        require!(
+            remaining >= fog_pool.reserved_amount.saturating_add(fog_pool.payables),
+            ShadeError::LiquidityReserved
+        );
+
+        let burn_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            Burn {
+                mint: ctx.accounts.lp_mint.to_account_info(),
+                from: ctx.accounts.owner_lp_account.to_account_info(),
+                authority: ctx.accounts.owner.to_account_info(),
+            },
+        );
+        token_interface::burn(burn_ctx, shares)?;
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.total_withdrawn = fog_pool
+            .total_withdrawn
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        let position = &mut ctx.accounts.lp_position;
+        if position.owner == Pubkey::default() {
+            position.owner = ctx.accounts.owner.key();
+            position.fog_pool = fog_pool.key();
+            position.bump = ctx.bumps.lp_position;
+        }
+        position.total_withdrawn = position
+            .total_withdrawn
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        if amount > 0 {
+            let fog_pool = &ctx.accounts.fog_pool;
+            let pool_key = fog_pool.key();
+            let seeds = &[
+                b"pool_vault_authority".as_ref(),
+                pool_key.as_ref(),
+                &[fog_pool.vault_authority_bump],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.owner_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            );
+            token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+        }
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            WithdrawalMade {
+                pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                owner: ctx.accounts.owner.key(),
+                shares,
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Pause spends and new authorizations on this pool (pool authority or operator)
+    /// An operational kill switch that doesn't affect other pools; like the
+    /// incident modes it is lifted through resume_fog_pool, so a single
+    /// compromised key can't both pause and unpause
+    pub fn pause_fog_pool(ctx: Context<PauseFogPool>) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        require!(
+            fog_pool.status == PoolStatus::Active,
+            ShadeError::PoolNotActive
+        );
+        fog_pool.status = PoolStatus::Paused;
+        fog_pool.resume_guard.resume_requested_at = 0;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolStatusChanged {
+                pool: fog_pool.key(),
+                sequence,
+                status: fog_pool.status,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Put the pool into withdraw-only incident mode (pool authority only)
+    /// While withdraw-only, new authorizations and spends are blocked but
+    /// LP withdrawals and claim flows stay open
+    pub fn enter_withdraw_only(ctx: Context<UpdateFogPool>) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.status = PoolStatus::WithdrawOnly;
+        fog_pool.resume_guard.resume_requested_at = 0;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolStatusChanged {
+                pool: fog_pool.key(),
+                sequence,
+                status: fog_pool.status,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Announce the intent to resume a paused or restricted pool, starting the timelock
+    pub fn announce_pool_resume(ctx: Context<UpdateFogPool>) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        require!(fog_pool.status != PoolStatus::Active, ShadeError::NotPaused);
+
+        let now = Clock::get()?.unix_timestamp;
+        fog_pool.resume_guard.resume_requested_at = now;
+
+        emit_event!(
+            ctx.accounts,
+            ResumeAnnounced {
+                target: fog_pool.key(),
+                eligible_at: now + RESUME_TIMELOCK,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Return a paused or restricted pool to normal operation
+    /// Needs either the pool guardian's co-signature or an announcement
+    /// made at least RESUME_TIMELOCK ago
+    pub fn resume_fog_pool(ctx: Context<ResumeFogPool>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let guardian = ctx.accounts.guardian.as_ref().map(|g| g.key());
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        require!(fog_pool.status != PoolStatus::Active, ShadeError::NotPaused);
+        fog_pool.resume_guard.authorize_resume(guardian, now)?;
+        fog_pool.status = PoolStatus::Active;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolStatusChanged {
+                pool: fog_pool.key(),
+                sequence,
+                status: fog_pool.status,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the pool's guardian, the second key that can approve an instant resume
+    /// Replacing an existing guardian needs its co-signature
+    pub fn set_pool_guardian(ctx: Context<SetPoolGuardian>, guardian: Pubkey) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let current_guardian = ctx.accounts.current_guardian.as_ref().map(|g| g.key());
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        require!(
+            fog_pool.status == PoolStatus::Active,
+            ShadeError::PoolNotActive
+        );
+        fog_pool
+            .resume_guard
+            .set_guardian(guardian, current_guardian, now)?;
+
+        emit_event!(
+            ctx.accounts,
+            GuardianUpdated {
+                target: fog_pool.key(),
+                guardian,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the pool's operator (pool authority only); Pubkey::default() removes it
+    /// The operator can pause the pool and revoke its authorizations, but can't
+    /// unpause, move funds or change the authority
+    pub fn set_pool_operator(ctx: Context<UpdateFogPool>, operator: Pubkey) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.operator = operator;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolOperatorUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                operator,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the Pyth feed pricing the pool mint in USD, with the oldest update
+    /// and widest confidence interval spends accept (pool authority only)
+    /// An all-zero feed id removes it; USD-cap authorizations can't spend then
+    pub fn set_price_feed(
+        ctx: Context<UpdateFogPool>,
+        price_feed_id: [u8; 32],
+        max_price_age: u32,
+        max_price_conf_bps: u16,
+    ) -> Result<()> {
+        require!(
+            price_feed_id == [0u8; 32]
+                || (max_price_age > 0 && max_price_conf_bps > 0 && max_price_conf_bps <= 10000),
+            ShadeError::InvalidPriceFeed
+        );
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.price_feed_id = price_feed_id;
+        fog_pool.max_price_age = max_price_age;
+        fog_pool.max_price_conf_bps = max_price_conf_bps;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PriceFeedUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                price_feed_id,
+                max_price_age,
+                max_price_conf_bps,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Re-check the spender's staking tier on every spend (pool authority
+    /// only), so unstaking after issuance can't keep a cap the tier no longer
+    /// allows. Spends must pass the spender's Staker and an authorization's
+    /// cumulative spending must stay within the current tier's cap limit
+    pub fn set_spend_tier_check(ctx: Context<UpdateFogPool>, enabled: bool) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.spend_tier_check = enabled;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SpendTierCheckUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                enabled,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Gate the pool on Solana Attestation Service credentials (pool authority
+    /// only): authorizations and spends need a live attestation of `subject`
+    /// issued under `credential` (and `schema`, unless default). A default
+    /// credential removes the requirement
+    pub fn set_attestation_requirement(
+        ctx: Context<UpdateFogPool>,
+        credential: Pubkey,
+        schema: Pubkey,
+        subject: AttestationSubject,
+    ) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.attestation_credential = credential;
+        fog_pool.attestation_schema = schema;
+        fog_pool.attestation_subject = subject;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AttestationRequirementUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                credential,
+                schema,
+                subject,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the pool owner's cut of the pool's spend fees and where it is paid
+    /// (pool authority only), capped at MAX_POOL_FEE_BPS
+    pub fn set_pool_fee(ctx: Context<SetPoolFee>, pool_fee_bps: u16) -> Result<()> {
+        require!(pool_fee_bps <= MAX_POOL_FEE_BPS, ShadeError::FeeTooHigh);
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.pool_fee_bps = pool_fee_bps;
+        fog_pool.pool_fee_vault = ctx.accounts.pool_fee_vault.key();
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolFeeUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                pool_fee_bps,
+                pool_fee_vault: fog_pool.pool_fee_vault,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the pool's utilization fee curve (pool authority only)
+    /// Spend fees rise with utilization, the pool's committed liquidity over
+    /// its vault balance net of pending fees, by up to slope_bps at full
+    /// utilization and never above ceiling_bps (max 10%); the extra fee stays
+    /// in the vault for LPs. A zero slope turns the curve off
+    pub fn set_fee_curve(
+        ctx: Context<UpdateFogPool>,
+        slope_bps: u16,
+        ceiling_bps: u16,
+    ) -> Result<()> {
+        require!(ceiling_bps <= 1000, ShadeError::FeeTooHigh); // Max 10%
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.fee_curve_slope_bps = slope_bps;
+        fog_pool.fee_ceiling_bps = ceiling_bps;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            FeeCurveUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                slope_bps,
+                ceiling_bps,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Cap the pool's utilization (pool authority only): spends fail once
+    /// committed liquidity plus the spend would exceed max_utilization_bps of
+    /// the vault balance net of pending fees, so LPs keep a withdrawal buffer.
+    /// 0 removes the cap
+    pub fn set_max_utilization(
+        ctx: Context<UpdateFogPool>,
+        max_utilization_bps: u16,
+    ) -> Result<()> {
+        require!(
+            max_utilization_bps <= 10000,
+            ShadeError::InvalidUtilizationCap
+        );
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.max_utilization_bps = max_utilization_bps;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            MaxUtilizationUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                max_utilization_bps,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Cap the LP liquidity the pool takes and optionally close it to
+    /// depositors outside its allowlist (pool authority only), for closed
+    /// treasury pools. A cap of 0 removes it
+    pub fn set_deposit_limits(
+        ctx: Context<UpdateFogPool>,
+        max_total_deposits: u64,
+        depositor_allowlist: bool,
+    ) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.max_total_deposits = max_total_deposits;
+        fog_pool.depositor_allowlist = depositor_allowlist;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            DepositLimitsUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                max_total_deposits,
+                depositor_allowlist,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Add a depositor to the pool's allowlist (pool authority only)
+    pub fn add_allowed_depositor(
+        ctx: Context<AddAllowedDepositor>,
+        depositor: Pubkey,
+    ) -> Result<()> {
+        let entry = &mut ctx.accounts.allowed_depositor;
+        entry.fog_pool = ctx.accounts.fog_pool.key();
+        entry.depositor = depositor;
+        entry.added_at = Clock::get()?.unix_timestamp;
+        entry.bump = ctx.bumps.allowed_depositor;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            DepositorAllowlistUpdated {
+                fog_pool: entry.fog_pool,
+                sequence,
+                depositor,
+                allowed: true,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Remove a depositor from the pool's allowlist (pool authority only);
+    /// their LP shares stay withdrawable
+    pub fn remove_allowed_depositor(ctx: Context<RemoveAllowedDepositor>) -> Result<()> {
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            DepositorAllowlistUpdated {
+                fog_pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                depositor: ctx.accounts.allowed_depositor.depositor,
+                allowed: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Bound the size of a single spend from the pool (pool authority only),
+    /// independently of authorization caps: a minimum filters dust spam and a
+    /// maximum caps per-transaction exposure. 0 removes either bound
+    pub fn set_spend_limits(
+        ctx: Context<UpdateFogPool>,
+        min_spend: u64,
+        max_single_spend: u64,
+    ) -> Result<()> {
+        require!(
+            max_single_spend == 0 || min_spend <= max_single_spend,
+            ShadeError::InvalidSpendLimits
+        );
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.min_spend = min_spend;
+        fog_pool.max_single_spend = max_single_spend;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SpendLimitsUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                min_spend,
+                max_single_spend,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Propose a new pool authority (pool authority only)
+    /// The handover completes only when the new key calls accept_pool_authority;
+    /// proposing the default pubkey cancels a pending proposal
+    pub fn propose_pool_authority(
+        ctx: Context<UpdateFogPool>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.pending_authority = new_authority;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolAuthorityProposed {
+                fog_pool: fog_pool.key(),
+                sequence,
+                authority: fog_pool.authority,
+                pending_authority: new_authority,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Accept a pending pool authority proposal (proposed key only)
+    /// Existing authorizations keep their original issuer
+    pub fn accept_pool_authority(ctx: Context<AcceptPoolAuthority>) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        let old_authority = fog_pool.authority;
+        fog_pool.authority = ctx.accounts.new_authority.key();
+        fog_pool.pending_authority = Pubkey::default();
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolAuthorityTransferred {
+                fog_pool: fog_pool.key(),
+                sequence,
+                old_authority,
+                new_authority: fog_pool.authority,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Let an operator issue authorizations from the pool (pool authority only)
+    /// The operator gets no other control over the pool
+    pub fn add_issuer(ctx: Context<AddIssuer>, operator: Pubkey) -> Result<()> {
+        let delegate = &mut ctx.accounts.issuer_delegate;
+        delegate.fog_pool = ctx.accounts.fog_pool.key();
+        delegate.operator = operator;
+        delegate.added_at = Clock::get()?.unix_timestamp;
+        delegate.bump = ctx.bumps.issuer_delegate;
+        delegate.templates_only = false;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            IssuerDelegateUpdated {
+                fog_pool: delegate.fog_pool,
+                sequence,
+                operator,
+                enabled: true,
+                templates_only: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Withdraw an operator's permission to issue authorizations (pool authority only)
+    /// Authorizations it already issued stay valid and remain under its control
+    pub fn remove_issuer(ctx: Context<RemoveIssuer>) -> Result<()> {
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            IssuerDelegateUpdated {
+                fog_pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                operator: ctx.accounts.issuer_delegate.operator,
+                enabled: false,
+                templates_only: ctx.accounts.issuer_delegate.templates_only,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Restrict an issuer delegate to issuing from authorization templates,
+    /// or lift the restriction (pool authority only)
+    pub fn set_issuer_templates_only(
+        ctx: Context<SetIssuerTemplatesOnly>,
+        templates_only: bool,
+    ) -> Result<()> {
+        let delegate = &mut ctx.accounts.issuer_delegate;
+        delegate.templates_only = templates_only;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            IssuerDelegateUpdated {
+                fog_pool: delegate.fog_pool,
+                sequence,
+                operator: delegate.operator,
+                enabled: true,
+                templates_only,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Allow a partner program's PDAs to hold authorizations from the pool
+    /// (pool authority only)
+    pub fn register_program(ctx: Context<RegisterProgram>, program_id: Pubkey) -> Result<()> {
+        require!(program_id != crate::ID, ShadeError::InvalidProgramSpender);
+
+        let registered = &mut ctx.accounts.registered_program;
+        registered.fog_pool = ctx.accounts.fog_pool.key();
+        registered.program_id = program_id;
+        registered.added_at = Clock::get()?.unix_timestamp;
+        registered.bump = ctx.bumps.registered_program;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            ProgramRegistryUpdated {
+                fog_pool: registered.fog_pool,
+                sequence,
+                program_id,
+                enabled: true,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Stop issuing authorizations to a partner program's PDAs (pool authority only)
+    /// Authorizations already held by its PDAs stay valid until revoked
+    pub fn unregister_program(ctx: Context<UnregisterProgram>) -> Result<()> {
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            ProgramRegistryUpdated {
+                fog_pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                program_id: ctx.accounts.registered_program.program_id,
+                enabled: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Record which registered program a PDA spender belongs to (permissionless)
+    /// `seeds` are the PDA's full seeds including the bump; the spender must be
+    /// derived from them under the registered program id
+    pub fn register_program_spender(
+        ctx: Context<RegisterProgramSpender>,
+        seeds: Vec<Vec<u8>>,
+    ) -> Result<()> {
+        let program_id = ctx.accounts.registered_program.program_id;
+        let seed_refs: Vec<&[u8]> = seeds.iter().map(|seed| seed.as_slice()).collect();
+        let derived = Pubkey::create_program_address(&seed_refs, &program_id)
+            .map_err(|_| ShadeError::InvalidProgramSpender)?;
+        require!(
+            derived == ctx.accounts.spender.key(),
+            ShadeError::InvalidProgramSpender
+        );
+
+        let program_spender = &mut ctx.accounts.program_spender;
+        program_spender.fog_pool = ctx.accounts.fog_pool.key();
+        program_spender.spender = derived;
+        program_spender.program_id = program_id;
+        program_spender.bump = ctx.bumps.program_spender;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            ProgramSpenderRegistered {
+                fog_pool: program_spender.fog_pool,
+                sequence,
+                spender: derived,
+                program_id,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Split a pool's accrued spend fees (permissionless crank)
+    /// Spends leave fees in the pool vault so they don't all write the shared
+    /// fee vault and config. The caller keeps keeper_reward_bps and the pool
+    /// owner pool_fee_bps; the rest is split by the fee split: the staker
+    /// share goes to the fee vault, the LP share stays in the pool vault as
+    /// yield and the treasury share goes to the fee treasury
+    pub fn sweep_fees(ctx: Context<SweepFees>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let fog_pool = &ctx.accounts.fog_pool;
+        let amount = fog_pool.pending_fees;
+        require!(amount > 0, ShadeError::InvalidAmount);
+        let keeper_reward = spend_fee(config.keeper_reward_bps, amount)?;
+        let owner_share = spend_fee(fog_pool.pool_fee_bps, amount - keeper_reward)?;
+        let net = amount - keeper_reward - owner_share;
+        let lp_share = spend_fee(config.lp_share_bps, net)?;
+        let treasury_share = spend_fee(config.treasury_share_bps, net)?;
+        let staker_share = net - lp_share - treasury_share;
+
+        let pool_key = fog_pool.key();
+        let seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[fog_pool.vault_authority_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let mut payouts = vec![
+            (
+                ctx.accounts.keeper_token_account.to_account_info(),
+                keeper_reward,
+            ),
+            (ctx.accounts.fee_vault.to_account_info(), staker_share),
+        ];
+        if owner_share > 0 {
+            let pool_fee_vault = ctx
+                .accounts
+                .pool_fee_vault
+                .as_ref()
+                .ok_or(ShadeError::InvalidVaultAuthority)?;
+            payouts.push((pool_fee_vault.to_account_info(), owner_share));
+        }
+        if treasury_share > 0 {
+            let fee_treasury = ctx
+                .accounts
+                .fee_treasury
+                .as_ref()
+                .ok_or(ShadeError::InvalidFeeSplit)?;
+            payouts.push((fee_treasury.to_account_info(), treasury_share));
+        }
+
+        let decimals = ctx.accounts.mint.decimals;
+        let fee_vault_balance = ctx.accounts.fee_vault.amount;
+        for (to, share) in payouts {
+            if share == 0 {
+                continue;
+            }
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to,
+                    authority: ctx.accounts.pool_vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            );
+            token_interface::transfer_checked(transfer_ctx, share, decimals)?;
+        }
+
+        // Stakers are credited with what reached the fee vault, net of any
+        // Token-2022 transfer fee
+        let staker_share = received_amount(&mut ctx.accounts.fee_vault, fee_vault_balance)?;
+
+        // The LP share stays in the vault; clearing pending fees releases it to LPs
+        ctx.accounts.fog_pool.pending_fees = 0;
+        book_protocol_fee(&mut ctx.accounts.protocol_config, staker_share)?;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            FeesSwept {
+                fog_pool: pool_key,
+                sequence,
+                amount,
+                keeper_reward,
+                owner_share,
+                staker_share,
+                lp_share,
+                treasury_share,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Copy the protocol fee rate, tier fee rebates and caps and circuit
+    /// breaker into a pool (permissionless)
+    /// Spends read the pool's cached fee settings, so fee changes reach a pool
+    /// once it is synced
+    pub fn sync_pool_config(ctx: Context<SyncPoolConfig>) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.sync_config(&ctx.accounts.protocol_config);
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PoolConfigSynced {
+                fog_pool: fog_pool.key(),
+                sequence,
+                fee_basis_points: fog_pool.fee_basis_points,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Start recording the pool's spends in a ring of the last `capacity`
+    /// spends (pool authority only)
+    /// Every spend must then pass the ring
+    pub fn init_spend_ring(ctx: Context<InitSpendRing>, capacity: u32) -> Result<()> {
+        require!(
+            capacity > 0 && capacity <= MAX_SPEND_RING_CAPACITY,
+            ShadeError::InvalidSpendRingCapacity
+        );
+
+        let mut ring = ctx.accounts.spend_ring.load_init()?;
+        ring.fog_pool = ctx.accounts.fog_pool.key();
+        ring.total_recorded = 0;
+        ring.capacity = capacity;
+        ring.bump = ctx.bumps.spend_ring;
+        drop(ring);
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.spend_ring = ctx.accounts.spend_ring.key();
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SpendRingUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                spend_ring: fog_pool.spend_ring,
+                capacity,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Change the number of spends the pool's ring holds (pool authority only)
+    /// The account is reallocated and the recorded spends are cleared
+    pub fn resize_spend_ring(ctx: Context<ResizeSpendRing>, capacity: u32) -> Result<()> {
+        require!(
+            capacity > 0 && capacity <= MAX_SPEND_RING_CAPACITY,
+            ShadeError::InvalidSpendRingCapacity
+        );
+
+        let mut ring = ctx.accounts.spend_ring.load_mut()?;
+        ring.total_recorded = 0;
+        ring.capacity = capacity;
+        drop(ring);
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SpendRingUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                spend_ring: fog_pool.spend_ring,
+                capacity,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Stop recording the pool's spends and close the ring (pool authority only)
+    pub fn close_spend_ring(ctx: Context<CloseSpendRing>) -> Result<()> {
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.spend_ring = Pubkey::default();
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SpendRingUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                spend_ring: Pubkey::default(),
+                capacity: 0,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // AUTHORIZATIONS
+    // ========================================================================
+
+    /// Create a spending authorization - permission to spend from the fog
+    /// The spending cap is validated against the spender's staking tier
+    /// The issuer is the pool authority or one of its issuer delegates
+    pub fn create_authorization(
+        ctx: Context<CreateAuthorization>,
+        _nonce: u64,
+        spending_cap: u64,
+        expires_at: i64,
+        purpose: String,
+    ) -> Result<()> {
+        process_create_authorization(ctx.accounts, &ctx.bumps, spending_cap, expires_at, purpose)
+    }
+
+    /// Create a streaming authorization - the spending cap unlocks linearly
+    /// from creation until `expires_at`, so the spender can only draw what
+    /// has accrued so far (payroll, continuous vendor payments)
+    pub fn create_stream(
+        ctx: Context<CreateAuthorization>,
+        _nonce: u64,
+        spending_cap: u64,
+        expires_at: i64,
+        purpose: String,
+    ) -> Result<()> {
+        process_create_authorization(ctx.accounts, &ctx.bumps, spending_cap, expires_at, purpose)?;
+
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.streaming = true;
+
+        emit_event!(
+            ctx.accounts,
+            StreamCreated {
+                authorization: authorization.key(),
+                spending_cap,
+                starts_at: authorization.created_at,
+                ends_at: expires_at,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Create a subscription - an authorization the spender (e.g. a merchant)
+    /// can pull up to `per_period_cap` from in each billing period
+    /// The allowance resets automatically every `period_length` seconds from
+    /// creation and runs for `periods` periods; the lifetime cap of
+    /// per_period_cap * periods is validated against the spender's tier
+    pub fn create_subscription(
+        ctx: Context<CreateAuthorization>,
+        _nonce: u64,
+        period_length: i64,
+        per_period_cap: u64,
+        periods: u32,
+        purpose: String,
+    ) -> Result<()> {
+        require!(
+            period_length > 0 && per_period_cap > 0 && periods > 0,
+            ShadeError::InvalidVelocityLimits
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let spending_cap = per_period_cap
+            .checked_mul(periods as u64)
+            .ok_or(ShadeError::Overflow)?;
+        let expires_at = period_length
+            .checked_mul(periods as i64)
+            .and_then(|duration| now.checked_add(duration))
+            .ok_or(ShadeError::Overflow)?;
+
+        process_create_authorization(ctx.accounts, &ctx.bumps, spending_cap, expires_at, purpose)?;
+
+        // The billing period is the authorization's first velocity window
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.velocity_windows[0] = VelocityWindow {
+            window_length: period_length,
+            max_amount: per_period_cap,
+            rollover_bps: 0,
+            window_start: now,
+            spent: 0,
+            carried_over: 0,
+        };
+
+        emit_event!(
+            ctx.accounts,
+            SubscriptionCreated {
+                authorization: authorization.key(),
+                period_length,
+                per_period_cap,
+                periods,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Commit a merkle root of authorizations the pool's spenders can claim
+    /// themselves (pool authority only)
+    /// Leaves are sha256(0x00 || index (u64 LE) || spender || spending_cap (u64 LE)
+    /// || expires_at (i64 LE))
+    pub fn create_authorization_campaign(
+        ctx: Context<CreateAuthorizationCampaign>,
+        campaign_id: u64,
+        merkle_root: [u8; 32],
+        purpose: String,
+    ) -> Result<()> {
+        require!(purpose.len() <= 64, ShadeError::PurposeTooLong);
+        require!(
+            ctx.accounts.fog_pool.status == PoolStatus::Active,
+            ShadeError::PoolNotActive
+        );
+
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.fog_pool = ctx.accounts.fog_pool.key();
+        campaign.campaign_id = campaign_id;
+        campaign.merkle_root = merkle_root;
+        campaign.issuer = ctx.accounts.authority.key();
+        campaign.purpose = purpose;
+        campaign.claimed = 0;
+        campaign.created_at = Clock::get()?.unix_timestamp;
+        campaign.bump = ctx.bumps.campaign;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationCampaignCreated {
+                campaign: campaign.key(),
+                fog_pool: campaign.fog_pool,
+                sequence,
+                campaign_id,
+                merkle_root,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Materialize a campaign entry as an Authorization PDA
+    /// Anyone can claim on the spender's behalf; the payer covers rent, and a
+    /// claim receipt per leaf index prevents double claims
+    pub fn claim_authorization(
+        ctx: Context<ClaimAuthorization>,
+        _nonce: u64,
+        index: u64,
+        spending_cap: u64,
+        expires_at: i64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let campaign = &ctx.accounts.campaign;
+        let leaf = authorization_campaign_leaf(
+            index,
+            &ctx.accounts.create.spender.key(),
+            spending_cap,
+            expires_at,
+        );
+        require!(
+            verify_merkle_proof(&proof, &campaign.merkle_root, leaf),
+            ShadeError::InvalidMerkleProof
+        );
+        require!(
+            campaign.issuer == ctx.accounts.create.fog_pool.authority,
+            ShadeError::Unauthorized
+        );
+
+        issue_authorization(
+            &mut ctx.accounts.create,
+            &ctx.bumps.create,
+            campaign.issuer,
+            spending_cap,
+            expires_at,
+            campaign.purpose.clone(),
+        )?;
+
+        let authorization = ctx.accounts.create.authorization.key();
+        let receipt = &mut ctx.accounts.claim_receipt;
+        receipt.authorization = authorization;
+        receipt.claimed_at = Clock::get()?.unix_timestamp;
+
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.claimed = campaign
+            .claimed
+            .checked_add(1)
+            .ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            AuthorizationClaimed {
+                campaign: campaign.key(),
+                authorization,
+                spender: ctx.accounts.create.spender.key(),
+                index,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Close an authorization campaign so no further entries can be claimed
+    /// Already-claimed authorizations are unaffected
+    pub fn close_authorization_campaign(ctx: Context<CloseAuthorizationCampaign>) -> Result<()> {
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationCampaignClosed {
+                campaign: ctx.accounts.campaign.key(),
+                fog_pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                claimed: ctx.accounts.campaign.claimed,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Save an authorization policy the pool can issue repeatedly
+    /// (pool authority only)
+    /// Authorizations issued from it get the template's cap, purpose, recipient
+    /// allowlist and use limit, and expire `duration` seconds after issuance
+    pub fn create_authorization_template(
+        ctx: Context<CreateAuthorizationTemplate>,
+        template_id: u64,
+        spending_cap: u64,
+        duration: i64,
+        purpose: String,
+        allowlist_root: [u8; 32],
+        max_uses: u32,
+    ) -> Result<()> {
+        require!(spending_cap > 0, ShadeError::InvalidAmount);
+        require!(duration > 0, ShadeError::InvalidExpiry);
+        require!(purpose.len() <= 64, ShadeError::PurposeTooLong);
+
+        let template = &mut ctx.accounts.template;
+        template.fog_pool = ctx.accounts.fog_pool.key();
+        template.template_id = template_id;
+        template.spending_cap = spending_cap;
+        template.duration = duration;
+        template.purpose = purpose;
+        template.allowlist_root = allowlist_root;
+        template.max_uses = max_uses;
+        template.issued = 0;
+        template.bump = ctx.bumps.template;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationTemplateUpdated {
+                template: template.key(),
+                fog_pool: template.fog_pool,
+                sequence,
+                template_id,
+                spending_cap,
+                duration,
+                closed: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Close an authorization template and reclaim its rent (pool authority only)
+    /// Authorizations already issued from it are unaffected
+    pub fn close_authorization_template(ctx: Context<CloseAuthorizationTemplate>) -> Result<()> {
+        let template = &ctx.accounts.template;
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationTemplateUpdated {
+                template: template.key(),
+                fog_pool: template.fog_pool,
+                sequence,
+                template_id: template.template_id,
+                spending_cap: template.spending_cap,
+                duration: template.duration,
+                closed: true,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Issue an authorization to `spender` from a template
+    /// The issuer is the pool authority or one of its issuer delegates,
+    /// including delegates restricted to templates
+    pub fn issue_from_template(ctx: Context<IssueFromTemplate>, _nonce: u64) -> Result<()> {
+        let create = &mut ctx.accounts.create;
+        require!(
+            create.issuer.key() == create.fog_pool.authority || create.issuer_delegate.is_some(),
+            ShadeError::Unauthorized
+        );
+
+        let template = &mut ctx.accounts.template;
+        let expires_at = Clock::get()?
+            .unix_timestamp
+            .checked_add(template.duration)
+            .ok_or(ShadeError::Overflow)?;
+        let issuer = create.issuer.key();
+        issue_authorization(
+            create,
+            &ctx.bumps.create,
+            issuer,
+            template.spending_cap,
+            expires_at,
+            template.purpose.clone(),
+        )?;
+
+        let authorization = &mut create.authorization;
+        authorization.allowlist_root = template.allowlist_root;
+        authorization.max_uses = template.max_uses;
+        template.issued = template.issued.checked_add(1).ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            AuthorizationIssuedFromTemplate {
+                template: ctx.accounts.template.key(),
+                authorization: ctx.accounts.create.authorization.key(),
+                spender: ctx.accounts.create.spender.key(),
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Spend using an authorization - the core of SHADE
+    /// Takes a protocol fee that goes to stakers
+    /// `recipient_proof` is only needed when the authorization has a recipient allowlist
+    /// `reference` (e.g. an invoice id) is echoed in SpendExecuted, and written
+    /// as a memo when the memo program is passed
+    /// Returns the fee, net amount and remaining cap so CPI callers can act on them
+    pub fn spend(
+        ctx: Context<Spend>,
+        amount: u64,
+        recipient_proof: Vec<[u8; 32]>,
+        reference: Option<[u8; 32]>,
+    ) -> Result<SpendReceipt> {
+        process_spend(
+            ctx.accounts,
+            amount,
+            false,
+            &recipient_proof,
+            None,
+            reference,
+        )
+    }
+
+    /// Revoke an authorization (issuer or the pool operator)
+    pub fn revoke_authorization(ctx: Context<RevokeAuthorization>) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        require!(authorization.is_active, ShadeError::AuthorizationInactive);
+
+        authorization.is_active = false;
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        retire_authorization(
+            authorization,
+            fog_pool,
+            &mut ctx.accounts.spender_index_head,
+            &mut ctx.accounts.spender_index,
+            &mut ctx.accounts.pool_index_page,
+        );
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationRevoked {
+                authorization: authorization.key(),
+                fog_pool: fog_pool.key(),
+                sequence,
+                revoked_by: ctx.accounts.issuer.key(),
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Revoke every authorization a spender holds on a pool in one call, e.g.
+    /// after the spender's key is compromised. Each authorization is passed in
+    /// remaining_accounts followed by the pool index page and the spender
+    /// index page it is listed on (all writable). The signer must be allowed
+    /// to revoke each one
+    pub fn revoke_all_for_spender<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RevokeAllForSpender<'info>>,
+        spender: Pubkey,
+    ) -> Result<()> {
+        let remaining = ctx.remaining_accounts;
+        require!(
+            !remaining.is_empty() && remaining.len().is_multiple_of(3),
+            ShadeError::InvalidBulkRevoke
+        );
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        let revoked_by = ctx.accounts.issuer.key();
+        let mut revoked = Vec::with_capacity(remaining.len() / 3);
+        for triple in remaining.chunks(3) {
+            require!(
+                triple.iter().all(|account| account.is_writable),
+                ShadeError::InvalidBulkRevoke
+            );
+            let mut authorization = Account::<Authorization>::try_from(&triple[0])?;
+            require_keys_eq!(
+                authorization.fog_pool,
+                fog_pool.key(),
+                ShadeError::InvalidBulkRevoke
+            );
+            require_keys_eq!(
+                authorization.authorized_spender,
+                spender,
+                ShadeError::InvalidBulkRevoke
+            );
+            require!(
+                authorization.issuer == revoked_by || fog_pool.operator == revoked_by,
+                ShadeError::Unauthorized
+            );
+            require!(authorization.is_active, ShadeError::AuthorizationInactive);
+
+            let mut pool_index_page = Account::<PoolAuthorizationPage>::try_from(&triple[1])?;
+            require_keys_eq!(
+                pool_index_page.fog_pool,
+                fog_pool.key(),
+                ShadeError::InvalidBulkRevoke
+            );
+            require!(
+                pool_index_page.page == authorization.index_page,
+                ShadeError::InvalidBulkRevoke
+            );
+
+            let mut spender_index = Account::<SpenderIndex>::try_from(&triple[2])?;
+            require_keys_eq!(
+                spender_index.spender,
+                spender,
+                ShadeError::InvalidBulkRevoke
+            );
+            require!(
+                spender_index.page == authorization.spender_index_page,
+                ShadeError::InvalidBulkRevoke
+            );
+
+            authorization.is_active = false;
+            retire_authorization(
+                &authorization,
+                fog_pool,
+                &mut ctx.accounts.spender_index_head,
+                &mut spender_index,
+                &mut pool_index_page,
+            );
+            authorization.exit(&crate::ID)?;
+            pool_index_page.exit(&crate::ID)?;
+            spender_index.exit(&crate::ID)?;
+            revoked.push(authorization.key());
+        }
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationsRevokedForSpender {
+                fog_pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                spender,
+                authorizations: revoked,
+                revoked_by,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Close a finished authorization and return its rent to the issuer
+    /// The issuer can close once it is revoked, fully spent or expired;
+    /// anyone can close it after expiry
+    pub fn close_authorization(ctx: Context<CloseAuthorization>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let authorization = &ctx.accounts.authorization;
+        let finished = !authorization.is_active
+            || (authorization.budget_window == 0
+                && authorization.amount_spent >= authorization.spending_cap);
+        require!(
+            now >= authorization.expires_at
+                || (finished && ctx.accounts.closer.key() == authorization.issuer),
+            ShadeError::AuthorizationStillUsable
+        );
+        // The approval policy PDA would be unreachable once the authorization is gone
+        require!(
+            authorization.approval_min_amount == 0 || ctx.accounts.approval_policy.is_some(),
+            ShadeError::InvalidApprovalPolicy
+        );
+
+        // Revoked authorizations were already removed from the indexes
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        if authorization.is_active {
+            retire_authorization(
+                authorization,
+                fog_pool,
+                &mut ctx.accounts.spender_index_head,
+                &mut ctx.accounts.spender_index,
+                &mut ctx.accounts.pool_index_page,
+            );
+        }
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationClosed {
+                authorization: authorization.key(),
+                fog_pool: fog_pool.key(),
+                sequence,
+                closed_by: ctx.accounts.closer.key(),
+                amount_spent: authorization.amount_spent,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Record the unused budget of an expired authorization for accounting
+    /// (issuer only), optionally closing it to reclaim its rent as well
+    pub fn reclaim_authorization_budget(
+        ctx: Context<ReclaimAuthorizationBudget>,
+        close: bool,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let authorization = &ctx.accounts.authorization;
+        require!(
+            now >= authorization.expires_at,
+            ShadeError::AuthorizationStillUsable
+        );
+        require!(
+            !authorization.budget_reclaimed,
+            ShadeError::BudgetAlreadyReclaimed
+        );
+        require!(
+            !close
+                || authorization.approval_min_amount == 0
+                || ctx.accounts.approval_policy.is_some(),
+            ShadeError::InvalidApprovalPolicy
+        );
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        if authorization.is_active {
+            retire_authorization(
+                authorization,
+                fog_pool,
+                &mut ctx.accounts.spender_index_head,
+                &mut ctx.accounts.spender_index,
+                &mut ctx.accounts.pool_index_page,
+            );
+        }
+        let unused = authorization
+            .spending_cap
+            .saturating_sub(authorization.amount_spent);
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationBudgetReclaimed {
+                authorization: authorization.key(),
+                fog_pool: fog_pool.key(),
+                sequence,
+                spending_cap: authorization.spending_cap,
+                amount_spent: authorization.amount_spent,
+                unused,
+                closed: close,
+            }
+        );
+
+        let issuer = ctx.accounts.issuer.to_account_info();
+        if close {
+            if let Some(policy) = &ctx.accounts.approval_policy {
+                policy.close(issuer.clone())?;
+            }
+            ctx.accounts.authorization.close(issuer)?;
+        } else {
+            let authorization = &mut ctx.accounts.authorization;
+            authorization.is_active = false;
+            authorization.budget_reclaimed = true;
+        }
+
+        Ok(())
+    }
+
+    /// Mark an expired authorization inactive (permissionless crank)
+    /// Drops it from the indexes and pool stats and pays the caller the
+    /// configured keeper reward from the fee vault
+    pub fn expire_authorization(ctx: Context<ExpireAuthorization>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let authorization = &mut ctx.accounts.authorization;
+        require!(authorization.is_active, ShadeError::AuthorizationInactive);
+        require!(
+            now >= authorization.expires_at,
+            ShadeError::AuthorizationStillUsable
+        );
+
+        authorization.is_active = false;
+        retire_authorization(
+            authorization,
+            &mut ctx.accounts.fog_pool,
+            &mut ctx.accounts.spender_index_head,
+            &mut ctx.accounts.spender_index,
+            &mut ctx.accounts.pool_index_page,
+        );
+
+        // Pay what the vault can cover; cleanup never fails for lack of a reward
+        let config = &ctx.accounts.protocol_config;
+        let reward = config.keeper_reward.min(ctx.accounts.fee_vault.amount);
+        if reward > 0 {
+            let config_key = config.key();
+            let seeds = &[
+                b"vault_authority".as_ref(),
+                config_key.as_ref(),
+                &[config.vault_authority_bump][..],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.fee_vault.to_account_info(),
+                    mint: ctx.accounts.fee_mint.to_account_info(),
+                    to: ctx.accounts.keeper_token_account.to_account_info(),
+                    authority: ctx.accounts.vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            );
+            token_interface::transfer_checked(
+                transfer_ctx,
+                reward,
+                ctx.accounts.fee_mint.decimals,
+            )?;
+        }
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationExpiryProcessed {
+                authorization: ctx.accounts.authorization.key(),
+                fog_pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                keeper: ctx.accounts.keeper.key(),
+                reward,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Amend an authorization in place instead of revoking and recreating it
+    /// (issuer only). The expiry can only be extended; the cap can be raised
+    /// (re-validated against the spender's tier) or lowered down to what has
+    /// already been spent
+    pub fn amend_authorization(
+        ctx: Context<AmendAuthorization>,
+        spending_cap: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        let authorization = &ctx.accounts.authorization;
+        require!(authorization.is_active, ShadeError::AuthorizationInactive);
+        require!(
+            expires_at >= authorization.expires_at,
+            ShadeError::InvalidExpiry
+        );
+        require!(
+            spending_cap > 0 && spending_cap >= authorization.amount_spent,
+            ShadeError::InvalidAmount
+        );
+        if spending_cap > authorization.spending_cap {
+            let delegator = tier_delegator(
+                &ctx.accounts.tier_delegation,
+                &ctx.accounts.delegator_staker,
+                ctx.accounts.authorization.authorized_spender,
+            )?;
+            check_tier_cap(
+                &ctx.accounts.protocol_config,
+                &ctx.accounts.staker,
+                &ctx.accounts.source_staker,
+                delegator,
+                spending_cap,
+            )?;
+        }
+
+        let authorization = &mut ctx.accounts.authorization;
+        let old_spending_cap = authorization.spending_cap;
+        let old_expires_at = authorization.expires_at;
+        authorization.spending_cap = spending_cap;
+        authorization.expires_at = expires_at;
+        if !authorization.cap_in_usd {
+            let fog_pool = &mut ctx.accounts.fog_pool;
+            if spending_cap > old_spending_cap {
+                reserve_liquidity(authorization, fog_pool, spending_cap - old_spending_cap)?;
+            } else {
+                release_liquidity(authorization, fog_pool, old_spending_cap - spending_cap);
+            }
+        }
+
+        emit_event!(
+            ctx.accounts,
+            AuthorizationAmended {
+                authorization: authorization.key(),
+                old_spending_cap,
+                spending_cap,
+                old_expires_at,
+                expires_at,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Limit how many times an authorization can be spent from (issuer only)
+    /// e.g. 1 for a single-use payment voucher; 0 removes the limit
+    pub fn set_max_uses(ctx: Context<UpdateAuthorization>, max_uses: u32) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        require!(
+            max_uses == 0 || max_uses > authorization.uses,
+            ShadeError::InvalidAmount
+        );
+        authorization.max_uses = max_uses;
+
+        emit_event!(
+            ctx.accounts,
+            MaxUsesSet {
+                authorization: authorization.key(),
+                max_uses,
+                uses: authorization.uses,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Schedule when an unused authorization becomes spendable (issuer only)
+    /// e.g. a budget that activates on the 1st of the month. Streams unlock
+    /// from the start time instead of creation
+    pub fn set_start_time(ctx: Context<UpdateAuthorization>, starts_at: i64) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        require!(
+            authorization.uses == 0,
+            ShadeError::AuthorizationAlreadyUsed
+        );
+        require!(
+            starts_at >= authorization.created_at && starts_at < authorization.expires_at,
+            ShadeError::InvalidTimestamp
+        );
+        authorization.starts_at = starts_at;
+
+        emit_event!(
+            ctx.accounts,
+            AuthorizationStartSet {
+                authorization: authorization.key(),
+                starts_at,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Turn the spending cap into a budget that resets every `budget_window`
+    /// seconds (e.g. a daily or weekly budget) instead of a lifetime cap
+    /// (issuer only). The first window starts now and counts what was already
+    /// spent; 0 goes back to a lifetime cap. Not available for streams
+    pub fn set_budget_window(ctx: Context<UpdateAuthorization>, budget_window: i64) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        require!(
+            budget_window >= 0 && !authorization.streaming,
+            ShadeError::InvalidBudgetWindow
+        );
+        authorization.budget_window = budget_window;
+        authorization.budget_window_start = if budget_window > 0 {
+            Clock::get()?.unix_timestamp
+        } else {
+            0
+        };
+
+        emit_event!(
+            ctx.accounts,
+            BudgetWindowSet {
+                authorization: authorization.key(),
+                budget_window,
+                window_start: authorization.budget_window_start,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Require a minimum gap between spends on an authorization (issuer only)
+    /// Together with a 24h velocity window this bounds how fast a compromised
+    /// spender key can drain the pool; 0 removes the gap
+    pub fn set_min_spend_interval(
+        ctx: Context<UpdateAuthorization>,
+        min_spend_interval: i64,
+    ) -> Result<()> {
+        require!(min_spend_interval >= 0, ShadeError::InvalidTimestamp);
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.min_spend_interval = min_spend_interval;
+
+        emit_event!(
+            ctx.accounts,
+            MinSpendIntervalSet {
+                authorization: authorization.key(),
+                min_spend_interval,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Only allow spends on some UTC weekdays and within a daily window
+    /// (issuer only), e.g. weekdays 09:00-18:00 for corporate cards.
+    /// `spend_days` is a bitmask with bit 0 = Monday; 0 removes the schedule.
+    /// The window is in minutes after UTC midnight and may run overnight
+    pub fn set_spend_schedule(
+        ctx: Context<UpdateAuthorization>,
+        spend_days: u8,
+        spend_window_start: u16,
+        spend_window_end: u16,
+    ) -> Result<()> {
+        require!(
+            spend_days < 1 << 7 && spend_window_start < 1440 && spend_window_end < 1440,
+            ShadeError::InvalidSpendSchedule
+        );
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.spend_days = spend_days;
+        authorization.spend_window_start = spend_window_start;
+        authorization.spend_window_end = spend_window_end;
+
+        emit_event!(
+            ctx.accounts,
+            SpendScheduleSet {
+                authorization: authorization.key(),
+                spend_days,
+                spend_window_start,
+                spend_window_end,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Temporarily suspend an authorization, e.g. during an investigation
+    /// (issuer only). Unlike revocation this is reversible and keeps the
+    /// authorization's spend history, limits and index listings
+    pub fn freeze_authorization(ctx: Context<UpdateAuthorization>) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        require!(authorization.is_active, ShadeError::AuthorizationInactive);
+        require!(!authorization.frozen, ShadeError::AuthorizationFrozen);
+        authorization.frozen = true;
+
+        emit_event!(
+            ctx.accounts,
+            AuthorizationFreezeChanged {
+                authorization: authorization.key(),
+                frozen: true,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Reinstate a frozen authorization (issuer only)
+    pub fn unfreeze_authorization(ctx: Context<UpdateAuthorization>) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        require!(authorization.frozen, ShadeError::AuthorizationNotFrozen);
+        authorization.frozen = false;
+
+        emit_event!(
+            ctx.accounts,
+            AuthorizationFreezeChanged {
+                authorization: authorization.key(),
+                frozen: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Configure velocity limits on an authorization (issuer only)
+    /// Each limit caps spending within a fixed window, e.g. 1k/day AND 5k/week;
+    /// all configured windows are enforced together in spend. A limit can roll
+    /// a fraction of its unspent allowance into the next window.
+    /// Replacing the limits restarts every window from now.
+    pub fn set_velocity_limits(
+        ctx: Context<UpdateAuthorization>,
+        limits: Vec<VelocityLimit>,
+    ) -> Result<()> {
+        require!(
+            limits.len() <= MAX_VELOCITY_WINDOWS,
+            ShadeError::InvalidVelocityLimits
+        );
+        for limit in limits.iter() {
+            require!(
+                limit.window_length > 0 && limit.max_amount > 0,
+                ShadeError::InvalidVelocityLimits
+            );
+            require!(
+                limit.rollover_bps <= 10000,
+                ShadeError::InvalidVelocityLimits
+            );
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.velocity_windows = [VelocityWindow::default(); MAX_VELOCITY_WINDOWS];
+        for (window, limit) in authorization.velocity_windows.iter_mut().zip(limits.iter()) {
+            window.window_length = limit.window_length;
+            window.max_amount = limit.max_amount;
+            window.rollover_bps = limit.rollover_bps;
+            window.window_start = now;
+            window.spent = 0;
+            window.carried_over = 0;
+        }
+
+        emit_event!(
+            ctx.accounts,
+            VelocityLimitsSet {
+                authorization: authorization.key(),
+                limits,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Restrict an authorization to recipients in a merkle allowlist (issuer only)
+    /// For large merchant sets that can't be stored on-chain; spends must then
+    /// prove the recipient token account's owner is in the tree
+    /// Pass an all-zero root to allow any recipient again
+    pub fn set_recipient_allowlist(
+        ctx: Context<UpdateAuthorization>,
+        allowlist_root: [u8; 32],
+    ) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.allowlist_root = allowlist_root;
+
+        emit_event!(
+            ctx.accounts,
+            RecipientAllowlistSet {
+                authorization: authorization.key(),
+                allowlist_root,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Choose whether the spend fee is deducted from the amount (default) or
+    /// charged on top of it against the spending cap, so the recipient
+    /// receives exactly the invoiced amount
+    pub fn set_fee_mode(ctx: Context<UpdateAuthorization>, fee_on_top: bool) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.fee_on_top = fee_on_top;
+
+        emit_event!(
+            ctx.accounts,
+            FeeModeSet {
+                authorization: authorization.key(),
+                fee_on_top,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Denominate the spending cap, velocity windows and approval threshold in
+    /// micro-USD instead of pool tokens (issuer only, before the first spend)
+    /// Spends are then priced with the pool's Pyth feed
+    /// The cap is re-validated against the spender's tier in its new unit, a
+    /// USD cap priced into tokens with the passed price update; switching back
+    /// to tokens reserves the cap in the pool again
+    pub fn set_usd_cap(ctx: Context<SetUsdCap>, cap_in_usd: bool) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let authorization = &ctx.accounts.authorization;
+        require!(
+            authorization.uses == 0 && authorization.amount_spent == 0,
+            ShadeError::SpendingCapUnitLocked
+        );
+        require!(
+            !cap_in_usd || ctx.accounts.fog_pool.price_feed_id != [0u8; 32],
+            ShadeError::InvalidPriceFeed
+        );
+
+        if cap_in_usd != authorization.cap_in_usd {
+            let token_cap = if cap_in_usd {
+                let price_update = ctx
+                    .accounts
+                    .price_update
+                    .as_ref()
+                    .ok_or(ShadeError::PriceUpdateRequired)?;
+                let (price, exponent) = read_pyth_price(price_update, &ctx.accounts.fog_pool, now)?;
+                token_value(
+                    authorization.spending_cap,
+                    ctx.accounts.mint.decimals,
+                    price,
+                    exponent,
+                )?
+            } else {
+                authorization.spending_cap
+            };
+            let delegator = tier_delegator(
+                &ctx.accounts.tier_delegation,
+                &ctx.accounts.delegator_staker,
+                authorization.authorized_spender,
+            )?;
+            check_tier_cap(
+                &ctx.accounts.protocol_config,
+                &ctx.accounts.staker,
+                &ctx.accounts.source_staker,
+                delegator,
+                token_cap,
+            )?;
+
+            // USD caps have no fixed token amount to reserve
+            let authorization = &mut ctx.accounts.authorization;
+            let fog_pool = &mut ctx.accounts.fog_pool;
+            if cap_in_usd {
+                release_liquidity(authorization, fog_pool, u64::MAX);
+            } else {
+                reserve_liquidity(authorization, fog_pool, token_cap)?;
+                require!(
+                    fog_pool.available_balance(ctx.accounts.vault.amount)
+                        >= fog_pool.reserved_amount.saturating_add(fog_pool.payables),
+                    ShadeError::LiquidityReserved
+                );
+            }
+        }
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.cap_in_usd = cap_in_usd;
+
+        emit_event!(
+            ctx.accounts,
+            UsdCapSet {
+                authorization: authorization.key(),
+                cap_in_usd,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the dispute window for escrowed spends and an optional arbiter who
+    /// may refund them alongside the issuer (issuer only)
+    /// A window of 0 disables spend_escrowed; pending escrows keep their terms
+    pub fn set_escrow_terms(
+        ctx: Context<UpdateAuthorization>,
+        dispute_window: i64,
+        arbiter: Pubkey,
+    ) -> Result<()> {
+        require!(
+            (0..=MAX_DISPUTE_WINDOW).contains(&dispute_window),
+            ShadeError::InvalidDisputeWindow
+        );
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.dispute_window = dispute_window;
+        authorization.arbiter = arbiter;
+
+        emit_event!(
+            ctx.accounts,
+            EscrowTermsSet {
+                authorization: authorization.key(),
+                dispute_window,
+                arbiter,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Reinstate limit consumption after a spend was reversed off-chain
+    /// (refund, voided hold, repayment). Restores the lifetime cap and any
+    /// velocity window that still contains the original spend.
+    pub fn reinstate_limits(
+        ctx: Context<ReinstateLimits>,
+        amount: u64,
+        spent_at: i64,
+    ) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        let authorization = &mut ctx.accounts.authorization;
+        require!(
+            spent_at >= authorization.created_at && spent_at <= now,
+            ShadeError::InvalidTimestamp
+        );
+        restore_spend_limits(
+            authorization,
+            &mut ctx.accounts.fog_pool,
+            ctx.accounts.vault.amount,
+            amount,
+            spent_at,
+            now,
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            LimitsReinstated {
+                authorization: authorization.key(),
+                amount,
+                spent_at,
+                remaining: authorization.spending_cap - authorization.amount_spent,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Return tokens from a prior spend to the pool (recipient only)
+    /// The spend is looked up by `spend_index` in the pool's spend ring, which
+    /// binds the refund to its authorization and recipient token account and
+    /// caps it at the amount not yet refunded. The returned amount is restored
+    /// to the authorization's cap and to the velocity window of the original
+    /// spend, and taken off the pool's spent total
+    /// A routed spend's refund first pays down the debt it created, into the
+    /// creditor pool's vault
+    pub fn refund_spend(ctx: Context<RefundSpend>, amount: u64, spend_index: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let (slot, mut record) = find_ring_spend(&ctx.accounts.spend_ring, spend_index)?;
+        require_keys_eq!(
+            record.authorization,
+            ctx.accounts.authorization.key(),
+            ShadeError::InvalidSpendIndex
+        );
+        require_keys_eq!(
+            record.recipient,
+            ctx.accounts.recipient_token_account.key(),
+            ShadeError::Unauthorized
+        );
+        require!(
+            amount <= record.amount - record.refunded,
+            ShadeError::RefundExceedsSpend
+        );
+        let decimals = ctx.accounts.mint.decimals;
+
+        let mut sent_to_creditor = 0;
+        let mut debt_repaid = 0;
+        if record.paid_by != Pubkey::default() {
+            let accounts = &mut *ctx.accounts;
+            let (Some(agreement), Some(creditor_pool), Some(creditor_vault)) = (
+                accounts.agreement.as_mut(),
+                accounts.creditor_pool.as_mut(),
+                accounts.creditor_vault.as_mut(),
+            ) else {
+                return err!(ShadeError::CreditorAccountsRequired);
+            };
+            require!(
+                creditor_pool.key() == record.paid_by
+                    && agreement.creditor_pool == record.paid_by
+                    && agreement.debtor_pool == accounts.fog_pool.key(),
+                ShadeError::CreditorAccountsRequired
+            );
+            require_keys_eq!(
+                creditor_vault.key(),
+                creditor_pool.vault,
+                ShadeError::InvalidVaultAuthority
+            );
+
+            sent_to_creditor = amount.min(agreement.outstanding);
+            if sent_to_creditor > 0 {
+                let creditor_balance = creditor_vault.amount;
+                let transfer_ctx = CpiContext::new(
+                    accounts.token_program.to_account_info(),
+                    TransferChecked {
+                        from: accounts.recipient_token_account.to_account_info(),
+                        mint: accounts.mint.to_account_info(),
+                        to: creditor_vault.to_account_info(),
+                        authority: accounts.recipient.to_account_info(),
+                    },
+                );
+                token_interface::transfer_checked(transfer_ctx, sent_to_creditor, decimals)?;
+
+                // The debt goes down by what the creditor received
+                debt_repaid = received_amount(creditor_vault, creditor_balance)?;
+                agreement.outstanding -= debt_repaid;
+                agreement.total_settled = agreement
+                    .total_settled
+                    .checked_add(debt_repaid)
+                    .ok_or(ShadeError::Overflow)?;
+                let debtor_pool = &mut accounts.fog_pool;
+                debtor_pool.payables = debtor_pool.payables.saturating_sub(debt_repaid);
+                creditor_pool.receivables = creditor_pool.receivables.saturating_sub(debt_repaid);
+            }
+        }
+
+        // The rest goes back to the pool's own vault
+        let mut returned = debt_repaid;
+        let to_vault = amount - sent_to_creditor;
+        if to_vault > 0 {
+            let vault_balance = ctx.accounts.vault.amount;
+            let transfer_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.recipient_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.recipient.to_account_info(),
+                },
+            );
+            token_interface::transfer_checked(transfer_ctx, to_vault, decimals)?;
+            returned += received_amount(&mut ctx.accounts.vault, vault_balance)?;
+        }
+
+        record.refunded += amount;
+        write_ring_spend(&ctx.accounts.spend_ring, slot, &record)?;
+
+        // Only what reached the vaults is restored, net of any transfer fee
+        let now = Clock::get()?.unix_timestamp;
+        let authorization = &mut ctx.accounts.authorization;
+        // USD limits are restored by the issuer with reinstate_limits
+        if !authorization.cap_in_usd {
+            restore_spend_limits(
+                authorization,
+                &mut ctx.accounts.fog_pool,
+                ctx.accounts.vault.amount,
+                returned,
+                record.timestamp,
+                now,
+            )?;
+        }
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.total_spent = fog_pool.total_spent.saturating_sub(returned);
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SpendRefunded {
+                authorization: authorization.key(),
+                fog_pool: fog_pool.key(),
+                sequence,
+                recipient: ctx.accounts.recipient.key(),
+                amount: returned,
+                debt_repaid,
+                spend_index,
+                remaining: authorization.spending_cap - authorization.amount_spent,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // APPROVAL POLICIES
+    // ========================================================================
+
+    /// Attach (or replace) an approval policy on an authorization
+    /// Spends of at least `min_amount` then need `threshold` approver signatures
+    pub fn set_approval_policy(
+        ctx: Context<SetApprovalPolicy>,
+        approvers: Vec<Pubkey>,
+        threshold: u8,
+        min_amount: u64,
+    ) -> Result<()> {
+        require!(min_amount > 0, ShadeError::InvalidAmount);
+        require!(
+            !approvers.is_empty() && approvers.len() <= MAX_APPROVERS,
+            ShadeError::InvalidApprovalPolicy
+        );
+        require!(
+            threshold > 0 && threshold as usize <= approvers.len(),
+            ShadeError::InvalidApprovalPolicy
+        );
+        for (i, approver) in approvers.iter().enumerate() {
+            require!(
+                !approvers[..i].contains(approver),
+                ShadeError::InvalidApprovalPolicy
+            );
+        }
+
+        let policy = &mut ctx.accounts.approval_policy;
+        policy.authorization = ctx.accounts.authorization.key();
+        policy.approvers = approvers.clone();
+        policy.threshold = threshold;
+        policy.min_amount = min_amount;
+        policy.bump = ctx.bumps.approval_policy;
+        // A new policy carries on from the last removed one's nonce
+        policy.approval_nonce = policy
+            .approval_nonce
+            .max(ctx.accounts.authorization.approval_nonce);
+
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.approval_min_amount = min_amount;
+
+        emit_event!(
+            ctx.accounts,
+            ApprovalPolicySet {
+                authorization: authorization.key(),
+                approvers,
+                threshold,
+                min_amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Remove the approval policy from an authorization
+    pub fn remove_approval_policy(ctx: Context<RemoveApprovalPolicy>) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.approval_min_amount = 0;
+        authorization.approval_nonce = ctx.accounts.approval_policy.approval_nonce;
+
+        emit_event!(
+            ctx.accounts,
+            ApprovalPolicyRemoved {
+                authorization: authorization.key(),
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Pay several recipients from one authorization in a single call
+    /// (payroll, mass payouts). Recipient token accounts, then their owners'
+    /// denylist entries, are passed in remaining_accounts in the same order as
+    /// `payments`. The whole batch is
+    /// checked against the authorization's limits at once and the protocol fee
+    /// is moved to the fee vault in one transfer; each payment still gets its
+    /// own spend index. Authorizations with a recipient allowlist or an
+    /// approval threshold the batch reaches must use `spend` instead
+    pub fn spend_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SpendBatch<'info>>,
+        payments: Vec<BatchPayment>,
+    ) -> Result<()> {
+        process_batch(ctx.accounts, ctx.remaining_accounts, &payments)
+    }
+
+    /// Spend with approver signatures collected off-chain
+    /// The transaction must include ed25519 program instructions in which
+    /// `threshold` distinct approvers signed the approval message
+    /// (see `spend_approval_message`)
+    pub fn spend_with_approvals(
+        ctx: Context<SpendWithApprovals>,
+        amount: u64,
+        recipient_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let policy = &ctx.accounts.approval_policy;
+        let message = spend_approval_message(
+            &ctx.accounts.spend.authorization.key(),
+            &ctx.accounts.spend.recipient_token_account.key(),
+            amount,
+            policy.approval_nonce,
+        );
+
+        let signers = ed25519_signers(&ctx.accounts.instructions_sysvar, &message)?;
+        let approvals: Vec<Pubkey> = signers
+            .into_iter()
+            .filter(|signer| policy.approvers.contains(signer))
+            .collect();
+        require!(
+            approvals.len() >= policy.threshold as usize,
+            ShadeError::InsufficientApprovals
+        );
+
+        // Consume the nonce so the same signatures can't be replayed
+        let policy = &mut ctx.accounts.approval_policy;
+        let approval_nonce = policy.approval_nonce;
+        policy.approval_nonce = policy
+            .approval_nonce
+            .checked_add(1)
+            .ok_or(ShadeError::Overflow)?;
+
+        process_spend(
+            &mut ctx.accounts.spend,
+            amount,
+            true,
+            &recipient_proof,
+            None,
+            None,
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            SpendApproved {
+                authorization: ctx.accounts.spend.authorization.key(),
+                approvers: approvals,
+                amount,
+                approval_nonce,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Propose a spend for on-chain approval (spender only)
+    /// The alternative to collecting ed25519 signatures off-chain: approvers
+    /// sign approve_spend transactions, then execute_spend moves the funds
+    pub fn propose_spend(ctx: Context<ProposeSpend>, _nonce: u64, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let pending = &mut ctx.accounts.pending_spend;
+        pending.authorization = ctx.accounts.authorization.key();
+        pending.proposer = ctx.accounts.spender.key();
+        pending.recipient_token_account = ctx.accounts.recipient_token_account.key();
+        pending.amount = amount;
+        pending.approvals = Vec::new();
+        pending.created_at = Clock::get()?.unix_timestamp;
+        pending.bump = ctx.bumps.pending_spend;
+
+        emit_event!(
+            ctx.accounts,
+            SpendProposed {
+                pending_spend: pending.key(),
+                authorization: pending.authorization,
+                recipient: pending.recipient_token_account,
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Approve a proposed spend (approvers of the authorization's policy only)
+    pub fn approve_spend(ctx: Context<ApproveSpend>) -> Result<()> {
+        let approver = ctx.accounts.approver.key();
+        require!(
+            ctx.accounts.approval_policy.approvers.contains(&approver),
+            ShadeError::Unauthorized
+        );
+
+        let pending = &mut ctx.accounts.pending_spend;
+        require!(
+            !pending.approvals.contains(&approver),
+            ShadeError::AlreadyApproved
+        );
+        pending.approvals.push(approver);
+
+        emit_event!(
+            ctx.accounts,
+            PendingSpendApproved {
+                pending_spend: pending.key(),
+                approver,
+                approvals: pending.approvals.len() as u8,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Execute a proposed spend once enough approvers have signed off
+    /// Only approvals from keys still on the policy count
+    pub fn execute_spend(ctx: Context<ExecuteSpend>, recipient_proof: Vec<[u8; 32]>) -> Result<()> {
+        let policy = &ctx.accounts.approval_policy;
+        let pending = &ctx.accounts.pending_spend;
+        let approvals: Vec<Pubkey> = pending
+            .approvals
+            .iter()
+            .filter(|approver| policy.approvers.contains(approver))
+            .copied()
+            .collect();
+        require!(
+            approvals.len() >= policy.threshold as usize,
+            ShadeError::InsufficientApprovals
+        );
+        let amount = pending.amount;
+
+        // Consume the nonce like an off-chain approved spend
+        let policy = &mut ctx.accounts.approval_policy;
+        let approval_nonce = policy.approval_nonce;
+        policy.approval_nonce = policy
+            .approval_nonce
+            .checked_add(1)
+            .ok_or(ShadeError::Overflow)?;
+
+        process_spend(
+            &mut ctx.accounts.spend,
+            amount,
+            true,
+            &recipient_proof,
+            None,
+            None,
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            SpendApproved {
+                authorization: ctx.accounts.spend.authorization.key(),
+                approvers: approvals,
+                amount,
+                approval_nonce,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Cancel a proposed spend (proposer or authorization issuer)
+    pub fn cancel_spend(ctx: Context<CancelSpend>) -> Result<()> {
+        let signer = ctx.accounts.signer.key();
+        require!(
+            signer == ctx.accounts.pending_spend.proposer
+                || signer == ctx.accounts.authorization.issuer,
+            ShadeError::Unauthorized
+        );
+
+        emit_event!(
+            ctx.accounts,
+            SpendProposalCancelled {
+                pending_spend: ctx.accounts.pending_spend.key(),
+                cancelled_by: signer,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // ESCROWED SPENDS
+    // ========================================================================
+
+    /// Spend into escrow (spender only)
+    /// The amount is drawn from the authorization now but stays reserved in
+    /// the pool vault for the authorization's dispute window; the recipient
+    /// claims it afterwards, or the issuer/arbiter refunds it within the window
+    pub fn spend_escrowed(
+        ctx: Context<SpendEscrowed>,
+        _nonce: u64,
+        amount: u64,
+        recipient_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let spend = &mut ctx.accounts.spend;
+        let dispute_window = spend.authorization.dispute_window;
+        require!(dispute_window > 0, ShadeError::EscrowNotEnabled);
+        let (amount, fee, surcharge, charged) =
+            prepare_spend(spend, amount, false, &recipient_proof, now)?;
+        require!(
+            spend.fog_pool.available_balance(spend.vault.amount) >= amount,
+            ShadeError::InsufficientPoolBalance
+        );
+
+        spend.authorization.record_spend(charged, now)?;
+        release_liquidity(&mut spend.authorization, &mut spend.fog_pool, amount);
+        record_category_spend(
+            &mut spend.category_budget,
+            &spend.fog_pool,
+            spend.authorization.category,
+            amount,
+            now,
+        )?;
+        spend.fog_pool.escrowed_amount = spend
+            .fog_pool
+            .escrowed_amount
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        let escrow = &mut ctx.accounts.escrowed_spend;
+        escrow.authorization = spend.authorization.key();
+        escrow.fog_pool = spend.fog_pool.key();
+        escrow.spender = spend.spender.key();
+        escrow.recipient_token_account = spend.recipient_token_account.key();
+        escrow.amount = amount;
+        escrow.fee = fee;
+        escrow.surcharge = surcharge;
+        escrow.created_at = now;
+        escrow.releases_at = now
+            .checked_add(dispute_window)
+            .ok_or(ShadeError::Overflow)?;
+        escrow.bump = ctx.bumps.escrowed_spend;
+
+        let sequence = spend.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            EscrowedSpendCreated {
+                escrowed_spend: escrow.key(),
+                authorization: escrow.authorization,
+                fog_pool: escrow.fog_pool,
+                sequence,
+                spender: escrow.spender,
+                recipient: escrow.recipient_token_account,
+                amount,
+                fee,
+                releases_at: escrow.releases_at,
+                remaining: spend.authorization.spending_cap - spend.authorization.amount_spent,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Claim an escrowed spend once its dispute window has passed (recipient only)
+    /// Pays the amount net of the fee and counts the spend in the pool stats
+    /// The funds were drawn when the spend was made, so claims stay open while
+    /// the pool or protocol is paused or in withdraw-only mode
+    pub fn claim_escrowed_spend(ctx: Context<ClaimEscrowedSpend>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let escrow = &ctx.accounts.escrowed_spend;
+        require!(now >= escrow.releases_at, ShadeError::DisputeWindowOpen);
+        let net_amount = escrow
+            .amount
+            .checked_sub(escrow.fee)
+            .ok_or(ShadeError::Overflow)?;
+
+        let fog_pool = &ctx.accounts.fog_pool;
+        let pool_key = fog_pool.key();
+        let seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[fog_pool.vault_authority_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.recipient_token_account.to_account_info(),
+                authority: ctx.accounts.pool_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, net_amount, ctx.accounts.mint.decimals)?;
+
+        // The fee stays in the vault until sweep_fees collects it
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.escrowed_amount = fog_pool
+            .escrowed_amount
+            .checked_sub(escrow.amount)
+            .ok_or(ShadeError::Overflow)?;
+        let spend_index = fog_pool.record_spend(escrow.amount, escrow.fee, escrow.surcharge)?;
+        record_ring_spend(
+            &ctx.accounts.spend_ring,
+            fog_pool,
+            SpendRecord {
+                amount: escrow.amount,
+                timestamp: now,
+                spender: escrow.spender,
+                authorization: escrow.authorization,
+                recipient: escrow.recipient_token_account,
+                spend_index,
+                ..Default::default()
+            },
+        )?;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            EscrowedSpendClaimed {
+                escrowed_spend: escrow.key(),
+                authorization: escrow.authorization,
+                fog_pool: fog_pool.key(),
+                sequence,
+                recipient: escrow.recipient_token_account,
+                amount: escrow.amount,
+                fee: escrow.fee,
+                net_amount,
+                spend_index,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Refund an escrowed spend within its dispute window (issuer or arbiter)
+    /// The reserved funds are released back to the pool and the amount is
+    /// restored to the authorization's cap and velocity windows
+    pub fn refund_escrowed_spend(ctx: Context<RefundEscrowedSpend>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let signer = ctx.accounts.signer.key();
+        let authorization = &mut ctx.accounts.authorization;
+        require!(
+            signer == authorization.issuer
+                || (authorization.arbiter != Pubkey::default() && signer == authorization.arbiter),
+            ShadeError::Unauthorized
+        );
+        let escrow = &ctx.accounts.escrowed_spend;
+        require!(now < escrow.releases_at, ShadeError::DisputeWindowClosed);
+
+        // Released first, so the escrowed funds count toward re-reserving
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.escrowed_amount = fog_pool
+            .escrowed_amount
+            .checked_sub(escrow.amount)
+            .ok_or(ShadeError::Overflow)?;
+        if !authorization.cap_in_usd {
+            restore_spend_limits(
+                authorization,
+                fog_pool,
+                ctx.accounts.vault.amount,
+                escrow.amount,
+                escrow.created_at,
+                now,
+            )?;
+        }
+
+        emit_event!(
+            ctx.accounts,
+            EscrowedSpendRefunded {
+                escrowed_spend: escrow.key(),
+                authorization: authorization.key(),
+                refunded_by: signer,
+                amount: escrow.amount,
+                remaining: authorization.spending_cap - authorization.amount_spent,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // INVOICES
+    // ========================================================================
+
+    /// Create an invoice payable to one of the caller's token accounts
+    /// `invoice_id` is the caller's own id for it (e.g. an invoice number hash)
+    pub fn create_invoice(
+        ctx: Context<CreateInvoice>,
+        invoice_id: [u8; 32],
+        amount: u64,
+        expires_at: i64,
+        memo: String,
+    ) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(memo.len() <= 64, ShadeError::InvoiceMemoTooLong);
+        let now = Clock::get()?.unix_timestamp;
+        require!(expires_at > now, ShadeError::InvalidExpiry);
+
+        let invoice = &mut ctx.accounts.invoice;
+        invoice.recipient = ctx.accounts.recipient.key();
+        invoice.recipient_token_account = ctx.accounts.recipient_token_account.key();
+        invoice.mint = ctx.accounts.recipient_token_account.mint;
+        invoice.invoice_id = invoice_id;
+        invoice.amount = amount;
+        invoice.created_at = now;
+        invoice.expires_at = expires_at;
+        invoice.memo = memo;
+        invoice.paid = false;
+        invoice.paid_by = Pubkey::default();
+        invoice.paid_at = 0;
+        invoice.bump = ctx.bumps.invoice;
+
+        emit_event!(
+            ctx.accounts,
+            InvoiceCreated {
+                invoice: invoice.key(),
+                recipient: invoice.recipient,
+                recipient_token_account: invoice.recipient_token_account,
+                invoice_id,
+                amount,
+                expires_at,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Pay an invoice in full from an authorization (spender only)
+    /// The spend carries the invoice id as its reference; an invoice can only
+    /// be paid once
+    pub fn pay_invoice(ctx: Context<PayInvoice>, recipient_proof: Vec<[u8; 32]>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let invoice = &ctx.accounts.invoice;
+        require!(!invoice.paid, ShadeError::InvoiceAlreadyPaid);
+        require!(now <= invoice.expires_at, ShadeError::InvoiceExpired);
+        let amount = invoice.amount;
+        let invoice_id = invoice.invoice_id;
+
+        let drawn = process_spend(
+            &mut ctx.accounts.spend,
+            amount,
+            false,
+            &recipient_proof,
+            None,
+            Some(invoice_id),
+        )?
+        .amount;
+
+        let authorization = ctx.accounts.spend.authorization.key();
+        let invoice = &mut ctx.accounts.invoice;
+        invoice.paid = true;
+        invoice.paid_by = authorization;
+        invoice.paid_at = now;
+
+        emit_event!(
+            ctx.accounts,
+            InvoicePaid {
+                invoice: invoice.key(),
+                authorization,
+                invoice_id,
+                amount: drawn,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Close an invoice and reclaim its rent (recipient only)
+    /// Closing an unpaid invoice cancels it
+    pub fn close_invoice(ctx: Context<CloseInvoice>) -> Result<()> {
+        let invoice = &ctx.accounts.invoice;
+
+        emit_event!(
+            ctx.accounts,
+            InvoiceClosed {
+                invoice: invoice.key(),
+                invoice_id: invoice.invoice_id,
+                paid: invoice.paid,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // CATEGORY BUDGETS
+    // ========================================================================
+
+    /// Put an authorization's spends under a purpose category (pool authority only)
+    pub fn set_authorization_category(
+        ctx: Context<SetAuthorizationCategory>,
+        category: PurposeCategory,
+    ) -> Result<()> {
+        let authorization = &mut ctx.accounts.authorization;
+        authorization.category = category;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            AuthorizationCategorySet {
+                authorization: authorization.key(),
+                fog_pool: authorization.fog_pool,
+                sequence,
+                category,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Cap the pool's total spending in a category per epoch (pool authority only)
+    /// Changing the epoch length starts a fresh epoch; changing only the cap
+    /// keeps the current epoch's spending
+    pub fn set_category_budget(
+        ctx: Context<SetCategoryBudget>,
+        category: PurposeCategory,
+        cap: u64,
+        epoch_length: i64,
+    ) -> Result<()> {
+        require!(
+            category != PurposeCategory::Uncategorized && epoch_length > 0,
+            ShadeError::InvalidCategoryBudget
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let budget = &mut ctx.accounts.category_budget;
+        if budget.fog_pool == Pubkey::default() || budget.epoch_length != epoch_length {
+            budget.epoch_start = now;
+            budget.spent = 0;
+        }
+        budget.fog_pool = ctx.accounts.fog_pool.key();
+        budget.category = category;
+        budget.cap = cap;
+        budget.epoch_length = epoch_length;
+        budget.bump = ctx.bumps.category_budget;
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.budgeted_categories |= category.bit();
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            CategoryBudgetUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                category,
+                cap,
+                epoch_length,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Remove a category budget and reclaim its rent (pool authority only)
+    pub fn remove_category_budget(ctx: Context<RemoveCategoryBudget>) -> Result<()> {
+        let category = ctx.accounts.category_budget.category;
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.budgeted_categories &= !category.bit();
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            CategoryBudgetUpdated {
+                fog_pool: fog_pool.key(),
+                sequence,
+                category,
+                cap: 0,
+                epoch_length: 0,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // SPLIT PAYMENTS
+    // ========================================================================
+
+    /// Create a revenue-share template for the pool (pool authority only)
+    /// Shares are in bps of each split spend and must total 10000
+    pub fn create_split_template(
+        ctx: Context<CreateSplitTemplate>,
+        template_id: u64,
+        shares: Vec<SplitShare>,
+    ) -> Result<()> {
+        validate_split_shares(&shares)?;
+
+        let template = &mut ctx.accounts.split_template;
+        template.fog_pool = ctx.accounts.fog_pool.key();
+        template.template_id = template_id;
+        template.shares = shares;
+        template.bump = ctx.bumps.split_template;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SplitTemplateUpdated {
+                split_template: template.key(),
+                fog_pool: template.fog_pool,
+                sequence,
+                recipients: template.shares.len() as u8,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Replace a split template's recipients and shares (pool authority only)
+    pub fn update_split_template(
+        ctx: Context<UpdateSplitTemplate>,
+        shares: Vec<SplitShare>,
+    ) -> Result<()> {
+        validate_split_shares(&shares)?;
+
+        let template = &mut ctx.accounts.split_template;
+        template.shares = shares;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SplitTemplateUpdated {
+                split_template: template.key(),
+                fog_pool: template.fog_pool,
+                sequence,
+                recipients: template.shares.len() as u8,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Close a split template and reclaim its rent (pool authority only)
+    pub fn close_split_template(ctx: Context<CloseSplitTemplate>) -> Result<()> {
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            SplitTemplateUpdated {
+                split_template: ctx.accounts.split_template.key(),
+                fog_pool: ctx.accounts.fog_pool.key(),
+                sequence,
+                recipients: 0,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Spend `amount` split across a template's recipients in one go
+    /// Runs as a spend_batch of the template's shares; rounding dust goes to
+    /// the first recipient. Recipient token accounts, then their owners'
+    /// denylist entries, are passed in remaining_accounts in template order
+    pub fn spend_split<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SpendSplit<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        let shares = &ctx.accounts.split_template.shares;
+        let mut payments = shares
+            .iter()
+            .map(|share| {
+                Ok(BatchPayment {
+                    recipient: share.recipient,
+                    amount: spend_fee(share.share_bps, amount)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let allotted: u64 = payments.iter().map(|payment| payment.amount).sum();
+        payments[0].amount += amount - allotted;
+
+        process_batch(&mut ctx.accounts.batch, ctx.remaining_accounts, &payments)?;
+
+        emit_event!(
+            ctx.accounts,
+            SplitSpendExecuted {
+                split_template: ctx.accounts.split_template.key(),
+                authorization: ctx.accounts.batch.authorization.key(),
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // SPEND PERMITS
+    // ========================================================================
+
+    /// Spend under a permit the pool authority signed off-chain, without an
+    /// Authorization account
+    /// The transaction must include an ed25519 program instruction in which the
+    /// pool authority signed the permit message (see `spend_permit_message`).
+    /// A permit is single-use: `amount` may be up to `cap` and the nonce is
+    /// then burned in the pool's nonce bitmap
+    pub fn spend_with_permit(
+        ctx: Context<SpendWithPermit>,
+        cap: u64,
+        nonce: u64,
+        expires_at: i64,
+        amount: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let accounts = &ctx.accounts;
+        require!(!accounts.protocol_config.paused, ShadeError::ProtocolPaused);
+        require!(
+            accounts.compromised_spender.data_is_empty(),
+            ShadeError::SpenderCompromised
+        );
+        require_not_denylisted!(
+            accounts,
+            accounts.spender_denylist_entry,
+            accounts.spender.key(),
+            DenylistRole::Spender
+        );
+        require_not_denylisted!(
+            accounts,
+            accounts.recipient_denylist_entry,
+            accounts.recipient_token_account.owner,
+            DenylistRole::Recipient
+        );
+        require!(
+            accounts.fog_pool.status == PoolStatus::Active,
+            ShadeError::PoolNotActive
+        );
+        require!(now < expires_at, ShadeError::PermitExpired);
+        require!(amount > 0 && amount <= cap, ShadeError::InvalidAmount);
+        accounts.fog_pool.check_spend_size(amount)?;
+        check_spend_attestations(
+            &accounts.fog_pool,
+            &accounts.spender_attestation,
+            accounts.spender.key(),
+            &accounts.recipient_attestation,
+            accounts.recipient_token_account.owner,
+            now,
+        )?;
+
+        let message = spend_permit_message(
+            &accounts.fog_pool.key(),
+            &accounts.spender.key(),
+            cap,
+            nonce,
+            expires_at,
+        );
+        let signers = ed25519_signers(&accounts.instructions_sysvar, &message)?;
+        require!(
+            signers.contains(&accounts.fog_pool.authority),
+            ShadeError::InvalidPermit
+        );
+
+        let bitmap = &mut ctx.accounts.permit_nonces;
+        if bitmap.fog_pool == Pubkey::default() {
+            bitmap.fog_pool = ctx.accounts.fog_pool.key();
+            bitmap.page = nonce / PERMIT_NONCES_PER_PAGE;
+            bitmap.bump = ctx.bumps.permit_nonces;
+        }
+        bitmap.consume(nonce)?;
+
+        let accounts = &ctx.accounts;
+        let delegator = tier_delegator(
+            &accounts.tier_delegation,
+            &accounts.delegator_staker,
+            accounts.spender.key(),
+        )?;
+        let spend_tier = spender_tier(&accounts.spender_staker, delegator);
+        // A permit is single-use, so the spend itself is checked against the tier cap
+        accounts.fog_pool.check_spend_tier(spend_tier, amount)?;
+        let tier = spend_tier.unwrap_or(0);
+        let vault_balance = accounts.vault.amount;
+        let (fee, surcharge) = accounts.fog_pool.spend_fees(amount, tier, vault_balance)?;
+        let net_amount = amount.checked_sub(fee).ok_or(ShadeError::Overflow)?;
+        let fog_pool = &accounts.fog_pool;
+        require!(
+            fog_pool.available_balance(accounts.vault.amount) >= amount,
+            ShadeError::InsufficientPoolBalance
+        );
+        fog_pool.check_utilization(amount, 0, vault_balance)?;
+
+        let pool_key = fog_pool.key();
+        let seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[fog_pool.vault_authority_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let transfer_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: accounts.vault.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: accounts.recipient_token_account.to_account_info(),
+                authority: accounts.pool_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, net_amount, accounts.mint.decimals)?;
+
+        // The fee stays in the vault until sweep_fees collects it
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        let spend_index = fog_pool.record_spend(amount, fee, surcharge)?;
+        record_ring_spend(
+            &ctx.accounts.spend_ring,
+            fog_pool,
+            SpendRecord {
+                amount,
+                timestamp: now,
+                spender: ctx.accounts.spender.key(),
+                recipient: ctx.accounts.recipient_token_account.key(),
+                spend_index,
+                ..Default::default()
+            },
+        )?;
+
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            PermitSpendExecuted {
+                fog_pool: fog_pool.key(),
+                sequence,
+                spender: ctx.accounts.spender.key(),
+                recipient: ctx.accounts.recipient_token_account.key(),
+                nonce,
+                amount,
+                fee,
+                net_amount,
+                spend_index,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // INCIDENT RESPONSE
+    // ========================================================================
+
+    /// Flag a spender key as compromised across every pool of this issuer
+    /// spend rejects any of the issuer's authorizations held by the key until cleared
+    pub fn flag_compromised_spender(
+        ctx: Context<FlagCompromisedSpender>,
+        spender: Pubkey,
+    ) -> Result<()> {
+        let flag = &mut ctx.accounts.compromised_spender;
+        flag.issuer = ctx.accounts.issuer.key();
+        flag.spender = spender;
+        flag.flagged_at = Clock::get()?.unix_timestamp;
+        flag.bump = ctx.bumps.compromised_spender;
+
+        emit_event!(
+            ctx.accounts,
+            SpenderCompromised {
+                issuer: flag.issuer,
+                spender,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Clear a compromised-spender flag, re-enabling the key's authorizations
+    pub fn clear_compromised_spender(ctx: Context<ClearCompromisedSpender>) -> Result<()> {
+        let flag = &ctx.accounts.compromised_spender;
+
+        emit_event!(
+            ctx.accounts,
+            SpenderCleared {
+                issuer: flag.issuer,
+                spender: flag.spender,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Add a key to the protocol-wide denylist (admin only)
+    /// Denylisted keys can't receive new authorizations or spend from any pool
+    pub fn add_to_denylist(ctx: Context<AddToDenylist>, account: Pubkey) -> Result<()> {
+        let entry = &mut ctx.accounts.denylist_entry;
+        entry.account = account;
+        entry.added_at = Clock::get()?.unix_timestamp;
+        entry.bump = ctx.bumps.denylist_entry;
+
+        emit_event!(
+            ctx.accounts,
+            DenylistUpdated {
+                account,
+                denied: true,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Remove a key from the protocol-wide denylist (admin only)
+    pub fn remove_from_denylist(ctx: Context<RemoveFromDenylist>) -> Result<()> {
+        emit_event!(
+            ctx.accounts,
+            DenylistUpdated {
+                account: ctx.accounts.denylist_entry.account,
+                denied: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Emergency pause of the whole protocol (authority or protocol guardian)
+    /// New authorizations, deposits and stakes fail while paused, and spends
+    /// once their pool is synced; unstaking and reward claims stay open
+    pub fn pause_protocol(ctx: Context<PauseProtocol>) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        config.paused = true;
+        config.resume_guard.resume_requested_at = 0;
+
+        emit_event!(
+            ctx.accounts,
+            ProtocolPauseChanged {
+                config: config.key(),
+                paused: true,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Announce the intent to unpause the protocol, starting the timelock
+    pub fn announce_protocol_resume(ctx: Context<UpdateProtocol>) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(config.paused, ShadeError::NotPaused);
+
+        let now = Clock::get()?.unix_timestamp;
+        config.resume_guard.resume_requested_at = now;
+
+        emit_event!(
+            ctx.accounts,
+            ResumeAnnounced {
+                target: config.key(),
+                eligible_at: now + RESUME_TIMELOCK,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Unpause the protocol (authority only)
+    /// Needs either the protocol guardian's co-signature or an announcement
+    /// made at least RESUME_TIMELOCK ago
+    pub fn unpause_protocol(ctx: Context<ResumeProtocol>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let guardian = ctx.accounts.guardian.as_ref().map(|g| g.key());
+
+        let config = &mut ctx.accounts.protocol_config;
+        require!(config.paused, ShadeError::NotPaused);
+        config.resume_guard.authorize_resume(guardian, now)?;
+        config.paused = false;
+
+        emit_event!(
+            ctx.accounts,
+            ProtocolPauseChanged {
+                config: config.key(),
+                paused: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set the protocol guardian, which can pause and co-sign an instant unpause
+    /// Replacing an existing guardian needs its co-signature
+    pub fn set_protocol_guardian(
+        ctx: Context<SetProtocolGuardian>,
+        guardian: Pubkey,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let current_guardian = ctx.accounts.current_guardian.as_ref().map(|g| g.key());
+
+        let config = &mut ctx.accounts.protocol_config;
+        require!(!config.paused, ShadeError::ProtocolPaused);
+        config
+            .resume_guard
+            .set_guardian(guardian, current_guardian, now)?;
+
+        emit_event!(
+            ctx.accounts,
+            GuardianUpdated {
+                target: config.key(),
+                guardian,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Configure the oracle circuit breaker (admin only): a pool's USD-priced
+    /// spends halt when its mint's price moves more than
+    /// `max_price_deviation_bps` from the reference price taken at the start
+    /// of a `price_deviation_window`. 0 bps disables it; pools pick the
+    /// settings up on sync_pool_config
+    pub fn set_price_circuit_breaker(
+        ctx: Context<UpdateProtocol>,
+        max_price_deviation_bps: u16,
+        price_deviation_window: i64,
+    ) -> Result<()> {
+        require!(
+            max_price_deviation_bps == 0
+                || (max_price_deviation_bps <= 10000 && price_deviation_window > 0),
+            ShadeError::InvalidCircuitBreaker
+        );
+
+        let config = &mut ctx.accounts.protocol_config;
+        config.max_price_deviation_bps = max_price_deviation_bps;
+        config.price_deviation_window = price_deviation_window;
+
+        emit_event!(
+            ctx.accounts,
+            PriceCircuitBreakerUpdated {
+                max_price_deviation_bps,
+                price_deviation_window,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Check a pool's price against its circuit breaker (permissionless)
+    /// Takes a new reference price once the last one's window has ended.
+    /// Within the window, a price beyond the threshold puts the pool into
+    /// withdraw-only mode; it resumes through the usual guardian or timelock
+    /// path and takes a fresh reference afterwards
+    pub fn check_price_circuit_breaker(ctx: Context<CheckPriceCircuitBreaker>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        require!(
+            fog_pool.max_price_deviation_bps > 0,
+            ShadeError::InvalidCircuitBreaker
+        );
+        let (price, _) = read_pyth_price(&ctx.accounts.price_update, fog_pool, now)?;
+
+        if !fog_pool.price_reference_live(now) {
+            fog_pool.reference_price = price;
+            fog_pool.reference_price_at = now;
+
+            let sequence = fog_pool.next_event_sequence()?;
+            emit_event!(
+                ctx.accounts,
+                PriceReferenceUpdated {
+                    fog_pool: fog_pool.key(),
+                    sequence,
+                    price,
+                }
+            );
+        } else if fog_pool.price_deviates(price) {
+            let reference_price = fog_pool.reference_price;
+            fog_pool.reference_price = 0;
+            fog_pool.reference_price_at = 0;
+            if fog_pool.status == PoolStatus::Active {
+                fog_pool.status = PoolStatus::WithdrawOnly;
+                fog_pool.resume_guard.resume_requested_at = 0;
+            }
+
+            let sequence = fog_pool.next_event_sequence()?;
+            emit_event!(
+                ctx.accounts,
+                PriceCircuitBreakerTripped {
+                    fog_pool: fog_pool.key(),
+                    sequence,
+                    reference_price,
+                    price,
+                    status: fog_pool.status,
+                }
+            );
+        }
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // VESTING
+    // ========================================================================
+
+    /// Create a SHADE vesting schedule for a team/investor allocation (admin only)
+    /// Tokens move from the authority's account into a vault owned by the schedule
+    /// and vest linearly from `start_at` over `vesting_duration`, nothing before the cliff
+    pub fn create_vesting_schedule(
+        ctx: Context<CreateVestingSchedule>,
+        schedule_id: u64,
+        total_amount: u64,
+        start_at: i64,
+        cliff_duration: i64,
+        vesting_duration: i64,
+        revocable: bool,
+    ) -> Result<()> {
+        require!(total_amount > 0, ShadeError::InvalidAmount);
+        require!(
+            vesting_duration > 0 && cliff_duration >= 0 && cliff_duration <= vesting_duration,
+            ShadeError::InvalidVestingSchedule
+        );
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.funder_token_account.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(
+            transfer_ctx,
+            total_amount,
+            ctx.accounts.shade_mint.decimals,
+        )?;
+
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        schedule.beneficiary = ctx.accounts.beneficiary.key();
+        schedule.vault = ctx.accounts.vault.key();
+        schedule.total_amount = total_amount;
+        schedule.claimed_amount = 0;
+        schedule.start_at = start_at;
+        schedule.cliff_at = start_at
+            .checked_add(cliff_duration)
+            .ok_or(ShadeError::Overflow)?;
+        schedule.end_at = start_at
+            .checked_add(vesting_duration)
+            .ok_or(ShadeError::Overflow)?;
+        schedule.revocable = revocable;
+        schedule.revoked_at = 0;
+        schedule.schedule_id = schedule_id;
+        schedule.bump = ctx.bumps.vesting_schedule;
+        schedule.counts_toward_tier = false;
+        schedule.tier_credit = 0;
+
+        emit_event!(
+            ctx.accounts,
+            VestingCreated {
+                schedule: schedule.key(),
+                beneficiary: schedule.beneficiary,
+                total_amount,
+                start_at,
+                cliff_at: schedule.cliff_at,
+                end_at: schedule.end_at,
+                revocable,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Claim all currently vested tokens (beneficiary only)
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let schedule = &ctx.accounts.vesting_schedule;
+        let claimable = schedule
+            .vested_amount(now)
+            .saturating_sub(schedule.claimed_amount);
+        require!(claimable > 0, ShadeError::NothingVested);
+
+        let config_key = ctx.accounts.protocol_config.key();
+        let id_bytes = schedule.schedule_id.to_le_bytes();
+        let seeds = &[
+            b"vesting".as_ref(),
+            config_key.as_ref(),
+            schedule.beneficiary.as_ref(),
+            id_bytes.as_ref(),
+            &[schedule.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.beneficiary_token_account.to_account_info(),
+                authority: schedule.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, claimable, ctx.accounts.mint.decimals)?;
+
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        schedule.claimed_amount = schedule
+            .claimed_amount
+            .checked_add(claimable)
+            .ok_or(ShadeError::Overflow)?;
+
+        // Vested tokens leave the tier credit before they can be staked
+        if schedule.tier_credit > 0 {
+            let staker = ctx
+                .accounts
+                .staker
+                .as_mut()
+                .ok_or(ShadeError::StakerRequired)?;
+            sync_vesting_tier_credit(schedule, staker, &ctx.accounts.protocol_config, now)?;
+        }
+
+        emit_event!(
+            ctx.accounts,
+            VestedClaimed {
+                schedule: schedule.key(),
+                beneficiary: schedule.beneficiary,
+                amount: claimable,
+                total_claimed: schedule.claimed_amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Revoke a revocable schedule (admin only)
+    /// Tokens vested so far stay claimable; the unvested rest returns to the authority
+    pub fn revoke_vesting(ctx: Context<RevokeVesting>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let schedule = &ctx.accounts.vesting_schedule;
+        require!(schedule.revocable, ShadeError::VestingNotRevocable);
+        require!(schedule.revoked_at == 0, ShadeError::VestingAlreadyRevoked);
+
+        let vested = schedule.vested_amount(now);
+        let unvested = schedule.total_amount.saturating_sub(vested);
+
+        if unvested > 0 {
+            let config_key = ctx.accounts.protocol_config.key();
+            let id_bytes = schedule.schedule_id.to_le_bytes();
+            let seeds = &[
+                b"vesting".as_ref(),
+                config_key.as_ref(),
+                schedule.beneficiary.as_ref(),
+                id_bytes.as_ref(),
+                &[schedule.bump],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.refund_token_account.to_account_info(),
+                    authority: schedule.to_account_info(),
+                },
+                signer_seeds,
+            );
+            token_interface::transfer_checked(transfer_ctx, unvested, ctx.accounts.mint.decimals)?;
+        }
+
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        schedule.total_amount = vested;
+        schedule.revoked_at = now;
+
+        if schedule.tier_credit > 0 {
+            let staker = ctx
+                .accounts
+                .staker
+                .as_mut()
+                .ok_or(ShadeError::StakerRequired)?;
+            sync_vesting_tier_credit(schedule, staker, &ctx.accounts.protocol_config, now)?;
+        }
+
+        emit_event!(
+            ctx.accounts,
+            VestingRevoked {
+                schedule: schedule.key(),
+                beneficiary: schedule.beneficiary,
+                vested,
+                returned: unvested,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Opt a schedule's unvested tokens into the beneficiary's tier calculation
+    /// The tokens count toward spending tiers but stay locked in the schedule
+    /// and earn no fee share
+    pub fn opt_in_vesting_tier(ctx: Context<OptInVestingTier>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let beneficiary = ctx.accounts.beneficiary.key();
+        let config = &ctx.accounts.protocol_config;
+
+        let staker = &mut ctx.accounts.staker;
+        if staker.user == Pubkey::default() {
+            init_staker(staker, beneficiary, ctx.bumps.staker)?;
+        }
+
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        require!(!schedule.counts_toward_tier, ShadeError::AlreadyOptedIn);
+        schedule.counts_toward_tier = true;
+        sync_vesting_tier_credit(schedule, staker, config, now)?;
+
+        emit_event!(
+            ctx.accounts,
+            VestingTierSynced {
+                schedule: schedule.key(),
+                beneficiary,
+                tier_credit: schedule.tier_credit,
+                tier: staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Refresh the unvested credit of an opted-in schedule (permissionless)
+    pub fn sync_vesting_tier(ctx: Context<SyncVestingTier>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        let staker = &mut ctx.accounts.staker;
+        sync_vesting_tier_credit(schedule, staker, &ctx.accounts.protocol_config, now)?;
+
+        emit_event!(
+            ctx.accounts,
+            VestingTierSynced {
+                schedule: schedule.key(),
+                beneficiary: schedule.beneficiary,
+                tier_credit: schedule.tier_credit,
+                tier: staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // AIRDROPS
+    // ========================================================================
+
+    /// Create a merkle-distributor airdrop and fund its vault (admin only)
+    /// Leaves are sha256(0x00 || index (u64 LE) || claimant || amount (u64 LE))
+    /// Claims close at `claim_deadline`, after which the admin can sweep what
+    /// is left
+    pub fn create_airdrop(
+        ctx: Context<CreateAirdrop>,
+        airdrop_id: u64,
+        merkle_root: [u8; 32],
+        amount: u64,
+        claim_deadline: i64,
+    ) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            claim_deadline > Clock::get()?.unix_timestamp,
+            ShadeError::InvalidClaimDeadline
+        );
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.funder_token_account.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.shade_mint.decimals)?;
+
+        let airdrop = &mut ctx.accounts.airdrop;
+        airdrop.airdrop_id = airdrop_id;
+        airdrop.merkle_root = merkle_root;
+        airdrop.vault = ctx.accounts.vault.key();
+        airdrop.total_amount = amount;
+        airdrop.claimed_amount = 0;
+        airdrop.bump = ctx.bumps.airdrop;
+        airdrop.protocol_config = ctx.accounts.protocol_config.key();
+        airdrop.claim_deadline = claim_deadline;
+
+        emit_event!(
+            ctx.accounts,
+            AirdropCreated {
+                airdrop: airdrop.key(),
+                merkle_root,
+                amount,
+                claim_deadline,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Claim an airdrop allocation into the claimant's wallet
+    pub fn claim_airdrop(
+        ctx: Context<ClaimAirdrop>,
+        index: u64,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let claimant = ctx.accounts.claimant.key();
+        verify_airdrop_claim(
+            &mut ctx.accounts.airdrop,
+            index,
+            &claimant,
+            amount,
+            &proof,
+            now,
+        )?;
+
+        let airdrop = &ctx.accounts.airdrop;
+        let id_bytes = airdrop.airdrop_id.to_le_bytes();
+        let seeds = &[
+            b"airdrop".as_ref(),
+            airdrop.protocol_config.as_ref(),
+            id_bytes.as_ref(),
+            &[airdrop.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.claimant_token_account.to_account_info(),
+                authority: airdrop.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        let receipt = &mut ctx.accounts.claim_receipt;
+        receipt.claimant = claimant;
+        receipt.amount = amount;
+        receipt.claimed_at = now;
+
+        emit_event!(
+            ctx.accounts,
+            AirdropClaimed {
+                airdrop: airdrop.key(),
+                claimant,
+                index,
+                amount,
+                staked: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Claim an airdrop allocation straight into the claimant's staking position
+    pub fn claim_airdrop_to_stake(
+        ctx: Context<ClaimAirdropToStake>,
+        index: u64,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let claimant = ctx.accounts.claimant.key();
+        verify_airdrop_claim(
+            &mut ctx.accounts.airdrop,
+            index,
+            &claimant,
+            amount,
+            &proof,
+            now,
+        )?;
+
+        let airdrop = &ctx.accounts.airdrop;
+        let id_bytes = airdrop.airdrop_id.to_le_bytes();
+        let seeds = &[
+            b"airdrop".as_ref(),
+            airdrop.protocol_config.as_ref(),
+            id_bytes.as_ref(),
+            &[airdrop.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.staking_vault.to_account_info(),
+                authority: airdrop.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        let bump = ctx.bumps.staker;
+        let staker = &mut ctx.accounts.staker;
+        credit_stake(
+            staker,
+            &mut ctx.accounts.protocol_config,
+            claimant,
+            bump,
+            amount,
+        )?;
+
+        let receipt = &mut ctx.accounts.claim_receipt;
+        receipt.claimant = claimant;
+        receipt.amount = amount;
+        receipt.claimed_at = now;
+
+        emit_event!(
+            ctx.accounts,
+            AirdropClaimed {
+                airdrop: ctx.accounts.airdrop.key(),
+                claimant,
+                index,
+                amount,
+                staked: true,
+            }
+        );
+
+        emit_event!(
+            ctx.accounts,
+            Staked {
+                user: claimant,
+                amount,
+                new_total: staker.staked_amount,
+                tier: staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Sweep an airdrop's unclaimed SHADE once its claim window has closed
+    /// (admin only)
+    pub fn sweep_airdrop(ctx: Context<SweepAirdrop>) -> Result<()> {
+        let airdrop = &ctx.accounts.airdrop;
+        require!(
+            Clock::get()?.unix_timestamp >= airdrop.claim_deadline,
+            ShadeError::AirdropClaimWindowOpen
+        );
+        let amount = ctx.accounts.vault.amount;
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let id_bytes = airdrop.airdrop_id.to_le_bytes();
+        let seeds = &[
+            b"airdrop".as_ref(),
+            airdrop.protocol_config.as_ref(),
+            id_bytes.as_ref(),
+            &[airdrop.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+                authority: airdrop.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        emit_event!(
+            ctx.accounts,
+            AirdropSwept {
+                airdrop: airdrop.key(),
+                destination: ctx.accounts.destination.key(),
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // TREASURY LOCKS
+    // ========================================================================
+
+    /// Lock treasury or founder tokens behind a cliff and tranche schedule (admin only)
+    /// Tranche i (0-based) unlocks at cliff_at + i * unlock_interval. There is no
+    /// early-unlock path: the schedule can only ever be pushed later
+    pub fn create_treasury_lock(
+        ctx: Context<CreateTreasuryLock>,
+        lock_id: u64,
+        label: String,
+        total_amount: u64,
+        cliff_at: i64,
+        unlock_interval: i64,
+        unlock_count: u16,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(total_amount > 0, ShadeError::InvalidAmount);
+        require!(label.len() <= 32, ShadeError::LabelTooLong);
+        require!(
+            cliff_at > now
+                && unlock_count > 0
+                && (unlock_count == 1 || unlock_interval > 0)
+                && unlock_interval >= 0,
+            ShadeError::InvalidTreasuryLock
+        );
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.funder_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.vault.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, total_amount, ctx.accounts.mint.decimals)?;
+
+        let lock = &mut ctx.accounts.treasury_lock;
+        lock.lock_id = lock_id;
+        lock.label = label;
+        lock.recipient = ctx.accounts.recipient.key();
+        lock.mint = ctx.accounts.mint.key();
+        lock.vault = ctx.accounts.vault.key();
+        lock.total_amount = total_amount;
+        lock.released_amount = 0;
+        lock.cliff_at = cliff_at;
+        lock.unlock_interval = unlock_interval;
+        lock.unlock_count = unlock_count;
+        lock.tranches_released = 0;
+        lock.created_at = now;
+        lock.bump = ctx.bumps.treasury_lock;
+        lock.protocol_config = ctx.accounts.protocol_config.key();
+
+        emit_event!(
+            ctx.accounts,
+            TreasuryLockCreated {
+                lock: lock.key(),
+                lock_id,
+                label: lock.label.clone(),
+                recipient: lock.recipient,
+                mint: lock.mint,
+                total_amount,
+                cliff_at,
+                unlock_interval,
+                unlock_count,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Release every tranche that has unlocked to the lock's recipient (permissionless)
+    pub fn release_treasury_lock(ctx: Context<ReleaseTreasuryLock>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let lock = &ctx.accounts.treasury_lock;
+        let tranches = lock.unlocked_tranches(now);
+        require!(
+            tranches > lock.tranches_released,
+            ShadeError::NothingUnlocked
+        );
+
+        let amount = lock
+            .unlocked_amount(tranches)
+            .saturating_sub(lock.released_amount);
+
+        let id_bytes = lock.lock_id.to_le_bytes();
+        let seeds = &[
+            b"treasury_lock".as_ref(),
+            lock.protocol_config.as_ref(),
+            id_bytes.as_ref(),
+            &[lock.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.recipient_token_account.to_account_info(),
+                authority: lock.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        let lock = &mut ctx.accounts.treasury_lock;
+        lock.released_amount = lock
+            .released_amount
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        lock.tranches_released = tranches;
+
+        emit_event!(
+            ctx.accounts,
+            TreasuryUnlocked {
+                lock: lock.key(),
+                recipient: lock.recipient,
+                amount,
+                tranches_released: tranches,
+                total_released: lock.released_amount,
+                next_unlock_at: lock.next_unlock_at(),
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Push a lock's cliff later (admin only)
+    /// Moving it earlier is rejected so holders can rely on the published schedule
+    pub fn extend_treasury_lock(ctx: Context<ExtendTreasuryLock>, new_cliff_at: i64) -> Result<()> {
+        let lock = &mut ctx.accounts.treasury_lock;
+        require!(
+            lock.tranches_released == 0,
+            ShadeError::EarlyUnlockProhibited
+        );
+        require!(
+            new_cliff_at > lock.cliff_at,
+            ShadeError::EarlyUnlockProhibited
+        );
+
+        let old_cliff_at = lock.cliff_at;
+        lock.cliff_at = new_cliff_at;
+
+        emit_event!(
+            ctx.accounts,
+            TreasuryLockExtended {
+                lock: lock.key(),
+                old_cliff_at,
+                new_cliff_at,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // REBASE
+    // ========================================================================
+
+    /// Set the share of protocol fees routed to rebases and the epoch length (admin only)
+    pub fn set_rebase_params(
+        ctx: Context<UpdateProtocol>,
+        rebase_bps: u16,
+        rebase_epoch_length: i64,
+    ) -> Result<()> {
+        require!(
+            rebase_bps <= MAX_REBASE_BPS && rebase_epoch_length >= 0,
+            ShadeError::InvalidRebaseParams
+        );
+
+        let config = &mut ctx.accounts.protocol_config;
+        config.rebase_bps = rebase_bps;
+        config.rebase_epoch_length = rebase_epoch_length;
+
+        emit_event!(
+            ctx.accounts,
+            RebaseParamsUpdated {
+                rebase_bps,
+                rebase_epoch_length,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Swap the fees set aside for rebase into SHADE and distribute it pro-rata
+    /// to stakers (admin only, at most once per rebase epoch)
+    /// The set-aside fees move to an escrow owned by the rebase_authority PDA,
+    /// which signs the CPI into a whitelisted AMM (route accounts in
+    /// remaining_accounts) and owns nothing else; whatever the route leaves
+    /// in the escrow goes back to the fee vault. The SHADE must land in the
+    /// staking vault, where it compounds into every position
+    pub fn execute_rebase<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ExecuteRebase<'info>>,
+        min_shade_out: u64,
+        swap_data: Vec<u8>,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let config = &ctx.accounts.protocol_config;
+        require!(config.total_staked > 0, ShadeError::NoStakers);
+        require!(config.rebase_fees_pending > 0, ShadeError::InvalidAmount);
+        require!(
+            now >= config
+                .last_rebase_at
+                .saturating_add(config.rebase_epoch_length),
+            ShadeError::RebaseEpochNotElapsed
+        );
+
+        let fees_before = ctx.accounts.fee_vault.amount;
+        let shade_before = ctx.accounts.staking_vault.amount;
+        let decimals = ctx.accounts.fee_mint.decimals;
+
+        // Only the rebase share is exposed to the route
+        let config_key = config.key();
+        let seeds = &[
+            b"vault_authority".as_ref(),
+            config_key.as_ref(),
+            &[config.vault_authority_bump][..],
+        ];
+        let signer_seeds = &[&seeds[..]];
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.fee_vault.to_account_info(),
+                mint: ctx.accounts.fee_mint.to_account_info(),
+                to: ctx.accounts.rebase_escrow.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, config.rebase_fees_pending, decimals)?;
+
+        let rebase_seeds = &[
+            b"rebase_authority".as_ref(),
+            config_key.as_ref(),
+            &[ctx.bumps.rebase_authority][..],
+        ];
+        invoke_amm(
+            &ctx.accounts.amm_program,
+            ctx.remaining_accounts,
+            ctx.accounts.rebase_authority.key(),
+            swap_data,
+            &rebase_seeds[..],
+        )?;
+
+        // Return what the route didn't use
+        ctx.accounts.rebase_escrow.reload()?;
+        let unused = ctx.accounts.rebase_escrow.amount;
+        if unused > 0 {
+            let rebase_signer = &[&rebase_seeds[..]];
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.rebase_escrow.to_account_info(),
+                    mint: ctx.accounts.fee_mint.to_account_info(),
+                    to: ctx.accounts.fee_vault.to_account_info(),
+                    authority: ctx.accounts.rebase_authority.to_account_info(),
+                },
+                rebase_signer,
+            );
+            token_interface::transfer_checked(transfer_ctx, unused, decimals)?;
+        }
+
+        // The route must pay out SHADE
+        ctx.accounts.fee_vault.reload()?;
+        ctx.accounts.staking_vault.reload()?;
+        let fees_in = fees_before.saturating_sub(ctx.accounts.fee_vault.amount);
+        let shade_out = ctx
+            .accounts
+            .staking_vault
+            .amount
+            .saturating_sub(shade_before);
+        require!(
+            fees_in <= ctx.accounts.protocol_config.rebase_fees_pending,
+            ShadeError::RebaseOverspent
+        );
+        require!(
+            shade_out > 0 && shade_out >= min_shade_out,
+            ShadeError::SlippageExceeded
+        );
+
+        let config = &mut ctx.accounts.protocol_config;
+        config.rebase_fees_pending -= fees_in;
+        config.rebase_per_share = config
+            .rebase_per_share
+            .checked_add(
+                (shade_out as u128)
+                    .checked_mul(REBASE_PRECISION)
+                    .ok_or(ShadeError::Overflow)?
+                    / config.total_staked as u128,
+            )
+            .ok_or(ShadeError::Overflow)?;
+        config.total_rebased = config
+            .total_rebased
+            .checked_add(shade_out)
+            .ok_or(ShadeError::Overflow)?;
+        config.last_rebase_at = now;
+        config.rebase_epoch = config.rebase_epoch.saturating_add(1);
+
+        emit_event!(
+            ctx.accounts,
+            RebaseExecuted {
+                epoch: config.rebase_epoch,
+                amm_program: ctx.accounts.amm_program.key(),
+                fees_in,
+                shade_out,
+                rebase_per_share: config.rebase_per_share,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Compound a staker's accrued rebase into their stake (permissionless)
+    pub fn compound_rebase(ctx: Context<CompoundRebase>) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        let staker = &mut ctx.accounts.staker;
+        let amount = settle_rebase(staker, config)?;
+        require!(amount > 0, ShadeError::NoRewardsToClaim);
+        staker.tier = calculate_tier(staker.tier_weight(), config);
+
+        emit_event!(
+            ctx.accounts,
+            RebaseCompounded {
+                user: staker.user,
+                amount,
+                new_total: staker.staked_amount,
+                tier: staker.tier,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // PROTOCOL-OWNED LIQUIDITY
+    // ========================================================================
+
+    /// Create the treasury PDA that owns protocol funds and LP positions (admin only)
+    pub fn initialize_treasury(ctx: Context<InitializeTreasury>) -> Result<()> {
+        let treasury = &mut ctx.accounts.treasury;
+        treasury.position_count = 0;
+        treasury.bump = ctx.bumps.treasury;
+
+        emit_event!(
+            ctx.accounts,
+            TreasuryInitialized {
+                treasury: treasury.key(),
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Withdraw from a treasury-owned token account, e.g. the fee treasury (admin only)
+    pub fn withdraw_treasury(ctx: Context<WithdrawTreasury>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let config_key = ctx.accounts.protocol_config.key();
+        let seeds = &[
+            b"treasury".as_ref(),
+            config_key.as_ref(),
+            &[ctx.accounts.treasury.bump][..],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.treasury_token_account.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+                authority: ctx.accounts.treasury.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        emit_event!(
+            ctx.accounts,
+            TreasuryWithdrawn {
+                token_account: ctx.accounts.treasury_token_account.key(),
+                destination: ctx.accounts.destination.key(),
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Register a protocol-owned LP position in a whitelisted AMM pool (admin only)
+    /// The LP token account must be owned by the treasury PDA
+    pub fn open_pol_position(ctx: Context<OpenPolPosition>, amm_pool: Pubkey) -> Result<()> {
+        let treasury = &mut ctx.accounts.treasury;
+        let position = &mut ctx.accounts.position;
+        position.position_id = treasury.position_count;
+        position.amm_program = ctx.accounts.amm.program_id;
+        position.amm_pool = amm_pool;
+        position.mint_a = ctx.accounts.mint_a.key();
+        position.mint_b = ctx.accounts.mint_b.key();
+        position.lp_token_account = ctx.accounts.lp_token_account.key();
+        position.lp_balance = ctx.accounts.lp_token_account.amount;
+        position.deposited_a = 0;
+        position.deposited_b = 0;
+        position.withdrawn_a = 0;
+        position.withdrawn_b = 0;
+        position.harvested_a = 0;
+        position.harvested_b = 0;
+        position.opened_at = Clock::get()?.unix_timestamp;
+        position.bump = ctx.bumps.position;
+
+        treasury.position_count = treasury
+            .position_count
+            .checked_add(1)
+            .ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            PolPositionOpened {
+                position: position.key(),
+                amm_program: position.amm_program,
+                amm_pool,
+                mint_a: position.mint_a,
+                mint_b: position.mint_b,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Add treasury liquidity to a position's AMM pool (admin only)
+    /// The AMM deposit instruction runs as a CPI signed by the treasury PDA
+    pub fn pol_add_liquidity<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ManagePolPosition<'info>>,
+        min_lp_out: u64,
+        amm_data: Vec<u8>,
+    ) -> Result<()> {
+        let (a_before, b_before, lp_before) = ctx.accounts.balances();
+        ctx.accounts.invoke(ctx.remaining_accounts, amm_data)?;
+        let (a_after, b_after, lp_after) = ctx.accounts.balances();
+
+        let amount_a = a_before.saturating_sub(a_after);
+        let amount_b = b_before.saturating_sub(b_after);
+        let lp_minted = lp_after.saturating_sub(lp_before);
+        require!(
+            lp_minted > 0 && lp_minted >= min_lp_out,
+            ShadeError::SlippageExceeded
+        );
+
+        let position = &mut ctx.accounts.position;
+        position.deposited_a = position
+            .deposited_a
+            .checked_add(amount_a)
+            .ok_or(ShadeError::Overflow)?;
+        position.deposited_b = position
+            .deposited_b
+            .checked_add(amount_b)
+            .ok_or(ShadeError::Overflow)?;
+        position.lp_balance = lp_after;
+
+        emit_event!(
+            ctx.accounts,
+            PolLiquidityChanged {
+                position: position.key(),
+                added: true,
+                amount_a,
+                amount_b,
+                lp_amount: lp_minted,
+                lp_balance: lp_after,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Pull liquidity from a position back into the treasury (admin only)
+    pub fn pol_remove_liquidity<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ManagePolPosition<'info>>,
+        min_amount_a: u64,
+        min_amount_b: u64,
+        amm_data: Vec<u8>,
+    ) -> Result<()> {
+        let (a_before, b_before, lp_before) = ctx.accounts.balances();
+        ctx.accounts.invoke(ctx.remaining_accounts, amm_data)?;
+        let (a_after, b_after, lp_after) = ctx.accounts.balances();
+
+        let amount_a = a_after.saturating_sub(a_before);
+        let amount_b = b_after.saturating_sub(b_before);
+        let lp_burned = lp_before.saturating_sub(lp_after);
+        require!(
+            a_after >= a_before && b_after >= b_before,
+            ShadeError::PolBalanceDecreased
+        );
+        require!(
+            amount_a >= min_amount_a && amount_b >= min_amount_b,
+            ShadeError::SlippageExceeded
+        );
+
+        let position = &mut ctx.accounts.position;
+        position.withdrawn_a = position
+            .withdrawn_a
+            .checked_add(amount_a)
+            .ok_or(ShadeError::Overflow)?;
+        position.withdrawn_b = position
+            .withdrawn_b
+            .checked_add(amount_b)
+            .ok_or(ShadeError::Overflow)?;
+        position.lp_balance = lp_after;
+
+        emit_event!(
+            ctx.accounts,
+            PolLiquidityChanged {
+                position: position.key(),
+                added: false,
+                amount_a,
+                amount_b,
+                lp_amount: lp_burned,
+                lp_balance: lp_after,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Collect a position's accrued trading fees into the treasury (admin only)
+    /// The harvest may not touch the LP balance or draw down treasury tokens
+    pub fn harvest_pol_fees<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ManagePolPosition<'info>>,
+        amm_data: Vec<u8>,
+    ) -> Result<()> {
+        let (a_before, b_before, lp_before) = ctx.accounts.balances();
+        ctx.accounts.invoke(ctx.remaining_accounts, amm_data)?;
+        let (a_after, b_after, lp_after) = ctx.accounts.balances();
+
+        require!(
+            a_after >= a_before && b_after >= b_before && lp_after >= lp_before,
+            ShadeError::PolBalanceDecreased
+        );
+        let amount_a = a_after - a_before;
+        let amount_b = b_after - b_before;
+        require!(amount_a > 0 || amount_b > 0, ShadeError::NoRewardsToClaim);
+
+        let position = &mut ctx.accounts.position;
+        position.harvested_a = position
+            .harvested_a
+            .checked_add(amount_a)
+            .ok_or(ShadeError::Overflow)?;
+        position.harvested_b = position
+            .harvested_b
+            .checked_add(amount_b)
+            .ok_or(ShadeError::Overflow)?;
+        position.lp_balance = lp_after;
+
+        emit_event!(
+            ctx.accounts,
+            PolFeesHarvested {
+                position: position.key(),
+                amount_a,
+                amount_b,
+                total_harvested_a: position.harvested_a,
+                total_harvested_b: position.harvested_b,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // BONDS
+    // ========================================================================
+
+    /// Open a bond market selling vested, discounted SHADE for liquidity in a
+    /// fog pool and fund its SHADE inventory (admin only)
+    pub fn create_bond_market(
+        ctx: Context<CreateBondMarket>,
+        market_id: u64,
+        terms: BondTerms,
+        epoch_length: i64,
+        vesting_duration: i64,
+        inventory: u64,
+    ) -> Result<()> {
+        require!(
+            terms.is_valid() && epoch_length > 0 && vesting_duration >= 0,
+            ShadeError::InvalidBondMarket
+        );
+        require!(inventory > 0, ShadeError::InvalidAmount);
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.funder_token_account.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.shade_vault.to_account_info(),
+                authority: ctx.accounts.authority.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(
+            transfer_ctx,
+            inventory,
+            ctx.accounts.shade_mint.decimals,
+        )?;
+
+        let market = &mut ctx.accounts.bond_market;
+        market.market_id = market_id;
+        market.fog_pool = ctx.accounts.fog_pool.key();
+        market.shade_vault = ctx.accounts.shade_vault.key();
+        market.terms = terms;
+        market.epoch_length = epoch_length;
+        market.epoch_start = Clock::get()?.unix_timestamp;
+        market.epoch_sold = 0;
+        market.vesting_duration = vesting_duration;
+        market.total_sold = 0;
+        market.total_raised = 0;
+        market.outstanding = 0;
+        market.bond_count = 0;
+        market.is_active = true;
+        market.bump = ctx.bumps.bond_market;
+        market.protocol_config = ctx.accounts.protocol_config.key();
+        market.total_shares = 0;
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            BondMarketCreated {
+                market: market.key(),
+                fog_pool: market.fog_pool,
+                sequence,
+                terms,
+                epoch_length,
+                vesting_duration,
+                inventory,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Update a bond market's pricing and per-epoch capacity (admin only)
+    pub fn update_bond_market(ctx: Context<UpdateBondMarket>, terms: BondTerms) -> Result<()> {
+        require!(terms.is_valid(), ShadeError::InvalidBondMarket);
+
+        let market = &mut ctx.accounts.bond_market;
+        market.terms = terms;
+
+        emit_event!(
+            ctx.accounts,
+            BondMarketUpdated {
+                market: market.key(),
+                terms,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Buy a bond: deposit pool-asset liquidity into the market's fog pool for
+    /// SHADE that vests linearly over the market's vesting duration
+    /// The discount shrinks along the pricing curve as the epoch's capacity fills.
+    /// The deposit is protocol-owned liquidity: its LP shares are minted to
+    /// the treasury, priced like any other deposit
+    pub fn purchase_bond(ctx: Context<PurchaseBond>, amount: u64, max_price: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            ctx.accounts.fog_pool.status == PoolStatus::Active,
+            ShadeError::PoolNotActive
+        );
+
+        // Liquidity goes straight into the fog pool
+        let vault_balance = ctx.accounts.pool_vault.amount;
+        let pool_balance = ctx.accounts.fog_pool.available_balance(vault_balance);
+        let pool_value = ctx.accounts.fog_pool.lp_value(vault_balance);
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.buyer_token_account.to_account_info(),
+                mint: ctx.accounts.payment_mint.to_account_info(),
+                to: ctx.accounts.pool_vault.to_account_info(),
+                authority: ctx.accounts.buyer.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(
+            transfer_ctx,
+            amount,
+            ctx.accounts.payment_mint.decimals,
+        )?;
+
+        // Bonds are priced on what reached the pool, net of any transfer fee
+        let amount = received_amount(&mut ctx.accounts.pool_vault, vault_balance)?;
+        let max_total_deposits = ctx.accounts.fog_pool.max_total_deposits;
+        require!(
+            max_total_deposits == 0
+                || pool_balance
+                    .checked_add(amount)
+                    .ok_or(ShadeError::Overflow)?
+                    <= max_total_deposits,
+            ShadeError::DepositCapExceeded
+        );
+        let share_supply = ctx
+            .accounts
+            .fog_pool
+            .share_supply(ctx.accounts.lp_mint.supply)?;
+        let (shares, locked) = lp_shares_for_deposit(amount, pool_value, share_supply)?;
+        require!(shares > 0, ShadeError::InvalidAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        let market = &mut ctx.accounts.bond_market;
+        require!(market.is_active, ShadeError::BondMarketClosed);
+        market.roll_epoch(now);
+
+        let price = market.current_price();
+        require!(price <= max_price, ShadeError::SlippageExceeded);
+
+        let payout = (amount as u128)
+            .checked_mul(BOND_PRICE_SCALE)
+            .ok_or(ShadeError::Overflow)?
+            .checked_div(price as u128)
+            .ok_or(ShadeError::Overflow)? as u64;
+        require!(payout > 0, ShadeError::InvalidAmount);
+        require!(
+            market.epoch_sold.saturating_add(payout) <= market.terms.epoch_capacity,
+            ShadeError::BondCapacityExceeded
+        );
+        require!(
+            ctx.accounts
+                .shade_vault
+                .amount
+                .saturating_sub(market.outstanding)
+                >= payout,
+            ShadeError::BondCapacityExceeded
+        );
+
+        let fog_pool = &mut ctx.accounts.fog_pool;
+        fog_pool.locked_shares = fog_pool
+            .locked_shares
+            .checked_add(locked)
+            .ok_or(ShadeError::Overflow)?;
+        fog_pool.total_deposited = fog_pool
+            .total_deposited
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        let pool_key = fog_pool.key();
+        let seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[fog_pool.vault_authority_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.lp_mint.to_account_info(),
+                to: ctx.accounts.treasury_lp_account.to_account_info(),
+                authority: ctx.accounts.pool_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::mint_to(mint_ctx, shares)?;
+
+        let bond = &mut ctx.accounts.bond;
+        bond.owner = ctx.accounts.buyer.key();
+        bond.market = market.key();
+        bond.bond_id = market.bond_count;
+        bond.payout = payout;
+        bond.claimed = 0;
+        bond.created_at = now;
+        bond.vests_at = now
+            .checked_add(market.vesting_duration)
+            .ok_or(ShadeError::Overflow)?;
+        bond.bump = ctx.bumps.bond;
+
+        market.bond_count = market.bond_count.saturating_add(1);
+        market.epoch_sold = market.epoch_sold.saturating_add(payout);
+        market.total_sold = market
+            .total_sold
+            .checked_add(payout)
+            .ok_or(ShadeError::Overflow)?;
+        market.total_raised = market
+            .total_raised
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        market.total_shares = market
+            .total_shares
+            .checked_add(shares)
+            .ok_or(ShadeError::Overflow)?;
+        market.outstanding = market
+            .outstanding
+            .checked_add(payout)
+            .ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            BondPurchased {
+                market: market.key(),
+                bond: bond.key(),
+                owner: bond.owner,
+                amount,
+                shares,
+                payout,
+                price,
+                vests_at: bond.vests_at,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Redeem the vested part of a bond (bond owner only)
+    pub fn redeem_bond(ctx: Context<RedeemBond>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let bond = &ctx.accounts.bond;
+        let claimable = bond.vested_amount(now).saturating_sub(bond.claimed);
+        require!(claimable > 0, ShadeError::NothingVested);
+
+        let market = &ctx.accounts.bond_market;
+        let id_bytes = market.market_id.to_le_bytes();
+        let seeds = &[
+            b"bond_market".as_ref(),
+            market.protocol_config.as_ref(),
+            id_bytes.as_ref(),
+            &[market.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.shade_vault.to_account_info(),
+                mint: ctx.accounts.shade_mint.to_account_info(),
+                to: ctx.accounts.owner_shade_account.to_account_info(),
+                authority: market.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(
+            transfer_ctx,
+            claimable,
+            ctx.accounts.shade_mint.decimals,
+        )?;
+
+        let bond = &mut ctx.accounts.bond;
+        bond.claimed = bond
+            .claimed
+            .checked_add(claimable)
+            .ok_or(ShadeError::Overflow)?;
+        let market = &mut ctx.accounts.bond_market;
+        market.outstanding = market.outstanding.saturating_sub(claimable);
+
+        emit_event!(
+            ctx.accounts,
+            BondRedeemed {
+                bond: bond.key(),
+                owner: bond.owner,
+                amount: claimable,
+                total_claimed: bond.claimed,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Stop sales and return unsold SHADE inventory (admin only)
+    /// SHADE owed to existing bonds stays in the vault
+    pub fn close_bond_market(ctx: Context<CloseBondMarket>) -> Result<()> {
+        let market = &ctx.accounts.bond_market;
+        require!(market.is_active, ShadeError::BondMarketClosed);
+        let unsold = ctx
+            .accounts
+            .shade_vault
+            .amount
+            .saturating_sub(market.outstanding);
+
+        if unsold > 0 {
+            let id_bytes = market.market_id.to_le_bytes();
+            let seeds = &[
+                b"bond_market".as_ref(),
+                market.protocol_config.as_ref(),
+                id_bytes.as_ref(),
+                &[market.bump],
+            ];
+            let signer_seeds = &[&seeds[..]];
+
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.shade_vault.to_account_info(),
+                    mint: ctx.accounts.shade_mint.to_account_info(),
+                    to: ctx.accounts.refund_token_account.to_account_info(),
+                    authority: market.to_account_info(),
+                },
+                signer_seeds,
+            );
+            token_interface::transfer_checked(
+                transfer_ctx,
+                unsold,
+                ctx.accounts.shade_mint.decimals,
+            )?;
+        }
+
+        let market = &mut ctx.accounts.bond_market;
+        market.is_active = false;
+
+        emit_event!(
+            ctx.accounts,
+            BondMarketClosed {
+                market: market.key(),
+                returned: unsold,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // GAUGES
+    // ========================================================================
+
+    /// Create a gauge where holders of `stake_mint` (LP share tokens) stake to
+    /// earn SHADE emissions at a governance-set rate (admin only)
+    /// `stake_mint` must be the fog pool's LP mint, so the gauge can only pay
+    /// out to that pool's liquidity and its operator controls the bonus
+    pub fn create_gauge(ctx: Context<CreateGauge>, emission_rate: u64) -> Result<()> {
+        let gauge = &mut ctx.accounts.gauge;
+        gauge.stake_mint = ctx.accounts.stake_mint.key();
+        gauge.fog_pool = ctx.accounts.fog_pool.key();
+        gauge.stake_vault = ctx.accounts.stake_vault.key();
+        gauge.reward_vault = ctx.accounts.reward_vault.key();
+        gauge.emissions = EmissionStream {
+            rate: emission_rate,
+            ..Default::default()
+        };
+        gauge.bonus_mint = Pubkey::default();
+        gauge.bonus_vault = Pubkey::default();
+        gauge.bonus = EmissionStream::default();
+        gauge.last_update = Clock::get()?.unix_timestamp;
+        gauge.total_staked = 0;
+        gauge.bump = ctx.bumps.gauge;
+        gauge.protocol_config = ctx.accounts.protocol_config.key();
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            GaugeCreated {
+                gauge: gauge.key(),
+                stake_mint: gauge.stake_mint,
+                fog_pool: gauge.fog_pool,
+                sequence,
+                emission_rate,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set a gauge's SHADE emission rate per second (admin only)
+    pub fn set_gauge_rate(ctx: Context<SetGaugeRate>, emission_rate: u64) -> Result<()> {
+        let gauge = &mut ctx.accounts.gauge;
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+        let old_rate = gauge.emissions.rate;
+        gauge.emissions.rate = emission_rate;
+
+        emit_event!(
+            ctx.accounts,
+            GaugeRateUpdated {
+                gauge: gauge.key(),
+                bonus: false,
+                old_rate,
+                new_rate: emission_rate,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Top up a gauge's SHADE or bonus reward vault (anyone)
+    /// The vault passed in selects which stream is funded
+    pub fn fund_gauge(ctx: Context<FundGauge>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.funder_token_account.to_account_info(),
+                mint: ctx.accounts.reward_mint.to_account_info(),
+                to: ctx.accounts.reward_vault.to_account_info(),
+                authority: ctx.accounts.funder.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.reward_mint.decimals)?;
+
+        let bonus = ctx.accounts.reward_vault.key() == ctx.accounts.gauge.bonus_vault;
+        let gauge = &mut ctx.accounts.gauge;
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+        let stream = if bonus {
+            &mut gauge.bonus
+        } else {
+            &mut gauge.emissions
+        };
+        stream.funded = stream
+            .funded
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            GaugeFunded {
+                gauge: gauge.key(),
+                funder: ctx.accounts.funder.key(),
+                bonus,
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Stake LP share tokens in a gauge
+    pub fn stake_in_gauge(ctx: Context<StakeInGauge>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let transfer_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.user_token_account.to_account_info(),
+                mint: ctx.accounts.stake_mint.to_account_info(),
+                to: ctx.accounts.stake_vault.to_account_info(),
+                authority: ctx.accounts.user.to_account_info(),
+            },
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.stake_mint.decimals)?;
+
+        let gauge = &mut ctx.accounts.gauge;
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+
+        let position = &mut ctx.accounts.gauge_stake;
+        if position.owner == Pubkey::default() {
+            position.owner = ctx.accounts.user.key();
+            position.gauge = gauge.key();
+            position.bump = ctx.bumps.gauge_stake;
+        }
+        position.settle(gauge)?;
+        position.amount = position
+            .amount
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        position.sync_debts(gauge);
+
+        gauge.total_staked = gauge
+            .total_staked
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            GaugeStaked {
+                gauge: gauge.key(),
+                user: position.owner,
+                amount,
+                new_total: position.amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Withdraw LP share tokens from a gauge
+    /// Accrued rewards stay pending until claimed
+    pub fn unstake_from_gauge(ctx: Context<UnstakeFromGauge>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            ctx.accounts.gauge_stake.amount >= amount,
+            ShadeError::InsufficientStake
+        );
+
+        let gauge = &mut ctx.accounts.gauge;
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+        let position = &mut ctx.accounts.gauge_stake;
+        position.settle(gauge)?;
+
+        let config_key = gauge.protocol_config;
+        let mint_key = gauge.stake_mint;
+        let seeds = &[
+            b"gauge".as_ref(),
+            config_key.as_ref(),
+            mint_key.as_ref(),
+            &[gauge.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.stake_vault.to_account_info(),
+                mint: ctx.accounts.stake_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: gauge.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.stake_mint.decimals)?;
+
+        position.amount -= amount;
+        position.sync_debts(gauge);
+        gauge.total_staked = gauge.total_staked.saturating_sub(amount);
+
+        emit_event!(
+            ctx.accounts,
+            GaugeUnstaked {
+                gauge: gauge.key(),
+                user: position.owner,
+                amount,
+                remaining: position.amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Claim accrued SHADE emissions from a gauge
+    pub fn claim_gauge_rewards(ctx: Context<ClaimGaugeRewards>) -> Result<()> {
+        let gauge = &mut ctx.accounts.gauge;
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+        let position = &mut ctx.accounts.gauge_stake;
+        position.settle(gauge)?;
+
+        let amount = position.pending_rewards;
+        require!(amount > 0, ShadeError::NoRewardsToClaim);
+
+        let config_key = gauge.protocol_config;
+        let mint_key = gauge.stake_mint;
+        let seeds = &[
+            b"gauge".as_ref(),
+            config_key.as_ref(),
+            mint_key.as_ref(),
+            &[gauge.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                mint: ctx.accounts.reward_mint.to_account_info(),
+                to: ctx.accounts.user_shade_account.to_account_info(),
+                authority: gauge.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.reward_mint.decimals)?;
+
+        position.pending_rewards = 0;
+
+        emit_event!(
+            ctx.accounts,
+            GaugeRewardsClaimed {
+                gauge: gauge.key(),
+                user: position.owner,
+                bonus: false,
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Attach a bonus reward token to a gauge (fog pool authority only)
+    /// The operator funds it through fund_gauge and sets its rate; it is paid
+    /// on top of SHADE emissions to the same stakers
+    pub fn add_gauge_bonus(ctx: Context<AddGaugeBonus>, rate: u64) -> Result<()> {
+        let gauge = &mut ctx.accounts.gauge;
+        require!(
+            gauge.bonus_mint == Pubkey::default(),
+            ShadeError::GaugeBonusExists
+        );
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+        gauge.bonus_mint = ctx.accounts.bonus_mint.key();
+        gauge.bonus_vault = ctx.accounts.bonus_vault.key();
+        gauge.bonus = EmissionStream {
+            rate,
+            ..Default::default()
+        };
+
+        emit_event!(
+            ctx.accounts,
+            GaugeBonusAdded {
+                gauge: gauge.key(),
+                bonus_mint: gauge.bonus_mint,
+                rate,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Set a gauge's bonus emission rate per second (fog pool authority only)
+    pub fn set_gauge_bonus_rate(ctx: Context<SetGaugeBonusRate>, rate: u64) -> Result<()> {
+        let gauge = &mut ctx.accounts.gauge;
+        require!(
+            gauge.bonus_mint != Pubkey::default(),
+            ShadeError::NoGaugeBonus
+        );
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+        let old_rate = gauge.bonus.rate;
+        gauge.bonus.rate = rate;
+
+        emit_event!(
+            ctx.accounts,
+            GaugeRateUpdated {
+                gauge: gauge.key(),
+                bonus: true,
+                old_rate,
+                new_rate: rate,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Claim accrued bonus tokens from a gauge
+    pub fn claim_gauge_bonus(ctx: Context<ClaimGaugeBonus>) -> Result<()> {
+        let gauge = &mut ctx.accounts.gauge;
+        gauge.accrue(Clock::get()?.unix_timestamp)?;
+        let position = &mut ctx.accounts.gauge_stake;
+        position.settle(gauge)?;
+
+        let amount = position.pending_bonus;
+        require!(amount > 0, ShadeError::NoRewardsToClaim);
+
+        let config_key = gauge.protocol_config;
+        let mint_key = gauge.stake_mint;
+        let seeds = &[
+            b"gauge".as_ref(),
+            config_key.as_ref(),
+            mint_key.as_ref(),
+            &[gauge.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.bonus_vault.to_account_info(),
+                mint: ctx.accounts.bonus_mint.to_account_info(),
+                to: ctx.accounts.user_token_account.to_account_info(),
+                authority: gauge.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.bonus_mint.decimals)?;
+
+        position.pending_bonus = 0;
+
+        emit_event!(
+            ctx.accounts,
+            GaugeRewardsClaimed {
+                gauge: gauge.key(),
+                user: position.owner,
+                bonus: true,
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // CROSS-POOL ROUTING
+    // ========================================================================
+
+    /// Create a cross-pool agreement: the creditor pool fronts spends made
+    /// under the debtor pool's authorizations, up to `credit_limit` outstanding
+    /// Both pool authorities must sign
+    pub fn create_pool_agreement(
+        ctx: Context<CreatePoolAgreement>,
+        credit_limit: u64,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.debtor_pool.key() != ctx.accounts.creditor_pool.key(),
+            ShadeError::InvalidPoolAgreement
+        );
+
+        let agreement = &mut ctx.accounts.agreement;
+        agreement.debtor_pool = ctx.accounts.debtor_pool.key();
+        agreement.creditor_pool = ctx.accounts.creditor_pool.key();
+        agreement.credit_limit = credit_limit;
+        agreement.outstanding = 0;
+        agreement.total_routed = 0;
+        agreement.total_settled = 0;
+        agreement.created_at = Clock::get()?.unix_timestamp;
+        agreement.bump = ctx.bumps.agreement;
+
+        emit_event!(
+            ctx.accounts,
+            PoolAgreementUpdated {
+                agreement: agreement.key(),
+                debtor_pool: agreement.debtor_pool,
+                creditor_pool: agreement.creditor_pool,
+                credit_limit,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Change how much the creditor pool is willing to front (creditor authority only)
+    /// Lowering the limit below the outstanding debt only blocks new routed spends
+    pub fn set_agreement_limit(ctx: Context<SetAgreementLimit>, credit_limit: u64) -> Result<()> {
+        let agreement = &mut ctx.accounts.agreement;
+        agreement.credit_limit = credit_limit;
+
+        emit_event!(
+            ctx.accounts,
+            PoolAgreementUpdated {
+                agreement: agreement.key(),
+                debtor_pool: agreement.debtor_pool,
+                creditor_pool: agreement.creditor_pool,
+                credit_limit,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Spend under an authorization on the debtor pool, paid from the creditor
+    /// pool's vault; the amount is added to the debtor pool's debt
+    pub fn routed_spend(
+        ctx: Context<RoutedSpend>,
+        amount: u64,
+        recipient_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.creditor_pool.status == PoolStatus::Active,
+            ShadeError::PoolNotActive
+        );
+
+        // The debt covers everything drawn, including a fee charged on top
+        let amount = process_spend(
+            &mut ctx.accounts.spend,
+            amount,
+            false,
+            &recipient_proof,
+            Some((
+                &ctx.accounts.creditor_pool,
+                &ctx.accounts.creditor_vault,
+                &ctx.accounts.creditor_vault_authority,
+            )),
+            None,
+        )?
+        .amount;
+
+        let agreement = &mut ctx.accounts.agreement;
+        let outstanding = agreement
+            .outstanding
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        require!(
+            outstanding <= agreement.credit_limit,
+            ShadeError::CreditLimitExceeded
+        );
+        agreement.outstanding = outstanding;
+        agreement.total_routed = agreement
+            .total_routed
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        // Both pools' LP value carries the debt until it is settled
+        let debtor_pool = &mut ctx.accounts.spend.fog_pool;
+        debtor_pool.payables = debtor_pool
+            .payables
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        let creditor_pool = &mut ctx.accounts.creditor_pool;
+        creditor_pool.receivables = creditor_pool
+            .receivables
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        emit_event!(
+            ctx.accounts,
+            SpendRouted {
+                authorization: ctx.accounts.spend.authorization.key(),
+                debtor_pool: agreement.debtor_pool,
+                creditor_pool: agreement.creditor_pool,
+                amount,
+                outstanding,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Repay routed spends from the debtor pool's vault to the creditor pool's
+    /// vault (either pool authority)
+    pub fn settle_pool_debt(ctx: Context<SettlePoolDebt>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+        require!(
+            amount <= ctx.accounts.agreement.outstanding,
+            ShadeError::InvalidAmount
+        );
+
+        let debtor_pool = &ctx.accounts.debtor_pool;
+        require!(
+            debtor_pool.available_balance(ctx.accounts.debtor_vault.amount) >= amount,
+            ShadeError::InsufficientPoolBalance
+        );
+        let pool_key = debtor_pool.key();
+        let seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[debtor_pool.vault_authority_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.debtor_vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.creditor_vault.to_account_info(),
+                authority: ctx.accounts.debtor_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        let creditor_balance = ctx.accounts.creditor_vault.amount;
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        // The debt goes down by what the creditor received, net of any transfer fee
+        let amount = received_amount(&mut ctx.accounts.creditor_vault, creditor_balance)?;
+        let agreement = &mut ctx.accounts.agreement;
+        agreement.outstanding -= amount;
+        agreement.total_settled = agreement
+            .total_settled
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        // Saturating: debt routed before the pools tracked it isn't booked
+        let debtor_pool = &mut ctx.accounts.debtor_pool;
+        debtor_pool.payables = debtor_pool.payables.saturating_sub(amount);
+        let creditor_pool = &mut ctx.accounts.creditor_pool;
+        creditor_pool.receivables = creditor_pool.receivables.saturating_sub(amount);
+
+        emit_event!(
+            ctx.accounts,
+            PoolDebtSettled {
+                agreement: agreement.key(),
+                amount,
+                outstanding: agreement.outstanding,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // FEDERATIONS
+    // ========================================================================
+
+    /// Create a federation founded by a fog pool (founding pool authority only)
+    /// Every member pool must hold the founder's mint; `debit_limit` caps how
+    /// far any member can fall behind on net settlement
+    pub fn create_federation(ctx: Context<CreateFederation>, debit_limit: u64) -> Result<()> {
+        let federation = &mut ctx.accounts.federation;
+        federation.authority = ctx.accounts.authority.key();
+        federation.founding_pool = ctx.accounts.fog_pool.key();
+        federation.mint = ctx.accounts.vault.mint;
+        federation.debit_limit = debit_limit;
+        federation.members = vec![FederationMember {
+            fog_pool: ctx.accounts.fog_pool.key(),
+            net_position: 0,
+        }];
+        federation.total_routed = 0;
+        federation.total_settled = 0;
+        federation.created_at = Clock::get()?.unix_timestamp;
+        federation.bump = ctx.bumps.federation;
+
+        emit_event!(
+            ctx.accounts,
+            FederationCreated {
+                federation: federation.key(),
+                founding_pool: federation.founding_pool,
+                mint: federation.mint,
+                debit_limit,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Change the federation's debit limit (federation authority only)
+    pub fn set_federation_debit_limit(
+        ctx: Context<UpdateFederation>,
+        debit_limit: u64,
+    ) -> Result<()> {
+        ctx.accounts.federation.debit_limit = debit_limit;
+
+        emit_event!(
+            ctx.accounts,
+            FederationDebitLimitUpdated {
+                federation: ctx.accounts.federation.key(),
+                debit_limit,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Add a pool to a federation
+    /// Both the federation authority and the joining pool's authority must sign
+    pub fn join_federation(ctx: Context<JoinFederation>) -> Result<()> {
+        let federation = &mut ctx.accounts.federation;
+        let fog_pool = ctx.accounts.fog_pool.key();
+        require!(
+            federation.position(&fog_pool).is_none(),
+            ShadeError::AlreadyFederationMember
+        );
+        require!(
+            federation.members.len() < MAX_FEDERATION_MEMBERS,
+            ShadeError::FederationFull
+        );
+        federation.members.push(FederationMember {
+            fog_pool,
+            net_position: 0,
+        });
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            FederationMembershipChanged {
+                federation: federation.key(),
+                fog_pool,
+                sequence,
+                joined: true,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Remove a pool from a federation once its net position is settled to zero
+    /// Either the federation authority or the pool's authority may sign
+    pub fn leave_federation(ctx: Context<LeaveFederation>) -> Result<()> {
+        let federation = &mut ctx.accounts.federation;
+        let fog_pool = ctx.accounts.fog_pool.key();
+        require!(
+            fog_pool != federation.founding_pool,
+            ShadeError::InvalidFederation
+        );
+        let i = federation.member_index(&fog_pool)?;
+        require!(
+            federation.members[i].net_position == 0,
+            ShadeError::FederationPositionOpen
+        );
+        federation.members.swap_remove(i);
+
+        let sequence = ctx.accounts.fog_pool.next_event_sequence()?;
+        emit_event!(
+            ctx.accounts,
+            FederationMembershipChanged {
+                federation: federation.key(),
+                fog_pool,
+                sequence,
+                joined: false,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Spend under an authorization issued on one member pool, paid from
+    /// another member pool's vault; the issuing pool owes the paying pool
+    pub fn federated_spend(
+        ctx: Context<FederatedSpend>,
+        amount: u64,
+        recipient_proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let issuing_pool = ctx.accounts.spend.fog_pool.key();
+        let paying_pool = ctx.accounts.paying_pool.key();
+        require!(issuing_pool != paying_pool, ShadeError::InvalidFederation);
+        require!(
+            ctx.accounts.paying_pool.status == PoolStatus::Active,
+            ShadeError::PoolNotActive
+        );
+
+        // The debt covers everything drawn, including a fee charged on top
+        let amount = process_spend(
+            &mut ctx.accounts.spend,
+            amount,
+            false,
+            &recipient_proof,
+            Some((
+                &ctx.accounts.paying_pool,
+                &ctx.accounts.paying_vault,
+                &ctx.accounts.paying_vault_authority,
+            )),
+            None,
+        )?
+        .amount;
+
+        let federation = &mut ctx.accounts.federation;
+        let debtor = federation.member_index(&issuing_pool)?;
+        let creditor = federation.member_index(&paying_pool)?;
+        let signed = i64::try_from(amount).map_err(|_| ShadeError::Overflow)?;
+        let debtor_before = federation.members[debtor].net_position;
+        let creditor_before = federation.members[creditor].net_position;
+        let debtor_position = debtor_before
+            .checked_sub(signed)
+            .ok_or(ShadeError::Overflow)?;
+        require!(
+            debtor_position >= 0 || debtor_position.unsigned_abs() <= federation.debit_limit,
+            ShadeError::FederationDebitLimitExceeded
+        );
+        let creditor_position = creditor_before
+            .checked_add(signed)
+            .ok_or(ShadeError::Overflow)?;
+        federation.members[debtor].net_position = debtor_position;
+        federation.members[creditor].net_position = creditor_position;
+        federation.total_routed = federation
+            .total_routed
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+
+        // Both pools' LP value carries their positions until settled
+        ctx.accounts
+            .spend
+            .fog_pool
+            .rebook_federation_position(debtor_before, debtor_position);
+        ctx.accounts
+            .paying_pool
+            .rebook_federation_position(creditor_before, creditor_position);
+
+        emit_event!(
+            ctx.accounts,
+            FederatedSpendExecuted {
+                federation: ctx.accounts.federation.key(),
+                authorization: ctx.accounts.spend.authorization.key(),
+                issuing_pool,
+                paying_pool,
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Settle between two members: a pool with a negative net position pays a
+    /// pool with a positive one (either pool's authority)
+    pub fn settle_federation(ctx: Context<SettleFederation>, amount: u64) -> Result<()> {
+        require!(amount > 0, ShadeError::InvalidAmount);
+
+        let federation = &ctx.accounts.federation;
+        let debtor = federation.member_index(&ctx.accounts.debtor_pool.key())?;
+        let creditor = federation.member_index(&ctx.accounts.creditor_pool.key())?;
+        let signed = i64::try_from(amount).map_err(|_| ShadeError::Overflow)?;
+        require!(
+            federation.members[debtor].net_position <= -signed
+                && federation.members[creditor].net_position >= signed,
+            ShadeError::SettleExceedsPosition
+        );
+
+        let debtor_pool = &ctx.accounts.debtor_pool;
+        require!(
+            debtor_pool.available_balance(ctx.accounts.debtor_vault.amount) >= amount,
+            ShadeError::InsufficientPoolBalance
+        );
+        let pool_key = debtor_pool.key();
+        let seeds = &[
+            b"pool_vault_authority".as_ref(),
+            pool_key.as_ref(),
+            &[debtor_pool.vault_authority_bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let transfer_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.accounts.debtor_vault.to_account_info(),
+                mint: ctx.accounts.mint.to_account_info(),
+                to: ctx.accounts.creditor_vault.to_account_info(),
+                authority: ctx.accounts.debtor_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        let creditor_balance = ctx.accounts.creditor_vault.amount;
+        token_interface::transfer_checked(transfer_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        // Positions move by what the creditor received, net of any transfer fee
+        let amount = received_amount(&mut ctx.accounts.creditor_vault, creditor_balance)?;
+        let signed = i64::try_from(amount).map_err(|_| ShadeError::Overflow)?;
+        let federation = &mut ctx.accounts.federation;
+        let debtor_before = federation.members[debtor].net_position;
+        let creditor_before = federation.members[creditor].net_position;
+        federation.members[debtor].net_position += signed;
+        federation.members[creditor].net_position -= signed;
+        federation.total_settled = federation
+            .total_settled
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        let debtor_position = federation.members[debtor].net_position;
+        let creditor_position = federation.members[creditor].net_position;
+        ctx.accounts
+            .debtor_pool
+            .rebook_federation_position(debtor_before, debtor_position);
+        ctx.accounts
+            .creditor_pool
+            .rebook_federation_position(creditor_before, creditor_position);
+
+        emit_event!(
+            ctx.accounts,
+            FederationSettled {
+                federation: ctx.accounts.federation.key(),
+                debtor_pool: ctx.accounts.debtor_pool.key(),
+                creditor_pool: ctx.accounts.creditor_pool.key(),
+                amount,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // MIGRATIONS
+    // ========================================================================
+
+    /// Upgrade a protocol config written with an older layout (permissionless)
+    /// The account grows to the current size, the payer covers the extra rent
+    /// and fields added since are filled in. A config written by the original
+    /// single-instance program signed for its fee and staking vaults itself;
+    /// they are handed to the vault_authority PDA
+    pub fn migrate_protocol_config(ctx: Context<MigrateProtocolConfig>) -> Result<()> {
+        let accounts = &ctx.accounts;
+        let vault_authority_bump = ctx.bumps.vault_authority;
+        let now = Clock::get()?.unix_timestamp;
+        let from_version = migrate_account::<ProtocolConfig>(
+            &accounts.protocol_config,
+            &accounts.payer,
+            &accounts.system_program,
+            |config, from_version| {
+                if from_version == 0 {
+                    config.upgrade_from_legacy(vault_authority_bump, now);
+                    hand_over_legacy_vaults(accounts, config)?;
+                }
+                Ok(())
+            },
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            AccountMigrated {
+                account: ctx.accounts.protocol_config.key(),
+                from_version,
+                to_version: ProtocolConfig::VERSION,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Upgrade a fog pool written with an older layout (permissionless)
+    /// The config settings the pool caches are re-read from its instance, so
+    /// fields added since are never left zeroed. Pools written by the original
+    /// program use migrate_legacy_fog_pool
+    pub fn migrate_fog_pool(ctx: Context<MigrateFogPool>) -> Result<()> {
+        let config = &ctx.accounts.protocol_config;
+        let from_version = migrate_account::<FogPool>(
+            &ctx.accounts.fog_pool,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            |fog_pool, from_version| {
+                require!(from_version > 0, ShadeError::InvalidMigration);
+                require_keys_eq!(
+                    fog_pool.protocol_config,
+                    config.key(),
+                    ShadeError::WrongProtocolInstance
+                );
+                fog_pool.sync_config(config);
+                Ok(())
+            },
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            AccountMigrated {
+                account: ctx.accounts.fog_pool.key(),
+                from_version,
+                to_version: FogPool::VERSION,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Upgrade a fog pool written by the original single-instance program
+    /// (permissionless)
+    /// The original layout is decoded and the pool rebuilt in the default
+    /// instance with its counters kept. Its vault was owned by the pool PDA,
+    /// so the balance moves to a new vault owned by the pool vault authority
+    /// and the old vault is closed to the pool authority. Deposits weren't
+    /// tokenized then, so LP shares for the moved balance (less the locked
+    /// minimum) are minted to the pool authority
+    pub fn migrate_legacy_fog_pool(ctx: Context<MigrateLegacyFogPool>) -> Result<()> {
+        let legacy = LegacyFogPool::decode(&ctx.accounts.fog_pool)?;
+        let pool_key = ctx.accounts.fog_pool.key();
+        let pool_address = Pubkey::create_program_address(
+            &[b"fog_pool", legacy.pool_seed.as_ref(), &[legacy.bump]],
+            &crate::ID,
+        )
+        .map_err(|_| error!(ShadeError::InvalidMigration))?;
+        require_keys_eq!(pool_address, pool_key, ShadeError::InvalidMigration);
+        require_keys_eq!(
+            ctx.accounts.legacy_vault.key(),
+            legacy.vault,
+            ShadeError::InvalidVaultAuthority
+        );
+        require_keys_eq!(
+            ctx.accounts.authority.key(),
+            legacy.authority,
+            ShadeError::Unauthorized
+        );
+
+        // Move the balance out of the vault the pool PDA signed for
+        let pool_seeds = &[
+            b"fog_pool".as_ref(),
+            legacy.pool_seed.as_ref(),
+            &[legacy.bump],
+        ];
+        let pool_signer = &[&pool_seeds[..]];
+        let moved = ctx.accounts.legacy_vault.amount;
+        if moved > 0 {
+            let transfer_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.legacy_vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.fog_pool.to_account_info(),
+                },
+                pool_signer,
+            );
+            token_interface::transfer_checked(transfer_ctx, moved, ctx.accounts.mint.decimals)?;
+        }
+        let close_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.legacy_vault.to_account_info(),
+                destination: ctx.accounts.authority.to_account_info(),
+                authority: ctx.accounts.fog_pool.to_account_info(),
+            },
+            pool_signer,
+        );
+        token_interface::close_account(close_ctx)?;
+        let received = received_amount(&mut ctx.accounts.vault, 0)?;
+
+        // Shares for the moved balance, priced like a first deposit
+        let locked = received.min(MIN_LP_LIQUIDITY);
+        let shares = received - locked;
+        if shares > 0 {
+            let authority_seeds = &[
+                b"pool_vault_authority".as_ref(),
+                pool_key.as_ref(),
+                &[ctx.bumps.pool_vault_authority],
+            ];
+            let authority_signer = &[&authority_seeds[..]];
+            let mint_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: ctx.accounts.lp_mint.to_account_info(),
+                    to: ctx.accounts.authority_lp_account.to_account_info(),
+                    authority: ctx.accounts.pool_vault_authority.to_account_info(),
+                },
+                authority_signer,
+            );
+            token_interface::mint_to(mint_ctx, shares)?;
+        }
+
+        let registry_index = register_pool(
+            &mut ctx.accounts.protocol_config,
+            &mut ctx.accounts.pool_registry_page,
+            ctx.bumps.pool_registry_page,
+            pool_key,
+        )?;
+
+        let mut fog_pool = FogPool {
+            authority: legacy.authority,
+            vault: ctx.accounts.vault.key(),
+            total_deposited: legacy.total_deposited,
+            total_spent: legacy.total_spent,
+            total_fees_generated: legacy.total_fees_generated,
+            active_authorizations: legacy.active_authorizations,
+            pool_seed: legacy.pool_seed,
+            bump: legacy.bump,
+            lp_mint: ctx.accounts.lp_mint.key(),
+            mint: ctx.accounts.mint.key(),
+            vault_authority_bump: ctx.bumps.pool_vault_authority,
+            locked_shares: locked,
+            ..FogPool::new(&ctx.accounts.protocol_config)
+        };
+        let sequence = fog_pool.next_event_sequence()?;
+        grow_account(
+            &ctx.accounts.fog_pool,
+            FogPool::LEN,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+        )?;
+        fog_pool.try_serialize(&mut &mut ctx.accounts.fog_pool.try_borrow_mut_data()?[..])?;
+
+        emit_event!(
+            ctx.accounts,
+            AccountMigrated {
+                account: pool_key,
+                from_version: 0,
+                to_version: FogPool::VERSION,
+            }
+        );
+        emit_event!(
+            ctx.accounts,
+            FogPoolCreated {
+                pool: pool_key,
+                sequence,
+                authority: fog_pool.authority,
+                vault: fog_pool.vault,
+                registry_index,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Move a staker position written by the original single-instance program
+    /// (permissionless)
+    /// Positions are now keyed by instance (`["staker", config, user]`), so the
+    /// old `["staker", user]` account is decoded, its snapshot-based fee share
+    /// settled into pending rewards and a current position created in its
+    /// place. The old account is closed to the user. Its config must be
+    /// migrated first
+    pub fn migrate_staker(ctx: Context<MigrateStaker>) -> Result<()> {
+        let legacy = LegacyStaker::decode(&ctx.accounts.legacy_staker)?;
+        require_keys_eq!(
+            legacy.user,
+            ctx.accounts.user.key(),
+            ShadeError::InvalidMigration
+        );
+
+        let config = &ctx.accounts.protocol_config;
+        let staker = &mut ctx.accounts.staker;
+        init_staker(staker, legacy.user, ctx.bumps.staker)?;
+        staker.staked_amount = legacy.staked_amount;
+        staker.pending_rewards = legacy
+            .pending_rewards
+            .checked_add(legacy.fee_share(config)?)
+            .ok_or(ShadeError::Overflow)?;
+        staker.last_claim_timestamp = legacy.last_claim_timestamp;
+        staker.tier = calculate_tier(staker.tier_weight(), config);
+
+        close_legacy_account(&ctx.accounts.legacy_staker, &ctx.accounts.user)?;
+
+        emit_event!(
+            ctx.accounts,
+            AccountMigrated {
+                account: ctx.accounts.staker.key(),
+                from_version: 0,
+                to_version: Staker::VERSION,
+            }
+        );
+
+        Ok(())
+    }
+
+    /// Upgrade an authorization written with an older layout (permissionless)
+    pub fn migrate_authorization(ctx: Context<MigrateAccount>) -> Result<()> {
+        let from_version = migrate_account::<Authorization>(
+            &ctx.accounts.account,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            |_, _| Ok(()),
+        )?;
+
+        emit_event!(
+            ctx.accounts,
+            AccountMigrated {
+                account: ctx.accounts.account.key(),
+                from_version,
+                to_version: Authorization::VERSION,
+            }
+        );
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // DEVNET (compiled only with the `devnet` feature)
+    // ========================================================================
+
+    /// Mint test tokens from the instance faucet
+    #[cfg(feature = "devnet")]
+    pub fn faucet_mint(ctx: Context<FaucetMint>, amount: u64) -> Result<()> {
+        devnet::process_faucet_mint(ctx, amount)
+    }
+
+    /// Move an authorization's expiry (issuer only)
+    #[cfg(feature = "devnet")]
+    pub fn set_authorization_expiry(
+        ctx: Context<SetAuthorizationExpiry>,
+        expires_at: i64,
+    ) -> Result<()> {
+        devnet::process_set_authorization_expiry(ctx, expires_at)
+    }
+
+    /// Create a fog pool pre-funded from the faucet
+    #[cfg(feature = "devnet")]
+    pub fn seed_demo_pool(
+        ctx: Context<SeedDemoPool>,
+        pool_seed: [u8; 32],
+        amount: u64,
+    ) -> Result<()> {
+        devnet::process_seed_demo_pool(ctx, pool_seed, amount)
+    }
+}
+
+// ============================================================================
+// Helper Functions
+// ============================================================================
+
+/// Self-CPI an event's data through the event authority PDA, the same
+/// instruction Anchor's emit_cpi! builds
+#[cfg(feature = "event-cpi")]
+fn emit_cpi_event<E: anchor_lang::Event>(event_authority: &AccountInfo, event: &E) -> Result<()> {
+    let (_, bump) = Pubkey::find_program_address(&[b"__event_authority"], &crate::ID);
+    let mut data = anchor_lang::event::EVENT_IX_TAG_LE.to_vec();
+    data.extend_from_slice(&event.data());
+    let ix = Instruction::new_with_bytes(
+        crate::ID,
+        &data,
+        vec![AccountMeta::new_readonly(event_authority.key(), true)],
+    );
+    invoke_signed(
+        &ix,
+        std::slice::from_ref(event_authority),
+        &[&[b"__event_authority", &[bump]]],
+    )?;
+    Ok(())
+}
+
+/// Core spend logic shared by every spend entrypoint
+/// `approved` is set when the spend carried the approvals its policy requires
+/// `source` pays the spend from another pool's vault (with its vault authority)
+/// instead of the authorization's own pool (routed spends); the caller tracks the debt
+/// `reference` is an optional caller-supplied id (e.g. an invoice) for reconciliation
+/// The receipt's amount is what was drawn from the authorization, including an on-top fee
+fn process_spend<'info>(
+    accounts: &mut Spend<'info>,
+    amount: u64,
+    approved: bool,
+    recipient_proof: &[[u8; 32]],
+    source: Option<(
+        &Account<'info, FogPool>,
+        &InterfaceAccount<'info, TokenAccount>,
+        &UncheckedAccount<'info>,
+    )>,
+    reference: Option<[u8; 32]>,
+) -> Result<SpendReceipt> {
+    let now = Clock::get()?.unix_timestamp;
+    let (amount, fee, surcharge, charged) =
+        prepare_spend(accounts, amount, approved, recipient_proof, now)?;
+    let net_amount = amount.checked_sub(fee).ok_or(ShadeError::Overflow)?;
+
+    // Transfer net amount from vault to recipient
+    let routed = source.is_some();
+    let (fog_pool, vault, vault_authority) = source.unwrap_or((
+        &accounts.fog_pool,
+        &accounts.vault,
+        &accounts.pool_vault_authority,
+    ));
+    require!(
+        fog_pool.available_balance(vault.amount) >= amount,
+        ShadeError::InsufficientPoolBalance
+    );
+    // A routed draw can't use the paying pool's reserved liquidity or what it
+    // owes other pools
+    if routed {
+        require!(
+            fog_pool.available_balance(vault.amount)
+                >= amount
+                    .saturating_add(fog_pool.reserved_amount)
+                    .saturating_add(fog_pool.payables),
+            ShadeError::LiquidityReserved
+        );
+    }
+    let pool_key = fog_pool.key();
+    let seeds = &[
+        b"pool_vault_authority".as_ref(),
+        pool_key.as_ref(),
+        &[fog_pool.vault_authority_bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    // Transfer to recipient
+    let recipient_balance = accounts.recipient_token_account.amount;
+    let decimals = accounts.mint.decimals;
+    let transfer_ctx = CpiContext::new_with_signer(
+        accounts.token_program.to_account_info(),
+        TransferChecked {
+            from: vault.to_account_info(),
+            mint: accounts.mint.to_account_info(),
+            to: accounts.recipient_token_account.to_account_info(),
+            authority: vault_authority.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token_interface::transfer_checked(transfer_ctx, net_amount, decimals)?;
+
+    // The fee stays in the issuing pool's vault until sweep_fees collects it;
+    // a routed spend moves it there from the paying vault. The surcharge
+    // stays with the pool whose liquidity was used
+    let issuing_vault_balance = accounts.vault.amount;
+    let mut fee_shortfall = 0;
+    if routed && fee > surcharge {
+        let fee_transfer_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: vault.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: accounts.vault.to_account_info(),
+                authority: vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(fee_transfer_ctx, fee - surcharge, decimals)?;
+        let fee_received = received_amount(&mut accounts.vault, issuing_vault_balance)?;
+        fee_shortfall = (fee - surcharge).saturating_sub(fee_received);
+    }
+
+    // What reached the recipient, net of any Token-2022 transfer fee
+    let received = received_amount(&mut accounts.recipient_token_account, recipient_balance)?;
+
+    if let (Some(reference), Some(memo_program)) = (reference, &accounts.memo_program) {
+        let memo_ctx = CpiContext::new(memo_program.to_account_info(), BuildMemo {});
+        memo::build_memo(memo_ctx, reference_memo(&reference).as_bytes())?;
+    }
+
+    // Update authorization
+    let authorization = &mut accounts.authorization;
+    authorization.record_spend(charged, now)?;
+    record_category_spend(
+        &mut accounts.category_budget,
+        &accounts.fog_pool,
+        authorization.category,
+        amount,
+        now,
+    )?;
+
+    // Update fog pool stats and pending fees
+    let fog_pool = &mut accounts.fog_pool;
+    let spend_index = fog_pool.record_spend(amount, fee, surcharge)?;
+    // A routed fee is pending only for what reached the issuing vault
+    fog_pool.pending_fees -= fee_shortfall;
+    release_liquidity(authorization, fog_pool, amount);
+    record_ring_spend(
+        &accounts.spend_ring,
+        fog_pool,
+        SpendRecord {
+            amount,
+            timestamp: now,
+            spender: accounts.spender.key(),
+            authorization: authorization.key(),
+            recipient: accounts.recipient_token_account.key(),
+            paid_by: if routed { pool_key } else { Pubkey::default() },
+            spend_index,
+            refunded: 0,
+        },
+    )?;
+    let remaining_cap = authorization.spending_cap - authorization.amount_spent;
+
+    let sequence = fog_pool.next_event_sequence()?;
+    emit_event!(
+        accounts,
+        SpendExecuted {
+            authorization: authorization.key(),
+            fog_pool: fog_pool.key(),
+            sequence,
+            spender: accounts.spender.key(),
+            recipient: accounts.recipient_token_account.key(),
+            amount,
+            fee,
+            net_amount,
+            received,
+            remaining: remaining_cap,
+            spend_index,
+            reference,
+        }
+    );
+
+    Ok(SpendReceipt {
+        amount,
+        fee,
+        net_amount,
+        remaining_cap,
+    })
+}
+
+/// Batch spend logic shared by spend_batch and spend_split
+/// `recipients` holds the recipient token accounts in the same order as `payments`
+fn process_batch<'info>(
+    accounts: &mut SpendBatch<'info>,
+    recipients: &'info [AccountInfo<'info>],
+    payments: &[BatchPayment],
+) -> Result<()> {
+    require!(
+        !payments.is_empty() && payments.len() <= MAX_BATCH_PAYMENTS,
+        ShadeError::InvalidBatch
+    );
+    // Remaining accounts are the recipients, then each recipient owner's
+    // denylist entry in the same order
+    require!(
+        recipients.len() == payments.len() * 2,
+        ShadeError::InvalidBatch
+    );
+    let (recipients, recipient_denylist_entries) = recipients.split_at(payments.len());
+
+    let now = Clock::get()?.unix_timestamp;
+    require!(!accounts.protocol_config.paused, ShadeError::ProtocolPaused);
+    require!(
+        accounts.compromised_spender.data_is_empty(),
+        ShadeError::SpenderCompromised
+    );
+    require_not_denylisted!(
+        accounts,
+        accounts.spender_denylist_entry,
+        accounts.spender.key(),
+        DenylistRole::Spender
+    );
+    require!(
+        accounts.fog_pool.status == PoolStatus::Active,
+        ShadeError::PoolNotActive
+    );
+    require!(
+        accounts.authorization.allowlist_root == [0u8; 32],
+        ShadeError::RecipientNotAllowlisted
+    );
+    require!(
+        !accounts.authorization.cap_in_usd,
+        ShadeError::PriceUpdateRequired
+    );
+    // Recipient attestations aren't taken per payment, so recipient-gated
+    // pools must use `spend`
+    require!(
+        !accounts
+            .fog_pool
+            .requires_attestation(AttestationSubject::Recipient),
+        ShadeError::AttestationRequired
+    );
+    if accounts
+        .fog_pool
+        .requires_attestation(AttestationSubject::Spender)
+    {
+        check_attestation(
+            &accounts.spender_attestation,
+            &accounts.fog_pool,
+            accounts.spender.key(),
+            now,
+        )?;
+    }
+
+    // With the fee charged on top, each payment draws its amount plus fee
+    let delegator = tier_delegator(
+        &accounts.tier_delegation,
+        &accounts.delegator_staker,
+        accounts.spender.key(),
+    )?;
+    let spend_tier = spender_tier(&accounts.spender_staker, delegator);
+    let tier = spend_tier.unwrap_or(0);
+    let mut fees = Vec::with_capacity(payments.len());
+    let mut total: u64 = 0;
+    for payment in payments.iter() {
+        require!(payment.amount > 0, ShadeError::InvalidAmount);
+        accounts.fog_pool.check_spend_size(payment.amount)?;
+        let (fee, surcharge) =
+            accounts
+                .fog_pool
+                .spend_fees(payment.amount, tier, accounts.vault.amount)?;
+        let gross = if accounts.authorization.fee_on_top {
+            payment
+                .amount
+                .checked_add(fee)
+                .ok_or(ShadeError::Overflow)?
+        } else {
+            payment.amount
+        };
+        total = total.checked_add(gross).ok_or(ShadeError::Overflow)?;
+        fees.push((gross, fee, surcharge));
+    }
+    accounts.authorization.check_spend(total, now, false)?;
+    accounts.fog_pool.check_utilization(
+        total,
+        accounts.authorization.reserved_amount,
+        accounts.vault.amount,
+    )?;
+    accounts.fog_pool.check_spend_tier(
+        spend_tier,
+        accounts
+            .authorization
+            .spent_in_budget(now)
+            .checked_add(total)
+            .ok_or(ShadeError::Overflow)?,
+    )?;
+
+    let fog_pool = &accounts.fog_pool;
+    require!(
+        fog_pool.available_balance(accounts.vault.amount) >= total,
+        ShadeError::InsufficientPoolBalance
+    );
+    let pool_key = fog_pool.key();
+    let seeds = &[
+        b"pool_vault_authority".as_ref(),
+        pool_key.as_ref(),
+        &[fog_pool.vault_authority_bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+    let decimals = accounts.mint.decimals;
+
+    // Each recipient receives its amount net of its share of the fee
+    let mut total_net: u64 = 0;
+    let mut received = Vec::with_capacity(payments.len());
+    for (((payment, recipient_info), denylist_entry), (gross, fee, _)) in payments
+        .iter()
+        .zip(recipients.iter())
+        .zip(recipient_denylist_entries.iter())
+        .zip(fees.iter())
+    {
+        require_keys_eq!(
+            recipient_info.key(),
+            payment.recipient,
+            ShadeError::InvalidBatch
+        );
+        let recipient = InterfaceAccount::<TokenAccount>::try_from(recipient_info)?;
+        require_keys_eq!(recipient.mint, fog_pool.mint, ShadeError::InvalidMint);
+        let (expected_entry, _) = Pubkey::find_program_address(
+            &[
+                b"denylist",
+                fog_pool.protocol_config.as_ref(),
+                recipient.owner.as_ref(),
+            ],
+            &crate::ID,
+        );
+        require_keys_eq!(
+            denylist_entry.key(),
+            expected_entry,
+            ShadeError::InvalidBatch
+        );
+        require_not_denylisted!(
+            accounts,
+            denylist_entry,
+            recipient.owner,
+            DenylistRole::Recipient
+        );
+
+        let net_amount = gross.checked_sub(*fee).ok_or(ShadeError::Overflow)?;
+        let transfer_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: accounts.vault.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: recipient_info.clone(),
+                authority: accounts.pool_vault_authority.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::transfer_checked(transfer_ctx, net_amount, decimals)?;
+        let recipient_balance = InterfaceAccount::<TokenAccount>::try_from(recipient_info)?.amount;
+        received.push(
+            recipient_balance
+                .checked_sub(recipient.amount)
+                .ok_or(ShadeError::Overflow)?,
+        );
+        total_net = total_net
+            .checked_add(net_amount)
+            .ok_or(ShadeError::Overflow)?;
+    }
+
+    // The fees stay in the vault until sweep_fees collects them
+    let total_fee = total.checked_sub(total_net).ok_or(ShadeError::Overflow)?;
+
+    let authorization_key = accounts.authorization.key();
+    let spender = accounts.spender.key();
+    accounts.authorization.record_spend(total, now)?;
+    release_liquidity(&mut accounts.authorization, &mut accounts.fog_pool, total);
+    record_category_spend(
+        &mut accounts.category_budget,
+        &accounts.fog_pool,
+        accounts.authorization.category,
+        total,
+        now,
+    )?;
+    let remaining = accounts.authorization.spending_cap - accounts.authorization.amount_spent;
+    let fog_pool = &mut accounts.fog_pool;
+    for ((payment, (gross, fee, surcharge)), received) in payments.iter().zip(fees).zip(received) {
+        let net_amount = gross - fee;
+        let spend_index = fog_pool.record_spend(gross, fee, surcharge)?;
+        record_ring_spend(
+            &accounts.spend_ring,
+            fog_pool,
+            SpendRecord {
+                amount: gross,
+                timestamp: now,
+                spender,
+                authorization: authorization_key,
+                recipient: payment.recipient,
+                spend_index,
+                ..Default::default()
+            },
+        )?;
+        let sequence = fog_pool.next_event_sequence()?;
+        emit_event!(
+            accounts,
+            SpendExecuted {
+                authorization: authorization_key,
+                fog_pool: fog_pool.key(),
+                sequence,
+                spender,
+                recipient: payment.recipient,
+                amount: gross,
+                fee,
+                net_amount,
+                received,
+                remaining,
+                spend_index,
+                reference: None,
+            }
+        );
+    }
+    let sequence = fog_pool.next_event_sequence()?;
+    emit_event!(
+        accounts,
+        BatchSpendExecuted {
+            authorization: authorization_key,
+            fog_pool: accounts.fog_pool.key(),
+            sequence,
+            payments: payments.len() as u8,
+            total_amount: total,
+            total_fee,
+        }
+    );
+
+    Ok(())
+}
+
+/// Validate a split template's shares: 1 to MAX_SPLIT_RECIPIENTS non-zero
+/// shares totalling 10000 bps
+fn validate_split_shares(shares: &[SplitShare]) -> Result<()> {
+    require!(
+        !shares.is_empty() && shares.len() <= MAX_SPLIT_RECIPIENTS,
+        ShadeError::InvalidSplitShares
+    );
+    require!(
+        shares.iter().all(|share| share.share_bps > 0),
+        ShadeError::InvalidSplitShares
+    );
+    let total: u32 = shares.iter().map(|share| share.share_bps as u32).sum();
+    require!(total == 10_000, ShadeError::InvalidSplitShares);
+    Ok(())
+}
+
+/// Tokens that reached `vault` since it held `balance_before`
+/// Token-2022 mints with a transfer fee deliver less than the amount sent
+fn received_amount(vault: &mut InterfaceAccount<TokenAccount>, balance_before: u64) -> Result<u64> {
+    vault.reload()?;
+    Ok(vault
+        .amount
+        .checked_sub(balance_before)
+        .ok_or(ShadeError::Overflow)?)
+}
+
+/// Memo text for a spend reference: its lowercase hex encoding
+fn reference_memo(reference: &[u8; 32]) -> String {
+    reference.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Validate a spend on `accounts` and price it, returning the amount drawn
+/// from the authorization (including an on-top fee), the fee and its surcharge
+fn prepare_spend(
+    accounts: &Spend,
+    amount: u64,
+    approved: bool,
+    recipient_proof: &[[u8; 32]],
+    now: i64,
+) -> Result<(u64, u64, u64, u64)> {
+    let authorization = &accounts.authorization;
+
+    // Validate authorization
+    require!(!accounts.protocol_config.paused, ShadeError::ProtocolPaused);
+    require!(
+        accounts.compromised_spender.data_is_empty(),
+        ShadeError::SpenderCompromised
+    );
+    require_not_denylisted!(
+        accounts,
+        accounts.spender_denylist_entry,
+        accounts.spender.key(),
+        DenylistRole::Spender
+    );
+    require_not_denylisted!(
+        accounts,
+        accounts.recipient_denylist_entry,
+        accounts.recipient_token_account.owner,
+        DenylistRole::Recipient
+    );
+    require!(
+        accounts.fog_pool.status == PoolStatus::Active,
+        ShadeError::PoolNotActive
+    );
+    check_spend_attestations(
+        &accounts.fog_pool,
+        &accounts.spender_attestation,
+        accounts.spender.key(),
+        &accounts.recipient_attestation,
+        accounts.recipient_token_account.owner,
+        now,
+    )?;
+
+    accounts.fog_pool.check_spend_size(amount)?;
+
+    // Calculate fee, including any utilization surcharge; charged on top, it
+    // is added to the amount drawn against the cap so the recipient gets `amount`
+    let delegator = tier_delegator(
+        &accounts.tier_delegation,
+        &accounts.delegator_staker,
+        accounts.spender.key(),
+    )?;
+    let spend_tier = spender_tier(&accounts.spender_staker, delegator);
+    let tier = spend_tier.unwrap_or(0);
+    let (fee, surcharge) = accounts
+        .fog_pool
+        .spend_fees(amount, tier, accounts.vault.amount)?;
+    let amount = if authorization.fee_on_top {
+        amount.checked_add(fee).ok_or(ShadeError::Overflow)?
+    } else {
+        amount
+    };
+    accounts.fog_pool.check_utilization(
+        amount,
+        authorization.reserved_amount,
+        accounts.vault.amount,
+    )?;
+
+    // USD-cap authorizations count the spend's USD value against their limits
+    let mut usd_price = None;
+    let charged = if authorization.cap_in_usd {
+        let price_update = accounts
+            .price_update
+            .as_ref()
+            .ok_or(ShadeError::PriceUpdateRequired)?;
+        let fog_pool = &accounts.fog_pool;
+        let (price, exponent) = read_pyth_price(price_update, fog_pool, now)?;
+        if fog_pool.max_price_deviation_bps > 0 {
+            require!(
+                fog_pool.price_reference_live(now),
+                ShadeError::PriceReferenceStale
+            );
+            require!(
+                !fog_pool.price_deviates(price),
+                ShadeError::PriceCircuitBreakerTripped
+            );
+        }
+        usd_price = Some((price, exponent));
+        usd_value(amount, accounts.mint.decimals, price, exponent)?
+    } else {
+        amount
+    };
+    authorization.check_spend(charged, now, approved)?;
+    // Tier caps are in pool tokens, so a USD budget is priced back into tokens
+    let spent = authorization
+        .spent_in_budget(now)
+        .checked_add(charged)
+        .ok_or(ShadeError::Overflow)?;
+    let spent = match usd_price {
+        Some((price, exponent)) => token_value(spent, accounts.mint.decimals, price, exponent)?,
+        None => spent,
+    };
+    accounts.fog_pool.check_spend_tier(spend_tier, spent)?;
+    if authorization.allowlist_root != [0u8; 32] {
+        let leaf = recipient_allowlist_leaf(&accounts.recipient_token_account.owner);
+        require!(
+            verify_merkle_proof(recipient_proof, &authorization.allowlist_root, leaf),
+            ShadeError::RecipientNotAllowlisted
+        );
+    }
+
+    Ok((amount, fee, surcharge, charged))
+}
+
+/// Read the pool mint's USD price from a Pyth price update, checking the
+/// feed, verification, staleness and confidence against the pool's settings
+/// Returns the price and its exponent
+fn read_pyth_price(price_update: &AccountInfo, fog_pool: &FogPool, now: i64) -> Result<(u64, i32)> {
+    require!(
+        fog_pool.price_feed_id != [0u8; 32],
+        ShadeError::InvalidPriceFeed
+    );
+    require_keys_eq!(
+        *price_update.owner,
+        PYTH_RECEIVER_PROGRAM_ID,
+        ShadeError::InvalidPriceUpdate
+    );
+    let data = price_update.try_borrow_data()?;
+    require!(
+        data.len() >= 8 && data[..8] == PYTH_PRICE_UPDATE_DISCRIMINATOR,
+        ShadeError::InvalidPriceUpdate
+    );
+    let update = PythPriceUpdate::deserialize(&mut &data[8..])?;
+    let message = &update.price_message;
+    require!(
+        update.verification_level == PythVerificationLevel::Full
+            && message.feed_id == fog_pool.price_feed_id
+            && message.price > 0,
+        ShadeError::InvalidPriceUpdate
+    );
+    require!(
+        now.saturating_sub(message.publish_time) <= fog_pool.max_price_age as i64,
+        ShadeError::StalePrice
+    );
+    let price = message.price as u64;
+    require!(
+        (message.conf as u128) * 10_000 <= (price as u128) * fog_pool.max_price_conf_bps as u128,
+        ShadeError::PriceConfidenceTooWide
+    );
+    Ok((price, message.exponent))
+}
+
+/// Check the spender's and recipient owner's attestations a spend from an
+/// attestation-gated pool requires
+fn check_spend_attestations(
+    fog_pool: &FogPool,
+    spender_attestation: &Option<UncheckedAccount>,
+    spender: Pubkey,
+    recipient_attestation: &Option<UncheckedAccount>,
+    recipient_owner: Pubkey,
+    now: i64,
+) -> Result<()> {
+    if fog_pool.requires_attestation(AttestationSubject::Spender) {
+        check_attestation(spender_attestation, fog_pool, spender, now)?;
+    }
+    if fog_pool.requires_attestation(AttestationSubject::Recipient) {
+        check_attestation(recipient_attestation, fog_pool, recipient_owner, now)?;
+    }
+    Ok(())
+}
+
+/// Check that `attestation` is a live Solana Attestation Service attestation
+/// of `subject` under the pool's credential (and schema, when one is set)
+fn check_attestation(
+    attestation: &Option<UncheckedAccount>,
+    fog_pool: &FogPool,
+    subject: Pubkey,
+    now: i64,
+) -> Result<()> {
+    let attestation = attestation
+        .as_ref()
+        .ok_or(ShadeError::AttestationRequired)?;
+    require_keys_eq!(
+        *attestation.owner,
+        SAS_PROGRAM_ID,
+        ShadeError::InvalidAttestation
+    );
+    let data = attestation.try_borrow_data()?;
+    require!(
+        data.first() == Some(&SAS_ATTESTATION_DISCRIMINATOR),
+        ShadeError::InvalidAttestation
+    );
+    let attestation = SasAttestation::deserialize(&mut &data[1..])?;
+    require!(
+        attestation.nonce == subject
+            && attestation.credential == fog_pool.attestation_credential
+            && (fog_pool.attestation_schema == Pubkey::default()
+                || attestation.schema == fog_pool.attestation_schema),
+        ShadeError::InvalidAttestation
+    );
+    require!(
+        attestation.expiry == 0 || now < attestation.expiry,
+        ShadeError::AttestationExpired
+    );
+    Ok(())
+}
+
+/// USD value of `amount` pool tokens in micro-USD, rounded up so spends are
+/// never undercounted
+fn usd_value(amount: u64, decimals: u8, price: u64, exponent: i32) -> Result<u64> {
+    let pow10 = |exp: u32| 10u128.checked_pow(exp).ok_or(ShadeError::Overflow);
+    let mut numerator = (amount as u128)
+        .checked_mul(price as u128)
+        .and_then(|value| value.checked_mul(10u128.pow(USD_DECIMALS)))
+        .ok_or(ShadeError::Overflow)?;
+    let mut denominator = pow10(decimals as u32)?;
+    if exponent >= 0 {
+        numerator = numerator
+            .checked_mul(pow10(exponent as u32)?)
+            .ok_or(ShadeError::Overflow)?;
+    } else {
+        denominator = denominator
+            .checked_mul(pow10(exponent.unsigned_abs())?)
+            .ok_or(ShadeError::Overflow)?;
+    }
+    let value = numerator.div_ceil(denominator);
+    u64::try_from(value).map_err(|_| error!(ShadeError::Overflow))
+}
+
+/// Pool token amount (raw units, rounded down) worth `usd` micro-USD at a Pyth
+/// price; the inverse of usd_value
+fn token_value(usd: u64, decimals: u8, price: u64, exponent: i32) -> Result<u64> {
+    require!(price > 0, ShadeError::InvalidPriceUpdate);
+    let pow10 = |exp: u32| 10u128.checked_pow(exp).ok_or(ShadeError::Overflow);
+    let mut numerator = (usd as u128)
+        .checked_mul(pow10(decimals as u32)?)
+        .ok_or(ShadeError::Overflow)?;
+    let mut denominator = (price as u128)
+        .checked_mul(10u128.pow(USD_DECIMALS))
+        .ok_or(ShadeError::Overflow)?;
+    if exponent >= 0 {
+        denominator = denominator
+            .checked_mul(pow10(exponent as u32)?)
+            .ok_or(ShadeError::Overflow)?;
+    } else {
+        numerator = numerator
+            .checked_mul(pow10(exponent.unsigned_abs())?)
+            .ok_or(ShadeError::Overflow)?;
+    }
+    u64::try_from(numerator / denominator).map_err(|_| error!(ShadeError::Overflow))
+}
+
+/// Validate a spending cap against the spender's staker tier; a tier held in
+/// the recognized source instance counts too
+/// The staker accounts are only accepted at the spender's canonical staker
+/// PDAs, and a missing one counts as tier 0, so omitting them can't skip the check
+fn check_tier_cap(
+    config: &ProtocolConfig,
+    staker: &Option<Account<Staker>>,
+    source_staker: &Option<Account<Staker>>,
+    delegator: Option<&Staker>,
+    spending_cap: u64,
+) -> Result<()> {
+    let local_tier = staker
+        .as_ref()
+        .filter(|staker| !staker.tier_delegated())
+        .map_or(0, |staker| calculate_tier(staker.tier_weight(), config));
+    let source_tier = source_staker
+        .as_ref()
+        .filter(|staker| !staker.tier_delegated())
+        .map_or(0, |staker| staker.tier);
+    let delegated_tier = delegator.map_or(0, |staker| calculate_tier(staker.tier_weight(), config));
+    let max_cap = get_max_cap_for_tier(local_tier.max(source_tier).max(delegated_tier), config);
+    require!(spending_cap <= max_cap, ShadeError::ExceedsTierLimit);
+    Ok(())
+}
+
+/// Staker whose tier `delegate` may use through a tier delegation, if passed
+/// The delegation and the delegator's staker account must be passed together
+/// and still point at each other
+fn tier_delegator<'a>(
+    delegation: &Option<Account<TierDelegation>>,
+    delegator_staker: &'a Option<Account<Staker>>,
+    delegate: Pubkey,
+) -> Result<Option<&'a Staker>> {
+    match (delegation, delegator_staker) {
+        (None, None) => Ok(None),
+        (Some(delegation), Some(staker)) => {
+            require!(
+                delegation.delegate == delegate
+                    && staker.user == delegation.delegator
+                    && staker.tier_delegate == delegate,
+                ShadeError::InvalidTierDelegation
+            );
+            Ok(Some(&**staker))
+        }
+        _ => err!(ShadeError::InvalidTierDelegation),
+    }
+}
+
+/// Tier a spender spends at: the higher of its own staker's and a delegator's
+/// None when neither staker account is passed
+/// A staker that delegated its tier spends at tier 0 itself
+fn spender_tier(staker: &Option<Account<Staker>>, delegator: Option<&Staker>) -> Option<u8> {
+    if staker.is_none() && delegator.is_none() {
+        return None;
+    }
+    let own = staker
+        .as_ref()
+        .filter(|staker| !staker.tier_delegated())
+        .map_or(0, |staker| staker.tier);
+    Some(own.max(delegator.map_or(0, |staker| staker.tier)))
+}
+
+/// Protocol fee on a spend of `amount`
+fn spend_fee(fee_basis_points: u16, amount: u64) -> Result<u64> {
+    Ok((amount as u128)
+        .checked_mul(fee_basis_points as u128)
+        .ok_or(ShadeError::Overflow)?
+        .checked_div(10000)
+        .ok_or(ShadeError::Overflow)? as u64)
+}
+
+/// Create an authorization and list it in the spender and pool indexes
+/// The spending cap is validated against the spender's staking tier
+fn process_create_authorization<'info>(
+    accounts: &mut CreateAuthorization<'info>,
+    bumps: &CreateAuthorizationBumps,
+    spending_cap: u64,
+    expires_at: i64,
+    purpose: String,
+) -> Result<()> {
+    if accounts.issuer.key() != accounts.fog_pool.authority {
+        let delegate = accounts
+            .issuer_delegate
+            .as_ref()
+            .ok_or(ShadeError::Unauthorized)?;
+        require!(!delegate.templates_only, ShadeError::TemplateRequired);
+    }
+    let issuer = accounts.issuer.key();
+    issue_authorization(accounts, bumps, issuer, spending_cap, expires_at, purpose)
+}
+
+/// Initialize a new authorization and list it in the spender and pool indexes
+/// `issuer` is recorded on the authorization; `accounts.issuer` only signs
+fn issue_authorization<'info>(
+    accounts: &mut CreateAuthorization<'info>,
+    bumps: &CreateAuthorizationBumps,
+    issuer: Pubkey,
+    spending_cap: u64,
+    expires_at: i64,
+    purpose: String,
+) -> Result<()> {
+    require!(spending_cap > 0, ShadeError::InvalidAmount);
+    require!(purpose.len() <= 64, ShadeError::PurposeTooLong);
+
+    // PDA spenders sign via invoke_signed, so only their program can spend;
+    // that program must be registered with the pool
+    if !accounts.spender.key().is_on_curve() {
+        match (&accounts.program_spender, &accounts.registered_program) {
+            (Some(program_spender), Some(registered))
+                if program_spender.program_id == registered.program_id => {}
+            _ => return err!(ShadeError::ProgramSpenderNotRegistered),
+        }
+    }
+
+    let clock = Clock::get()?;
+    require!(expires_at > clock.unix_timestamp, ShadeError::InvalidExpiry);
+    require!(!accounts.protocol_config.paused, ShadeError::ProtocolPaused);
+    require_not_denylisted!(
+        accounts,
+        accounts.spender_denylist_entry,
+        accounts.spender.key(),
+        DenylistRole::Spender
+    );
+    if accounts
+        .fog_pool
+        .requires_attestation(AttestationSubject::Spender)
+    {
+        check_attestation(
+            &accounts.spender_attestation,
+            &accounts.fog_pool,
+            accounts.spender.key(),
+            clock.unix_timestamp,
+        )?;
+    }
+    require!(
+        accounts.fog_pool.status == PoolStatus::Active,
+        ShadeError::PoolNotActive
+    );
+
+    let delegator = tier_delegator(
+        &accounts.tier_delegation,
+        &accounts.delegator_staker,
+        accounts.spender.key(),
+    )?;
+    check_tier_cap(
+        &accounts.protocol_config,
+        &accounts.staker,
+        &accounts.source_staker,
+        delegator,
+        spending_cap,
+    )?;
+
+    let authorization = &mut accounts.authorization;
+    authorization.fog_pool = accounts.fog_pool.key();
+    authorization.authorized_spender = accounts.spender.key();
+    authorization.issuer = issuer;
+    authorization.spending_cap = spending_cap;
+    authorization.amount_spent = 0;
+    authorization.created_at = clock.unix_timestamp;
+    authorization.expires_at = expires_at;
+    authorization.purpose = purpose.clone();
+    authorization.is_active = true;
+    authorization.bump = bumps.authorization;
+    authorization.approval_min_amount = 0;
+    authorization.velocity_windows = [VelocityWindow::default(); MAX_VELOCITY_WINDOWS];
+    authorization.streaming = false;
+    authorization.allowlist_root = [0u8; 32];
+    authorization.frozen = false;
+    authorization.max_uses = 0;
+    authorization.uses = 0;
+    authorization.min_spend_interval = 0;
+    authorization.last_spend_at = 0;
+    authorization.budget_window = 0;
+    authorization.budget_window_start = 0;
+    authorization.starts_at = clock.unix_timestamp;
+    authorization.budget_reclaimed = false;
+    authorization.fee_on_top = false;
+    authorization.dispute_window = 0;
+    authorization.arbiter = Pubkey::default();
+    authorization.version = Authorization::VERSION;
+    authorization.category = PurposeCategory::Uncategorized;
+    authorization.cap_in_usd = false;
+    authorization.reserved_amount = 0;
+    authorization.spend_days = 0;
+    authorization.spend_window_start = 0;
+    authorization.spend_window_end = 0;
+    authorization.approval_nonce = 0;
+    authorization.reserved = [0; 11];
+
+    let head = &mut accounts.spender_index_head;
+    if head.spender == Pubkey::default() {
+        head.spender = accounts.spender.key();
+        head.bump = bumps.spender_index_head;
+    }
+    let spender_page = &mut accounts.spender_index;
+    let spender_page_number = head.next_page();
+    if spender_page.spender == Pubkey::default() {
+        spender_page.spender = accounts.spender.key();
+        spender_page.page = spender_page_number;
+        spender_page.bump = bumps.spender_index;
+    }
+    spender_page.authorizations.push(authorization.key());
+    authorization.spender_index_page = spender_page_number;
+    if spender_page_number != head.tail_page {
+        head.tail_page = spender_page_number;
+        head.tail_len = 0;
+    }
+    head.tail_len += 1;
+
+    let fog_pool = &mut accounts.fog_pool;
+    let page = &mut accounts.pool_index_page;
+    let page_number = fog_pool.next_index_page();
+    if page.fog_pool == Pubkey::default() {
+        page.fog_pool = fog_pool.key();
+        page.page = page_number;
+        page.bump = bumps.pool_index_page;
+    }
+    page.authorizations.push(authorization.key());
+    authorization.index_page = page_number;
+    if page_number != fog_pool.index_tail_page {
+        fog_pool.index_tail_page = page_number;
+        fog_pool.index_tail_len = 0;
+    }
+    fog_pool.index_tail_len += 1;
+
+    // Update fog pool stats
+    fog_pool.active_authorizations = fog_pool
+        .active_authorizations
+        .checked_add(1)
+        .ok_or(ShadeError::Overflow)?;
+    reserve_liquidity(authorization, fog_pool, spending_cap)?;
+
+    let sequence = fog_pool.next_event_sequence()?;
+    emit_event!(
+        accounts,
+        AuthorizationCreated {
+            authorization: authorization.key(),
+            fog_pool: fog_pool.key(),
+            sequence,
+            spender: authorization.authorized_spender,
+            issuer: authorization.issuer,
+            spending_cap,
+            expires_at,
+            purpose,
+        }
+    );
+
+    Ok(())
+}
+
+/// Drop a no-longer-active authorization from the spender and pool indexes
+/// and from the pool's active count, releasing its reserved liquidity
+fn retire_authorization(
+    authorization: &Account<Authorization>,
+    fog_pool: &mut FogPool,
+    spender_index_head: &mut SpenderIndexHead,
+    spender_index: &mut SpenderIndex,
+    pool_index_page: &mut PoolAuthorizationPage,
+) {
+    if spender_index.remove(&authorization.key())
+        && authorization.spender_index_page == spender_index_head.tail_page
+    {
+        spender_index_head.tail_len = spender_index_head.tail_len.saturating_sub(1);
+    }
+    if pool_index_page.remove(&authorization.key())
+        && authorization.index_page == fog_pool.index_tail_page
+    {
+        fog_pool.index_tail_len = fog_pool.index_tail_len.saturating_sub(1);
+    }
+    fog_pool.active_authorizations = fog_pool.active_authorizations.saturating_sub(1);
+    fog_pool.reserved_amount = fog_pool
+        .reserved_amount
+        .saturating_sub(authorization.reserved_amount);
+}
+
+/// Hold back `amount` more of the pool's liquidity for an authorization
+fn reserve_liquidity(
+    authorization: &mut Authorization,
+    fog_pool: &mut FogPool,
+    amount: u64,
+) -> Result<()> {
+    authorization.reserved_amount = authorization
+        .reserved_amount
+        .checked_add(amount)
+        .ok_or(ShadeError::Overflow)?;
+    fog_pool.reserved_amount = fog_pool
+        .reserved_amount
+        .checked_add(amount)
+        .ok_or(ShadeError::Overflow)?;
+    Ok(())
+}
+
+/// Release up to `amount` of an authorization's reserved liquidity
+fn release_liquidity(authorization: &mut Authorization, fog_pool: &mut FogPool, amount: u64) {
+    let amount = amount.min(authorization.reserved_amount);
+    authorization.reserved_amount -= amount;
+    fog_pool.reserved_amount = fog_pool.reserved_amount.saturating_sub(amount);
+}
+
+/// Give back limit consumption for a reversed spend of `amount` made at `spent_at`
+/// Every reversal path (refunds, voided holds, repayments) goes through here so
+/// velocity windows are restored along with the lifetime cap
+/// A token cap's restored allowance is reserved again, failing if the pool's
+/// free liquidity at `vault_balance` can't cover it
+fn restore_spend_limits(
+    authorization: &mut Authorization,
+    fog_pool: &mut FogPool,
+    vault_balance: u64,
+    amount: u64,
+    spent_at: i64,
+    now: i64,
+) -> Result<()> {
+    // A budget window that has since reset no longer counts the spend
+    if authorization.budget_in_window(spent_at, now) {
+        require!(
+            amount <= authorization.amount_spent,
+            ShadeError::ReinstateExceedsSpent
+        );
+        authorization.amount_spent -= amount;
+        if !authorization.cap_in_usd {
+            reserve_liquidity(authorization, fog_pool, amount)?;
+            require!(
+                fog_pool.available_balance(vault_balance)
+                    >= fog_pool.reserved_amount.saturating_add(fog_pool.payables),
+                ShadeError::LiquidityReserved
+            );
+        }
+    }
+    for window in authorization.velocity_windows.iter_mut() {
+        window.release(spent_at, now, amount);
+    }
+    Ok(())
+}
+
+/// Message a pool authority signs off-chain to permit a spend:
+/// domain || program id || fog pool || spender || cap (LE) || nonce (LE) ||
+/// expires_at (LE)
+pub fn spend_permit_message(
+    fog_pool: &Pubkey,
+    spender: &Pubkey,
+    cap: u64,
+    nonce: u64,
+    expires_at: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(SPEND_PERMIT_DOMAIN.len() + 32 + 32 + 32 + 8 + 8 + 8);
+    message.extend_from_slice(SPEND_PERMIT_DOMAIN);
+    message.extend_from_slice(crate::ID.as_ref());
+    message.extend_from_slice(fog_pool.as_ref());
+    message.extend_from_slice(spender.as_ref());
+    message.extend_from_slice(&cap.to_le_bytes());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&expires_at.to_le_bytes());
+    message
+}
+
+/// Message approvers sign off-chain to approve a spend:
+/// domain || program id || authorization || recipient token account ||
+/// amount (LE) || approval nonce (LE)
+pub fn spend_approval_message(
+    authorization: &Pubkey,
+    recipient: &Pubkey,
+    amount: u64,
+    approval_nonce: u64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(SPEND_APPROVAL_DOMAIN.len() + 32 + 32 + 32 + 8 + 8);
+    message.extend_from_slice(SPEND_APPROVAL_DOMAIN);
+    message.extend_from_slice(crate::ID.as_ref());
+    message.extend_from_slice(authorization.as_ref());
+    message.extend_from_slice(recipient.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&approval_nonce.to_le_bytes());
+    message
+}
+
+/// Collect the distinct public keys that signed `message` in ed25519 program
+/// instructions of the current transaction
+/// The ed25519 program has already verified the signatures by the time we run;
+/// we only accept entries whose key, signature and message live inside the
+/// ed25519 instruction itself so offsets can't point at unrelated data
+fn ed25519_signers(instructions: &AccountInfo, message: &[u8]) -> Result<Vec<Pubkey>> {
+    const SELF_INSTRUCTION: u16 = u16::MAX;
+    const OFFSETS_START: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+
+    let mut signers: Vec<Pubkey> = Vec::new();
+    let mut index = 0;
+    while let Ok(ix) = instructions_sysvar::load_instruction_at_checked(index, instructions) {
+        index += 1;
+        if ix.program_id != ed25519_program::ID || ix.data.len() < OFFSETS_START {
+            continue;
+        }
+
+        let data = &ix.data;
+        let read_u16 = |at: usize| -> Result<u16> {
+            let bytes = data
+                .get(at..at + 2)
+                .ok_or(ShadeError::InvalidSignatureInstruction)?;
+            Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+        };
+
+        let count = data[0] as usize;
+        for i in 0..count {
+            let base = OFFSETS_START + i * OFFSETS_LEN;
+            let signature_ix = read_u16(base + 2)?;
+            let pubkey_offset = read_u16(base + 4)? as usize;
+            let pubkey_ix = read_u16(base + 6)?;
+            let message_offset = read_u16(base + 8)? as usize;
+            let message_size = read_u16(base + 10)? as usize;
+            let message_ix = read_u16(base + 12)?;
+
+            if signature_ix != SELF_INSTRUCTION
+                || pubkey_ix != SELF_INSTRUCTION
+                || message_ix != SELF_INSTRUCTION
+            {
+                continue;
+            }
+
+            let signed = data
+                .get(message_offset..message_offset + message_size)
+                .ok_or(ShadeError::InvalidSignatureInstruction)?;
+            if signed != message {
+                continue;
+            }
+
+            let key_bytes = data
+                .get(pubkey_offset..pubkey_offset + 32)
+                .ok_or(ShadeError::InvalidSignatureInstruction)?;
+            let key =
+                Pubkey::try_from(key_bytes).map_err(|_| ShadeError::InvalidSignatureInstruction)?;
+            if !signers.contains(&key) {
+                signers.push(key);
+            }
+        }
+    }
+
+    Ok(signers)
+}
+
+/// CPI into a whitelisted AMM program with the route's accounts taken from
+/// remaining_accounts; `signer` is the program PDA that signs with `signer_seeds`
+fn invoke_amm<'info>(
+    amm_program: &UncheckedAccount<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    signer: Pubkey,
+    data: Vec<u8>,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let metas = remaining_accounts
+        .iter()
+        .map(|account| AccountMeta {
+            pubkey: account.key(),
+            is_signer: account.is_signer || account.key() == signer,
+            is_writable: account.is_writable,
+        })
+        .collect();
+    let ix = Instruction {
+        program_id: amm_program.key(),
+        accounts: metas,
+        data,
+    };
+    let mut account_infos = remaining_accounts.to_vec();
+    account_infos.push(amm_program.to_account_info());
+    invoke_signed(&ix, &account_infos, &[signer_seeds])?;
+    Ok(())
+}
+
+/// Book a protocol fee, setting aside the configured rebase share for the next
+/// buyback; only the remainder is shared with stakers through the fee vault
+/// The staker share accrues to acc_fee_per_share, so no per-staker crank is needed
+fn book_protocol_fee(config: &mut ProtocolConfig, fee: u64) -> Result<()> {
+    let rebase_cut = (fee as u128)
+        .checked_mul(config.rebase_bps as u128)
+        .ok_or(ShadeError::Overflow)?
+        .checked_div(10_000)
+        .ok_or(ShadeError::Overflow)? as u64;
+    config.rebase_fees_pending = config
+        .rebase_fees_pending
+        .checked_add(rebase_cut)
+        .ok_or(ShadeError::Overflow)?;
+    let staker_share = fee - rebase_cut;
+    config.total_fees_collected = config
+        .total_fees_collected
+        .checked_add(staker_share)
+        .ok_or(ShadeError::Overflow)?;
+    if config.total_fee_shares() > 0 {
+        let increment = (staker_share as u128)
+            .checked_mul(FEE_PRECISION)
+            .ok_or(ShadeError::Overflow)?
+            .checked_div(config.total_fee_shares() as u128)
+            .ok_or(ShadeError::Overflow)?;
+        config.acc_fee_per_share = config
+            .acc_fee_per_share
+            .checked_add(increment)
+            .ok_or(ShadeError::Overflow)?;
+    }
+    Ok(())
+}
+
+/// Emit staker SHADE for the time since the last accrual over all fee shares
+/// Emissions pause while nothing is staked and stop when the vault funding runs out
+fn accrue_staking_emissions(config: &mut ProtocolConfig, now: i64) -> Result<()> {
+    let elapsed = now.saturating_sub(config.emissions_updated_at).max(0) as u64;
+    config.emissions_updated_at = now;
+    if elapsed == 0 {
+        return Ok(());
+    }
+    let shares = config.total_emission_shares();
+    config.staking_emissions.accrue(elapsed, shares)
+}
+
+/// Accrue staking emissions and move the staker's share since the last
+/// settlement into pending_emissions
+/// Unlike fees, emissions are time-based, so warming stake earns them too
+fn settle_emissions(staker: &mut Staker, config: &mut ProtocolConfig) -> Result<()> {
+    accrue_staking_emissions(config, Clock::get()?.unix_timestamp)?;
+    let entitlement = staker.emission_entitlement(config.staking_emissions.reward_per_share);
+    staker.pending_emissions = staker
+        .pending_emissions
+        .checked_add(entitlement.saturating_sub(staker.emission_debt) as u64)
+        .ok_or(ShadeError::Overflow)?;
+    staker.emission_debt = entitlement;
+    Ok(())
+}
+
+/// Move fees accrued since the last settlement into pending_rewards, settling
+/// staking emissions along the way
+/// Warming stake that has waited out min_stake_age activates here, with its
+/// debt checkpointed at the current index, so it only earns fees booked from
+/// now on; fees booked while it warmed went to the active stake
+/// Returns the fee amount credited
+fn settle_fees(staker: &mut Staker, config: &mut ProtocolConfig) -> Result<u64> {
+    settle_emissions(staker, config)?;
+    let earned = staker
+        .fee_entitlement(config.acc_fee_per_share)
+        .saturating_sub(staker.reward_debt) as u64;
+    staker.pending_rewards = staker
+        .pending_rewards
+        .checked_add(earned)
+        .ok_or(ShadeError::Overflow)?;
+    if staker.warming_amount > 0
+        && staker.warming_matured(config.min_stake_age, Clock::get()?.unix_timestamp)
+    {
+        drop_warming(staker, config, staker.warming_amount);
+    }
+    staker.reward_debt = staker.fee_entitlement(config.acc_fee_per_share);
+    Ok(earned)
+}
+
+/// Take up to `amount` out of a staker's warming stake, activating it or
+/// following it out of the position, and out of the instance's warming total
+fn drop_warming(staker: &mut Staker, config: &mut ProtocolConfig, amount: u64) {
+    let dropped = amount.min(staker.warming_amount);
+    staker.warming_amount -= dropped;
+    config.total_warming = config.total_warming.saturating_sub(dropped);
+}
+
+/// Compound rebased SHADE accrued since the last settlement into the stake
+/// Fees are settled first so the compounded stake does not earn past fees
+/// Returns the amount added
+fn settle_rebase(staker: &mut Staker, config: &mut ProtocolConfig) -> Result<u64> {
+    settle_fees(staker, config)?;
+    let accrued = staker
+        .rebase_entitlement(config.rebase_per_share)
+        .saturating_sub(staker.rebase_debt) as u64;
+    if accrued > 0 {
+        staker.staked_amount = staker
+            .staked_amount
+            .checked_add(accrued)
+            .ok_or(ShadeError::Overflow)?;
+        config.total_staked = config
+            .total_staked
+            .checked_add(accrued)
+            .ok_or(ShadeError::Overflow)?;
+    }
+    staker.rebase_debt = staker.rebase_entitlement(config.rebase_per_share);
+    staker.reward_debt = staker.fee_entitlement(config.acc_fee_per_share);
+    staker.emission_debt = staker.emission_entitlement(config.staking_emissions.reward_per_share);
+    Ok(accrued)
+}
+
+/// Early-exit penalty on `amount` of stake
+fn early_exit_penalty(config: &ProtocolConfig, amount: u64) -> u64 {
+    (amount as u128 * config.early_exit_penalty_bps as u128 / 10_000) as u64
+}
+
+/// Move an early-exit penalty from the staking vault to the fee vault and
+/// book it as a protocol fee for stakers
+/// set_early_exit_penalty only allows a penalty when the fee vault holds $SHADE
+fn book_exit_penalty<'info>(
+    config: &mut Account<'info, ProtocolConfig>,
+    staking_vault: &InterfaceAccount<'info, TokenAccount>,
+    shade_mint: &InterfaceAccount<'info, Mint>,
+    fee_vault: &InterfaceAccount<'info, TokenAccount>,
+    vault_authority: &UncheckedAccount<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    penalty: u64,
+) -> Result<()> {
+    if penalty == 0 {
+        return Ok(());
+    }
+    let config_key = config.key();
+    let seeds = &[
+        b"vault_authority".as_ref(),
+        config_key.as_ref(),
+        &[config.vault_authority_bump][..],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: staking_vault.to_account_info(),
+            mint: shade_mint.to_account_info(),
+            to: fee_vault.to_account_info(),
+            authority: vault_authority.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token_interface::transfer_checked(transfer_ctx, penalty, shade_mint.decimals)?;
+
+    book_protocol_fee(config, penalty)
+}
+
+/// Forfeit a leaving staker's pending and unsettled fees, rebases and
+/// emissions: fees and rebases to the remaining stakers, emissions back to
+/// the stream. `staker` is its state before leaving; `config` must already
+/// have it out of the totals
+/// Returns the (fees, rebase, emissions) forfeited
+fn forfeit_staker_rewards(staker: &Staker, config: &mut ProtocolConfig) -> Result<(u64, u64, u64)> {
+    let fees = staker
+        .pending_rewards
+        .checked_add(
+            staker
+                .fee_entitlement(config.acc_fee_per_share)
+                .saturating_sub(staker.reward_debt) as u64,
+        )
+        .ok_or(ShadeError::Overflow)?;
+    let rebase = staker
+        .rebase_entitlement(config.rebase_per_share)
+        .saturating_sub(staker.rebase_debt) as u64;
+    let emissions = staker
+        .pending_emissions
+        .checked_add(
+            staker
+                .emission_entitlement(config.staking_emissions.reward_per_share)
+                .saturating_sub(staker.emission_debt) as u64,
+        )
+        .ok_or(ShadeError::Overflow)?;
+
+    forfeit_fees_to_stakers(config, fees)?;
+    if rebase > 0 && config.total_staked > 0 {
+        config.rebase_per_share = config
+            .rebase_per_share
+            .checked_add(
+                (rebase as u128)
+                    .checked_mul(REBASE_PRECISION)
+                    .ok_or(ShadeError::Overflow)?
+                    / config.total_staked as u128,
+            )
+            .ok_or(ShadeError::Overflow)?;
+    }
+    // Forfeited emissions go back to the stream to be emitted again
+    config.staking_emissions.allocated =
+        config.staking_emissions.allocated.saturating_sub(emissions);
+    Ok((fees, rebase, emissions))
+}
+
+/// Share forfeited fees, already held in the fee vault, among stakers through
+/// acc_fee_per_share
+/// With no stakers left the fees stay in the fee vault unallocated
+fn forfeit_fees_to_stakers(config: &mut ProtocolConfig, fees: u64) -> Result<()> {
+    let shares = config.total_fee_shares();
+    if fees > 0 && shares > 0 {
+        config.acc_fee_per_share = config
+            .acc_fee_per_share
+            .checked_add(
+                (fees as u128)
+                    .checked_mul(FEE_PRECISION)
+                    .ok_or(ShadeError::Overflow)?
+                    / shares as u128,
+            )
+            .ok_or(ShadeError::Overflow)?;
+    }
+    Ok(())
+}
+
+/// Pay `amount` of a staker's pending unstake from the staking vault and
+/// clear the request
+fn pay_unstake(accounts: &mut CompleteUnstake, amount: u64) -> Result<()> {
+    let config = &accounts.protocol_config;
+    let config_key = config.key();
+    let seeds = &[
+        b"vault_authority".as_ref(),
+        config_key.as_ref(),
+        &[config.vault_authority_bump][..],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    let transfer_ctx = CpiContext::new_with_signer(
+        accounts.token_program.to_account_info(),
+        TransferChecked {
+            from: accounts.staking_vault.to_account_info(),
+            mint: accounts.shade_mint.to_account_info(),
+            to: accounts.user_shade_account.to_account_info(),
+            authority: accounts.vault_authority.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token_interface::transfer_checked(transfer_ctx, amount, accounts.shade_mint.decimals)?;
+
+    let staker = &mut accounts.staker;
+    staker.pending_unstake_amount = 0;
+    staker.unstake_requested_at = 0;
+    Ok(())
+}
+
+/// Re-credit a staker's lock bonus for the stake still locked at `now`,
+/// settling fees earned at the old bonus first
+fn refresh_lock_bonus(staker: &mut Staker, config: &mut ProtocolConfig, now: i64) -> Result<()> {
+    settle_fees(staker, config)?;
+    let bonus = staker.lock_bonus_at(now);
+    config.total_lock_bonus = config
+        .total_lock_bonus
+        .saturating_sub(staker.lock_bonus)
+        .checked_add(bonus)
+        .ok_or(ShadeError::Overflow)?;
+    staker.lock_bonus = bonus;
+    if now >= staker.lock_end {
+        staker.locked_amount = 0;
+        staker.lock_boost_bps = 0;
+    }
+    staker.reward_debt = staker.fee_entitlement(config.acc_fee_per_share);
+    staker.emission_debt = staker.emission_entitlement(config.staking_emissions.reward_per_share);
+    staker.tier = calculate_tier(staker.tier_weight(), config);
+    Ok(())
+}
+
+/// Pay a staker's pending fee rewards from the fee vault to `destination`
+/// Returns the amount paid
+fn pay_rewards<'info>(
+    config: &mut Account<'info, ProtocolConfig>,
+    staker: &mut Account<'info, Staker>,
+    fee_vault: &InterfaceAccount<'info, TokenAccount>,
+    fee_mint: &InterfaceAccount<'info, Mint>,
+    vault_authority: &UncheckedAccount<'info>,
+    destination: &InterfaceAccount<'info, TokenAccount>,
+    token_program: &Interface<'info, TokenInterface>,
+) -> Result<u64> {
+    settle_fees(staker, config)?;
+    let pending = staker.pending_rewards;
+    require!(pending > 0, ShadeError::NoRewardsToClaim);
+
+    // Transfer rewards from fee vault to the destination
+    let config_key = config.key();
+    let seeds = &[
+        b"vault_authority".as_ref(),
+        config_key.as_ref(),
+        &[config.vault_authority_bump][..],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    let transfer_ctx = CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        TransferChecked {
+            from: fee_vault.to_account_info(),
+            mint: fee_mint.to_account_info(),
+            to: destination.to_account_info(),
+            authority: vault_authority.to_account_info(),
+        },
+        signer_seeds,
+    );
+    token_interface::transfer_checked(transfer_ctx, pending, fee_mint.decimals)?;
+
+    // Update staker
+    staker.pending_rewards = 0;
+    staker.last_claim_timestamp = Clock::get()?.unix_timestamp;
+
+    // Update protocol stats
+    config.total_fees_distributed = config
+        .total_fees_distributed
+        .checked_add(pending)
+        .ok_or(ShadeError::Overflow)?;
+
+    Ok(pending)
+}
+
+/// Append a new fog pool to its instance's pool registry
+/// Returns the pool's registry index
+pub fn register_pool(
+    config: &mut Account<ProtocolConfig>,
+    page: &mut PoolRegistryPage,
+    page_bump: u8,
+    fog_pool: Pubkey,
+) -> Result<u64> {
+    let index = config.pool_count;
+    if page.protocol_config == Pubkey::default() {
+        page.protocol_config = config.key();
+        page.page = config.next_pool_page();
+        page.bump = page_bump;
+    }
+    page.pools.push(fog_pool);
+    config.pool_count = index.checked_add(1).ok_or(ShadeError::Overflow)?;
+    Ok(index)
+}
+
+/// Count a spend against the pool's budget for the authorization's category,
+/// if the pool budgets that category
+fn record_category_spend(
+    category_budget: &mut Option<Account<CategoryBudget>>,
+    fog_pool: &Account<FogPool>,
+    category: PurposeCategory,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    if !fog_pool.has_category_budget(category) {
+        return Ok(());
+    }
+    let budget = category_budget
+        .as_mut()
+        .ok_or(ShadeError::CategoryBudgetRequired)?;
+    require!(
+        budget.fog_pool == fog_pool.key() && budget.category == category,
+        ShadeError::CategoryBudgetRequired
+    );
+    budget.record(amount, now)
+}
+
+/// Record a spend in the pool's spend ring, overwriting the oldest slot once full
+/// A pool with a ring must be passed it on every spend
+fn record_ring_spend(
+    spend_ring: &Option<AccountLoader<SpendRing>>,
+    fog_pool: &FogPool,
+    record: SpendRecord,
+) -> Result<()> {
+    if fog_pool.spend_ring == Pubkey::default() {
+        return Ok(());
+    }
+    let spend_ring = spend_ring.as_ref().ok_or(ShadeError::SpendRingRequired)?;
+    require_keys_eq!(
+        spend_ring.key(),
+        fog_pool.spend_ring,
+        ShadeError::SpendRingRequired
+    );
+
+    let slot = {
+        let mut ring = spend_ring.load_mut()?;
+        let slot = ring.total_recorded % ring.capacity as u64;
+        ring.total_recorded = ring
+            .total_recorded
+            .checked_add(1)
+            .ok_or(ShadeError::Overflow)?;
+        slot as usize
+    };
+    write_ring_spend(spend_ring, slot, &record)
+}
+
+/// Find the ring record of the pool's spend `spend_index`, returning its slot
+/// Fails once the spend has been overwritten or cleared by a resize
+fn find_ring_spend(
+    spend_ring: &AccountLoader<SpendRing>,
+    spend_index: u64,
+) -> Result<(usize, SpendRecord)> {
+    let recorded = {
+        let ring = spend_ring.load()?;
+        ring.total_recorded.min(ring.capacity as u64) as usize
+    };
+    let info = spend_ring.to_account_info();
+    let data = info.try_borrow_data()?;
+    for slot in 0..recorded {
+        let offset = SpendRing::slot_offset(slot);
+        let record = SpendRecord::deserialize(&mut &data[offset..offset + SpendRecord::LEN])?;
+        if record.spend_index == spend_index {
+            return Ok((slot, record));
+        }
+    }
+    err!(ShadeError::InvalidSpendIndex)
+}
+
+/// Write `record` to `slot` of a spend ring
+fn write_ring_spend(
+    spend_ring: &AccountLoader<SpendRing>,
+    slot: usize,
+    record: &SpendRecord,
+) -> Result<()> {
+    let info = spend_ring.to_account_info();
+    let mut data = info.try_borrow_mut_data()?;
+    let offset = SpendRing::slot_offset(slot);
+    record.serialize(&mut &mut data[offset..offset + SpendRecord::LEN])?;
+    Ok(())
+}
+
+/// Grow an account written with an older layout of `T` to the current size
+/// and stamp the current version; fields added since start zeroed until
+/// `upgrade` fills them in for the version the account is upgraded from
+/// Returns the version the account was upgraded from
+fn migrate_account<'info, T: VersionedAccount>(
+    account: &UncheckedAccount<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    upgrade: impl FnOnce(&mut T, u8) -> Result<()>,
+) -> Result<u8> {
+    require_keys_eq!(*account.owner, crate::ID, ShadeError::InvalidMigration);
+    require!(
+        account.try_borrow_data()?.get(..8) == Some(T::DISCRIMINATOR),
+        ShadeError::InvalidMigration
+    );
+    grow_account(account, T::SPACE, payer, system_program)?;
+
+    let mut state = T::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+    let from_version = state.version();
+    require!(from_version < T::VERSION, ShadeError::AlreadyMigrated);
+    upgrade(&mut state, from_version)?;
+    state.set_version(T::VERSION);
+    state.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+    Ok(from_version)
+}
+
+/// Grow an account to `space` bytes, if smaller, with `payer` topping up rent
+/// The new bytes are zeroed
+fn grow_account<'info>(
+    account: &UncheckedAccount<'info>,
+    space: usize,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    if account.data_len() >= space {
+        return Ok(());
+    }
+    let shortfall = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if shortfall > 0 {
+        let transfer_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: payer.to_account_info(),
+                to: account.to_account_info(),
+            },
+        );
+        anchor_lang::system_program::transfer(transfer_ctx, shortfall)?;
+    }
+    account.resize(space)?;
+    Ok(())
+}
+
+/// Hand the fee and staking vaults of a config written by the original
+/// program from the config PDA, which owned them, to the vault_authority PDA
+fn hand_over_legacy_vaults(
+    accounts: &MigrateProtocolConfig,
+    config: &ProtocolConfig,
+) -> Result<()> {
+    require_keys_eq!(
+        accounts.fee_vault.key(),
+        config.fee_vault,
+        ShadeError::InvalidVaultAuthority
+    );
+    require_keys_eq!(
+        accounts.staking_vault.key(),
+        config.staking_vault,
+        ShadeError::InvalidVaultAuthority
+    );
+
+    let config_key = accounts.protocol_config.key();
+    let seeds = &[
+        b"protocol_config".as_ref(),
+        config.config_seed(),
+        &[config.bump],
+    ];
+    let signer_seeds = &[&seeds[..]];
+
+    for vault in [&accounts.fee_vault, &accounts.staking_vault] {
+        if vault.owner != config_key {
+            continue;
+        }
+        let set_authority_ctx = CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            SetAuthority {
+                current_authority: accounts.protocol_config.to_account_info(),
+                account_or_mint: vault.to_account_info(),
+            },
+            signer_seeds,
+        );
+        token_interface::set_authority(
+            set_authority_ctx,
+            AuthorityType::AccountOwner,
+            Some(accounts.vault_authority.key()),
+        )?;
+    }
+    Ok(())
+}
+
+/// Close an account written by the original program once its state has been
+/// moved, sending its rent to `destination`
+fn close_legacy_account(account: &AccountInfo, destination: &AccountInfo) -> Result<()> {
+    let lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ShadeError::Overflow)?;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.assign(&anchor_lang::system_program::ID);
+    account.resize(0)?;
+    Ok(())
+}
+
+/// Initialize a fresh staker position
+fn init_staker(staker: &mut Staker, user: Pubkey, bump: u8) -> Result<()> {
+    staker.user = user;
+    staker.staked_amount = 0;
+    staker.pending_rewards = 0;
+    staker.last_claim_timestamp = Clock::get()?.unix_timestamp;
+    staker.reward_debt = 0;
+    staker.vesting_tier_credit = 0;
+    staker.rebase_debt = 0;
+    staker.claim_delegate = Pubkey::default();
+    staker.reward_destination = Pubkey::default();
+    staker.warming_amount = 0;
+    staker.warming_since = 0;
+    staker.pending_unstake_amount = 0;
+    staker.unstake_requested_at = 0;
+    staker.bump = bump;
+    staker.version = Staker::VERSION;
+    staker.locked_amount = 0;
+    staker.lock_start = 0;
+    staker.lock_end = 0;
+    staker.lock_boost_bps = 0;
+    staker.lock_bonus = 0;
+    staker.emission_debt = 0;
+    staker.pending_emissions = 0;
+    staker.tier_delegate = Pubkey::default();
+    staker.reserved = [0; 4];
+    Ok(())
+}
+
+/// Re-sync the unvested amount a schedule contributes to its beneficiary's tier
+/// The credit shrinks as tokens vest, so vested tokens are never counted twice
+/// once they are claimed and staked
+fn sync_vesting_tier_credit(
+    schedule: &mut VestingSchedule,
+    staker: &mut Staker,
+    config: &ProtocolConfig,
+    now: i64,
+) -> Result<()> {
+    let credit = if schedule.counts_toward_tier {
+        schedule
+            .total_amount
+            .saturating_sub(schedule.vested_amount(now))
+    } else {
+        0
+    };
+    staker.vesting_tier_credit = staker
+        .vesting_tier_credit
+        .saturating_sub(schedule.tier_credit)
+        .checked_add(credit)
+        .ok_or(ShadeError::Overflow)?;
+    schedule.tier_credit = credit;
+    staker.tier = calculate_tier(staker.tier_weight(), config);
+    Ok(())
+}
+
+/// Credit `amount` of SHADE that has already landed in the staking vault to a
+/// staker position, initializing the position on first use
+fn credit_stake(
+    staker: &mut Staker,
+    config: &mut ProtocolConfig,
+    user: Pubkey,
+    bump: u8,
+    amount: u64,
+) -> Result<()> {
+    if staker.user == Pubkey::default() {
+        init_staker(staker, user, bump)?;
+    }
+    // Settles fees earned at the current stake before adding to it
+    settle_rebase(staker, config)?;
+
+    // New stake warms up for min_stake_age before it shares in fees; stake
+    // that already matured was activated by the settlement above
+    if config.min_stake_age > 0 {
+        staker.warming_amount = staker
+            .warming_amount
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        staker.warming_since = Clock::get()?.unix_timestamp;
+        config.total_warming = config
+            .total_warming
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+    }
+
+    staker.staked_amount = staker
+        .staked_amount
+        .checked_add(amount)
+        .ok_or(ShadeError::Overflow)?;
+    staker.rebase_debt = staker.rebase_entitlement(config.rebase_per_share);
+    staker.reward_debt = staker.fee_entitlement(config.acc_fee_per_share);
+    staker.emission_debt = staker.emission_entitlement(config.staking_emissions.reward_per_share);
+
+    // Update tier
+    staker.tier = calculate_tier(staker.tier_weight(), config);
+
+    // Update protocol total
+    config.total_staked = config
+        .total_staked
+        .checked_add(amount)
+        .ok_or(ShadeError::Overflow)?;
+
+    Ok(())
+}
+
+fn calculate_tier(staked_amount: u64, config: &ProtocolConfig) -> u8 {
+    if staked_amount >= config.gold_threshold {
+        3 // Gold
+    } else if staked_amount >= config.silver_threshold {
+        2 // Silver
+    } else if staked_amount >= config.bronze_threshold {
+        1 // Bronze
+    } else {
+        0 // No tier
+    }
+}
+
+fn get_max_cap_for_tier(tier: u8, config: &ProtocolConfig) -> u64 {
+    let multiplier = match tier {
+        3 => config.gold_cap_multiplier,
+        2 => config.silver_cap_multiplier,
+        1 => config.bronze_cap_multiplier,
+        _ => 50, // Non-stakers get 0.5x base (50 basis points)
+    };
+
+    (config.base_spending_cap as u128)
+        .checked_mul(multiplier as u128)
+        .unwrap_or(0)
+        .checked_div(100)
+        .unwrap_or(0) as u64
+}
+
+/// LP shares minted for depositing `amount` into a vault holding `vault_balance`,
+/// and the shares locked in the pool by the deposit
+/// Into a pool with no shares, the deposit sets the price at one share per
+/// token: whatever the vault already holds plus MIN_LP_LIQUIDITY is locked,
+/// so donated or orphaned tokens never go to the next depositor
+fn lp_shares_for_deposit(amount: u64, vault_balance: u64, share_supply: u64) -> Result<(u64, u64)> {
+    if share_supply == 0 {
+        require!(amount > MIN_LP_LIQUIDITY, ShadeError::FirstDepositTooSmall);
+        let locked = vault_balance
+            .checked_add(MIN_LP_LIQUIDITY)
+            .ok_or(ShadeError::Overflow)?;
+        return Ok((amount - MIN_LP_LIQUIDITY, locked));
+    }
+    require!(vault_balance > 0, ShadeError::PoolDepleted);
+    let shares = (amount as u128)
+        .checked_mul(share_supply as u128)
+        .ok_or(ShadeError::Overflow)?
+        / vault_balance as u128;
+    let shares = u64::try_from(shares).map_err(|_| error!(ShadeError::Overflow))?;
+    Ok((shares, 0))
+}
+
+/// Tokens paid out for burning `shares` against a vault holding `vault_balance`
+/// `share_supply` includes the locked shares, which are never redeemed
+fn lp_amount_for_shares(shares: u64, vault_balance: u64, share_supply: u64) -> Result<u64> {
+    require!(
+        shares > 0 && shares <= share_supply,
+        ShadeError::InvalidAmount
+    );
+    let amount = (shares as u128)
+        .checked_mul(vault_balance as u128)
+        .ok_or(ShadeError::Overflow)?
+        / share_supply as u128;
+    Ok(amount as u64)
+}
+
+/// Check a merkle proof against `root`, hashing sibling pairs in sorted order
+/// Leaves and internal nodes use different prefixes (0x00 / 0x01)
+pub fn verify_merkle_proof(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    let mut computed = leaf;
+    for node in proof.iter() {
+        computed = if computed <= *node {
+            hashv(&[&[1u8], &computed, node]).to_bytes()
+        } else {
+            hashv(&[&[1u8], node, &computed]).to_bytes()
+        };
+    }
+    computed == *root
+}
+
+/// Merkle leaf for a recipient wallet in an authorization's allowlist
+pub fn recipient_allowlist_leaf(recipient: &Pubkey) -> [u8; 32] {
+    hashv(&[&[0u8], recipient.as_ref()]).to_bytes()
+}
+
+/// Merkle leaf for an authorization campaign entry
+pub fn authorization_campaign_leaf(
+    index: u64,
+    spender: &Pubkey,
+    spending_cap: u64,
+    expires_at: i64,
+) -> [u8; 32] {
+    hashv(&[
+        &[0u8],
+        &index.to_le_bytes(),
+        spender.as_ref(),
+        &spending_cap.to_le_bytes(),
+        &expires_at.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Validate an airdrop claim against the merkle root and record it as claimed
+fn verify_airdrop_claim(
+    airdrop: &mut Airdrop,
+    index: u64,
+    claimant: &Pubkey,
+    amount: u64,
+    proof: &[[u8; 32]],
+    now: i64,
+) -> Result<()> {
+    require!(amount > 0, ShadeError::InvalidAmount);
+    require!(
+        now < airdrop.claim_deadline,
+        ShadeError::AirdropClaimWindowClosed
+    );
+    let leaf = hashv(&[
+        &[0u8],
+        &index.to_le_bytes(),
+        claimant.as_ref(),
+        &amount.to_le_bytes(),
+    ])
+    .to_bytes();
+    require!(
+        verify_merkle_proof(proof, &airdrop.merkle_root, leaf),
+        ShadeError::InvalidMerkleProof
+    );
+
+    airdrop.claimed_amount = airdrop
+        .claimed_amount
+        .checked_add(amount)
+        .ok_or(ShadeError::Overflow)?;
+    require!(
+        airdrop.claimed_amount <= airdrop.total_amount,
+        ShadeError::AirdropExhausted
+    );
+    Ok(())
+}
+
+// ============================================================================
+// Account Structures
+// ============================================================================
+
+/// Accounts with a versioned layout that the migrate_* instructions upgrade
+/// New fields are carved out of `reserved`, or appended and picked up by a
+/// migration that grows the account
+pub trait VersionedAccount: AccountSerialize + AccountDeserialize + Discriminator {
+    /// Size of the current layout
+    const SPACE: usize;
+    /// Current layout version
+    const VERSION: u8;
+
+    fn version(&self) -> u8;
+    fn set_version(&mut self, version: u8);
+}
+
+impl VersionedAccount for ProtocolConfig {
+    const SPACE: usize = ProtocolConfig::LEN;
+    const VERSION: u8 = 2;
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+}
+
+impl VersionedAccount for FogPool {
+    const SPACE: usize = FogPool::LEN;
+    const VERSION: u8 = 4;
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+}
+
+impl VersionedAccount for Staker {
+    const SPACE: usize = Staker::LEN;
+    const VERSION: u8 = 1;
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+}
+
+impl VersionedAccount for Authorization {
+    const SPACE: usize = Authorization::LEN;
+    const VERSION: u8 = 1;
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+}
+
+/// Staker position as written by the original single-instance program, at
+/// `["staker", user]`; fees were shared by snapshotting total_fees_collected
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct LegacyStaker {
+    pub user: Pubkey,
+    pub staked_amount: u64,
+    pub pending_rewards: u64,
+    pub last_claim_timestamp: i64,
+    pub last_fees_snapshot: u64,
+    pub tier: u8,
+    pub bump: u8,
+}
+
+impl LegacyStaker {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // user
+        8 +  // staked_amount
+        8 +  // pending_rewards
+        8 +  // last_claim_timestamp
+        8 +  // last_fees_snapshot
+        1 +  // tier
+        1; // bump
+
+    /// Read a legacy position, checking its owner, discriminator and size
+    pub fn decode(account: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*account.owner, crate::ID, ShadeError::InvalidMigration);
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() == Self::LEN && data[..8] == *Staker::DISCRIMINATOR,
+            ShadeError::InvalidMigration
+        );
+        Ok(Self::deserialize(&mut &data[8..])?)
+    }
+
+    /// Fees the position earned under the snapshot model up to the config's
+    /// migration: its share of the fees collected since its last snapshot
+    pub fn fee_share(&self, config: &ProtocolConfig) -> Result<u64> {
+        if config.legacy_total_staked == 0 {
+            return Ok(0);
+        }
+        let new_fees = config
+            .legacy_fees_collected
+            .saturating_sub(self.last_fees_snapshot);
+        let share = (new_fees as u128)
+            .checked_mul(self.staked_amount as u128)
+            .ok_or(ShadeError::Overflow)?
+            / config.legacy_total_staked as u128;
+        u64::try_from(share).map_err(|_| error!(ShadeError::Overflow))
+    }
+}
+
+/// Fog pool as written by the original single-instance program; its vault
+/// was owned by the pool PDA itself
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct LegacyFogPool {
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub total_deposited: u64,
+    pub total_spent: u64,
+    pub total_fees_generated: u64,
+    pub active_authorizations: u64,
+    pub pool_seed: [u8; 32],
+    pub bump: u8,
+}
+
+impl LegacyFogPool {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // authority
+        32 + // vault
+        8 +  // total_deposited
+        8 +  // total_spent
+        8 +  // total_fees_generated
+        8 +  // active_authorizations
+        32 + // pool_seed
+        1; // bump
+
+    /// Read a legacy pool, checking its owner, discriminator and size
+    pub fn decode(account: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(*account.owner, crate::ID, ShadeError::InvalidMigration);
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() == Self::LEN && data[..8] == *FogPool::DISCRIMINATOR,
+            ShadeError::InvalidMigration
+        );
+        Ok(Self::deserialize(&mut &data[8..])?)
+    }
+}
+
+/// Protocol configuration - global settings
+#[account]
+#[derive(Default)]
+pub struct ProtocolConfig {
+    /// Protocol admin
+    pub authority: Pubkey,
+    /// $SHADE token mint
+    pub shade_mint: Pubkey,
+    /// Fee vault for collected fees
     pub fee_vault: Pubkey,
     /// Staking vault for staked $SHADE
     pub staking_vault: Pubkey,
     /// Fee in basis points (e.g., 10 = 0.1%)
     pub fee_basis_points: u16,
-    /// Total $SHADE staked
-    pub total_staked: u64,
-    /// Total fees collected
-    pub total_fees_collected: u64,
-    /// Total fees distributed to stakers
-    pub total_fees_distributed: u64,
-    /// Bronze tier threshold
-    pub bronze_threshold: u64,
-    /// Silver tier threshold
-    pub silver_threshold: u64,
-    /// Gold tier threshold
-    pub gold_threshold: u64,
-    /// Bronze tier cap multiplier (basis points)
+    /// Total $SHADE staked
+    pub total_staked: u64,
+    /// Total fees collected
+    pub total_fees_collected: u64,
+    /// Total fees distributed to stakers
+    pub total_fees_distributed: u64,
+    /// Bronze tier threshold
+    pub bronze_threshold: u64,
+    /// Silver tier threshold
+    pub silver_threshold: u64,
+    /// Gold tier threshold
+    pub gold_threshold: u64,
+    /// Bronze tier cap multiplier (basis points)
+    pub bronze_cap_multiplier: u16,
+    /// Silver tier cap multiplier (basis points)
+    pub silver_cap_multiplier: u16,
+    /// Gold tier cap multiplier (basis points)
+    pub gold_cap_multiplier: u16,
+    /// PDA bump
+    pub bump: u8,
+    /// Share of each protocol fee set aside for SHADE rebases (basis points)
+    pub rebase_bps: u16,
+    /// Minimum seconds between rebases
+    pub rebase_epoch_length: i64,
+    /// Fees set aside and not yet spent on a rebase
+    pub rebase_fees_pending: u64,
+    /// When the last rebase ran
+    pub last_rebase_at: i64,
+    /// Number of rebases executed
+    pub rebase_epoch: u64,
+    /// Rebased SHADE per staked SHADE, scaled by REBASE_PRECISION
+    pub rebase_per_share: u128,
+    /// Total SHADE bought back and distributed through rebases
+    pub total_rebased: u64,
+    /// Seconds new stake must be held before it shares in fees
+    pub min_stake_age: i64,
+    /// Seed distinguishing this protocol instance (PDA seed)
+    pub instance_seed: [u8; 32],
+    /// Instance whose staker tiers are also recognized (default = none)
+    pub tier_source: Pubkey,
+    /// Emergency pause: spends, new authorizations, deposits and stakes are blocked
+    pub paused: bool,
+    /// Dual-control rules for unpausing
+    pub resume_guard: ResumeGuard,
+    /// Proposed new authority awaiting acceptance (default = none)
+    pub pending_authority: Pubkey,
+    /// Staker fees accrued per staked SHADE, scaled by FEE_PRECISION
+    pub acc_fee_per_share: u128,
+    /// Fee-vault reward paid per expired authorization cleaned up
+    pub keeper_reward: u64,
+    /// Seconds between requesting an unstake and withdrawing it
+    pub unstake_cooldown: i64,
+    /// Spending cap at a 1x tier multiplier (pool asset base units)
+    pub base_spending_cap: u64,
+    /// Bump of the vault_authority PDA that owns the fee and staking vaults
+    pub vault_authority_bump: u8,
+    /// Keeper's cut of the fees moved by sweep_fees, in basis points
+    pub keeper_reward_bps: u16,
+    /// Share of swept fees paid to stakers (bps)
+    pub staker_share_bps: u16,
+    /// Share of swept fees left in the pool vault for LPs (bps)
+    pub lp_share_bps: u16,
+    /// Share of swept fees paid to the fee treasury (bps)
+    pub treasury_share_bps: u16,
+    /// Treasury-PDA-owned token account receiving the treasury share
+    pub fee_treasury: Pubkey,
+    /// Share of the protocol fee rebated to Bronze spenders (bps)
+    pub bronze_fee_rebate_bps: u16,
+    /// Share of the protocol fee rebated to Silver spenders (bps)
+    pub silver_fee_rebate_bps: u16,
+    /// Share of the protocol fee rebated to Gold spenders (bps)
+    pub gold_fee_rebate_bps: u16,
+    /// Layout version (0 = written before versioning; see migrate_*)
+    pub version: u8,
+    /// Fog pools created in this instance, listed in the pool registry
+    pub pool_count: u64,
+    /// Oracle price move within price_deviation_window that trips a pool's
+    /// circuit breaker (bps, 0 = disabled)
+    pub max_price_deviation_bps: u16,
+    /// Seconds a circuit breaker reference price stays valid
+    pub price_deviation_window: i64,
+    /// Extra effective stake credited to stake locks, sharing fees alongside
+    /// total_staked
+    pub total_lock_bonus: u64,
+    /// Share of stake forfeited when leaving a lock or the unstake cooldown early (bps)
+    pub early_exit_penalty_bps: u16,
+    /// Unused; once the SHADE shared out by early exits, kept so later fields
+    /// keep their offsets
+    pub reserved_penalties: u64,
+    /// SHADE emitted to stakers on top of fee sharing, paid from the emissions vault
+    pub staking_emissions: EmissionStream,
+    /// Last time staking emissions were accrued
+    pub emissions_updated_at: i64,
+    /// total_fees_collected when a config written by the original program was
+    /// migrated; settles its stakers' snapshot-based fee shares
+    pub legacy_fees_collected: u64,
+    /// total_staked when a config written by the original program was migrated
+    pub legacy_total_staked: u64,
+    /// Stake still warming up, left out of fee sharing until it activates
+    pub total_warming: u64,
+    /// Reserved for future fields
+    pub reserved: [u64; 16],
+}
+
+impl ProtocolConfig {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // authority
+        32 + // shade_mint
+        32 + // fee_vault
+        32 + // staking_vault
+        2 +  // fee_basis_points
+        8 +  // total_staked
+        8 +  // total_fees_collected
+        8 +  // total_fees_distributed
+        8 +  // bronze_threshold
+        8 +  // silver_threshold
+        8 +  // gold_threshold
+        2 +  // bronze_cap_multiplier
+        2 +  // silver_cap_multiplier
+        2 +  // gold_cap_multiplier
+        1 +  // bump
+        2 +  // rebase_bps
+        8 +  // rebase_epoch_length
+        8 +  // rebase_fees_pending
+        8 +  // last_rebase_at
+        8 +  // rebase_epoch
+        16 + // rebase_per_share
+        8 +  // total_rebased
+        8 +  // min_stake_age
+        32 + // instance_seed
+        32 + // tier_source
+        1 +  // paused
+        ResumeGuard::LEN + // resume_guard
+        32 + // pending_authority
+        16 + // acc_fee_per_share
+        8 +  // keeper_reward
+        8 +  // unstake_cooldown
+        8 +  // base_spending_cap
+        1 +  // vault_authority_bump
+        2 +  // keeper_reward_bps
+        2 +  // staker_share_bps
+        2 +  // lp_share_bps
+        2 +  // treasury_share_bps
+        32 + // fee_treasury
+        2 +  // bronze_fee_rebate_bps
+        2 +  // silver_fee_rebate_bps
+        2 +  // gold_fee_rebate_bps
+        1 +  // version
+        8 +  // pool_count
+        2 +  // max_price_deviation_bps
+        8 +  // price_deviation_window
+        8 +  // total_lock_bonus
+        2 +  // early_exit_penalty_bps
+        8 +  // reserved_penalties
+        EmissionStream::LEN + // staking_emissions
+        8 +  // emissions_updated_at
+        8 +  // legacy_fees_collected
+        8 +  // legacy_total_staked
+        8 +  // total_warming
+        8 * 16; // reserved
+
+    /// Pool registry page the next fog pool is appended to
+    pub fn next_pool_page(&self) -> u32 {
+        (self.pool_count / POOL_REGISTRY_PAGE_SIZE as u64) as u32
+    }
+
+    /// Effective stake sharing in fees: active staked SHADE plus lock bonuses
+    pub fn total_fee_shares(&self) -> u64 {
+        self.total_emission_shares()
+            .saturating_sub(self.total_warming)
+    }
+
+    /// Effective stake sharing in emissions: staked SHADE, warming or not,
+    /// plus lock bonuses
+    pub fn total_emission_shares(&self) -> u64 {
+        self.total_staked.saturating_add(self.total_lock_bonus)
+    }
+
+    /// Tier fee rebates in tier order (Bronze, Silver, Gold)
+    pub fn fee_rebate_bps(&self) -> [u16; 3] {
+        [
+            self.bronze_fee_rebate_bps,
+            self.silver_fee_rebate_bps,
+            self.gold_fee_rebate_bps,
+        ]
+    }
+
+    /// Spending cap limit of each tier, in tier order (none, Bronze, Silver, Gold)
+    pub fn tier_max_caps(&self) -> [u64; 4] {
+        [0, 1, 2, 3].map(|tier| get_max_cap_for_tier(tier, self))
+    }
+
+    /// Instance part of the config PDA seeds (see instance_config_seed)
+    pub fn config_seed(&self) -> &[u8] {
+        instance_config_seed(&self.instance_seed)
+    }
+
+    /// Fill in the fields a config written by the original single-instance
+    /// program (version 0) lacks, with the defaults initialize_protocol uses
+    /// Freezes the original fee snapshot model for migrate_staker
+    pub fn upgrade_from_legacy(&mut self, vault_authority_bump: u8, now: i64) {
+        self.vault_authority_bump = vault_authority_bump;
+        self.base_spending_cap = DEFAULT_BASE_SPENDING_CAP;
+        self.staker_share_bps = 10_000;
+        self.emissions_updated_at = now;
+        self.legacy_fees_collected = self.total_fees_collected;
+        self.legacy_total_staked = self.total_staked;
+    }
+}
+
+/// Instance part of the config PDA seeds: the instance seed, or nothing for
+/// the default (all-zero) instance, which keeps the original program's
+/// `["protocol_config"]` address
+pub fn instance_config_seed(instance_seed: &[u8; 32]) -> &[u8] {
+    if *instance_seed == [0; 32] {
+        &[]
+    } else {
+        instance_seed
+    }
+}
+
+/// Staker account - tracks user's staking info
+#[account]
+#[derive(Default)]
+pub struct Staker {
+    /// User's wallet
+    pub user: Pubkey,
+    /// Amount of $SHADE staked
+    pub staked_amount: u64,
+    /// Pending rewards to claim
+    pub pending_rewards: u64,
+    /// Last reward claim timestamp
+    pub last_claim_timestamp: i64,
+    /// Fees already accounted for at the current stake
+    pub reward_debt: u128,
+    /// Current tier (0=None, 1=Bronze, 2=Silver, 3=Gold)
+    pub tier: u8,
+    /// PDA bump
+    pub bump: u8,
+    /// Unvested SHADE from opted-in vesting schedules (counts for tier only)
+    pub vesting_tier_credit: u64,
+    /// Rebased SHADE already accounted for at the current stake
+    pub rebase_debt: u128,
+    /// Delegate allowed to claim rewards for this staker (default = none)
+    pub claim_delegate: Pubkey,
+    /// Staker-owned token account that delegated claims pay into
+    pub reward_destination: Pubkey,
+    /// Most recently added stake that has not yet reached min_stake_age
+    pub warming_amount: u64,
+    /// When warming_amount was last added to
+    pub warming_since: i64,
+    /// Unstaked SHADE waiting out the cooldown
+    pub pending_unstake_amount: u64,
+    /// When the pending unstake was last requested
+    pub unstake_requested_at: i64,
+    /// Layout version (0 = written before versioning; see migrate_*)
+    pub version: u8,
+    /// Stake committed to the current lock when it was last (re)locked
+    pub locked_amount: u64,
+    /// When the current lock's unlock schedule started
+    pub lock_start: i64,
+    /// When the lock is fully unlocked
+    pub lock_end: i64,
+    /// Extra effective stake per locked SHADE (bps), set by the lock duration
+    pub lock_boost_bps: u16,
+    /// Lock bonus currently credited to tier weight and fee share
+    pub lock_bonus: u64,
+    /// Staking emissions already accounted for at the current stake
+    pub emission_debt: u128,
+    /// Settled staking emissions waiting to be claimed
+    pub pending_emissions: u64,
+    /// Wallet this staker's tier is delegated to (default = none)
+    pub tier_delegate: Pubkey,
+    /// Reserved for future fields
+    pub reserved: [u64; 4],
+}
+
+impl Staker {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // user
+        8 +  // staked_amount
+        8 +  // pending_rewards
+        8 +  // last_claim_timestamp
+        16 + // reward_debt
+        1 +  // tier
+        1 +  // bump
+        8 +  // vesting_tier_credit
+        16 + // rebase_debt
+        32 + // claim_delegate
+        32 + // reward_destination
+        8 +  // warming_amount
+        8 +  // warming_since
+        8 +  // pending_unstake_amount
+        8 +  // unstake_requested_at
+        1 +  // version
+        8 +  // locked_amount
+        8 +  // lock_start
+        8 +  // lock_end
+        2 +  // lock_boost_bps
+        8 +  // lock_bonus
+        16 + // emission_debt
+        8 +  // pending_emissions
+        32 + // tier_delegate
+        8 * 4; // reserved
+
+    /// Whether the staker's tier is delegated to another wallet, suspending it
+    /// for the staker's own wallet
+    pub fn tier_delegated(&self) -> bool {
+        self.tier_delegate != Pubkey::default()
+    }
+
+    /// Amount used for tier calculation: staked SHADE plus opted-in unvested
+    /// SHADE and the lock bonus
+    /// Fee sharing only uses staked_amount and the lock bonus
+    pub fn tier_weight(&self) -> u64 {
+        self.staked_amount
+            .saturating_add(self.vesting_tier_credit)
+            .saturating_add(self.lock_bonus)
+    }
+
+    /// Stake still locked at `now`; the lock unlocks linearly until lock_end
+    pub fn locked_stake(&self, now: i64) -> u64 {
+        if now >= self.lock_end {
+            return 0;
+        }
+        let remaining = (self.lock_end - now) as u128;
+        let duration = self.lock_end.saturating_sub(self.lock_start).max(1) as u128;
+        ((self.locked_amount as u128 * remaining) / duration).min(self.staked_amount as u128) as u64
+    }
+
+    /// Lock bonus the still-locked stake earns at `now`
+    pub fn lock_bonus_at(&self, now: i64) -> u64 {
+        (self.locked_stake(now) as u128 * self.lock_boost_bps as u128 / 10_000) as u64
+    }
+
+    /// Whether the warming stake has waited out `min_stake_age` at `now`
+    pub fn warming_matured(&self, min_stake_age: i64, now: i64) -> bool {
+        now >= self.warming_since.saturating_add(min_stake_age)
+    }
+
+    /// Fees attributable to the active stake at `acc_fee_per_share`
+    /// Warming stake earns nothing until it activates
+    pub fn fee_entitlement(&self, acc_fee_per_share: u128) -> u128 {
+        (self
+            .staked_amount
+            .saturating_sub(self.warming_amount)
+            .saturating_add(self.lock_bonus) as u128)
+            .saturating_mul(acc_fee_per_share)
+            / FEE_PRECISION
+    }
+
+    /// Staking emissions attributable to the current stake at `reward_per_share`
+    /// Emissions are shared like fees: staked SHADE plus the lock bonus
+    pub fn emission_entitlement(&self, reward_per_share: u128) -> u128 {
+        (self.staked_amount.saturating_add(self.lock_bonus) as u128)
+            .saturating_mul(reward_per_share)
+            / REWARD_PRECISION
+    }
+
+    /// Rebased SHADE attributable to the current stake at `rebase_per_share`
+    pub fn rebase_entitlement(&self, rebase_per_share: u128) -> u128 {
+        (self.staked_amount as u128).saturating_mul(rebase_per_share) / REBASE_PRECISION
+    }
+}
+
+/// Tier delegation - lets a staker's tier (not their stake) count for another
+/// wallet, e.g. a cold-wallet staker's hot spending wallet
+#[account]
+#[derive(Default)]
+pub struct TierDelegation {
+    /// Staker delegating their tier
+    pub delegator: Pubkey,
+    /// Wallet using the tier (PDA seed)
+    pub delegate: Pubkey,
+    /// When the delegation was created
+    pub created_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl TierDelegation {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // delegator
+        32 + // delegate
+        8 +  // created_at
+        1; // bump
+}
+
+/// Fog Pool - Shared liquidity reservoir where ownership is non-attributable
+#[account]
+#[derive(Default)]
+pub struct FogPool {
+    /// Authority who controls the fog pool
+    pub authority: Pubkey,
+    /// Token vault holding the pooled funds
+    pub vault: Pubkey,
+    /// Total tokens deposited into the pool
+    pub total_deposited: u64,
+    /// Total tokens spent from the pool
+    pub total_spent: u64,
+    /// Total fees generated from this pool
+    pub total_fees_generated: u64,
+    /// Number of active authorizations
+    pub active_authorizations: u64,
+    /// Unique seed for PDA derivation
+    pub pool_seed: [u8; 32],
+    /// PDA bump seed
+    pub bump: u8,
+    /// Operating status (incident modes restrict activity)
+    pub status: PoolStatus,
+    /// Dual-control rules for leaving an incident mode
+    pub resume_guard: ResumeGuard,
+    /// Number of spends executed; each spend's index is its value before the spend
+    pub spend_count: u64,
+    /// Last page of the authorization index
+    pub index_tail_page: u32,
+    /// Entries on the last index page
+    pub index_tail_len: u32,
+    /// Protocol instance the pool belongs to
+    pub protocol_config: Pubkey,
+    /// LP share-token mint (authority: the pool vault authority PDA)
+    pub lp_mint: Pubkey,
+    /// Total tokens withdrawn by LPs
+    pub total_withdrawn: u64,
+    /// Token mint the pool holds
+    pub mint: Pubkey,
+    /// Operator that can pause the pool and revoke authorizations (default = none)
+    pub operator: Pubkey,
+    /// Proposed new authority (default = none)
+    pub pending_authority: Pubkey,
+    /// Bump of the pool_vault_authority PDA that owns the vault and LP mint
+    pub vault_authority_bump: u8,
+    /// Spend fees held in the vault until swept to the fee vault
+    pub pending_fees: u64,
+    /// Protocol fee rate, cached from the config by sync_pool_config
+    pub fee_basis_points: u16,
+    /// Unused; once the cached protocol pause flag, kept so later fields keep
+    /// their offsets
+    pub reserved_flag: bool,
+    /// Pool owner's cut of the pool's spend fees (bps)
+    pub pool_fee_bps: u16,
+    /// Token account receiving the pool owner's cut
+    pub pool_fee_vault: Pubkey,
+    /// Extra fee at 100% utilization (bps; 0 = no fee curve)
+    pub fee_curve_slope_bps: u16,
+    /// Highest fee the curve can reach (bps)
+    pub fee_ceiling_bps: u16,
+    /// Tier fee rebates (Bronze, Silver, Gold), cached by sync_pool_config
+    pub fee_rebate_bps: [u16; 3],
+    /// Escrowed spends held in the vault until claimed or refunded
+    pub escrowed_amount: u64,
+    /// Sequence number of the next pool event, so indexers can detect gaps
+    pub event_sequence: u64,
+    /// Layout version (0 = written before versioning; see migrate_*)
+    pub version: u8,
+    /// Spend ring every spend is recorded in (default = none)
+    pub spend_ring: Pubkey,
+    /// Bitmask of purpose categories with a category budget (bit = category)
+    pub budgeted_categories: u64,
+    /// Pyth feed id pricing the pool mint in USD (all zeros = none)
+    pub price_feed_id: [u8; 32],
+    /// Oldest price update accepted, in seconds
+    pub max_price_age: u32,
+    /// Widest confidence interval accepted, in bps of the price
+    pub max_price_conf_bps: u16,
+    /// Circuit breaker threshold, cached from the protocol config by sync_pool_config
+    pub max_price_deviation_bps: u16,
+    /// Circuit breaker window, cached from the protocol config by sync_pool_config
+    pub price_deviation_window: i64,
+    /// Circuit breaker reference price for the current window (0 = none)
+    pub reference_price: u64,
+    /// When the reference price was taken
+    pub reference_price_at: i64,
+    /// Highest utilization spends may push the pool to (bps, 0 = no cap)
+    pub max_utilization_bps: u16,
+    /// Vault liquidity held back from LP withdrawals for active authorizations
+    pub reserved_amount: u64,
+    /// Most LP liquidity the vault may hold after a deposit (0 = no cap)
+    pub max_total_deposits: u64,
+    /// Only depositors on the pool's allowlist may deposit
+    pub depositor_allowlist: bool,
+    /// Smallest amount a single spend may pay (0 = no minimum)
+    pub min_spend: u64,
+    /// Largest amount a single spend may pay (0 = no maximum)
+    pub max_single_spend: u64,
+    /// SAS credential whose attestations the pool requires (default = none)
+    pub attestation_credential: Pubkey,
+    /// SAS schema the attestations must use (default = any)
+    pub attestation_schema: Pubkey,
+    /// Who must hold an attestation when a credential is set
+    pub attestation_subject: AttestationSubject,
+    /// Spends re-check the spender's current staking tier
+    pub spend_tier_check: bool,
+    /// Cached per-tier spending cap limits (none, Bronze, Silver, Gold)
+    pub tier_max_caps: [u64; 4],
+    /// LP shares locked by the first deposit; counted in the share supply but
+    /// never minted
+    pub locked_shares: u64,
+    /// Routed spends this pool fronted, and positive federation positions,
+    /// that other pools still owe it
+    pub receivables: u64,
+    /// Routed spends under this pool's authorizations, and negative federation
+    /// positions, it still owes other pools
+    pub payables: u64,
+    /// Reserved for future fields
+    pub reserved: [u64; 16],
+}
+
+impl FogPool {
+    /// A new, active pool in `config`, with the config's settings cached and
+    /// every counter at zero; callers fill in the pool's own accounts
+    pub fn new(config: &Account<ProtocolConfig>) -> Self {
+        let mut fog_pool = FogPool {
+            protocol_config: config.key(),
+            version: FogPool::VERSION,
+            ..FogPool::default()
+        };
+        fog_pool.sync_config(config);
+        fog_pool
+    }
+
+    /// Copy the config settings the pool caches: the protocol fee rate, tier
+    /// fee rebates and caps and circuit breaker
+    pub fn sync_config(&mut self, config: &ProtocolConfig) {
+        self.fee_basis_points = config.fee_basis_points;
+        self.fee_rebate_bps = config.fee_rebate_bps();
+        self.tier_max_caps = config.tier_max_caps();
+        self.max_price_deviation_bps = config.max_price_deviation_bps;
+        self.price_deviation_window = config.price_deviation_window;
+    }
+
+    pub const LEN: usize = 8 + // discriminator
+        32 + // authority
+        32 + // vault
+        8 +  // total_deposited
+        8 +  // total_spent
+        8 +  // total_fees_generated
+        8 +  // active_authorizations
+        32 + // pool_seed
+        1 +  // bump
+        1 +  // status
+        ResumeGuard::LEN + // resume_guard
+        8 +  // spend_count
+        4 +  // index_tail_page
+        4 +  // index_tail_len
+        32 + // protocol_config
+        32 + // lp_mint
+        8 +  // total_withdrawn
+        32 + // mint
+        32 + // operator
+        32 + // pending_authority
+        1 +  // vault_authority_bump
+        8 +  // pending_fees
+        2 +  // fee_basis_points
+        1 +  // reserved_flag
+        2 +  // pool_fee_bps
+        32 + // pool_fee_vault
+        2 +  // fee_curve_slope_bps
+        2 +  // fee_ceiling_bps
+        2 * 3 + // fee_rebate_bps
+        8 +  // escrowed_amount
+        8 +  // event_sequence
+        1 +  // version
+        32 + // spend_ring
+        8 +  // budgeted_categories
+        32 + // price_feed_id
+        4 +  // max_price_age
+        2 +  // max_price_conf_bps
+        2 +  // max_price_deviation_bps
+        8 +  // price_deviation_window
+        8 +  // reference_price
+        8 +  // reference_price_at
+        2 +  // max_utilization_bps
+        8 +  // reserved_amount
+        8 +  // max_total_deposits
+        1 +  // depositor_allowlist
+        8 +  // min_spend
+        8 +  // max_single_spend
+        32 + // attestation_credential
+        32 + // attestation_schema
+        1 +  // attestation_subject
+        1 +  // spend_tier_check
+        8 * 4 + // tier_max_caps
+        8 +  // locked_shares
+        8 +  // receivables
+        8 +  // payables
+        8 * 16; // reserved
+
+    /// Index page the next authorization is appended to
+    pub fn next_index_page(&self) -> u32 {
+        if self.index_tail_len as usize >= POOL_INDEX_PAGE_SIZE {
+            self.index_tail_page + 1
+        } else {
+            self.index_tail_page
+        }
+    }
+
+    /// LP shares the vault balance is split between: the LP mint supply plus
+    /// the locked shares
+    pub fn share_supply(&self, lp_supply: u64) -> Result<u64> {
+        Ok(lp_supply
+            .checked_add(self.locked_shares)
+            .ok_or(ShadeError::Overflow)?)
+    }
+
+    /// Vault balance that belongs to LPs, excluding fees not yet swept and
+    /// escrowed spends
+    pub fn available_balance(&self, vault_balance: u64) -> u64 {
+        vault_balance
+            .saturating_sub(self.pending_fees)
+            .saturating_sub(self.escrowed_amount)
+    }
+
+    /// Carry a move in the pool's federation net position from `old` to `new`
+    /// into its receivables (positive) and payables (negative)
+    /// Saturating: positions opened before the pools tracked them aren't booked
+    pub fn rebook_federation_position(&mut self, old: i64, new: i64) {
+        self.receivables = self
+            .receivables
+            .saturating_sub(old.max(0).unsigned_abs())
+            .saturating_add(new.max(0).unsigned_abs());
+        self.payables = self
+            .payables
+            .saturating_sub(old.min(0).unsigned_abs())
+            .saturating_add(new.min(0).unsigned_abs());
+    }
+
+    /// What LP shares are priced against: the available balance plus what
+    /// other pools owe this one, less what it owes them
+    pub fn lp_value(&self, vault_balance: u64) -> u64 {
+        self.available_balance(vault_balance)
+            .saturating_add(self.receivables)
+            .saturating_sub(self.payables)
+    }
+
+    /// Spend fee on `amount` for a spender of staking `tier`, and the
+    /// utilization surcharge included in it
+    /// Without a fee curve the fee is the cached protocol rate, less the tier's
+    /// rebate; with one it rises by fee_curve_slope_bps at full utilization,
+    /// up to fee_ceiling_bps
+    pub fn spend_fees(&self, amount: u64, tier: u8, vault_balance: u64) -> Result<(u64, u64)> {
+        let protocol_fee = spend_fee(self.fee_basis_points, amount)?;
+        let rebate = match tier {
+            1..=3 => spend_fee(self.fee_rebate_bps[tier as usize - 1], protocol_fee)?,
+            _ => 0,
+        };
+        let base_fee = protocol_fee - rebate;
+        if self.fee_curve_slope_bps == 0 {
+            return Ok((base_fee, 0));
+        }
+        let utilization = self.utilization_bps(vault_balance);
+        let curve_bps = (self.fee_basis_points as u64
+            + utilization * self.fee_curve_slope_bps as u64 / 10_000)
+            .min(self.fee_ceiling_bps.max(self.fee_basis_points) as u64);
+        let surcharge = spend_fee(curve_bps as u16, amount)? - protocol_fee;
+        Ok((base_fee + surcharge, surcharge))
+    }
+
+    /// Liquidity committed right now: reserved for authorizations, held in
+    /// escrow and owed to other pools
+    pub fn committed_liquidity(&self) -> u128 {
+        self.reserved_amount as u128 + self.escrowed_amount as u128 + self.payables as u128
+    }
+
+    /// Share of the vault's liquidity, net of pending fees, that is committed
+    /// (bps, capped at 10000)
+    pub fn utilization_bps(&self, vault_balance: u64) -> u64 {
+        let liquidity = vault_balance.saturating_sub(self.pending_fees);
+        if liquidity == 0 {
+            return 10_000;
+        }
+        ((self.committed_liquidity() * 10_000) / liquidity as u128).min(10_000) as u64
+    }
+
+    /// Check that drawing `amount`, of which `reserved` was already reserved
+    /// for the authorization, keeps committed liquidity plus the draw within
+    /// max_utilization_bps of the vault's liquidity, leaving LPs a withdrawal buffer
+    pub fn check_utilization(&self, amount: u64, reserved: u64, vault_balance: u64) -> Result<()> {
+        if self.max_utilization_bps == 0 {
+            return Ok(());
+        }
+        let liquidity = vault_balance.saturating_sub(self.pending_fees);
+        let used = self.committed_liquidity() + amount.saturating_sub(reserved) as u128;
+        require!(
+            used * 10_000 <= liquidity as u128 * self.max_utilization_bps as u128,
+            ShadeError::UtilizationCapExceeded
+        );
+        Ok(())
+    }
+
+    /// Check that a single spend of `amount` is within the pool's minimum and
+    /// maximum spend sizes
+    pub fn check_spend_size(&self, amount: u64) -> Result<()> {
+        require!(amount >= self.min_spend, ShadeError::SpendBelowMinimum);
+        require!(
+            self.max_single_spend == 0 || amount <= self.max_single_spend,
+            ShadeError::SpendAboveMaximum
+        );
+        Ok(())
+    }
+
+    /// Check that an authorization's spending, `spent` including this spend,
+    /// stays within the spender's current tier cap when the pool re-checks
+    /// tiers at spend time; `tier` is None when no staker account was passed
+    pub fn check_spend_tier(&self, tier: Option<u8>, spent: u64) -> Result<()> {
+        if !self.spend_tier_check {
+            return Ok(());
+        }
+        let tier = tier.ok_or(ShadeError::StakerRequired)?;
+        let max_cap = self.tier_max_caps[tier.min(3) as usize];
+        require!(spent <= max_cap, ShadeError::ExceedsTierLimit);
+        Ok(())
+    }
+
+    /// Whether the pool requires an attestation for `role`
+    pub fn requires_attestation(&self, role: AttestationSubject) -> bool {
+        self.attestation_credential != Pubkey::default()
+            && (self.attestation_subject == role
+                || self.attestation_subject == AttestationSubject::Both)
+    }
+
+    /// Whether the circuit breaker has a reference price at `now`
+    pub fn price_reference_live(&self, now: i64) -> bool {
+        self.reference_price > 0
+            && now
+                < self
+                    .reference_price_at
+                    .saturating_add(self.price_deviation_window)
+    }
+
+    /// Whether `price` is further from the reference price than the circuit
+    /// breaker allows
+    pub fn price_deviates(&self, price: u64) -> bool {
+        (price.abs_diff(self.reference_price) as u128) * 10_000
+            > (self.reference_price as u128) * self.max_price_deviation_bps as u128
+    }
+
+    /// Whether spends in `category` count against a category budget
+    pub fn has_category_budget(&self, category: PurposeCategory) -> bool {
+        self.budgeted_categories & category.bit() != 0
+    }
+
+    /// Take the next number in the pool's event sequence (starts at 0)
+    pub fn next_event_sequence(&mut self) -> Result<u64> {
+        let sequence = self.event_sequence;
+        self.event_sequence = sequence.checked_add(1).ok_or(ShadeError::Overflow)?;
+        Ok(sequence)
+    }
+
+    /// Count a spend in the pool stats, returning its spend index
+    /// The fee is left in the vault as a pending fee, except the utilization
+    /// surcharge, which belongs to LPs straight away
+    pub fn record_spend(&mut self, amount: u64, fee: u64, surcharge: u64) -> Result<u64> {
+        let spend_index = self.spend_count;
+        self.spend_count = spend_index.checked_add(1).ok_or(ShadeError::Overflow)?;
+        self.total_spent = self
+            .total_spent
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        self.total_fees_generated = self
+            .total_fees_generated
+            .checked_add(fee)
+            .ok_or(ShadeError::Overflow)?;
+        self.pending_fees = self
+            .pending_fees
+            .checked_add(fee - surcharge)
+            .ok_or(ShadeError::Overflow)?;
+        Ok(spend_index)
+    }
+}
+
+/// Spend ring - the last `capacity` spends from a fog pool, for on-chain
+/// monitoring and circuit breakers
+/// Zero-copy header; the `SpendRecord` slots follow it in the account data,
+/// and slot `total_recorded % capacity` is written next
+#[account(zero_copy)]
+pub struct SpendRing {
+    /// The fog pool whose spends are recorded
+    pub fog_pool: Pubkey,
+    /// Spends recorded since the ring was created or resized
+    pub total_recorded: u64,
+    /// Number of slots
+    pub capacity: u32,
+    /// PDA bump seed
+    pub bump: u8,
+    pub _padding: [u8; 3],
+}
+
+impl SpendRing {
+    pub const HEADER_LEN: usize = 8 + // discriminator
+        32 + // fog_pool
+        8 +  // total_recorded
+        4 +  // capacity
+        1 +  // bump
+        3; // _padding
+
+    /// Account size for a ring with `capacity` slots
+    pub fn space(capacity: u32) -> usize {
+        Self::HEADER_LEN + capacity as usize * SpendRecord::LEN
+    }
+
+    /// Offset of `slot` in the account data
+    pub fn slot_offset(slot: usize) -> usize {
+        Self::HEADER_LEN + slot * SpendRecord::LEN
+    }
+}
+
+/// One spend in a spend ring, doubling as the receipt refund_spend is checked against
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct SpendRecord {
+    /// Amount drawn, including the fee
+    pub amount: u64,
+    /// When the spend happened
+    pub timestamp: i64,
+    /// Spender of the authorization
+    pub spender: Pubkey,
+    /// Authorization spent under (default for permit spends)
+    pub authorization: Pubkey,
+    /// Token account paid
+    pub recipient: Pubkey,
+    /// Creditor pool whose vault paid a routed spend (default otherwise)
+    pub paid_by: Pubkey,
+    /// The pool's spend index
+    pub spend_index: u64,
+    /// Amount returned through refund_spend so far
+    pub refunded: u64,
+}
+
+impl SpendRecord {
+    pub const LEN: usize = 8 + 8 + 32 + 32 + 32 + 32 + 8 + 8;
+}
+
+/// LP position - a depositor's deposit/withdrawal history in one fog pool
+/// The shares themselves are the pool's transferable LP tokens
+#[account]
+#[derive(Default)]
+pub struct LpPosition {
+    /// Depositor the history belongs to
+    pub owner: Pubkey,
+    /// Fog pool deposited into
+    pub fog_pool: Pubkey,
+    /// Total tokens deposited
+    pub total_deposited: u64,
+    /// Total tokens withdrawn
+    pub total_withdrawn: u64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl LpPosition {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // owner
+        32 + // fog_pool
+        8 +  // total_deposited
+        8 +  // total_withdrawn
+        1; // bump
+}
+
+/// Dual control for resuming a paused/restricted pool or protocol:
+/// a resume needs the guardian's co-signature, or an announcement that has
+/// aged past RESUME_TIMELOCK, so one compromised key can't flip it back instantly
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct ResumeGuard {
+    /// Second key that can approve an immediate resume (default = none)
+    pub guardian: Pubkey,
+    /// When the guardian was set; a new guardian is only trusted after the timelock
+    pub guardian_set_at: i64,
+    /// When a resume was announced (0 = no pending announcement)
+    pub resume_requested_at: i64,
+}
+
+impl ResumeGuard {
+    pub const LEN: usize = 32 + // guardian
+        8 + // guardian_set_at
+        8; // resume_requested_at
+
+    fn authorize_resume(&mut self, guardian_signer: Option<Pubkey>, now: i64) -> Result<()> {
+        let guardian_approved = self.guardian != Pubkey::default()
+            && guardian_signer == Some(self.guardian)
+            && now >= self.guardian_set_at.saturating_add(RESUME_TIMELOCK);
+        let timelock_elapsed = self.resume_requested_at != 0
+            && now >= self.resume_requested_at.saturating_add(RESUME_TIMELOCK);
+        require!(
+            guardian_approved || timelock_elapsed,
+            ShadeError::ResumeNotAuthorized
+        );
+        self.resume_requested_at = 0;
+        Ok(())
+    }
+
+    fn set_guardian(
+        &mut self,
+        guardian: Pubkey,
+        current_guardian_signer: Option<Pubkey>,
+        now: i64,
+    ) -> Result<()> {
+        if self.guardian != Pubkey::default() {
+            require!(
+                current_guardian_signer == Some(self.guardian),
+                ShadeError::Unauthorized
+            );
+        }
+        self.guardian = guardian;
+        self.guardian_set_at = now;
+        Ok(())
+    }
+}
+
+/// Fog pool operating status
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum PoolStatus {
+    /// Normal operation
+    #[default]
+    Active,
+    /// Incident mode: no new authorizations or spends, withdrawals stay open
+    WithdrawOnly,
+    /// Operator pause: no new authorizations or spends until resumed
+    Paused,
+}
+
+/// Party an attestation-gated pool checks for a credential
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum AttestationSubject {
+    /// The authorization's spender, at issuance and on every spend
+    #[default]
+    Spender,
+    /// The owner of each spend's recipient token account
+    Recipient,
+    /// Both the spender and the recipient owner
+    Both,
+}
+
+/// Role in which a denylisted key was blocked
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DenylistRole {
+    /// Authorization spender (new authorizations and spends)
+    Spender,
+    /// Owner of a spend's recipient token account
+    Recipient,
+    /// LP depositing into a pool
+    Depositor,
+}
+
+/// Purpose category an authorization's spends are budgeted under
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum PurposeCategory {
+    /// No category; never budgeted
+    #[default]
+    Uncategorized,
+    Payroll,
+    Vendor,
+    Travel,
+    Marketing,
+    Software,
+    Operations,
+    Other,
+}
+
+impl PurposeCategory {
+    /// The category's bit in a pool's budgeted_categories mask
+    pub fn bit(self) -> u64 {
+        1 << self as u8
+    }
+}
+
+/// Pyth pull-oracle price update (the receiver program's PriceUpdateV2 layout)
+#[derive(AnchorDeserialize)]
+pub struct PythPriceUpdate {
+    pub write_authority: Pubkey,
+    pub verification_level: PythVerificationLevel,
+    pub price_message: PythPriceMessage,
+    pub posted_slot: u64,
+}
+
+/// How many Wormhole guardian signatures verified a Pyth price update
+#[derive(AnchorDeserialize, PartialEq, Eq)]
+pub enum PythVerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+/// Price of one Pyth feed: price * 10^exponent, +/- conf * 10^exponent
+#[derive(AnchorDeserialize)]
+pub struct PythPriceMessage {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub prev_publish_time: i64,
+    pub ema_price: i64,
+    pub ema_conf: u64,
+}
+
+/// Solana Attestation Service attestation (after the account type byte)
+#[derive(AnchorDeserialize)]
+pub struct SasAttestation {
+    pub nonce: Pubkey,
+    pub credential: Pubkey,
+    pub schema: Pubkey,
+    pub data: Vec<u8>,
+    pub signer: Pubkey,
+    pub expiry: i64,
+    pub token_account: Pubkey,
+}
+
+/// Authorization - Cryptographic permission to spend from the fog
+#[account]
+#[derive(Default)]
+pub struct Authorization {
+    /// The fog pool this authorization draws from
+    pub fog_pool: Pubkey,
+    /// Who can use this authorization to spend
+    pub authorized_spender: Pubkey,
+    /// Who issued this authorization
+    pub issuer: Pubkey,
+    /// Maximum amount that can be spent
+    pub spending_cap: u64,
+    /// Amount already spent (in the current budget window, if one is set)
+    pub amount_spent: u64,
+    /// When the authorization was created
+    pub created_at: i64,
+    /// When the authorization expires
+    pub expires_at: i64,
+    /// Purpose description (max 64 chars)
+    pub purpose: String,
+    /// Whether the authorization is still valid
+    pub is_active: bool,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Spends of at least this amount need approver signatures (0 = no policy)
+    pub approval_min_amount: u64,
+    /// Concurrent velocity windows (unused slots have window_length 0)
+    pub velocity_windows: [VelocityWindow; MAX_VELOCITY_WINDOWS],
+    /// Page of the pool's authorization index this authorization is listed on
+    pub index_page: u32,
+    /// Spending cap unlocks linearly between starts_at and expires_at
+    pub streaming: bool,
+    /// Merkle root of wallets spends may pay (all zeros = any recipient)
+    pub allowlist_root: [u8; 32],
+    /// Temporarily suspended by the issuer
+    pub frozen: bool,
+    /// Maximum number of spends (0 = unlimited)
+    pub max_uses: u32,
+    /// Number of spends made so far (a spend_batch counts once)
+    pub uses: u32,
+    /// Minimum seconds between spends (0 = no gap)
+    pub min_spend_interval: i64,
+    /// When the last spend was made (0 = never)
+    pub last_spend_at: i64,
+    /// Seconds after which amount_spent resets (0 = lifetime cap)
+    pub budget_window: i64,
+    /// Start of the current budget window
+    pub budget_window_start: i64,
+    /// Spends are rejected before this time (defaults to created_at)
+    pub starts_at: i64,
+    /// Unused budget was recorded by the issuer after expiry
+    pub budget_reclaimed: bool,
+    /// Spend fee is charged on top of the amount instead of deducted from it
+    pub fee_on_top: bool,
+    /// Seconds an escrowed spend can be disputed before the recipient may
+    /// claim it (0 = escrowed spends disabled)
+    pub dispute_window: i64,
+    /// Key that may refund escrowed spends alongside the issuer (default = none)
+    pub arbiter: Pubkey,
+    /// Layout version (0 = written before versioning; see migrate_*)
+    pub version: u8,
+    /// Budget category spends count against
+    pub category: PurposeCategory,
+    /// Spending cap and limits are in micro-USD, priced by the pool's Pyth feed
+    pub cap_in_usd: bool,
+    /// Pool liquidity still reserved for this authorization
+    pub reserved_amount: u64,
+    /// UTC weekdays spends are allowed on (bit 0 = Monday, 0 = no schedule)
+    pub spend_days: u8,
+    /// Minute of the UTC day the daily spend window opens
+    pub spend_window_start: u16,
+    /// Minute of the UTC day the daily spend window closes (before start =
+    /// overnight, equal to start = all day)
+    pub spend_window_end: u16,
+    /// Page of the spender's authorization index this is listed on
+    pub spender_index_page: u32,
+    /// Next approval nonce, kept while no approval policy is attached so a
+    /// re-created policy doesn't accept old approvals again
+    pub approval_nonce: u64,
+    /// Reserved for future fields
+    pub reserved: [u64; 11],
+}
+
+impl Authorization {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        32 + // authorized_spender
+        32 + // issuer
+        8 +  // spending_cap
+        8 +  // amount_spent
+        8 +  // created_at
+        8 +  // expires_at
+        68 + // purpose (4 byte len + 64 chars max)
+        1 +  // is_active
+        1 +  // bump
+        8 +  // approval_min_amount
+        VelocityWindow::LEN * MAX_VELOCITY_WINDOWS + // velocity_windows
+        4 +  // index_page
+        1 +  // streaming
+        32 + // allowlist_root
+        1 +  // frozen
+        4 +  // max_uses
+        4 +  // uses
+        8 +  // min_spend_interval
+        8 +  // last_spend_at
+        8 +  // budget_window
+        8 +  // budget_window_start
+        8 +  // starts_at
+        1 +  // budget_reclaimed
+        1 +  // fee_on_top
+        8 +  // dispute_window
+        32 + // arbiter
+        1 +  // version
+        1 +  // category
+        1 +  // cap_in_usd
+        8 +  // reserved_amount
+        1 +  // spend_days
+        2 +  // spend_window_start
+        2 +  // spend_window_end
+        4 +  // spender_index_page
+        8 +  // approval_nonce
+        8 * 11; // reserved
+
+    /// Portion of the spending cap unlocked at `now` (the full cap unless streaming)
+    pub fn unlocked_cap(&self, now: i64) -> u64 {
+        if !self.streaming || now >= self.expires_at {
+            return self.spending_cap;
+        }
+        let elapsed = now.saturating_sub(self.starts_at).max(0) as u128;
+        let duration = self.expires_at.saturating_sub(self.starts_at).max(1) as u128;
+        ((self.spending_cap as u128).saturating_mul(elapsed) / duration) as u64
+    }
+
+    /// Accrued but unspent balance at `now`
+    pub fn streamed_balance(&self, now: i64) -> u64 {
+        self.unlocked_cap(now).saturating_sub(self.amount_spent)
+    }
+
+    /// Start of the budget window containing `now`
+    fn current_budget_start(&self, now: i64) -> i64 {
+        let elapsed = now.saturating_sub(self.budget_window_start).max(0);
+        self.budget_window_start + elapsed - elapsed % self.budget_window
+    }
+
+    /// Amount counted against the spending cap at `now`
+    /// With a budget window, spending from earlier windows no longer counts
+    pub fn spent_in_budget(&self, now: i64) -> u64 {
+        if self.budget_in_window(self.budget_window_start, now) {
+            self.amount_spent
+        } else {
+            0
+        }
+    }
+
+    /// Whether a spend made at `spent_at` still counts against the cap at `now`
+    fn budget_in_window(&self, spent_at: i64, now: i64) -> bool {
+        if self.budget_window == 0 {
+            return true;
+        }
+        let window_end = self.budget_window_start.saturating_add(self.budget_window);
+        spent_at >= self.budget_window_start && now < window_end
+    }
+
+    /// Whether `now` falls in the authorization's weekly spend schedule
+    /// An overnight window belongs to the weekday it opened on
+    pub fn in_spend_schedule(&self, now: i64) -> bool {
+        if self.spend_days == 0 {
+            return true;
+        }
+        let (start, end) = (self.spend_window_start, self.spend_window_end);
+        let minute = (now.rem_euclid(86_400) / 60) as u16;
+        let in_window = match start.cmp(&end) {
+            std::cmp::Ordering::Equal => true,
+            std::cmp::Ordering::Less => minute >= start && minute < end,
+            std::cmp::Ordering::Greater => minute >= start || minute < end,
+        };
+        let mut day = now.div_euclid(86_400);
+        if start > end && minute < end {
+            day -= 1;
+        }
+        // 1970-01-01 was a Thursday
+        let weekday = (day + 3).rem_euclid(7);
+        in_window && self.spend_days & (1 << weekday) != 0
+    }
+
+    /// Check that `amount` can be spent at `now` under the cap, stream,
+    /// velocity windows and approval policy
+    pub fn check_spend(&self, amount: u64, now: i64, approved: bool) -> Result<()> {
+        require!(self.is_active, ShadeError::AuthorizationInactive);
+        require!(!self.frozen, ShadeError::AuthorizationFrozen);
+        require!(now >= self.starts_at, ShadeError::AuthorizationNotStarted);
+        require!(now < self.expires_at, ShadeError::AuthorizationExpired);
+        require!(
+            self.max_uses == 0 || self.uses < self.max_uses,
+            ShadeError::MaxUsesReached
+        );
+        require!(
+            self.last_spend_at == 0
+                || now >= self.last_spend_at.saturating_add(self.min_spend_interval),
+            ShadeError::SpendTooSoon
+        );
+        require!(
+            self.in_spend_schedule(now),
+            ShadeError::OutsideSpendSchedule
+        );
+
+        let remaining = self
+            .spending_cap
+            .checked_sub(self.spent_in_budget(now))
+            .ok_or(ShadeError::Overflow)?;
+        require!(amount <= remaining, ShadeError::ExceedsSpendingCap);
+        if self.streaming {
+            require!(
+                amount <= self.streamed_balance(now),
+                ShadeError::ExceedsStreamedBalance
+            );
+        }
+
+        // Every configured velocity window must have room for this spend
+        for window in self.velocity_windows.iter() {
+            require!(
+                amount <= window.remaining(now),
+                ShadeError::VelocityLimitExceeded
+            );
+        }
+
+        // Large spends must go through the authorization's approval policy
+        require!(
+            approved || self.approval_min_amount == 0 || amount < self.approval_min_amount,
+            ShadeError::ApprovalRequired
+        );
+        Ok(())
+    }
+
+    /// Count a spend of `amount` at `now` against the cap, use count, spend
+    /// interval and velocity windows
+    pub fn record_spend(&mut self, amount: u64, now: i64) -> Result<()> {
+        if self.budget_window > 0 {
+            let start = self.current_budget_start(now);
+            if start != self.budget_window_start {
+                self.budget_window_start = start;
+                self.amount_spent = 0;
+            }
+        }
+        self.amount_spent = self
+            .amount_spent
+            .checked_add(amount)
+            .ok_or(ShadeError::Overflow)?;
+        self.uses = self.uses.checked_add(1).ok_or(ShadeError::Overflow)?;
+        self.last_spend_at = now;
+        for window in self.velocity_windows.iter_mut() {
+            window.record(now, amount)?;
+        }
+        Ok(())
+    }
+}
+
+/// One payment in a spend_batch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct BatchPayment {
+    /// Recipient token account (must match the pool mint)
+    pub recipient: Pubkey,
+    /// Gross amount drawn for this recipient, before the protocol fee
+    pub amount: u64,
+}
+
+/// Return data of `spend`, readable by CPI callers via get_return_data
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct SpendReceipt {
+    /// Amount drawn from the authorization, including an on-top fee
+    pub amount: u64,
+    /// Protocol fee, including any utilization surcharge
+    pub fee: u64,
+    /// Amount sent to the recipient, before any Token-2022 transfer fee
+    pub net_amount: u64,
+    /// Spending cap left on the authorization
+    pub remaining_cap: u64,
+}
+
+/// One recipient's share in a split template
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct SplitShare {
+    /// Recipient token account (must match the pool mint)
+    pub recipient: Pubkey,
+    /// Share of each split spend (bps)
+    pub share_bps: u16,
+}
+
+impl SplitShare {
+    pub const LEN: usize = 32 + 2;
+}
+
+/// Velocity limit requested by the issuer
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct VelocityLimit {
+    /// Window length in seconds (e.g. 86400 for daily)
+    pub window_length: i64,
+    /// Maximum amount spendable within one window
+    pub max_amount: u64,
+    /// Share of the unspent allowance carried into the next window (basis points)
+    pub rollover_bps: u16,
+}
+
+/// Fixed spending window tracked on an authorization
+/// Periodic budgets (subscriptions, payroll) are windows; with rollover_bps set,
+/// part of a window's unspent base allowance carries into the next window
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct VelocityWindow {
+    /// Window length in seconds (0 = unused slot)
+    pub window_length: i64,
+    /// Maximum amount spendable within one window
+    pub max_amount: u64,
+    /// Share of unspent allowance rolled into the next window (basis points)
+    pub rollover_bps: u16,
+    /// Start of the current window
+    pub window_start: i64,
+    /// Amount spent in the current window
+    pub spent: u64,
+    /// Extra allowance carried over from the previous window
+    pub carried_over: u64,
+}
+
+impl VelocityWindow {
+    pub const LEN: usize = 8 + // window_length
+        8 + // max_amount
+        2 + // rollover_bps
+        8 + // window_start
+        8 + // spent
+        8; // carried_over
+
+    fn is_enabled(&self) -> bool {
+        self.window_length > 0
+    }
+
+    /// Start of the window containing `now`
+    fn current_start(&self, now: i64) -> i64 {
+        if now < self.window_start.saturating_add(self.window_length) {
+            return self.window_start;
+        }
+        let elapsed_windows = (now - self.window_start) / self.window_length;
+        self.window_start + elapsed_windows * self.window_length
+    }
+
+    /// If `now` is past the current window, the new window start and the
+    /// allowance carried into it
+    /// Only the base allowance rolls over, so carries never compound
+    fn rollover(&self, now: i64) -> Option<(i64, u64)> {
+        let start = self.current_start(now);
+        if start == self.window_start {
+            return None;
+        }
+        // Windows skipped entirely had their whole base allowance unspent
+        let unspent = if start == self.window_start + self.window_length {
+            self.max_amount.saturating_sub(self.spent)
+        } else {
+            self.max_amount
+        };
+        let carry = (unspent as u128)
+            .saturating_mul(self.rollover_bps as u128)
+            .checked_div(10000)
+            .unwrap_or(0) as u64;
+        Some((start, carry))
+    }
+
+    /// Amount still spendable in the window containing `now`
+    fn remaining(&self, now: i64) -> u64 {
+        if !self.is_enabled() {
+            return u64::MAX;
+        }
+        match self.rollover(now) {
+            Some((_, carry)) => self.max_amount.saturating_add(carry),
+            None => self
+                .max_amount
+                .saturating_add(self.carried_over)
+                .saturating_sub(self.spent),
+        }
+    }
+
+    /// Undo consumption for a spend made at `spent_at`, if that spend still
+    /// counts against the current window
+    fn release(&mut self, spent_at: i64, now: i64, amount: u64) {
+        if !self.is_enabled() || self.rollover(now).is_some() || spent_at < self.window_start {
+            return;
+        }
+        self.spent = self.spent.saturating_sub(amount);
+    }
+
+    /// Record a spend, rolling into a fresh window first if the old one ended
+    fn record(&mut self, now: i64, amount: u64) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if let Some((start, carry)) = self.rollover(now) {
+            self.window_start = start;
+            self.spent = 0;
+            self.carried_over = carry;
+        }
+        self.spent = self.spent.checked_add(amount).ok_or(ShadeError::Overflow)?;
+        Ok(())
+    }
+}
+
+/// Approval policy - co-signers who must approve large spends on an authorization
+#[account]
+#[derive(Default)]
+pub struct ApprovalPolicy {
+    /// The authorization this policy guards
+    pub authorization: Pubkey,
+    /// Keys allowed to approve spends (max 10)
+    pub approvers: Vec<Pubkey>,
+    /// Number of distinct approvals required
+    pub threshold: u8,
+    /// Spends of at least this amount require approval
+    pub min_amount: u64,
+    /// Incremented on every approved spend (replay protection)
+    pub approval_nonce: u64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl ApprovalPolicy {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // authorization
+        4 + 32 * MAX_APPROVERS + // approvers
+        1 +  // threshold
+        8 +  // min_amount
+        8 +  // approval_nonce
+        1; // bump
+}
+
+/// Issuer delegate - an operator allowed to issue authorizations from a pool
+#[account]
+#[derive(Default)]
+pub struct IssuerDelegate {
+    /// The fog pool the operator may issue from
+    pub fog_pool: Pubkey,
+    /// Operator key
+    pub operator: Pubkey,
+    /// When the permission was granted
+    pub added_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// May only issue from authorization templates
+    pub templates_only: bool,
+}
+
+impl IssuerDelegate {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        32 + // operator
+        8 +  // added_at
+        1 +  // bump
+        1; // templates_only
+}
+
+/// Registered program - a partner program whose PDAs may hold authorizations
+#[account]
+#[derive(Default)]
+pub struct RegisteredProgram {
+    /// The fog pool the program may receive authorizations from
+    pub fog_pool: Pubkey,
+    /// Partner program id
+    pub program_id: Pubkey,
+    /// When the program was registered
+    pub added_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl RegisteredProgram {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        32 + // program_id
+        8 +  // added_at
+        1; // bump
+}
+
+/// Allowed depositor - a key that may deposit into an allowlist-only pool
+#[account]
+#[derive(Default)]
+pub struct AllowedDepositor {
+    /// The fog pool the depositor may deposit into
+    pub fog_pool: Pubkey,
+    /// Depositor key
+    pub depositor: Pubkey,
+    /// When the depositor was added
+    pub added_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl AllowedDepositor {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        32 + // depositor
+        8 +  // added_at
+        1; // bump
+}
+
+/// Program spender - binds a PDA spender to the program that derives it
+#[account]
+#[derive(Default)]
+pub struct ProgramSpender {
+    /// The fog pool the binding was recorded for
+    pub fog_pool: Pubkey,
+    /// The PDA spender
+    pub spender: Pubkey,
+    /// Program the spender is derived from
+    pub program_id: Pubkey,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl ProgramSpender {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        32 + // spender
+        32 + // program_id
+        1; // bump
+}
+
+/// Authorization campaign - merkle root of authorizations spenders claim
+#[account]
+#[derive(Default)]
+pub struct AuthorizationCampaign {
+    /// The fog pool the authorizations draw on
+    pub fog_pool: Pubkey,
+    /// Campaign id (PDA seed)
+    pub campaign_id: u64,
+    /// Merkle root of (index, spender, spending_cap, expires_at) leaves
+    pub merkle_root: [u8; 32],
+    /// Pool authority that committed the root; recorded as each issuer
+    pub issuer: Pubkey,
+    /// Purpose given to every claimed authorization
+    pub purpose: String,
+    /// Number of entries claimed so far
+    pub claimed: u64,
+    /// When the root was committed
+    pub created_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl AuthorizationCampaign {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        8 +  // campaign_id
+        32 + // merkle_root
+        32 + // issuer
+        4 + 64 + // purpose
+        8 +  // claimed
+        8 +  // created_at
+        1; // bump
+}
+
+/// Campaign claim receipt - its existence marks a leaf index as claimed
+#[account]
+#[derive(Default)]
+pub struct CampaignClaim {
+    /// Authorization the entry was materialized as
+    pub authorization: Pubkey,
+    /// When it was claimed
+    pub claimed_at: i64,
+}
+
+impl CampaignClaim {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // authorization
+        8; // claimed_at
+}
+
+/// Authorization template - a pool policy issued repeatedly with issue_from_template
+#[account]
+#[derive(Default)]
+pub struct AuthorizationTemplate {
+    /// Pool the template belongs to
+    pub fog_pool: Pubkey,
+    /// Pool authority's id for the template
+    pub template_id: u64,
+    /// Spending cap of each issued authorization
+    pub spending_cap: u64,
+    /// Seconds from issuance until an issued authorization expires
+    pub duration: i64,
+    /// Purpose copied to issued authorizations (max 64 chars)
+    pub purpose: String,
+    /// Recipient allowlist root copied to issued authorizations (all zeros = any)
+    pub allowlist_root: [u8; 32],
+    /// Use limit copied to issued authorizations (0 = unlimited)
+    pub max_uses: u32,
+    /// Authorizations issued so far
+    pub issued: u64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl AuthorizationTemplate {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        8 +  // template_id
+        8 +  // spending_cap
+        8 +  // duration
+        68 + // purpose (4 byte len + 64 chars max)
+        32 + // allowlist_root
+        4 +  // max_uses
+        8 +  // issued
+        1; // bump
+}
+
+/// Pending spend - a spend proposed for on-chain approval
+#[account]
+#[derive(Default)]
+pub struct PendingSpend {
+    /// The authorization the spend draws on
+    pub authorization: Pubkey,
+    /// Spender who proposed it; receives the rent back
+    pub proposer: Pubkey,
+    /// Token account the spend pays
+    pub recipient_token_account: Pubkey,
+    /// Gross amount to spend
+    pub amount: u64,
+    /// Approvers who have signed off (max 10)
+    pub approvals: Vec<Pubkey>,
+    /// When the spend was proposed
+    pub created_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PendingSpend {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // authorization
+        32 + // proposer
+        32 + // recipient_token_account
+        8 +  // amount
+        4 + 32 * MAX_APPROVERS + // approvals
+        8 +  // created_at
+        1; // bump
+}
+
+/// Escrowed spend - a spend held in the pool vault during its dispute window
+#[account]
+#[derive(Default)]
+pub struct EscrowedSpend {
+    /// The authorization the spend drew on
+    pub authorization: Pubkey,
+    /// Pool whose vault holds the funds
+    pub fog_pool: Pubkey,
+    /// Spender who made the spend; receives the rent back
+    pub spender: Pubkey,
+    /// Token account the spend pays
+    pub recipient_token_account: Pubkey,
+    /// Gross amount drawn from the authorization
+    pub amount: u64,
+    /// Spend fee, taken when the recipient claims
+    pub fee: u64,
+    /// Utilization surcharge included in the fee
+    pub surcharge: u64,
+    /// When the spend was made
+    pub created_at: i64,
+    /// When the dispute window ends and the recipient may claim
+    pub releases_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl EscrowedSpend {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // authorization
+        32 + // fog_pool
+        32 + // spender
+        32 + // recipient_token_account
+        8 +  // amount
+        8 +  // fee
+        8 +  // surcharge
+        8 +  // created_at
+        8 +  // releases_at
+        1; // bump
+}
+
+/// Invoice - a bill a recipient issues, payable once from an authorization
+#[account]
+#[derive(Default)]
+pub struct Invoice {
+    /// Wallet that issued the invoice; receives the rent back
+    pub recipient: Pubkey,
+    /// Token account the invoice is paid to
+    pub recipient_token_account: Pubkey,
+    /// Token mint the invoice is billed in
+    pub mint: Pubkey,
+    /// Recipient's id for the invoice, used as the paying spend's reference
+    pub invoice_id: [u8; 32],
+    /// Amount billed
+    pub amount: u64,
+    /// When the invoice was created
+    pub created_at: i64,
+    /// Invoice can't be paid after this time
+    pub expires_at: i64,
+    /// Memo shown to the payer (max 64 chars)
+    pub memo: String,
+    /// Whether the invoice has been paid
+    pub paid: bool,
+    /// Authorization that paid the invoice (default = unpaid)
+    pub paid_by: Pubkey,
+    /// When the invoice was paid (0 = unpaid)
+    pub paid_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Invoice {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // recipient
+        32 + // recipient_token_account
+        32 + // mint
+        32 + // invoice_id
+        8 +  // amount
+        8 +  // created_at
+        8 +  // expires_at
+        68 + // memo (4 byte len + 64 chars max)
+        1 +  // paid
+        32 + // paid_by
+        8 +  // paid_at
+        1; // bump
+}
+
+/// Category budget - a pool's aggregate spending cap for one purpose category
+#[account]
+#[derive(Default)]
+pub struct CategoryBudget {
+    /// Pool the budget belongs to
+    pub fog_pool: Pubkey,
+    /// Budgeted category (PDA seed)
+    pub category: PurposeCategory,
+    /// Maximum spent in the category per epoch
+    pub cap: u64,
+    /// Epoch length in seconds
+    pub epoch_length: i64,
+    /// Start of the current epoch
+    pub epoch_start: i64,
+    /// Amount spent in the current epoch
+    pub spent: u64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl CategoryBudget {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        1 +  // category
+        8 +  // cap
+        8 +  // epoch_length
+        8 +  // epoch_start
+        8 +  // spent
+        1; // bump
+
+    /// Count a spend of `amount` at `now`, starting a new epoch when the
+    /// current one has ended
+    pub fn record(&mut self, amount: u64, now: i64) -> Result<()> {
+        if now >= self.epoch_start.saturating_add(self.epoch_length) {
+            let elapsed_epochs = (now - self.epoch_start) / self.epoch_length;
+            self.epoch_start += elapsed_epochs * self.epoch_length;
+            self.spent = 0;
+        }
+        let spent = self.spent.checked_add(amount).ok_or(ShadeError::Overflow)?;
+        require!(spent <= self.cap, ShadeError::CategoryBudgetExceeded);
+        self.spent = spent;
+        Ok(())
+    }
+}
+
+/// Split template - a pool's revenue-share list for split spends
+#[account]
+#[derive(Default)]
+pub struct SplitTemplate {
+    /// Pool the template belongs to
+    pub fog_pool: Pubkey,
+    /// Pool authority's id for the template
+    pub template_id: u64,
+    /// Recipients and their shares, totalling 10000 bps (max 10)
+    pub shares: Vec<SplitShare>,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl SplitTemplate {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        8 +  // template_id
+        4 + SplitShare::LEN * MAX_SPLIT_RECIPIENTS + // shares
+        1; // bump
+}
+
+/// Permit nonce bitmap - one page of a pool's used spend permit nonces
+#[account]
+#[derive(Default)]
+pub struct PermitNonces {
+    /// Pool whose permits the page tracks
+    pub fog_pool: Pubkey,
+    /// Page number (nonce / PERMIT_NONCES_PER_PAGE)
+    pub page: u64,
+    /// One bit per nonce on the page; set once the permit is used
+    pub bitmap: [u64; 32],
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PermitNonces {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        8 +  // page
+        8 * 32 + // bitmap
+        1; // bump
+
+    /// Mark `nonce` used, failing if it already was
+    pub fn consume(&mut self, nonce: u64) -> Result<()> {
+        let bit = (nonce % PERMIT_NONCES_PER_PAGE) as usize;
+        let mask = 1u64 << (bit % 64);
+        let word = &mut self.bitmap[bit / 64];
+        require!(*word & mask == 0, ShadeError::PermitNonceUsed);
+        *word |= mask;
+        Ok(())
+    }
+}
+
+/// Compromised spender flag - blocks a key across all of an issuer's pools
+#[account]
+#[derive(Default)]
+pub struct CompromisedSpender {
+    /// Issuer that raised the flag
+    pub issuer: Pubkey,
+    /// The compromised spender key
+    pub spender: Pubkey,
+    /// When the flag was raised
+    pub flagged_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl CompromisedSpender {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // issuer
+        32 + // spender
+        8 +  // flagged_at
+        1; // bump
+}
+
+/// Denylist entry - presence blocks the key protocol-wide
+#[account]
+#[derive(Default)]
+pub struct DenylistEntry {
+    /// The denied key
+    pub account: Pubkey,
+    /// When the key was denied
+    pub added_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl DenylistEntry {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // account
+        8 +  // added_at
+        1; // bump
+}
+
+/// Vesting schedule - linear SHADE vesting with a cliff for one beneficiary
+#[account]
+#[derive(Default)]
+pub struct VestingSchedule {
+    /// Who receives the vested tokens
+    pub beneficiary: Pubkey,
+    /// Token vault holding the unclaimed allocation
+    pub vault: Pubkey,
+    /// Total allocation (reduced to the vested amount on revoke)
+    pub total_amount: u64,
+    /// Amount already claimed
+    pub claimed_amount: u64,
+    /// Vesting start
+    pub start_at: i64,
+    /// Nothing is claimable before the cliff
+    pub cliff_at: i64,
+    /// Everything is vested at this time
+    pub end_at: i64,
+    /// Whether the admin can revoke unvested tokens
+    pub revocable: bool,
+    /// When the schedule was revoked (0 = not revoked)
+    pub revoked_at: i64,
+    /// Per-beneficiary schedule id (PDA seed)
+    pub schedule_id: u64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Whether the beneficiary opted unvested tokens into tier calculation
+    pub counts_toward_tier: bool,
+    /// Unvested amount currently credited to the beneficiary's tier
+    pub tier_credit: u64,
+}
+
+impl VestingSchedule {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // beneficiary
+        32 + // vault
+        8 +  // total_amount
+        8 +  // claimed_amount
+        8 +  // start_at
+        8 +  // cliff_at
+        8 +  // end_at
+        1 +  // revocable
+        8 +  // revoked_at
+        8 +  // schedule_id
+        1 +  // bump
+        1 +  // counts_toward_tier
+        8; // tier_credit
+
+    /// Amount vested at `now`
+    /// After a revoke total_amount already equals what had vested, so the
+    /// schedule reports it as fully vested
+    pub fn vested_amount(&self, now: i64) -> u64 {
+        if self.revoked_at != 0 || now >= self.end_at {
+            return self.total_amount;
+        }
+        if now < self.cliff_at || now <= self.start_at {
+            return 0;
+        }
+        let elapsed = (now - self.start_at) as u128;
+        let duration = (self.end_at - self.start_at) as u128;
+        (self.total_amount as u128)
+            .saturating_mul(elapsed)
+            .checked_div(duration)
+            .unwrap_or(0) as u64
+    }
+}
+
+/// Airdrop - merkle distributor for SHADE
+#[account]
+#[derive(Default)]
+pub struct Airdrop {
+    /// Airdrop id (PDA seed)
+    pub airdrop_id: u64,
+    /// Merkle root of (index, claimant, amount) leaves
+    pub merkle_root: [u8; 32],
+    /// Vault holding the undistributed SHADE
+    pub vault: Pubkey,
+    /// Total SHADE funded
+    pub total_amount: u64,
+    /// Total SHADE claimed so far
+    pub claimed_amount: u64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Protocol instance this account belongs to (PDA seed)
+    pub protocol_config: Pubkey,
+    /// Claims are accepted until this time; the rest can then be swept
+    pub claim_deadline: i64,
+}
+
+impl Airdrop {
+    pub const LEN: usize = 8 +  // discriminator
+        8 +  // airdrop_id
+        32 + // merkle_root
+        32 + // vault
+        8 +  // total_amount
+        8 +  // claimed_amount
+        1 +  // bump
+        32 + // protocol_config
+        8; // claim_deadline
+}
+
+/// Airdrop claim receipt - its existence marks a leaf index as claimed
+#[account]
+#[derive(Default)]
+pub struct AirdropClaim {
+    /// Who claimed
+    pub claimant: Pubkey,
+    /// Amount claimed
+    pub amount: u64,
+    /// When it was claimed
+    pub claimed_at: i64,
+}
+
+impl AirdropClaim {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // claimant
+        8 +  // amount
+        8; // claimed_at
+}
+
+/// Treasury lock - treasury/founder tokens released in tranches after a cliff
+#[account]
+#[derive(Default)]
+pub struct TreasuryLock {
+    /// Lock id (PDA seed)
+    pub lock_id: u64,
+    /// Public label, e.g. "team" or "ecosystem" (max 32 chars)
+    pub label: String,
+    /// Owner of the token account tranches are released to
+    pub recipient: Pubkey,
+    /// Locked token mint
+    pub mint: Pubkey,
+    /// Token vault holding the locked tokens
+    pub vault: Pubkey,
+    /// Total amount locked
+    pub total_amount: u64,
+    /// Amount released so far
+    pub released_amount: u64,
+    /// First tranche unlocks at this time
+    pub cliff_at: i64,
+    /// Seconds between tranches
+    pub unlock_interval: i64,
+    /// Number of equal tranches
+    pub unlock_count: u16,
+    /// Tranches released so far
+    pub tranches_released: u16,
+    /// When the lock was created
+    pub created_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Protocol instance this account belongs to (PDA seed)
+    pub protocol_config: Pubkey,
+}
+
+impl TreasuryLock {
+    pub const LEN: usize = 8 +  // discriminator
+        8 +  // lock_id
+        4 + 32 + // label
+        32 + // recipient
+        32 + // mint
+        32 + // vault
+        8 +  // total_amount
+        8 +  // released_amount
+        8 +  // cliff_at
+        8 +  // unlock_interval
+        2 +  // unlock_count
+        2 +  // tranches_released
+        8 +  // created_at
+        1 +  // bump
+        32; // protocol_config
+
+    /// Number of tranches unlocked at `now`
+    pub fn unlocked_tranches(&self, now: i64) -> u16 {
+        if now < self.cliff_at {
+            return 0;
+        }
+        if self.unlock_interval == 0 {
+            return self.unlock_count;
+        }
+        let elapsed = ((now - self.cliff_at) / self.unlock_interval) as u64;
+        elapsed.saturating_add(1).min(self.unlock_count as u64) as u16
+    }
+
+    /// Cumulative amount unlocked once `tranches` tranches are open
+    /// The last tranche picks up the rounding remainder
+    pub fn unlocked_amount(&self, tranches: u16) -> u64 {
+        if tranches >= self.unlock_count {
+            return self.total_amount;
+        }
+        ((self.total_amount as u128) * (tranches as u128) / (self.unlock_count as u128)) as u64
+    }
+
+    /// When the next unreleased tranche unlocks (0 = fully released)
+    pub fn next_unlock_at(&self) -> i64 {
+        if self.tranches_released >= self.unlock_count {
+            return 0;
+        }
+        self.cliff_at.saturating_add(
+            self.unlock_interval
+                .saturating_mul(self.tranches_released as i64),
+        )
+    }
+}
+
+/// Whitelisted AMM program - protocol funds may only be routed through these
+#[account]
+#[derive(Default)]
+pub struct AmmProgram {
+    /// AMM program id
+    pub program_id: Pubkey,
+    /// When it was whitelisted
+    pub added_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl AmmProgram {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // program_id
+        8 +  // added_at
+        1; // bump
+}
+
+/// Treasury - PDA that owns protocol treasury token accounts and LP positions
+#[account]
+#[derive(Default)]
+pub struct Treasury {
+    /// Number of LP positions opened (next position id)
+    pub position_count: u64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Treasury {
+    pub const LEN: usize = 8 +  // discriminator
+        8 +  // position_count
+        1; // bump
+}
+
+/// Protocol-owned LP position - on-chain record of treasury liquidity in an AMM pool
+#[account]
+#[derive(Default)]
+pub struct PolPosition {
+    /// Position id (PDA seed)
+    pub position_id: u64,
+    /// Whitelisted AMM program holding the liquidity
+    pub amm_program: Pubkey,
+    /// AMM pool account
+    pub amm_pool: Pubkey,
+    /// Pool token A mint
+    pub mint_a: Pubkey,
+    /// Pool token B mint
+    pub mint_b: Pubkey,
+    /// Treasury-owned LP token account
+    pub lp_token_account: Pubkey,
+    /// LP tokens held after the last operation
+    pub lp_balance: u64,
+    /// Token A added to the pool
+    pub deposited_a: u64,
+    /// Token B added to the pool
+    pub deposited_b: u64,
+    /// Token A pulled back out
+    pub withdrawn_a: u64,
+    /// Token B pulled back out
+    pub withdrawn_b: u64,
+    /// Token A trading fees harvested
+    pub harvested_a: u64,
+    /// Token B trading fees harvested
+    pub harvested_b: u64,
+    /// When the position was opened
+    pub opened_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PolPosition {
+    pub const LEN: usize = 8 +  // discriminator
+        8 +  // position_id
+        32 + // amm_program
+        32 + // amm_pool
+        32 + // mint_a
+        32 + // mint_b
+        32 + // lp_token_account
+        8 +  // lp_balance
+        8 +  // deposited_a
+        8 +  // deposited_b
+        8 +  // withdrawn_a
+        8 +  // withdrawn_b
+        8 +  // harvested_a
+        8 +  // harvested_b
+        8 +  // opened_at
+        1; // bump
+}
+
+/// Bond pricing and capacity terms set by governance
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct BondTerms {
+    /// Reference price (pool-asset base units per whole SHADE)
+    pub base_price: u64,
+    /// Discount off base_price for the first bond of an epoch
+    pub discount_bps: u16,
+    /// Price increase (bps of base_price) once the epoch's capacity is sold
+    pub curve_bps: u16,
+    /// SHADE that can be sold per epoch
+    pub epoch_capacity: u64,
+}
+
+impl BondTerms {
+    pub const LEN: usize = 8 + // base_price
+        2 +  // discount_bps
+        2 +  // curve_bps
+        8; // epoch_capacity
+
+    pub fn is_valid(&self) -> bool {
+        self.base_price > 0 && self.discount_bps < 10_000 && self.epoch_capacity > 0
+    }
+}
+
+/// Bond market - sells vested, discounted SHADE for fog pool liquidity
+#[account]
+#[derive(Default)]
+pub struct BondMarket {
+    /// Market id (PDA seed)
+    pub market_id: u64,
+    /// Fog pool receiving the bonded liquidity
+    pub fog_pool: Pubkey,
+    /// Vault holding the SHADE inventory, owned by the market PDA
+    pub shade_vault: Pubkey,
+    /// Pricing and capacity terms
+    pub terms: BondTerms,
+    /// Seconds per capacity epoch
+    pub epoch_length: i64,
+    /// Start of the current epoch
+    pub epoch_start: i64,
+    /// SHADE sold in the current epoch
+    pub epoch_sold: u64,
+    /// Linear vesting duration of each bond
+    pub vesting_duration: i64,
+    /// Total SHADE sold
+    pub total_sold: u64,
+    /// Total pool-asset liquidity raised
+    pub total_raised: u64,
+    /// SHADE owed to bonds and not yet redeemed
+    pub outstanding: u64,
+    /// Number of bonds sold (next bond id)
+    pub bond_count: u64,
+    /// Whether the market is selling
+    pub is_active: bool,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Protocol instance this account belongs to (PDA seed)
+    pub protocol_config: Pubkey,
+    /// LP shares minted to the treasury for the raised liquidity
+    pub total_shares: u64,
+}
+
+impl BondMarket {
+    pub const LEN: usize = 8 +  // discriminator
+        8 +  // market_id
+        32 + // fog_pool
+        32 + // shade_vault
+        BondTerms::LEN + // terms
+        8 +  // epoch_length
+        8 +  // epoch_start
+        8 +  // epoch_sold
+        8 +  // vesting_duration
+        8 +  // total_sold
+        8 +  // total_raised
+        8 +  // outstanding
+        8 +  // bond_count
+        1 +  // is_active
+        1 +  // bump
+        32 + // protocol_config
+        8; // total_shares
+
+    /// Start a new capacity epoch if the current one has ended
+    pub fn roll_epoch(&mut self, now: i64) {
+        if now >= self.epoch_start.saturating_add(self.epoch_length) {
+            self.epoch_start = now - (now - self.epoch_start) % self.epoch_length;
+            self.epoch_sold = 0;
+        }
+    }
+
+    /// Current bond price: discounted base price, rising linearly with the
+    /// share of this epoch's capacity already sold
+    pub fn current_price(&self) -> u64 {
+        let terms = &self.terms;
+        let filled_bps = (terms.curve_bps as u128).saturating_mul(self.epoch_sold as u128)
+            / terms.epoch_capacity as u128;
+        let price_bps = 10_000u128 - terms.discount_bps as u128 + filled_bps;
+        ((terms.base_price as u128).saturating_mul(price_bps) / 10_000).max(1) as u64
+    }
+}
+
+/// Bond - SHADE owed to a buyer, vesting linearly
+#[account]
+#[derive(Default)]
+pub struct Bond {
+    /// Bond owner
+    pub owner: Pubkey,
+    /// Market the bond was bought from
+    pub market: Pubkey,
+    /// Per-market bond id (PDA seed)
+    pub bond_id: u64,
+    /// Total SHADE owed
+    pub payout: u64,
+    /// SHADE already redeemed
+    pub claimed: u64,
+    /// Purchase time (vesting start)
+    pub created_at: i64,
+    /// Fully vested at this time
+    pub vests_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Bond {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // owner
+        32 + // market
+        8 +  // bond_id
+        8 +  // payout
+        8 +  // claimed
+        8 +  // created_at
+        8 +  // vests_at
+        1; // bump
+
+    /// SHADE vested at `now`
+    pub fn vested_amount(&self, now: i64) -> u64 {
+        if now >= self.vests_at {
+            return self.payout;
+        }
+        if now <= self.created_at {
+            return 0;
+        }
+        let elapsed = (now - self.created_at) as u128;
+        let duration = (self.vests_at - self.created_at) as u128;
+        ((self.payout as u128).saturating_mul(elapsed) / duration) as u64
+    }
+}
+
+/// Reward stream emitted at a fixed rate and funded up front
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EmissionStream {
+    /// Tokens emitted per second across all stakers
+    pub rate: u64,
+    /// Accumulated tokens per staked token, scaled by REWARD_PRECISION
+    pub reward_per_share: u128,
+    /// Total tokens funded into the stream's vault
+    pub funded: u64,
+    /// Total tokens emitted to stakers so far
+    pub allocated: u64,
+}
+
+impl EmissionStream {
+    pub const LEN: usize = 8 + // rate
+        16 + // reward_per_share
+        8 +  // funded
+        8; // allocated
+
+    /// Emit `elapsed` seconds of rewards over `total_staked`
+    /// Emission stops when the funded tokens run out
+    pub fn accrue(&mut self, elapsed: u64, total_staked: u64) -> Result<()> {
+        let emitted = self
+            .rate
+            .saturating_mul(elapsed)
+            .min(self.funded.saturating_sub(self.allocated));
+        if emitted == 0 || total_staked == 0 {
+            return Ok(());
+        }
+        self.reward_per_share = self
+            .reward_per_share
+            .checked_add(
+                (emitted as u128)
+                    .checked_mul(REWARD_PRECISION)
+                    .ok_or(ShadeError::Overflow)?
+                    / total_staked as u128,
+            )
+            .ok_or(ShadeError::Overflow)?;
+        self.allocated += emitted;
+        Ok(())
+    }
+}
+
+/// Gauge - SHADE emissions (plus an optional operator bonus token) for stakers
+/// of one LP share token
+#[account]
+#[derive(Default)]
+pub struct Gauge {
+    /// Mint of the staked LP share token (PDA seed)
+    pub stake_mint: Pubkey,
+    /// Fog pool whose liquidity the share token represents
+    pub fog_pool: Pubkey,
+    /// Vault holding staked LP share tokens, owned by the gauge PDA
+    pub stake_vault: Pubkey,
+    /// Vault holding SHADE emissions, owned by the gauge PDA
+    pub reward_vault: Pubkey,
+    /// SHADE emissions (rate set by governance)
+    pub emissions: EmissionStream,
+    /// Bonus reward token mint (default = no bonus)
+    pub bonus_mint: Pubkey,
+    /// Vault holding the bonus token, owned by the gauge PDA
+    pub bonus_vault: Pubkey,
+    /// Bonus emissions (funded and rate set by the pool operator)
+    pub bonus: EmissionStream,
+    /// Last time emissions were accrued
+    pub last_update: i64,
+    /// Total LP share tokens staked
+    pub total_staked: u64,
+    /// PDA bump seed
+    pub bump: u8,
+    /// Protocol instance this account belongs to (PDA seed)
+    pub protocol_config: Pubkey,
+}
+
+impl Gauge {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // stake_mint
+        32 + // fog_pool
+        32 + // stake_vault
+        32 + // reward_vault
+        EmissionStream::LEN + // emissions
+        32 + // bonus_mint
+        32 + // bonus_vault
+        EmissionStream::LEN + // bonus
+        8 +  // last_update
+        8 +  // total_staked
+        1 +  // bump
+        32; // protocol_config
+
+    /// Accrue both streams for the time since the last update
+    /// Emissions pause while nothing is staked
+    pub fn accrue(&mut self, now: i64) -> Result<()> {
+        let elapsed = now.saturating_sub(self.last_update).max(0) as u64;
+        self.last_update = now;
+        if elapsed == 0 || self.total_staked == 0 {
+            return Ok(());
+        }
+        self.emissions.accrue(elapsed, self.total_staked)?;
+        self.bonus.accrue(elapsed, self.total_staked)
+    }
+}
+
+/// Gauge stake - one user's LP share tokens staked in a gauge
+#[account]
+#[derive(Default)]
+pub struct GaugeStake {
+    /// Staker
+    pub owner: Pubkey,
+    /// Gauge staked in
+    pub gauge: Pubkey,
+    /// LP share tokens staked
+    pub amount: u64,
+    /// SHADE already accounted for at the current stake
+    pub reward_debt: u128,
+    /// Settled SHADE waiting to be claimed
+    pub pending_rewards: u64,
+    /// Bonus tokens already accounted for at the current stake
+    pub bonus_debt: u128,
+    /// Settled bonus tokens waiting to be claimed
+    pub pending_bonus: u64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl GaugeStake {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // owner
+        32 + // gauge
+        8 +  // amount
+        16 + // reward_debt
+        8 +  // pending_rewards
+        16 + // bonus_debt
+        8 +  // pending_bonus
+        1; // bump
+
+    /// Rewards attributable to the current stake at `reward_per_share`
+    pub fn entitlement(&self, reward_per_share: u128) -> u128 {
+        (self.amount as u128).saturating_mul(reward_per_share) / REWARD_PRECISION
+    }
+
+    /// Move rewards accrued since the last settlement into the pending balances
+    pub fn settle(&mut self, gauge: &Gauge) -> Result<()> {
+        let entitlement = self.entitlement(gauge.emissions.reward_per_share);
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_add(entitlement.saturating_sub(self.reward_debt) as u64)
+            .ok_or(ShadeError::Overflow)?;
+        self.reward_debt = entitlement;
+
+        let bonus_entitlement = self.entitlement(gauge.bonus.reward_per_share);
+        self.pending_bonus = self
+            .pending_bonus
+            .checked_add(bonus_entitlement.saturating_sub(self.bonus_debt) as u64)
+            .ok_or(ShadeError::Overflow)?;
+        self.bonus_debt = bonus_entitlement;
+        Ok(())
+    }
+
+    /// Reset the debts after the stake changed
+    pub fn sync_debts(&mut self, gauge: &Gauge) {
+        self.reward_debt = self.entitlement(gauge.emissions.reward_per_share);
+        self.bonus_debt = self.entitlement(gauge.bonus.reward_per_share);
+    }
+}
+
+/// Head of a spender's authorization index - tracks the last page so new
+/// authorizations always land on a page with room
+#[account]
+#[derive(Default)]
+pub struct SpenderIndexHead {
+    /// The indexed spender
+    pub spender: Pubkey,
+    /// Last page of the index
+    pub tail_page: u32,
+    /// Entries on the last index page
+    pub tail_len: u32,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl SpenderIndexHead {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // spender
+        4 +  // tail_page
+        4 +  // tail_len
+        1; // bump
+
+    /// Index page the next authorization is appended to
+    pub fn next_page(&self) -> u32 {
+        if self.tail_len as usize >= SPENDER_INDEX_PAGE_SIZE {
+            self.tail_page + 1
+        } else {
+            self.tail_page
+        }
+    }
+}
+
+/// One page of a spender's authorization index - the active authorizations a
+/// key can spend from, so wallets can list their spending permissions by
+/// walking pages 0 to the head's tail_page
+#[account]
+#[derive(Default)]
+pub struct SpenderIndex {
+    /// The indexed spender
+    pub spender: Pubkey,
+    /// Page number (PDA seed)
+    pub page: u32,
+    /// Active authorizations on this page (max 32)
+    pub authorizations: Vec<Pubkey>,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl SpenderIndex {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // spender
+        4 +  // page
+        4 + 32 * SPENDER_INDEX_PAGE_SIZE + // authorizations
+        1; // bump
+
+    /// Drop an authorization from the page (no-op if it isn't listed)
+    pub fn remove(&mut self, authorization: &Pubkey) -> bool {
+        match self.authorizations.iter().position(|a| a == authorization) {
+            Some(i) => {
+                self.authorizations.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// One page of a pool's authorization index
+/// Pages are numbered from 0 up to the pool's index_tail_page; walking them in
+/// order lists every active authorization without a getProgramAccounts scan
+#[account]
+#[derive(Default)]
+pub struct PoolAuthorizationPage {
+    /// The indexed fog pool
+    pub fog_pool: Pubkey,
+    /// Page number (PDA seed)
+    pub page: u32,
+    /// Active authorizations on this page (max 32)
+    pub authorizations: Vec<Pubkey>,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PoolAuthorizationPage {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // fog_pool
+        4 +  // page
+        4 + 32 * POOL_INDEX_PAGE_SIZE + // authorizations
+        1; // bump
+
+    /// Drop an authorization from the page (no-op if it isn't listed)
+    pub fn remove(&mut self, authorization: &Pubkey) -> bool {
+        match self.authorizations.iter().position(|a| a == authorization) {
+            Some(i) => {
+                self.authorizations.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Page of an instance's pool registry - every fog pool in creation order
+/// Pages are numbered from 0 up to the instance's next_pool_page(), so clients
+/// can list all pools without a getProgramAccounts scan
+#[account]
+#[derive(Default)]
+pub struct PoolRegistryPage {
+    /// The protocol instance the pools belong to
+    pub protocol_config: Pubkey,
+    /// Page number (PDA seed)
+    pub page: u32,
+    /// Pools on this page (max 64)
+    pub pools: Vec<Pubkey>,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PoolRegistryPage {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // protocol_config
+        4 +  // page
+        4 + 32 * POOL_REGISTRY_PAGE_SIZE + // pools
+        1; // bump
+}
+
+/// Cross-pool agreement - the creditor pool fronts spends for the debtor pool
+/// and the resulting debt is tracked until settled
+#[account]
+#[derive(Default)]
+pub struct PoolAgreement {
+    /// Pool whose authorizations are routed (owes the debt)
+    pub debtor_pool: Pubkey,
+    /// Pool that pays the routed spends (is owed the debt)
+    pub creditor_pool: Pubkey,
+    /// Maximum outstanding debt
+    pub credit_limit: u64,
+    /// Routed amount not yet settled
+    pub outstanding: u64,
+    /// Total amount routed
+    pub total_routed: u64,
+    /// Total amount settled
+    pub total_settled: u64,
+    /// When the agreement was created
+    pub created_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl PoolAgreement {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // debtor_pool
+        32 + // creditor_pool
+        8 +  // credit_limit
+        8 +  // outstanding
+        8 +  // total_routed
+        8 +  // total_settled
+        8 +  // created_at
+        1; // bump
+}
+
+/// A member pool and its net settlement position within a federation
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct FederationMember {
+    /// Member fog pool
+    pub fog_pool: Pubkey,
+    /// Paid for other members minus paid by other members (negative = owes)
+    pub net_position: i64,
+}
+
+impl FederationMember {
+    pub const LEN: usize = 32 + // fog_pool
+        8; // net_position
+}
+
+/// Federation - a group of same-mint pools that honor each other's authorizations
+#[account]
+#[derive(Default)]
+pub struct Federation {
+    /// Authority who admits members and sets the debit limit
+    pub authority: Pubkey,
+    /// Pool that founded the federation (PDA seed; cannot leave)
+    pub founding_pool: Pubkey,
+    /// Token mint every member pool holds
+    pub mint: Pubkey,
+    /// Maximum amount any member may owe the others
+    pub debit_limit: u64,
+    /// Member pools (max 16)
+    pub members: Vec<FederationMember>,
+    /// Total amount spent across pools
+    pub total_routed: u64,
+    /// Total amount settled between pools
+    pub total_settled: u64,
+    /// When the federation was created
+    pub created_at: i64,
+    /// PDA bump seed
+    pub bump: u8,
+}
+
+impl Federation {
+    pub const LEN: usize = 8 +  // discriminator
+        32 + // authority
+        32 + // founding_pool
+        32 + // mint
+        8 +  // debit_limit
+        4 + FederationMember::LEN * MAX_FEDERATION_MEMBERS + // members
+        8 +  // total_routed
+        8 +  // total_settled
+        8 +  // created_at
+        1; // bump
+
+    /// Index of a pool in the member list, if it is a member
+    pub fn position(&self, fog_pool: &Pubkey) -> Option<usize> {
+        self.members.iter().position(|m| &m.fog_pool == fog_pool)
+    }
+
+    /// Index of a pool that must be a member
+    pub fn member_index(&self, fog_pool: &Pubkey) -> Result<usize> {
+        self.position(fog_pool)
+            .ok_or_else(|| error!(ShadeError::NotFederationMember))
+    }
+}
+
+// ============================================================================
+// Context Structures (Account Validation)
+// ============================================================================
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(instance_seed: [u8; 32])]
+pub struct InitializeProtocol<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = ProtocolConfig::LEN,
+        seeds = [b"protocol_config", instance_config_seed(&instance_seed)],
+        bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// The $SHADE token mint
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA that owns the fee and staking vaults; holds no data
+    #[account(seeds = [b"vault_authority", protocol_config.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// Fee vault (token account for collected fees)
+    #[account(
+        mut,
+        constraint = fee_vault.owner == vault_authority.key() @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Staking vault (token account for staked $SHADE)
+    #[account(
+        mut,
+        constraint = staking_vault.owner == vault_authority.key() @ ShadeError::InvalidVaultAuthority
+    )]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateProtocol<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetEarlyExitPenalty<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Checked to hold $SHADE before a penalty is set
+    #[account(address = protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority)]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetFeeSplit<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Treasury PDA address; only used to check the fee treasury owner
+    #[account(seeds = [b"treasury", protocol_config.key().as_ref()], bump)]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// New fee treasury (keeps the current one if omitted)
+    #[account(
+        constraint = fee_treasury.owner == treasury.key() @ ShadeError::InvalidVaultAuthority,
+        constraint = fee_treasury.mint == fee_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub fee_treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(address = protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority)]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.pending_authority != Pubkey::default()
+            && protocol_config.pending_authority == new_authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub new_authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetTierSource<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Instance whose staker tiers are recognized; omit to stop recognizing
+    #[account(
+        seeds = [b"protocol_config", source_config.config_seed()],
+        bump = source_config.bump
+    )]
+    pub source_config: Option<Account<'info, ProtocolConfig>>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = Staker::LEN,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        constraint = staking_vault.key() == protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority,
+        constraint = staking_vault.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_shade_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub user_shade_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(user: Pubkey)]
+pub struct StakeFor<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// The beneficiary's staker position
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = Staker::LEN,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.as_ref()],
+        bump
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        constraint = staking_vault.key() == protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority,
+        constraint = staking_vault.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = funder_shade_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub funder_shade_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub funder: Signer<'info>,
+
+    /// Pays rent for a new staker position; may be the funder
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RequestUnstake<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    pub user: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RefreshStakeLock<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), staker.user.as_ref()],
+        bump = staker.bump
+    )]
+    pub staker: Account<'info, Staker>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct FundStakingEmissions<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// SHADE backing staking emissions, owned by the vault authority PDA
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"emissions_vault", protocol_config.key().as_ref()],
+        bump,
+        token::mint = shade_mint,
+        token::authority = vault_authority
+    )]
+    pub emissions_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = authority_shade_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub authority_shade_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimStakingEmissions<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        seeds = [b"emissions_vault", protocol_config.key().as_ref()],
+        bump
+    )]
+    pub emissions_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_shade_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub user_shade_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CompleteUnstake<'info> {
+    /// Written by complete_unstake_early to book the penalty
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        constraint = staking_vault.key() == protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority,
+        constraint = staking_vault.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_shade_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub user_shade_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives complete_unstake_early's penalty, required when one is charged
+    #[account(
+        mut,
+        address = protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub user: Signer<'info>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UnlockStakeEarly<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        constraint = staking_vault.key() == protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority,
+        constraint = staking_vault.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the penalty
+    #[account(
+        mut,
+        address = protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        constraint = fee_vault.key() == protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fee_vault.mint @ ShadeError::InvalidMint)]
+    pub fee_mint: InterfaceAccount<'info, Mint>,
+
+    /// User's token account to receive rewards (must match fee vault mint)
+    #[account(
+        mut,
+        constraint = user_token_account.mint == fee_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetClaimDelegate<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(address = protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority)]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Where delegated claims are paid; must be the staker's own fee-token account
+    #[account(
+        constraint = reward_destination.owner == user.key() @ ShadeError::Unauthorized,
+        constraint = reward_destination.mint == fee_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub reward_destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetTierDelegate<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    /// Wallet accepting the tier; signs so its delegation slot can't be squatted
+    pub delegate: Signer<'info>,
+
+    #[account(
+        init,
+        payer = user,
+        space = TierDelegation::LEN,
+        seeds = [b"tier_delegation", protocol_config.key().as_ref(), delegate.key().as_ref()],
+        bump
+    )]
+    pub tier_delegation: Account<'info, TierDelegation>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RevokeTierDelegate<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump = staker.bump,
+        constraint = staker.user == user.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"tier_delegation", protocol_config.key().as_ref(), staker.tier_delegate.as_ref()],
+        bump = tier_delegation.bump
+    )]
+    pub tier_delegation: Account<'info, TierDelegation>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimRewardsAsDelegate<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), staker.user.as_ref()],
+        bump = staker.bump,
+        constraint = staker.claim_delegate != Pubkey::default()
+            && staker.claim_delegate == delegate.key() @ ShadeError::Unauthorized
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        constraint = fee_vault.key() == protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fee_vault.mint @ ShadeError::InvalidMint)]
+    pub fee_mint: InterfaceAccount<'info, Mint>,
+
+    /// The staker's registered reward destination
+    #[account(
+        mut,
+        address = staker.reward_destination,
+        constraint = reward_destination.owner == staker.user @ ShadeError::Unauthorized
+    )]
+    pub reward_destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub delegate: Signer<'info>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(pool_seed: [u8; 32])]
+pub struct InitializeFogPool<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = FogPool::LEN,
+        seeds = [b"fog_pool", pool_seed.as_ref()],
+        bump
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Pool registry page with room for this pool
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = PoolRegistryPage::LEN,
+        seeds = [
+            b"pool_registry",
+            protocol_config.key().as_ref(),
+            &protocol_config.next_pool_page().to_le_bytes()
+        ],
+        bump
+    )]
+    pub pool_registry_page: Account<'info, PoolRegistryPage>,
+
+    /// CHECK: PDA that owns the vault and LP mint; holds no data
+    #[account(seeds = [b"pool_vault_authority", fog_pool.key().as_ref()], bump)]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    /// Token the pool holds
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Vault token account - the pool vault authority's associated token account
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = pool_vault_authority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// LP share-token mint, minted by the pool vault authority
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"lp_mint", fog_pool.key().as_ref()],
+        bump,
+        mint::decimals = mint.decimals,
+        mint::authority = pool_vault_authority
+    )]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct DepositToFog<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority,
+        constraint = vault.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Depositor's token account (must match the pool mint)
+    #[account(
+        mut,
+        constraint = depositor_token_account.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub depositor_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = fog_pool.lp_mint @ ShadeError::InvalidMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the minted LP share tokens
+    #[account(
+        mut,
+        constraint = depositor_lp_account.mint == lp_mint.key() @ ShadeError::InvalidMint
+    )]
+    pub depositor_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Depositor's LP position in this pool
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        space = LpPosition::LEN,
+        seeds = [b"lp_position", fog_pool.key().as_ref(), depositor.key().as_ref()],
+        bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    /// Required when the pool only takes allowlisted depositors
+    #[account(
+        seeds = [b"depositor_allowlist", fog_pool.key().as_ref(), depositor.key().as_ref()],
+        bump = depositor_allowlist_entry.bump
+    )]
+    pub depositor_allowlist_entry: Option<Account<'info, AllowedDepositor>>,
+
+    /// CHECK: Protocol denylist entry for the depositor; must not exist
+    #[account(seeds = [b"denylist", protocol_config.key().as_ref(), depositor.key().as_ref()], bump)]
+    pub depositor_denylist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: Signs for the pool's vault and LP mint; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct WithdrawFromFog<'info> {
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority,
+        constraint = vault.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut, address = fog_pool.lp_mint @ ShadeError::InvalidMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    /// LP share tokens to burn
+    #[account(
+        mut,
+        constraint = owner_lp_account.mint == lp_mint.key() @ ShadeError::InvalidMint
+    )]
+    pub owner_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = LpPosition::LEN,
+        seeds = [b"lp_position", fog_pool.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub lp_position: Account<'info, LpPosition>,
+
+    /// Owner's token account (must match the pool mint)
+    #[account(
+        mut,
+        constraint = owner_token_account.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub owner_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// CHECK: Signs for the pool's vault and LP mint; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Pool-authority-only update of fog pool settings
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateFogPool<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetPoolFee<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// Token account receiving the owner's cut (must match the pool mint)
+    #[account(constraint = pool_fee_vault.mint == fog_pool.mint @ ShadeError::InvalidMint)]
+    pub pool_fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AcceptPoolAuthority<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.pending_authority != Pubkey::default()
+            && fog_pool.pending_authority == new_authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    pub new_authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SweepFees<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = fee_vault.key() == protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority,
+        constraint = fee_vault.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Pool owner's fee vault, required when the pool takes a fee cut
+    #[account(
+        mut,
+        address = fog_pool.pool_fee_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub pool_fee_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Fee treasury, required when the fee split has a treasury share
+    #[account(
+        mut,
+        address = protocol_config.fee_treasury @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Keeper's token account for its cut (must match the pool mint)
+    #[account(
+        mut,
+        constraint = keeper_token_account.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Signs for the pool's vault and LP mint; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SyncPoolConfig<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(capacity: u32)]
+pub struct InitSpendRing<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized,
+        constraint = fog_pool.spend_ring == Pubkey::default() @ ShadeError::InvalidSpendRing
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = SpendRing::space(capacity),
+        seeds = [b"spend_ring", fog_pool.key().as_ref()],
+        bump
+    )]
+    pub spend_ring: AccountLoader<'info, SpendRing>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(capacity: u32)]
+pub struct ResizeSpendRing<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        address = fog_pool.spend_ring @ ShadeError::InvalidSpendRing,
+        realloc = SpendRing::space(capacity),
+        realloc::payer = authority,
+        realloc::zero = true
+    )]
+    pub spend_ring: AccountLoader<'info, SpendRing>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseSpendRing<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        address = fog_pool.spend_ring @ ShadeError::InvalidSpendRing
+    )]
+    pub spend_ring: AccountLoader<'info, SpendRing>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+/// Pause by the pool authority or its operator
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct PauseFogPool<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key()
+            || fog_pool.operator == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// Pool authority or operator
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(operator: Pubkey)]
+pub struct AddIssuer<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = IssuerDelegate::LEN,
+        seeds = [b"issuer_delegate", fog_pool.key().as_ref(), operator.as_ref()],
+        bump
+    )]
+    pub issuer_delegate: Account<'info, IssuerDelegate>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RemoveIssuer<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"issuer_delegate",
+            fog_pool.key().as_ref(),
+            issuer_delegate.operator.as_ref()
+        ],
+        bump = issuer_delegate.bump
+    )]
+    pub issuer_delegate: Account<'info, IssuerDelegate>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetIssuerTemplatesOnly<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"issuer_delegate",
+            fog_pool.key().as_ref(),
+            issuer_delegate.operator.as_ref()
+        ],
+        bump = issuer_delegate.bump
+    )]
+    pub issuer_delegate: Account<'info, IssuerDelegate>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey)]
+pub struct RegisterProgram<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = RegisteredProgram::LEN,
+        seeds = [b"registered_program", fog_pool.key().as_ref(), program_id.as_ref()],
+        bump
+    )]
+    pub registered_program: Account<'info, RegisteredProgram>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UnregisterProgram<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"registered_program",
+            fog_pool.key().as_ref(),
+            registered_program.program_id.as_ref()
+        ],
+        bump = registered_program.bump
+    )]
+    pub registered_program: Account<'info, RegisteredProgram>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(depositor: Pubkey)]
+pub struct AddAllowedDepositor<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AllowedDepositor::LEN,
+        seeds = [b"depositor_allowlist", fog_pool.key().as_ref(), depositor.as_ref()],
+        bump
+    )]
+    pub allowed_depositor: Account<'info, AllowedDepositor>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RemoveAllowedDepositor<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"depositor_allowlist",
+            fog_pool.key().as_ref(),
+            allowed_depositor.depositor.as_ref()
+        ],
+        bump = allowed_depositor.bump
+    )]
+    pub allowed_depositor: Account<'info, AllowedDepositor>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RegisterProgramSpender<'info> {
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        seeds = [
+            b"registered_program",
+            fog_pool.key().as_ref(),
+            registered_program.program_id.as_ref()
+        ],
+        bump = registered_program.bump
+    )]
+    pub registered_program: Account<'info, RegisteredProgram>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = ProgramSpender::LEN,
+        seeds = [b"program_spender", fog_pool.key().as_ref(), spender.key().as_ref()],
+        bump
+    )]
+    pub program_spender: Account<'info, ProgramSpender>,
+
+    /// CHECK: The PDA spender; checked against the seeds in the handler
+    pub spender: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ResumeFogPool<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    pub authority: Signer<'info>,
+
+    /// Pool guardian co-signing an immediate resume
+    pub guardian: Option<Signer<'info>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetPoolGuardian<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    pub authority: Signer<'info>,
+
+    /// Existing guardian, required when replacing one
+    pub current_guardian: Option<Signer<'info>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CreateAuthorization<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = Authorization::LEN,
+        seeds = [
+            b"authorization",
+            fog_pool.key().as_ref(),
+            spender.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Spender's staker account for tier validation; omitted counts as tier 0
+    #[account(
+        seeds = [b"staker", protocol_config.key().as_ref(), spender.key().as_ref()],
+        bump
+    )]
+    pub staker: Option<Account<'info, Staker>>,
+
+    /// Spender's staker account in the tier-source instance; omitted counts as tier 0
+    #[account(
+        seeds = [b"staker", protocol_config.tier_source.as_ref(), spender.key().as_ref()],
+        bump,
+        constraint = protocol_config.tier_source != Pubkey::default() @ ShadeError::InvalidTierSource
+    )]
+    pub source_staker: Option<Account<'info, Staker>>,
+
+    /// Another staker's tier delegated to the spender
+    #[account(
+        seeds = [b"tier_delegation", protocol_config.key().as_ref(), spender.key().as_ref()],
+        bump = tier_delegation.bump
+    )]
+    pub tier_delegation: Option<Account<'info, TierDelegation>>,
+
+    /// Staker position of the tier delegator, passed with tier_delegation
+    #[account(
+        seeds = [b"staker", protocol_config.key().as_ref(), delegator_staker.user.as_ref()],
+        bump = delegator_staker.bump
+    )]
+    pub delegator_staker: Option<Account<'info, Staker>>,
+
+    /// CHECK: Can be any account that will receive the authorization
+    pub spender: AccountInfo<'info>,
+
+    /// CHECK: Protocol denylist entry for the spender; must not exist
+    #[account(seeds = [b"denylist", protocol_config.key().as_ref(), spender.key().as_ref()], bump)]
+    pub spender_denylist_entry: UncheckedAccount<'info>,
+
+    /// Head of the spender's authorization index
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = SpenderIndexHead::LEN,
+        seeds = [b"spender_index_head", spender.key().as_ref()],
+        bump
+    )]
+    pub spender_index_head: Account<'info, SpenderIndexHead>,
+
+    /// Spender authorization index page with room for this authorization
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = SpenderIndex::LEN,
+        seeds = [
+            b"spender_index",
+            spender.key().as_ref(),
+            &spender_index_head.next_page().to_le_bytes()
+        ],
+        bump
+    )]
+    pub spender_index: Account<'info, SpenderIndex>,
+
+    /// Pool authorization index page with room for this authorization
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = PoolAuthorizationPage::LEN,
+        seeds = [
+            b"pool_auth_page",
+            fog_pool.key().as_ref(),
+            &fog_pool.next_index_page().to_le_bytes()
+        ],
+        bump
+    )]
+    pub pool_index_page: Account<'info, PoolAuthorizationPage>,
+
+    /// Required when the issuer is not the pool authority
+    #[account(
+        seeds = [b"issuer_delegate", fog_pool.key().as_ref(), issuer.key().as_ref()],
+        bump = issuer_delegate.bump
+    )]
+    pub issuer_delegate: Option<Account<'info, IssuerDelegate>>,
+
+    /// Required when the spender is a PDA
+    #[account(
+        seeds = [b"program_spender", fog_pool.key().as_ref(), spender.key().as_ref()],
+        bump = program_spender.bump
+    )]
+    pub program_spender: Option<Account<'info, ProgramSpender>>,
+
+    /// Registry entry for the PDA spender's program
+    #[account(
+        seeds = [
+            b"registered_program",
+            fog_pool.key().as_ref(),
+            registered_program.program_id.as_ref()
+        ],
+        bump = registered_program.bump
+    )]
+    pub registered_program: Option<Account<'info, RegisteredProgram>>,
+
+    /// CHECK: SAS attestation of the spender; required by attestation-gated
+    /// pools and checked in check_attestation
+    pub spender_attestation: Option<UncheckedAccount<'info>>,
+
+    pub issuer: Signer<'info>,
+
+    /// Pays rent for the authorization and any new index page; may be the
+    /// issuer or a third party
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(campaign_id: u64)]
+pub struct CreateAuthorizationCampaign<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AuthorizationCampaign::LEN,
+        seeds = [b"auth_campaign", fog_pool.key().as_ref(), &campaign_id.to_le_bytes()],
+        bump
+    )]
+    pub campaign: Account<'info, AuthorizationCampaign>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(nonce: u64, index: u64)]
+pub struct ClaimAuthorization<'info> {
+    /// `issuer` is the claimant; the campaign issuer is recorded instead
+    pub create: CreateAuthorization<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"auth_campaign",
+            campaign.fog_pool.as_ref(),
+            &campaign.campaign_id.to_le_bytes()
+        ],
+        bump = campaign.bump,
+        constraint = campaign.fog_pool == create.fog_pool.key() @ ShadeError::InvalidAuthorizationCampaign
+    )]
+    pub campaign: Account<'info, AuthorizationCampaign>,
+
+    #[account(
+        init,
+        payer = create.payer,
+        space = CampaignClaim::LEN,
+        seeds = [b"campaign_claim", campaign.key().as_ref(), &index.to_le_bytes()],
+        bump
+    )]
+    pub claim_receipt: Account<'info, CampaignClaim>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseAuthorizationCampaign<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        constraint = campaign.fog_pool == fog_pool.key() @ ShadeError::InvalidAuthorizationCampaign
+    )]
+    pub campaign: Account<'info, AuthorizationCampaign>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(template_id: u64)]
+pub struct CreateAuthorizationTemplate<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AuthorizationTemplate::LEN,
+        seeds = [b"auth_template", fog_pool.key().as_ref(), &template_id.to_le_bytes()],
+        bump
+    )]
+    pub template: Account<'info, AuthorizationTemplate>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseAuthorizationTemplate<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        constraint = template.fog_pool == fog_pool.key() @ ShadeError::InvalidAuthorizationTemplate
+    )]
+    pub template: Account<'info, AuthorizationTemplate>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct IssueFromTemplate<'info> {
+    pub create: CreateAuthorization<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"auth_template",
+            template.fog_pool.as_ref(),
+            &template.template_id.to_le_bytes()
+        ],
+        bump = template.bump,
+        constraint = template.fog_pool == create.fog_pool.key() @ ShadeError::InvalidAuthorizationTemplate
+    )]
+    pub template: Account<'info, AuthorizationTemplate>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct Spend<'info> {
+    #[account(
+        mut,
+        constraint = authorization.authorized_spender == spender.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// Carries the cached fee settings, so spends never lock the config
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key()
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// Read-only, for the instance's pause flag
+    #[account(address = fog_pool.protocol_config @ ShadeError::WrongProtocolInstance)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority,
+        constraint = vault.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Recipient's token account (must match the pool mint)
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Wallet, or a registered program's PDA signing via invoke_signed
+    pub spender: Signer<'info>,
+
+    /// Spender's staker position; earns its tier's fee rebate when passed
+    #[account(
+        seeds = [b"staker", fog_pool.protocol_config.as_ref(), spender.key().as_ref()],
+        bump = spender_staker.bump
+    )]
+    pub spender_staker: Option<Account<'info, Staker>>,
+
+    /// Another staker's tier delegated to the spender
+    #[account(
+        seeds = [b"tier_delegation", fog_pool.protocol_config.as_ref(), spender.key().as_ref()],
+        bump = tier_delegation.bump
+    )]
+    pub tier_delegation: Option<Account<'info, TierDelegation>>,
+
+    /// Staker position of the tier delegator, passed with tier_delegation
+    #[account(
+        seeds = [b"staker", fog_pool.protocol_config.as_ref(), delegator_staker.user.as_ref()],
+        bump = delegator_staker.bump
+    )]
+    pub delegator_staker: Option<Account<'info, Staker>>,
+
+    /// CHECK: Issuer's compromised flag for this spender; must not exist
+    #[account(
+        seeds = [
+            b"compromised_spender",
+            authorization.issuer.as_ref(),
+            spender.key().as_ref()
+        ],
+        bump
+    )]
+    pub compromised_spender: UncheckedAccount<'info>,
+
+    /// CHECK: Protocol denylist entry for the spender; must not exist
+    #[account(seeds = [b"denylist", fog_pool.protocol_config.as_ref(), spender.key().as_ref()], bump)]
+    pub spender_denylist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: Protocol denylist entry for the recipient's owner; must not exist
+    #[account(
+        seeds = [
+            b"denylist",
+            fog_pool.protocol_config.as_ref(),
+            recipient_token_account.owner.as_ref()
+        ],
+        bump
+    )]
+    pub recipient_denylist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: Signs for the pool's vault and LP mint; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// SPL Memo program; when passed, the spend's reference is also written as a memo
+    pub memo_program: Option<Program<'info, Memo>>,
+
+    /// The pool's spend ring; required once the pool has one
+    #[account(mut)]
+    pub spend_ring: Option<AccountLoader<'info, SpendRing>>,
+
+    /// Budget for the authorization's category; required when the pool budgets it
+    #[account(mut)]
+    pub category_budget: Option<Account<'info, CategoryBudget>>,
+
+    /// CHECK: Pyth price update for the pool's feed; required for USD-cap
+    /// authorizations and checked in read_pyth_price
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: SAS attestation of the spender; required by attestation-gated
+    /// pools and checked in check_attestation
+    pub spender_attestation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: SAS attestation of the recipient's owner; required by
+    /// recipient-gated pools and checked in check_attestation
+    pub recipient_attestation: Option<UncheckedAccount<'info>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SpendBatch<'info> {
+    #[account(
+        mut,
+        constraint = authorization.authorized_spender == spender.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// Carries the cached fee settings, so spends never lock the config
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key()
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// Read-only, for the instance's pause flag
+    #[account(address = fog_pool.protocol_config @ ShadeError::WrongProtocolInstance)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority,
+        constraint = vault.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub spender: Signer<'info>,
+
+    /// Spender's staker position; earns its tier's fee rebate when passed
+    #[account(
+        seeds = [b"staker", fog_pool.protocol_config.as_ref(), spender.key().as_ref()],
+        bump = spender_staker.bump
+    )]
+    pub spender_staker: Option<Account<'info, Staker>>,
+
+    /// Another staker's tier delegated to the spender
+    #[account(
+        seeds = [b"tier_delegation", fog_pool.protocol_config.as_ref(), spender.key().as_ref()],
+        bump = tier_delegation.bump
+    )]
+    pub tier_delegation: Option<Account<'info, TierDelegation>>,
+
+    /// Staker position of the tier delegator, passed with tier_delegation
+    #[account(
+        seeds = [b"staker", fog_pool.protocol_config.as_ref(), delegator_staker.user.as_ref()],
+        bump = delegator_staker.bump
+    )]
+    pub delegator_staker: Option<Account<'info, Staker>>,
+
+    /// CHECK: Issuer's compromised flag for this spender; must not exist
+    #[account(
+        seeds = [
+            b"compromised_spender",
+            authorization.issuer.as_ref(),
+            spender.key().as_ref()
+        ],
+        bump
+    )]
+    pub compromised_spender: UncheckedAccount<'info>,
+
+    /// CHECK: Protocol denylist entry for the spender; must not exist
+    #[account(seeds = [b"denylist", fog_pool.protocol_config.as_ref(), spender.key().as_ref()], bump)]
+    pub spender_denylist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: Signs for the pool's vault and LP mint; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// The pool's spend ring; required once the pool has one
+    #[account(mut)]
+    pub spend_ring: Option<AccountLoader<'info, SpendRing>>,
+
+    /// Budget for the authorization's category; required when the pool budgets it
+    #[account(mut)]
+    pub category_budget: Option<Account<'info, CategoryBudget>>,
+
+    /// CHECK: SAS attestation of the spender; required by attestation-gated
+    /// pools and checked in check_attestation
+    pub spender_attestation: Option<UncheckedAccount<'info>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RevokeAuthorization<'info> {
+    #[account(mut)]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key(),
+        constraint = authorization.issuer == issuer.key()
+            || fog_pool.operator == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"spender_index_head", authorization.authorized_spender.as_ref()],
+        bump = spender_index_head.bump
+    )]
+    pub spender_index_head: Account<'info, SpenderIndexHead>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"spender_index",
+            authorization.authorized_spender.as_ref(),
+            &authorization.spender_index_page.to_le_bytes()
+        ],
+        bump = spender_index.bump
+    )]
+    pub spender_index: Account<'info, SpenderIndex>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool_auth_page",
+            fog_pool.key().as_ref(),
+            &authorization.index_page.to_le_bytes()
+        ],
+        bump = pool_index_page.bump
+    )]
+    pub pool_index_page: Account<'info, PoolAuthorizationPage>,
+
+    /// Authorization issuer or the pool operator
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(spender: Pubkey)]
+pub struct RevokeAllForSpender<'info> {
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"spender_index_head", spender.as_ref()],
+        bump = spender_index_head.bump
+    )]
+    pub spender_index_head: Account<'info, SpenderIndexHead>,
+
+    /// Issuer of every passed authorization, or the pool operator
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseAuthorization<'info> {
+    #[account(
+        mut,
+        close = issuer
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key()
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"spender_index_head", authorization.authorized_spender.as_ref()],
+        bump = spender_index_head.bump
+    )]
+    pub spender_index_head: Account<'info, SpenderIndexHead>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"spender_index",
+            authorization.authorized_spender.as_ref(),
+            &authorization.spender_index_page.to_le_bytes()
+        ],
+        bump = spender_index.bump
+    )]
+    pub spender_index: Account<'info, SpenderIndex>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool_auth_page",
+            fog_pool.key().as_ref(),
+            &authorization.index_page.to_le_bytes()
+        ],
+        bump = pool_index_page.bump
+    )]
+    pub pool_index_page: Account<'info, PoolAuthorizationPage>,
+
+    /// Approval policy to close alongside, required if one is attached
+    #[account(
+        mut,
+        close = issuer,
+        seeds = [b"approval_policy", authorization.key().as_ref()],
+        bump = approval_policy.bump
+    )]
+    pub approval_policy: Option<Account<'info, ApprovalPolicy>>,
+
+    /// CHECK: Issuer of the authorization, receives the reclaimed rent
+    #[account(
+        mut,
+        address = authorization.issuer @ ShadeError::Unauthorized
+    )]
+    pub issuer: UncheckedAccount<'info>,
+
+    pub closer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ExpireAuthorization<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key(),
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"spender_index_head", authorization.authorized_spender.as_ref()],
+        bump = spender_index_head.bump
+    )]
+    pub spender_index_head: Account<'info, SpenderIndexHead>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"spender_index",
+            authorization.authorized_spender.as_ref(),
+            &authorization.spender_index_page.to_le_bytes()
+        ],
+        bump = spender_index.bump
+    )]
+    pub spender_index: Account<'info, SpenderIndex>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool_auth_page",
+            fog_pool.key().as_ref(),
+            &authorization.index_page.to_le_bytes()
+        ],
+        bump = pool_index_page.bump
+    )]
+    pub pool_index_page: Account<'info, PoolAuthorizationPage>,
+
+    #[account(
+        mut,
+        constraint = fee_vault.key() == protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fee_vault.mint @ ShadeError::InvalidMint)]
+    pub fee_mint: InterfaceAccount<'info, Mint>,
+
+    /// Keeper's token account for the reward (must match fee vault mint)
+    #[account(
+        mut,
+        constraint = keeper_token_account.mint == fee_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub keeper: Signer<'info>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AmendAuthorization<'info> {
+    #[account(
+        mut,
+        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key(),
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Spender's staker account for tier validation of a raised cap;
+    /// omitted counts as tier 0
+    #[account(
+        seeds = [
+            b"staker",
+            protocol_config.key().as_ref(),
+            authorization.authorized_spender.as_ref()
+        ],
+        bump
+    )]
+    pub staker: Option<Account<'info, Staker>>,
+
+    /// Spender's staker account in the tier-source instance; omitted counts as tier 0
+    #[account(
+        seeds = [
+            b"staker",
+            protocol_config.tier_source.as_ref(),
+            authorization.authorized_spender.as_ref()
+        ],
+        bump,
+        constraint = protocol_config.tier_source != Pubkey::default() @ ShadeError::InvalidTierSource
+    )]
+    pub source_staker: Option<Account<'info, Staker>>,
+
+    /// Another staker's tier delegated to the spender
+    #[account(
+        seeds = [b"tier_delegation", protocol_config.key().as_ref(), authorization.authorized_spender.as_ref()],
+        bump = tier_delegation.bump
+    )]
+    pub tier_delegation: Option<Account<'info, TierDelegation>>,
+
+    /// Staker position of the tier delegator, passed with tier_delegation
+    #[account(
+        seeds = [b"staker", protocol_config.key().as_ref(), delegator_staker.user.as_ref()],
+        bump = delegator_staker.bump
+    )]
+    pub delegator_staker: Option<Account<'info, Staker>>,
+
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ReclaimAuthorizationBudget<'info> {
+    #[account(
+        mut,
+        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key()
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        seeds = [b"spender_index_head", authorization.authorized_spender.as_ref()],
+        bump = spender_index_head.bump
+    )]
+    pub spender_index_head: Account<'info, SpenderIndexHead>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"spender_index",
+            authorization.authorized_spender.as_ref(),
+            &authorization.spender_index_page.to_le_bytes()
+        ],
+        bump = spender_index.bump
+    )]
+    pub spender_index: Account<'info, SpenderIndex>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool_auth_page",
+            fog_pool.key().as_ref(),
+            &authorization.index_page.to_le_bytes()
+        ],
+        bump = pool_index_page.bump
+    )]
+    pub pool_index_page: Account<'info, PoolAuthorizationPage>,
+
+    /// Approval policy to close alongside, required when closing if one is attached
+    #[account(
+        mut,
+        seeds = [b"approval_policy", authorization.key().as_ref()],
+        bump = approval_policy.bump
+    )]
+    pub approval_policy: Option<Account<'info, ApprovalPolicy>>,
+
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+}
+
+/// Issuer-only update of an authorization's settings
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateAuthorization<'info> {
+    #[account(
+        mut,
+        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetUsdCap<'info> {
+    #[account(
+        mut,
+        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key(),
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Spender's staker account for tier validation of the cap in its new
+    /// unit; omitted counts as tier 0
+    #[account(
+        seeds = [
+            b"staker",
+            protocol_config.key().as_ref(),
+            authorization.authorized_spender.as_ref()
+        ],
+        bump
+    )]
+    pub staker: Option<Account<'info, Staker>>,
+
+    /// Spender's staker account in the tier-source instance; omitted counts as tier 0
+    #[account(
+        seeds = [
+            b"staker",
+            protocol_config.tier_source.as_ref(),
+            authorization.authorized_spender.as_ref()
+        ],
+        bump,
+        constraint = protocol_config.tier_source != Pubkey::default() @ ShadeError::InvalidTierSource
+    )]
+    pub source_staker: Option<Account<'info, Staker>>,
+
+    /// Another staker's tier delegated to the spender
+    #[account(
+        seeds = [b"tier_delegation", protocol_config.key().as_ref(), authorization.authorized_spender.as_ref()],
+        bump = tier_delegation.bump
+    )]
+    pub tier_delegation: Option<Account<'info, TierDelegation>>,
+
+    /// Staker position of the tier delegator, passed with tier_delegation
+    #[account(
+        seeds = [b"staker", protocol_config.key().as_ref(), delegator_staker.user.as_ref()],
+        bump = delegator_staker.bump
+    )]
+    pub delegator_staker: Option<Account<'info, Staker>>,
+
+    /// CHECK: Pyth price update for the pool's feed; required when switching
+    /// to a USD cap and checked in read_pyth_price
+    pub price_update: Option<UncheckedAccount<'info>>,
+
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ReinstateLimits<'info> {
+    #[account(
+        mut,
+        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(mut, constraint = authorization.fog_pool == fog_pool.key())]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RefundSpend<'info> {
+    #[account(mut)]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key()
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Token account the refund is paid from
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == recipient.key() @ ShadeError::Unauthorized
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub recipient: Signer<'info>,
+
+    /// The pool's spend ring, holding the spend being refunded
+    #[account(mut, address = fog_pool.spend_ring @ ShadeError::SpendRingRequired)]
+    pub spend_ring: AccountLoader<'info, SpendRing>,
+
+    /// Agreement a routed spend was booked under; required to refund one
+    #[account(mut)]
+    pub agreement: Option<Account<'info, PoolAgreement>>,
+
+    /// Creditor pool that paid a routed spend; required to refund one
+    #[account(mut)]
+    pub creditor_pool: Option<Account<'info, FogPool>>,
+
+    /// Creditor pool's vault, repaid first when refunding a routed spend
+    #[account(mut)]
+    pub creditor_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetApprovalPolicy<'info> {
+    #[account(
+        mut,
+        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        init_if_needed,
+        payer = issuer,
+        space = ApprovalPolicy::LEN,
+        seeds = [b"approval_policy", authorization.key().as_ref()],
+        bump
+    )]
+    pub approval_policy: Account<'info, ApprovalPolicy>,
+
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RemoveApprovalPolicy<'info> {
+    #[account(
+        mut,
+        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        close = issuer,
+        seeds = [b"approval_policy", authorization.key().as_ref()],
+        bump = approval_policy.bump
+    )]
+    pub approval_policy: Account<'info, ApprovalPolicy>,
+
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SpendWithApprovals<'info> {
+    pub spend: Spend<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"approval_policy", spend.authorization.key().as_ref()],
+        bump = approval_policy.bump
+    )]
+    pub approval_policy: Account<'info, ApprovalPolicy>,
+
+    /// CHECK: Instructions sysvar, used to read the ed25519 approval instructions
+    #[account(address = INSTRUCTIONS_SYSVAR_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct ProposeSpend<'info> {
+    #[account(
+        init,
+        payer = spender,
+        space = PendingSpend::LEN,
+        seeds = [
+            b"pending_spend",
+            authorization.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub pending_spend: Account<'info, PendingSpend>,
+
+    #[account(
+        constraint = authorization.authorized_spender == spender.key() @ ShadeError::Unauthorized
+    )]
+    pub authorization: Account<'info, Authorization>,
+
+    /// The authorization must have an approval policy
+    #[account(
+        seeds = [b"approval_policy", authorization.key().as_ref()],
+        bump = approval_policy.bump
+    )]
+    pub approval_policy: Account<'info, ApprovalPolicy>,
+
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub spender: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ApproveSpend<'info> {
+    #[account(mut)]
+    pub pending_spend: Account<'info, PendingSpend>,
+
+    #[account(
+        seeds = [b"approval_policy", pending_spend.authorization.as_ref()],
+        bump = approval_policy.bump
+    )]
+    pub approval_policy: Account<'info, ApprovalPolicy>,
+
+    pub approver: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ExecuteSpend<'info> {
+    pub spend: Spend<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"approval_policy", spend.authorization.key().as_ref()],
+        bump = approval_policy.bump
+    )]
+    pub approval_policy: Account<'info, ApprovalPolicy>,
+
+    #[account(
+        mut,
+        close = proposer,
+        constraint = pending_spend.authorization == spend.authorization.key() @ ShadeError::InvalidPendingSpend,
+        constraint = pending_spend.recipient_token_account == spend.recipient_token_account.key() @ ShadeError::InvalidPendingSpend
+    )]
+    pub pending_spend: Account<'info, PendingSpend>,
+
+    /// CHECK: Proposer of the spend, receives the proposal rent
+    #[account(
+        mut,
+        address = pending_spend.proposer @ ShadeError::Unauthorized
+    )]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CancelSpend<'info> {
+    #[account(
+        mut,
+        close = proposer,
+        constraint = pending_spend.authorization == authorization.key() @ ShadeError::InvalidPendingSpend
+    )]
+    pub pending_spend: Account<'info, PendingSpend>,
+
+    pub authorization: Account<'info, Authorization>,
+
+    /// CHECK: Proposer of the spend, receives the proposal rent
+    #[account(
+        mut,
+        address = pending_spend.proposer @ ShadeError::Unauthorized
+    )]
+    pub proposer: UncheckedAccount<'info>,
+
+    pub signer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct SpendEscrowed<'info> {
+    pub spend: Spend<'info>,
+
+    #[account(
+        init,
+        payer = spend.spender,
+        space = EscrowedSpend::LEN,
+        seeds = [
+            b"escrowed_spend",
+            spend.authorization.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    pub escrowed_spend: Account<'info, EscrowedSpend>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimEscrowedSpend<'info> {
+    #[account(
+        mut,
+        close = spender,
+        constraint = escrowed_spend.fog_pool == fog_pool.key() @ ShadeError::InvalidEscrowedSpend,
+        constraint = escrowed_spend.recipient_token_account == recipient_token_account.key() @ ShadeError::InvalidEscrowedSpend
+    )]
+    pub escrowed_spend: Account<'info, EscrowedSpend>,
+
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == recipient.key() @ ShadeError::Unauthorized
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub recipient: Signer<'info>,
+
+    /// CHECK: Spender who made the spend, receives the escrow rent
+    #[account(
+        mut,
+        address = escrowed_spend.spender @ ShadeError::Unauthorized
+    )]
+    pub spender: UncheckedAccount<'info>,
+
+    /// CHECK: Signs for the pool's vault; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+
+    /// The pool's spend ring; required once the pool has one
+    #[account(mut)]
+    pub spend_ring: Option<AccountLoader<'info, SpendRing>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RefundEscrowedSpend<'info> {
+    #[account(
+        mut,
+        close = spender,
+        constraint = escrowed_spend.authorization == authorization.key() @ ShadeError::InvalidEscrowedSpend,
+        constraint = escrowed_spend.fog_pool == fog_pool.key() @ ShadeError::InvalidEscrowedSpend
+    )]
+    pub escrowed_spend: Account<'info, EscrowedSpend>,
+
+    #[account(mut)]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Spender who made the spend, receives the escrow rent
+    #[account(
+        mut,
+        address = escrowed_spend.spender @ ShadeError::Unauthorized
+    )]
+    pub spender: UncheckedAccount<'info>,
+
+    pub signer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(invoice_id: [u8; 32])]
+pub struct CreateInvoice<'info> {
+    #[account(
+        init,
+        payer = recipient,
+        space = Invoice::LEN,
+        seeds = [b"invoice", recipient.key().as_ref(), invoice_id.as_ref()],
+        bump
+    )]
+    pub invoice: Account<'info, Invoice>,
+
+    #[account(
+        constraint = recipient_token_account.owner == recipient.key() @ ShadeError::Unauthorized
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct PayInvoice<'info> {
+    pub spend: Spend<'info>,
+
+    #[account(
+        mut,
+        constraint = invoice.recipient_token_account == spend.recipient_token_account.key() @ ShadeError::InvalidInvoice,
+        constraint = invoice.mint == spend.fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub invoice: Account<'info, Invoice>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseInvoice<'info> {
+    #[account(
+        mut,
+        close = recipient,
+        has_one = recipient @ ShadeError::Unauthorized
+    )]
+    pub invoice: Account<'info, Invoice>,
+
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetAuthorizationCategory<'info> {
+    #[account(mut)]
+    pub authorization: Account<'info, Authorization>,
+
+    #[account(
+        mut,
+        constraint = authorization.fog_pool == fog_pool.key(),
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(category: PurposeCategory)]
+pub struct SetCategoryBudget<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = CategoryBudget::LEN,
+        seeds = [b"category_budget", fog_pool.key().as_ref(), &[category as u8]],
+        bump
+    )]
+    pub category_budget: Account<'info, CategoryBudget>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RemoveCategoryBudget<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [
+            b"category_budget",
+            fog_pool.key().as_ref(),
+            &[category_budget.category as u8]
+        ],
+        bump = category_budget.bump
+    )]
+    pub category_budget: Account<'info, CategoryBudget>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(template_id: u64)]
+pub struct CreateSplitTemplate<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = SplitTemplate::LEN,
+        seeds = [b"split_template", fog_pool.key().as_ref(), &template_id.to_le_bytes()],
+        bump
+    )]
+    pub split_template: Account<'info, SplitTemplate>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateSplitTemplate<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = split_template.fog_pool == fog_pool.key() @ ShadeError::InvalidSplitTemplate
+    )]
+    pub split_template: Account<'info, SplitTemplate>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseSplitTemplate<'info> {
+    #[account(
+        mut,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        constraint = split_template.fog_pool == fog_pool.key() @ ShadeError::InvalidSplitTemplate
+    )]
+    pub split_template: Account<'info, SplitTemplate>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SpendSplit<'info> {
+    pub batch: SpendBatch<'info>,
+
+    #[account(
+        constraint = split_template.fog_pool == batch.fog_pool.key() @ ShadeError::InvalidSplitTemplate
+    )]
+    pub split_template: Account<'info, SplitTemplate>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(cap: u64, nonce: u64)]
+pub struct SpendWithPermit<'info> {
+    /// Carries the cached fee settings, so spends never lock the config
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// Read-only, for the instance's pause flag
+    #[account(address = fog_pool.protocol_config @ ShadeError::WrongProtocolInstance)]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = spender,
+        space = PermitNonces::LEN,
+        seeds = [
+            b"permit_nonces",
+            fog_pool.key().as_ref(),
+            &(nonce / PERMIT_NONCES_PER_PAGE).to_le_bytes()
+        ],
+        bump
+    )]
+    pub permit_nonces: Account<'info, PermitNonces>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fog_pool.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Recipient's token account (must match the pool mint)
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == fog_pool.mint @ ShadeError::InvalidMint
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub spender: Signer<'info>,
+
+    /// Spender's staker position; earns its tier's fee rebate when passed
+    #[account(
+        seeds = [b"staker", fog_pool.protocol_config.as_ref(), spender.key().as_ref()],
+        bump = spender_staker.bump
+    )]
+    pub spender_staker: Option<Account<'info, Staker>>,
+
+    /// Another staker's tier delegated to the spender
+    #[account(
+        seeds = [b"tier_delegation", fog_pool.protocol_config.as_ref(), spender.key().as_ref()],
+        bump = tier_delegation.bump
+    )]
+    pub tier_delegation: Option<Account<'info, TierDelegation>>,
+
+    /// Staker position of the tier delegator, passed with tier_delegation
+    #[account(
+        seeds = [b"staker", fog_pool.protocol_config.as_ref(), delegator_staker.user.as_ref()],
+        bump = delegator_staker.bump
+    )]
+    pub delegator_staker: Option<Account<'info, Staker>>,
+
+    /// CHECK: Pool authority's compromised flag for this spender; must not exist
+    #[account(
+        seeds = [
+            b"compromised_spender",
+            fog_pool.authority.as_ref(),
+            spender.key().as_ref()
+        ],
+        bump
+    )]
+    pub compromised_spender: UncheckedAccount<'info>,
+
+    /// CHECK: Protocol denylist entry for the spender; must not exist
+    #[account(seeds = [b"denylist", fog_pool.protocol_config.as_ref(), spender.key().as_ref()], bump)]
+    pub spender_denylist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: Protocol denylist entry for the recipient's owner; must not exist
+    #[account(
+        seeds = [
+            b"denylist",
+            fog_pool.protocol_config.as_ref(),
+            recipient_token_account.owner.as_ref()
+        ],
+        bump
+    )]
+    pub recipient_denylist_entry: UncheckedAccount<'info>,
+
+    /// CHECK: Signs for the pool's vault; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Instructions sysvar, used to read the ed25519 permit instruction
+    #[account(address = INSTRUCTIONS_SYSVAR_ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+
+    /// The pool's spend ring; required once the pool has one
+    #[account(mut)]
+    pub spend_ring: Option<AccountLoader<'info, SpendRing>>,
+
+    /// CHECK: SAS attestation of the spender; required by attestation-gated
+    /// pools and checked in check_attestation
+    pub spender_attestation: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: SAS attestation of the recipient's owner; required by
+    /// recipient-gated pools and checked in check_attestation
+    pub recipient_attestation: Option<UncheckedAccount<'info>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(spender: Pubkey)]
+pub struct FlagCompromisedSpender<'info> {
+    #[account(
+        init,
+        payer = issuer,
+        space = CompromisedSpender::LEN,
+        seeds = [b"compromised_spender", issuer.key().as_ref(), spender.as_ref()],
+        bump
+    )]
+    pub compromised_spender: Account<'info, CompromisedSpender>,
+
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClearCompromisedSpender<'info> {
+    #[account(
+        mut,
+        close = issuer,
+        seeds = [
+            b"compromised_spender",
+            issuer.key().as_ref(),
+            compromised_spender.spender.as_ref()
+        ],
+        bump = compromised_spender.bump
+    )]
+    pub compromised_spender: Account<'info, CompromisedSpender>,
+
+    #[account(mut)]
+    pub issuer: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(account: Pubkey)]
+pub struct AddToDenylist<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = DenylistEntry::LEN,
+        seeds = [b"denylist", protocol_config.key().as_ref(), account.as_ref()],
+        bump
+    )]
+    pub denylist_entry: Account<'info, DenylistEntry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RemoveFromDenylist<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"denylist", protocol_config.key().as_ref(), denylist_entry.account.as_ref()],
+        bump = denylist_entry.bump
+    )]
+    pub denylist_entry: Account<'info, DenylistEntry>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct PauseProtocol<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = authority.key() == protocol_config.authority
+            || (protocol_config.resume_guard.guardian != Pubkey::default()
+                && authority.key() == protocol_config.resume_guard.guardian) @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Protocol authority or guardian
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ResumeProtocol<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Protocol guardian co-signing an immediate unpause
+    pub guardian: Option<Signer<'info>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetProtocolGuardian<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub authority: Signer<'info>,
+
+    /// Existing guardian, required when replacing one
+    pub current_guardian: Option<Signer<'info>>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CheckPriceCircuitBreaker<'info> {
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// CHECK: Pyth price update for the pool's feed; checked in read_pyth_price
+    pub price_update: UncheckedAccount<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(schedule_id: u64)]
+pub struct CreateVestingSchedule<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = VestingSchedule::LEN,
+        seeds = [
+            b"vesting",
+            protocol_config.key().as_ref(),
+            beneficiary.key().as_ref(),
+            &schedule_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    /// Vault holding the allocation, owned by the schedule PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"vesting_vault", vesting_schedule.key().as_ref()],
+        bump,
+        token::mint = shade_mint,
+        token::authority = vesting_schedule
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Any account can be a beneficiary
+    pub beneficiary: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"vesting",
+            protocol_config.key().as_ref(),
+            beneficiary.key().as_ref(),
+            &vesting_schedule.schedule_id.to_le_bytes()
+        ],
+        bump = vesting_schedule.bump,
+        constraint = vesting_schedule.beneficiary == beneficiary.key() @ ShadeError::Unauthorized
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == vesting_schedule.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = beneficiary_token_account.mint == vault.mint @ ShadeError::InvalidMint
+    )]
+    pub beneficiary_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Beneficiary's staker account, required once the schedule counts toward tier
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), beneficiary.key().as_ref()],
+        bump = staker.bump
+    )]
+    pub staker: Option<Account<'info, Staker>>,
+
+    pub beneficiary: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RevokeVesting<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting",
+            protocol_config.key().as_ref(),
+            vesting_schedule.beneficiary.as_ref(),
+            &vesting_schedule.schedule_id.to_le_bytes()
+        ],
+        bump = vesting_schedule.bump
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == vesting_schedule.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the unvested tokens
+    #[account(
+        mut,
+        constraint = refund_token_account.mint == vault.mint @ ShadeError::InvalidMint
+    )]
+    pub refund_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Beneficiary's staker account, required once the schedule counts toward tier
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), vesting_schedule.beneficiary.as_ref()],
+        bump = staker.bump
+    )]
+    pub staker: Option<Account<'info, Staker>>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(airdrop_id: u64)]
+pub struct CreateAirdrop<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Airdrop::LEN,
+        seeds = [b"airdrop".as_ref(), protocol_config.key().as_ref(), &airdrop_id.to_le_bytes()],
+        bump
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    /// Vault holding the airdrop, owned by the airdrop PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"airdrop_vault", airdrop.key().as_ref()],
+        bump,
+        token::mint = shade_mint,
+        token::authority = airdrop
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ClaimAirdrop<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop".as_ref(),
+            airdrop.protocol_config.as_ref(),
+            &airdrop.airdrop_id.to_le_bytes()
+        ],
+        bump = airdrop.bump
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    #[account(
+        init,
+        payer = claimant,
+        space = AirdropClaim::LEN,
+        seeds = [b"airdrop_claim", airdrop.key().as_ref(), &index.to_le_bytes()],
+        bump
+    )]
+    pub claim_receipt: Account<'info, AirdropClaim>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == airdrop.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = claimant_token_account.mint == vault.mint @ ShadeError::InvalidMint
+    )]
+    pub claimant_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub claimant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(index: u64)]
+pub struct ClaimAirdropToStake<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"airdrop".as_ref(),
+            protocol_config.key().as_ref(),
+            &airdrop.airdrop_id.to_le_bytes()
+        ],
+        bump = airdrop.bump
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    #[account(
+        init,
+        payer = claimant,
+        space = AirdropClaim::LEN,
+        seeds = [b"airdrop_claim", airdrop.key().as_ref(), &index.to_le_bytes()],
+        bump
+    )]
+    pub claim_receipt: Account<'info, AirdropClaim>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == airdrop.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = claimant,
+        space = Staker::LEN,
+        seeds = [b"staker", protocol_config.key().as_ref(), claimant.key().as_ref()],
+        bump
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(
+        mut,
+        constraint = staking_vault.key() == protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority,
+        constraint = staking_vault.mint == vault.mint @ ShadeError::InvalidMint
+    )]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub claimant: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SweepAirdrop<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        seeds = [
+            b"airdrop".as_ref(),
+            protocol_config.key().as_ref(),
+            &airdrop.airdrop_id.to_le_bytes()
+        ],
+        bump = airdrop.bump
+    )]
+    pub airdrop: Account<'info, Airdrop>,
+
+    #[account(
+        mut,
+        constraint = vault.key() == airdrop.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives the unclaimed SHADE
+    #[account(
+        mut,
+        constraint = destination.mint == vault.mint @ ShadeError::InvalidMint
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct OptInVestingTier<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting",
+            protocol_config.key().as_ref(),
+            beneficiary.key().as_ref(),
+            &vesting_schedule.schedule_id.to_le_bytes()
+        ],
+        bump = vesting_schedule.bump,
+        constraint = vesting_schedule.beneficiary == beneficiary.key() @ ShadeError::Unauthorized
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        space = Staker::LEN,
+        seeds = [b"staker", protocol_config.key().as_ref(), beneficiary.key().as_ref()],
+        bump
+    )]
+    pub staker: Account<'info, Staker>,
+
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SyncVestingTier<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"vesting",
+            protocol_config.key().as_ref(),
+            vesting_schedule.beneficiary.as_ref(),
+            &vesting_schedule.schedule_id.to_le_bytes()
+        ],
+        bump = vesting_schedule.bump
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), vesting_schedule.beneficiary.as_ref()],
+        bump = staker.bump
+    )]
+    pub staker: Account<'info, Staker>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(lock_id: u64)]
+pub struct CreateTreasuryLock<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = TreasuryLock::LEN,
+        seeds = [b"treasury_lock".as_ref(), protocol_config.key().as_ref(), &lock_id.to_le_bytes()],
+        bump
+    )]
+    pub treasury_lock: Account<'info, TreasuryLock>,
+
+    /// Vault holding the locked tokens, owned by the lock PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"treasury_lock_vault", treasury_lock.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = treasury_lock
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == mint.key() @ ShadeError::InvalidMint
+    )]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Any account can receive released tranches
+    pub recipient: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ReleaseTreasuryLock<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"treasury_lock".as_ref(),
+            treasury_lock.protocol_config.as_ref(),
+            &treasury_lock.lock_id.to_le_bytes()
+        ],
+        bump = treasury_lock.bump
+    )]
+    pub treasury_lock: Account<'info, TreasuryLock>,
+
+    #[account(
+        mut,
+        address = treasury_lock.vault
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = recipient_token_account.owner == treasury_lock.recipient @ ShadeError::Unauthorized,
+        constraint = recipient_token_account.mint == treasury_lock.mint @ ShadeError::InvalidMint
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ExtendTreasuryLock<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"treasury_lock".as_ref(),
+            protocol_config.key().as_ref(),
+            &treasury_lock.lock_id.to_le_bytes()
+        ],
+        bump = treasury_lock.bump
+    )]
+    pub treasury_lock: Account<'info, TreasuryLock>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AddAmmProgram<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = AmmProgram::LEN,
+        seeds = [b"amm", protocol_config.key().as_ref(), amm_program.key().as_ref()],
+        bump
+    )]
+    pub amm: Account<'info, AmmProgram>,
+
+    /// CHECK: Only needs to be an executable program
+    #[account(executable)]
+    pub amm_program: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RemoveAmmProgram<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"amm", protocol_config.key().as_ref(), amm.program_id.as_ref()],
+        bump = amm.bump
+    )]
+    pub amm: Account<'info, AmmProgram>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ExecuteRebase<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        seeds = [b"amm", protocol_config.key().as_ref(), amm_program.key().as_ref()],
+        bump = amm.bump
+    )]
+    pub amm: Account<'info, AmmProgram>,
+
+    /// CHECK: Whitelisted through the amm PDA
+    #[account(executable)]
+    pub amm_program: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        address = protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = fee_vault.mint @ ShadeError::InvalidMint)]
+    pub fee_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Signs for the protocol's vaults; holds no data
+    #[account(
+        seeds = [b"vault_authority", protocol_config.key().as_ref()],
+        bump = protocol_config.vault_authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Signs the swap; owns only the rebase escrow and holds no data
+    #[account(seeds = [b"rebase_authority", protocol_config.key().as_ref()], bump)]
+    pub rebase_authority: UncheckedAccount<'info>,
+
+    /// Holds the rebase share during the swap - the rebase authority's
+    /// associated token account
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = fee_mint,
+        associated_token::authority = rebase_authority,
+        associated_token::token_program = token_program
+    )]
+    pub rebase_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CompoundRebase<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"staker", protocol_config.key().as_ref(), staker.user.as_ref()],
+        bump = staker.bump
+    )]
+    pub staker: Account<'info, Staker>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct InitializeTreasury<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Treasury::LEN,
+        seeds = [b"treasury", protocol_config.key().as_ref()],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        seeds = [b"treasury", protocol_config.key().as_ref()],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == treasury.key() @ ShadeError::InvalidVaultAuthority
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = treasury_token_account.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == treasury_token_account.mint @ ShadeError::InvalidMint
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct OpenPolPosition<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"treasury", protocol_config.key().as_ref()],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = PolPosition::LEN,
+        seeds = [
+            b"pol_position".as_ref(),
+            protocol_config.key().as_ref(),
+            &treasury.position_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub position: Account<'info, PolPosition>,
+
+    #[account(
+        seeds = [b"amm", protocol_config.key().as_ref(), amm.program_id.as_ref()],
+        bump = amm.bump
+    )]
+    pub amm: Account<'info, AmmProgram>,
+
+    pub mint_a: InterfaceAccount<'info, Mint>,
+    pub mint_b: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        constraint = lp_token_account.owner == treasury.key() @ ShadeError::InvalidVaultAuthority
+    )]
+    pub lp_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ManagePolPosition<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        seeds = [b"treasury", protocol_config.key().as_ref()],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pol_position".as_ref(),
+            protocol_config.key().as_ref(),
+            &position.position_id.to_le_bytes()
+        ],
+        bump = position.bump
+    )]
+    pub position: Account<'info, PolPosition>,
+
+    /// The position's AMM must still be whitelisted
+    #[account(
+        seeds = [b"amm", protocol_config.key().as_ref(), position.amm_program.as_ref()],
+        bump = amm.bump
+    )]
+    pub amm: Account<'info, AmmProgram>,
+
+    /// CHECK: Must be the position's AMM program
+    #[account(executable, address = position.amm_program)]
+    pub amm_program: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = treasury_token_a.owner == treasury.key() @ ShadeError::InvalidVaultAuthority,
+        constraint = treasury_token_a.mint == position.mint_a @ ShadeError::InvalidMint
+    )]
+    pub treasury_token_a: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury_token_b.owner == treasury.key() @ ShadeError::InvalidVaultAuthority,
+        constraint = treasury_token_b.mint == position.mint_b @ ShadeError::InvalidMint
+    )]
+    pub treasury_token_b: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = position.lp_token_account
+    )]
+    pub lp_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+}
+
+impl<'info> ManagePolPosition<'info> {
+    /// Current (token A, token B, LP) balances of the treasury accounts
+    fn balances(&self) -> (u64, u64, u64) {
+        (
+            self.treasury_token_a.amount,
+            self.treasury_token_b.amount,
+            self.lp_token_account.amount,
+        )
+    }
+
+    /// Run the AMM instruction as the treasury and reload the tracked balances
+    fn invoke(&mut self, remaining_accounts: &[AccountInfo<'info>], data: Vec<u8>) -> Result<()> {
+        let config_key = self.protocol_config.key();
+        let seeds = &[
+            b"treasury".as_ref(),
+            config_key.as_ref(),
+            &[self.treasury.bump][..],
+        ];
+        invoke_amm(
+            &self.amm_program,
+            remaining_accounts,
+            self.treasury.key(),
+            data,
+            &seeds[..],
+        )?;
+        self.treasury_token_a.reload()?;
+        self.treasury_token_b.reload()?;
+        self.lp_token_account.reload()?;
+        Ok(())
+    }
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(market_id: u64)]
+pub struct CreateBondMarket<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = BondMarket::LEN,
+        seeds = [b"bond_market".as_ref(), protocol_config.key().as_ref(), &market_id.to_le_bytes()],
+        bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+
+    #[account(
+        mut,
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// SHADE inventory, owned by the market PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"bond_vault", bond_market.key().as_ref()],
+        bump,
+        token::mint = shade_mint,
+        token::authority = bond_market
+    )]
+    pub shade_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
+    )]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateBondMarket<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"bond_market".as_ref(),
+            protocol_config.key().as_ref(),
+            &bond_market.market_id.to_le_bytes()
+        ],
+        bump = bond_market.bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct PurchaseBond<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"bond_market".as_ref(),
+            bond_market.protocol_config.as_ref(),
+            &bond_market.market_id.to_le_bytes()
+        ],
+        bump = bond_market.bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+
+    #[account(
+        init,
+        payer = buyer,
+        space = Bond::LEN,
+        seeds = [b"bond", bond_market.key().as_ref(), &bond_market.bond_count.to_le_bytes()],
+        bump
+    )]
+    pub bond: Account<'info, Bond>,
+
+    #[account(address = bond_market.shade_vault)]
+    pub shade_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = bond_market.fog_pool
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = pool_vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub pool_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = pool_vault.mint @ ShadeError::InvalidMint)]
+    pub payment_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = buyer_token_account.mint == pool_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut, address = fog_pool.lp_mint @ ShadeError::InvalidMint)]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA that mints the pool's LP shares; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", fog_pool.key().as_ref()],
+        bump = fog_pool.vault_authority_bump
+    )]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"treasury", bond_market.protocol_config.as_ref()],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+
+    /// Treasury's LP account, receives the shares for the bonded liquidity
+    #[account(
+        mut,
+        constraint = treasury_lp_account.owner == treasury.key() @ ShadeError::InvalidVaultAuthority,
+        constraint = treasury_lp_account.mint == fog_pool.lp_mint @ ShadeError::InvalidMint
+    )]
+    pub treasury_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RedeemBond<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"bond_market".as_ref(),
+            bond_market.protocol_config.as_ref(),
+            &bond_market.market_id.to_le_bytes()
+        ],
+        bump = bond_market.bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+
+    #[account(
+        mut,
+        seeds = [b"bond", bond_market.key().as_ref(), &bond.bond_id.to_le_bytes()],
+        bump = bond.bump,
+        constraint = bond.owner == owner.key() @ ShadeError::Unauthorized
+    )]
+    pub bond: Account<'info, Bond>,
+
+    #[account(
+        mut,
+        address = bond_market.shade_vault
+    )]
+    pub shade_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = shade_vault.mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = owner_shade_account.mint == shade_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub owner_shade_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub owner: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CloseBondMarket<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"bond_market".as_ref(),
+            protocol_config.key().as_ref(),
+            &bond_market.market_id.to_le_bytes()
+        ],
+        bump = bond_market.bump
+    )]
+    pub bond_market: Account<'info, BondMarket>,
+
+    #[account(
+        mut,
+        address = bond_market.shade_vault
+    )]
+    pub shade_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = shade_vault.mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = refund_token_account.mint == shade_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub refund_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CreateGauge<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = Gauge::LEN,
+        seeds = [b"gauge", protocol_config.key().as_ref(), stake_mint.key().as_ref()],
+        bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = fog_pool.protocol_config == protocol_config.key() @ ShadeError::WrongProtocolInstance,
+        constraint = fog_pool.lp_mint == stake_mint.key() @ ShadeError::InvalidMint
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    /// Staked LP share tokens, owned by the gauge PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"gauge_stake_vault", gauge.key().as_ref()],
+        bump,
+        token::mint = stake_mint,
+        token::authority = gauge
+    )]
+    pub stake_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// SHADE emissions, owned by the gauge PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"gauge_reward_vault", gauge.key().as_ref()],
+        bump,
+        token::mint = shade_mint,
+        token::authority = gauge
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = protocol_config.shade_mint @ ShadeError::InvalidMint)]
+    pub shade_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetGaugeRate<'info> {
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"gauge", protocol_config.key().as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct FundGauge<'info> {
+    #[account(
+        mut,
+        seeds = [b"gauge", gauge.protocol_config.as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    /// Either the SHADE reward vault or the bonus vault
+    #[account(
+        mut,
+        constraint = reward_vault.key() == gauge.reward_vault
+            || (gauge.bonus_mint != Pubkey::default() && reward_vault.key() == gauge.bonus_vault)
+            @ ShadeError::InvalidVaultAuthority
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = reward_vault.mint @ ShadeError::InvalidMint)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = funder_token_account.mint == reward_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub funder_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub funder: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct StakeInGauge<'info> {
+    #[account(
+        mut,
+        seeds = [b"gauge", gauge.protocol_config.as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = GaugeStake::LEN,
+        seeds = [b"gauge_stake", gauge.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub gauge_stake: Account<'info, GaugeStake>,
+
+    #[account(
+        mut,
+        address = gauge.stake_vault
+    )]
+    pub stake_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.mint @ ShadeError::InvalidMint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == gauge.stake_mint @ ShadeError::InvalidMint
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UnstakeFromGauge<'info> {
+    #[account(
+        mut,
+        seeds = [b"gauge", gauge.protocol_config.as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    #[account(
+        mut,
+        seeds = [b"gauge_stake", gauge.key().as_ref(), user.key().as_ref()],
+        bump = gauge_stake.bump
+    )]
+    pub gauge_stake: Account<'info, GaugeStake>,
+
+    #[account(
+        mut,
+        address = gauge.stake_vault
+    )]
+    pub stake_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = stake_vault.mint @ ShadeError::InvalidMint)]
+    pub stake_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == gauge.stake_mint @ ShadeError::InvalidMint
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimGaugeRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"gauge", gauge.protocol_config.as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    #[account(
+        mut,
+        seeds = [b"gauge_stake", gauge.key().as_ref(), user.key().as_ref()],
+        bump = gauge_stake.bump
+    )]
+    pub gauge_stake: Account<'info, GaugeStake>,
+
+    #[account(
+        mut,
+        address = gauge.reward_vault
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = reward_vault.mint @ ShadeError::InvalidMint)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_shade_account.mint == reward_vault.mint @ ShadeError::InvalidMint
+    )]
+    pub user_shade_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct AddGaugeBonus<'info> {
+    #[account(
+        mut,
+        seeds = [b"gauge", gauge.protocol_config.as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    #[account(
+        address = gauge.fog_pool,
+        constraint = fog_pool.lp_mint == gauge.stake_mint @ ShadeError::InvalidMint,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    pub bonus_mint: InterfaceAccount<'info, Mint>,
+
+    /// Bonus token vault, owned by the gauge PDA
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"gauge_bonus_vault", gauge.key().as_ref()],
+        bump,
+        token::mint = bonus_mint,
+        token::authority = gauge
+    )]
+    pub bonus_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetGaugeBonusRate<'info> {
+    #[account(
+        mut,
+        seeds = [b"gauge", gauge.protocol_config.as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    #[account(
+        address = gauge.fog_pool,
+        constraint = fog_pool.lp_mint == gauge.stake_mint @ ShadeError::InvalidMint,
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct ClaimGaugeBonus<'info> {
+    #[account(
+        mut,
+        seeds = [b"gauge", gauge.protocol_config.as_ref(), gauge.stake_mint.as_ref()],
+        bump = gauge.bump
+    )]
+    pub gauge: Account<'info, Gauge>,
+
+    #[account(
+        mut,
+        seeds = [b"gauge_stake", gauge.key().as_ref(), user.key().as_ref()],
+        bump = gauge_stake.bump
+    )]
+    pub gauge_stake: Account<'info, GaugeStake>,
+
+    #[account(
+        mut,
+        address = gauge.bonus_vault
+    )]
+    pub bonus_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = bonus_vault.mint @ ShadeError::InvalidMint)]
+    pub bonus_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.mint == gauge.bonus_mint @ ShadeError::InvalidMint
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub user: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CreatePoolAgreement<'info> {
+    #[account(
+        init,
+        payer = debtor_authority,
+        space = PoolAgreement::LEN,
+        seeds = [
+            b"pool_agreement",
+            debtor_pool.key().as_ref(),
+            creditor_pool.key().as_ref()
+        ],
+        bump
+    )]
+    pub agreement: Account<'info, PoolAgreement>,
+
+    #[account(
+        constraint = debtor_pool.authority == debtor_authority.key() @ ShadeError::Unauthorized
+    )]
+    pub debtor_pool: Account<'info, FogPool>,
+
+    #[account(
+        constraint = creditor_pool.authority == creditor_authority.key() @ ShadeError::Unauthorized
+    )]
+    pub creditor_pool: Account<'info, FogPool>,
+
+    #[account(mut)]
+    pub debtor_authority: Signer<'info>,
+
+    pub creditor_authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SetAgreementLimit<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"pool_agreement",
+            agreement.debtor_pool.as_ref(),
+            creditor_pool.key().as_ref()
+        ],
+        bump = agreement.bump
+    )]
+    pub agreement: Account<'info, PoolAgreement>,
+
+    #[account(
+        constraint = creditor_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub creditor_pool: Account<'info, FogPool>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct RoutedSpend<'info> {
+    pub spend: Spend<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"pool_agreement",
+            spend.fog_pool.key().as_ref(),
+            creditor_pool.key().as_ref()
+        ],
+        bump = agreement.bump
+    )]
+    pub agreement: Account<'info, PoolAgreement>,
+
+    #[account(
+        mut,
+        constraint = creditor_pool.protocol_config == spend.fog_pool.protocol_config @ ShadeError::WrongProtocolInstance
+    )]
+    pub creditor_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = creditor_vault.key() == creditor_pool.vault @ ShadeError::InvalidVaultAuthority,
+        constraint = creditor_vault.mint == spend.vault.mint @ ShadeError::InvalidMint
+    )]
+    pub creditor_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Signs for the creditor pool's vault; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", creditor_pool.key().as_ref()],
+        bump = creditor_pool.vault_authority_bump
+    )]
+    pub creditor_vault_authority: UncheckedAccount<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SettlePoolDebt<'info> {
+    #[account(
+        mut,
+        seeds = [
+            b"pool_agreement",
+            debtor_pool.key().as_ref(),
+            creditor_pool.key().as_ref()
+        ],
+        bump = agreement.bump
+    )]
+    pub agreement: Account<'info, PoolAgreement>,
+
+    #[account(mut)]
+    pub debtor_pool: Account<'info, FogPool>,
+
+    #[account(mut)]
+    pub creditor_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = debtor_vault.key() == debtor_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub debtor_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = debtor_vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = creditor_vault.key() == creditor_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub creditor_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Signs for the debtor pool's vault; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", debtor_pool.key().as_ref()],
+        bump = debtor_pool.vault_authority_bump
+    )]
+    pub debtor_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = authority.key() == debtor_pool.authority
+            || authority.key() == creditor_pool.authority @ ShadeError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CreateFederation<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Federation::LEN,
+        seeds = [b"federation", fog_pool.key().as_ref()],
+        bump
+    )]
+    pub federation: Account<'info, Federation>,
+
+    #[account(
+        constraint = fog_pool.authority == authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct UpdateFederation<'info> {
+    #[account(
+        mut,
+        seeds = [b"federation", federation.founding_pool.as_ref()],
+        bump = federation.bump,
+        has_one = authority @ ShadeError::Unauthorized
+    )]
+    pub federation: Account<'info, Federation>,
+
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct JoinFederation<'info> {
+    #[account(
+        mut,
+        seeds = [b"federation", federation.founding_pool.as_ref()],
+        bump = federation.bump,
+        has_one = authority @ ShadeError::Unauthorized
+    )]
+    pub federation: Account<'info, Federation>,
+
+    #[account(
+        constraint = fog_pool.authority == pool_authority.key() @ ShadeError::Unauthorized
+    )]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority,
+        constraint = vault.mint == federation.mint @ ShadeError::InvalidMint
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+
+    pub pool_authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct LeaveFederation<'info> {
+    #[account(
+        mut,
+        seeds = [b"federation", federation.founding_pool.as_ref()],
+        bump = federation.bump
+    )]
+    pub federation: Account<'info, Federation>,
+
+    #[account(mut)]
+    pub fog_pool: Account<'info, FogPool>,
+
+    #[account(
+        constraint = authority.key() == federation.authority
+            || authority.key() == fog_pool.authority @ ShadeError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct FederatedSpend<'info> {
+    pub spend: Spend<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"federation", federation.founding_pool.as_ref()],
+        bump = federation.bump,
+        constraint = federation.mint == spend.vault.mint @ ShadeError::InvalidMint
+    )]
+    pub federation: Account<'info, Federation>,
+
+    #[account(
+        mut,
+        constraint = paying_pool.protocol_config == spend.fog_pool.protocol_config @ ShadeError::WrongProtocolInstance
+    )]
+    pub paying_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = paying_vault.key() == paying_pool.vault @ ShadeError::InvalidVaultAuthority,
+        constraint = paying_vault.mint == federation.mint @ ShadeError::InvalidMint
+    )]
+    pub paying_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Signs for the paying pool's vault; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", paying_pool.key().as_ref()],
+        bump = paying_pool.vault_authority_bump
+    )]
+    pub paying_vault_authority: UncheckedAccount<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct SettleFederation<'info> {
+    #[account(
+        mut,
+        seeds = [b"federation", federation.founding_pool.as_ref()],
+        bump = federation.bump
+    )]
+    pub federation: Account<'info, Federation>,
+
+    #[account(mut)]
+    pub debtor_pool: Account<'info, FogPool>,
+
+    #[account(mut)]
+    pub creditor_pool: Account<'info, FogPool>,
+
+    #[account(
+        mut,
+        constraint = debtor_vault.key() == debtor_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub debtor_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = debtor_vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = creditor_vault.key() == creditor_pool.vault @ ShadeError::InvalidVaultAuthority
+    )]
+    pub creditor_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Signs for the debtor pool's vault; holds no data
+    #[account(
+        seeds = [b"pool_vault_authority", debtor_pool.key().as_ref()],
+        bump = debtor_pool.vault_authority_bump
+    )]
+    pub debtor_vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = authority.key() == debtor_pool.authority
+            || authority.key() == creditor_pool.authority @ ShadeError::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct MigrateProtocolConfig<'info> {
+    /// CHECK: Owner and discriminator are checked in the handler, which
+    /// deserializes it once it has grown to the current layout
+    #[account(mut)]
+    pub protocol_config: UncheckedAccount<'info>,
+
+    /// CHECK: PDA that takes over the fee and staking vaults; holds no data
+    #[account(seeds = [b"vault_authority", protocol_config.key().as_ref()], bump)]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    /// Checked against the config in the handler
+    #[account(mut)]
+    pub fee_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Checked against the config in the handler
+    #[account(mut)]
+    pub staking_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct MigrateFogPool<'info> {
+    /// CHECK: Owner and discriminator are checked in the handler, which
+    /// deserializes it once it has grown to the current layout
+    #[account(mut)]
+    pub fog_pool: UncheckedAccount<'info>,
+
+    /// The pool's instance, checked in the handler
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct MigrateLegacyFogPool<'info> {
+    /// CHECK: Owner, discriminator, size and address are checked in the
+    /// handler, which decodes the original layout
+    #[account(mut)]
+    pub fog_pool: UncheckedAccount<'info>,
+
+    /// The default instance, the only one the original program had
+    #[account(
+        mut,
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.instance_seed == [0; 32] @ ShadeError::InvalidMigration
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// Pool registry page with room for this pool
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = PoolRegistryPage::LEN,
+        seeds = [
+            b"pool_registry",
+            protocol_config.key().as_ref(),
+            &protocol_config.next_pool_page().to_le_bytes()
+        ],
+        bump
+    )]
+    pub pool_registry_page: Account<'info, PoolRegistryPage>,
+
+    /// The pool's original vault, owned by the pool PDA; checked in the handler
+    #[account(
+        mut,
+        constraint = legacy_vault.owner == fog_pool.key() @ ShadeError::InvalidVaultAuthority
+    )]
+    pub legacy_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = legacy_vault.mint @ ShadeError::InvalidMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA that owns the new vault and LP mint; holds no data
+    #[account(seeds = [b"pool_vault_authority", fog_pool.key().as_ref()], bump)]
+    pub pool_vault_authority: UncheckedAccount<'info>,
+
+    /// New vault - the pool vault authority's associated token account
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = pool_vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [b"lp_mint", fog_pool.key().as_ref()],
+        bump,
+        mint::decimals = mint.decimals,
+        mint::authority = pool_vault_authority,
+        mint::token_program = token_program
+    )]
+    pub lp_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Pool authority recorded in the legacy pool, checked in the
+    /// handler; receives the LP shares and the old vault's rent
+    #[account(mut)]
+    pub authority: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = lp_mint,
+        associated_token::authority = authority,
+        associated_token::token_program = token_program
+    )]
+    pub authority_lp_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct MigrateStaker<'info> {
+    /// The default instance, the only one the original program had
+    #[account(
+        seeds = [b"protocol_config", protocol_config.config_seed()],
+        bump = protocol_config.bump,
+        constraint = protocol_config.instance_seed == [0; 32] @ ShadeError::InvalidMigration
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    /// CHECK: Position at the original program's seeds; decoded in the handler
+    #[account(mut, seeds = [b"staker", user.key().as_ref()], bump)]
+    pub legacy_staker: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Staker::LEN,
+        seeds = [b"staker", protocol_config.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub staker: Account<'info, Staker>,
+
+    /// CHECK: Owner recorded in the legacy position; receives its rent
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    /// CHECK: Owner and discriminator are checked in the handler, which
+    /// deserializes it once it has grown to the current layout
+    #[account(mut)]
+    pub account: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+#[event]
+pub struct ProtocolInitialized {
+    pub config: Pubkey,
+    pub authority: Pubkey,
+    pub fee_basis_points: u16,
+}
+
+#[event]
+pub struct FeeUpdated {
+    pub old_fee: u16,
+    pub new_fee: u16,
+}
+
+#[event]
+pub struct TiersUpdated {
+    pub bronze: u64,
+    pub silver: u64,
+    pub gold: u64,
     pub bronze_cap_multiplier: u16,
-    /// Silver tier cap multiplier (basis points)
     pub silver_cap_multiplier: u16,
-    /// Gold tier cap multiplier (basis points)
     pub gold_cap_multiplier: u16,
-    /// PDA bump
-    pub bump: u8,
 }
 
-impl ProtocolConfig {
-    pub const LEN: usize = 8 +  // discriminator
-        32 + // authority
-        32 + // shade_mint
-        32 + // fee_vault
-        32 + // staking_vault
-        2 +  // fee_basis_points
-        8 +  // total_staked
-        8 +  // total_fees_collected
-        8 +  // total_fees_distributed
-        8 +  // bronze_threshold
-        8 +  // silver_threshold
-        8 +  // gold_threshold
-        2 +  // bronze_cap_multiplier
-        2 +  // silver_cap_multiplier
-        2 +  // gold_cap_multiplier
-        1;   // bump
+#[event]
+pub struct Staked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub new_total: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct EmergencyUnstaked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub forfeited_fees: u64,
+    pub forfeited_rebase: u64,
+    pub forfeited_emissions: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct StakedFor {
+    pub funder: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub new_total: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct UnstakedEarly {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub penalty: u64,
+    pub remaining: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct StakeLocked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub locked_amount: u64,
+    pub lock_end: i64,
+    pub lock_boost_bps: u16,
+    pub tier: u8,
+}
+
+#[event]
+pub struct StakeUnlockedEarly {
+    pub user: Pubkey,
+    pub released: u64,
+    pub penalty: u64,
+    pub remaining: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct StakeLockRefreshed {
+    pub user: Pubkey,
+    pub locked: u64,
+    pub lock_bonus: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct StakingEmissionsFunded {
+    pub amount: u64,
+    pub funded: u64,
+    pub allocated: u64,
+}
+
+#[event]
+pub struct StakingEmissionRateUpdated {
+    pub old_rate: u64,
+    pub new_rate: u64,
+}
+
+#[event]
+pub struct StakingEmissionsClaimed {
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct UnstakeRequested {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub pending: u64,
+    pub available_at: i64,
+    pub remaining: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct Unstaked {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
+    pub tier: u8,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct FogPoolCreated {
+    pub pool: Pubkey,
+    pub sequence: u64,
+    pub authority: Pubkey,
+    pub vault: Pubkey,
+    pub registry_index: u64,
+}
+
+#[event]
+pub struct DepositMade {
+    pub pool: Pubkey,
+    pub sequence: u64,
+    pub depositor: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+#[event]
+pub struct WithdrawalMade {
+    pub pool: Pubkey,
+    pub sequence: u64,
+    pub owner: Pubkey,
+    pub shares: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PoolStatusChanged {
+    pub pool: Pubkey,
+    pub sequence: u64,
+    pub status: PoolStatus,
+}
+
+#[event]
+pub struct PriceCircuitBreakerUpdated {
+    pub max_price_deviation_bps: u16,
+    pub price_deviation_window: i64,
+}
+
+#[event]
+pub struct PriceReferenceUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub price: u64,
+}
+
+#[event]
+pub struct PriceCircuitBreakerTripped {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub reference_price: u64,
+    pub price: u64,
+    pub status: PoolStatus,
+}
+
+#[event]
+pub struct ResumeAnnounced {
+    pub target: Pubkey,
+    pub eligible_at: i64,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub target: Pubkey,
+    pub guardian: Pubkey,
+}
+
+#[event]
+pub struct ProtocolPauseChanged {
+    pub config: Pubkey,
+    pub paused: bool,
 }
 
-/// Staker account - tracks user's staking info
-#[account]
-#[derive(Default)]
-pub struct Staker {
-    /// User's wallet
-    pub user: Pubkey,
-    /// Amount of $SHADE staked
-    pub staked_amount: u64,
-    /// Pending rewards to claim
-    pub pending_rewards: u64,
-    /// Last reward claim timestamp
-    pub last_claim_timestamp: i64,
-    /// Snapshot of total_fees_collected at last distribution (prevents double-claiming)
-    pub last_fees_snapshot: u64,
-    /// Current tier (0=None, 1=Bronze, 2=Silver, 3=Gold)
-    pub tier: u8,
-    /// PDA bump
-    pub bump: u8,
+#[event]
+pub struct FeesSwept {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub amount: u64,
+    pub keeper_reward: u64,
+    pub owner_share: u64,
+    pub staker_share: u64,
+    pub lp_share: u64,
+    pub treasury_share: u64,
 }
 
-impl Staker {
-    pub const LEN: usize = 8 +  // discriminator
-        32 + // user
-        8 +  // staked_amount
-        8 +  // pending_rewards
-        8 +  // last_claim_timestamp
-        8 +  // last_fees_snapshot
-        1 +  // tier
-        1;   // bump
+#[event]
+pub struct FeeCurveUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub slope_bps: u16,
+    pub ceiling_bps: u16,
 }
 
-/// Fog Pool - Shared liquidity reservoir where ownership is non-attributable
-#[account]
-#[derive(Default)]
-pub struct FogPool {
-    /// Authority who controls the fog pool
+#[event]
+pub struct PoolFeeUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub pool_fee_bps: u16,
+    pub pool_fee_vault: Pubkey,
+}
+
+#[event]
+pub struct PoolConfigSynced {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub fee_basis_points: u16,
+}
+
+#[event]
+pub struct SpendRingUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spend_ring: Pubkey,
+    pub capacity: u32,
+}
+
+#[event]
+pub struct PoolAuthorityProposed {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
     pub authority: Pubkey,
-    /// Token vault holding the pooled funds
-    pub vault: Pubkey,
-    /// Total tokens deposited into the pool
-    pub total_deposited: u64,
-    /// Total tokens spent from the pool
-    pub total_spent: u64,
-    /// Total fees generated from this pool
-    pub total_fees_generated: u64,
-    /// Number of active authorizations
-    pub active_authorizations: u64,
-    /// Unique seed for PDA derivation
-    pub pool_seed: [u8; 32],
-    /// PDA bump seed
-    pub bump: u8,
+    pub pending_authority: Pubkey,
 }
 
-impl FogPool {
-    pub const LEN: usize = 8 + // discriminator
-        32 + // authority
-        32 + // vault
-        8 +  // total_deposited
-        8 +  // total_spent
-        8 +  // total_fees_generated
-        8 +  // active_authorizations
-        32 + // pool_seed
-        1;   // bump
+#[event]
+pub struct PoolAuthorityTransferred {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct PoolOperatorUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub operator: Pubkey,
+}
+
+#[event]
+pub struct MaxUtilizationUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub max_utilization_bps: u16,
+}
+
+#[event]
+pub struct PriceFeedUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub price_feed_id: [u8; 32],
+    pub max_price_age: u32,
+    pub max_price_conf_bps: u16,
+}
+
+#[event]
+pub struct IssuerDelegateUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub operator: Pubkey,
+    pub enabled: bool,
+    pub templates_only: bool,
+}
+
+#[event]
+pub struct SpendTierCheckUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct AttestationRequirementUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub credential: Pubkey,
+    pub schema: Pubkey,
+    pub subject: AttestationSubject,
+}
+
+#[event]
+pub struct DepositLimitsUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub max_total_deposits: u64,
+    pub depositor_allowlist: bool,
+}
+
+#[event]
+pub struct SpendLimitsUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub min_spend: u64,
+    pub max_single_spend: u64,
+}
+
+#[event]
+pub struct DepositorAllowlistUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub depositor: Pubkey,
+    pub allowed: bool,
+}
+
+#[event]
+pub struct ProgramRegistryUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub program_id: Pubkey,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct ProgramSpenderRegistered {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spender: Pubkey,
+    pub program_id: Pubkey,
+}
+
+#[event]
+pub struct AuthorizationCreated {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spender: Pubkey,
+    pub issuer: Pubkey,
+    pub spending_cap: u64,
+    pub expires_at: i64,
+    pub purpose: String,
+}
+
+#[event]
+pub struct AuthorizationCampaignCreated {
+    pub campaign: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub campaign_id: u64,
+    pub merkle_root: [u8; 32],
+}
+
+#[event]
+pub struct AuthorizationClaimed {
+    pub campaign: Pubkey,
+    pub authorization: Pubkey,
+    pub spender: Pubkey,
+    pub index: u64,
+}
+
+#[event]
+pub struct AuthorizationCampaignClosed {
+    pub campaign: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub claimed: u64,
+}
+
+#[event]
+pub struct AuthorizationTemplateUpdated {
+    pub template: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub template_id: u64,
+    pub spending_cap: u64,
+    pub duration: i64,
+    pub closed: bool,
+}
+
+#[event]
+pub struct AuthorizationIssuedFromTemplate {
+    pub template: Pubkey,
+    pub authorization: Pubkey,
+    pub spender: Pubkey,
+}
+
+#[event]
+pub struct BatchSpendExecuted {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub payments: u8,
+    pub total_amount: u64,
+    pub total_fee: u64,
+}
+
+#[event]
+pub struct SpendExecuted {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spender: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub net_amount: u64,
+    /// Amount that reached the recipient: net_amount less any Token-2022 transfer fee
+    pub received: u64,
+    pub remaining: u64,
+    /// Per-pool monotonic spend counter (starts at 0), an exactly-once
+    /// ordering key for reconciliation
+    pub spend_index: u64,
+    /// Caller-supplied reference (e.g. an invoice id), if any
+    pub reference: Option<[u8; 32]>,
+}
+
+#[event]
+pub struct StreamCreated {
+    pub authorization: Pubkey,
+    pub spending_cap: u64,
+    pub starts_at: i64,
+    pub ends_at: i64,
+}
+
+#[event]
+pub struct SubscriptionCreated {
+    pub authorization: Pubkey,
+    pub period_length: i64,
+    pub per_period_cap: u64,
+    pub periods: u32,
+}
+
+#[event]
+pub struct AuthorizationAmended {
+    pub authorization: Pubkey,
+    pub old_spending_cap: u64,
+    pub spending_cap: u64,
+    pub old_expires_at: i64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct AuthorizationStartSet {
+    pub authorization: Pubkey,
+    pub starts_at: i64,
+}
+
+#[event]
+pub struct BudgetWindowSet {
+    pub authorization: Pubkey,
+    pub budget_window: i64,
+    pub window_start: i64,
+}
+
+#[event]
+pub struct MinSpendIntervalSet {
+    pub authorization: Pubkey,
+    pub min_spend_interval: i64,
+}
+
+#[event]
+pub struct SpendScheduleSet {
+    pub authorization: Pubkey,
+    pub spend_days: u8,
+    pub spend_window_start: u16,
+    pub spend_window_end: u16,
+}
+
+#[event]
+pub struct MaxUsesSet {
+    pub authorization: Pubkey,
+    pub max_uses: u32,
+    pub uses: u32,
+}
+
+#[event]
+pub struct AuthorizationFreezeChanged {
+    pub authorization: Pubkey,
+    pub frozen: bool,
+}
+
+#[event]
+pub struct AuthorizationRevoked {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub revoked_by: Pubkey,
+}
+
+#[event]
+pub struct AuthorizationsRevokedForSpender {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spender: Pubkey,
+    pub authorizations: Vec<Pubkey>,
+    pub revoked_by: Pubkey,
+}
+
+#[event]
+pub struct AuthorizationExpiryProcessed {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub keeper: Pubkey,
+    pub reward: u64,
+}
+
+#[event]
+pub struct AuthorizationBudgetReclaimed {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spending_cap: u64,
+    pub amount_spent: u64,
+    pub unused: u64,
+    pub closed: bool,
 }
 
-/// Authorization - Cryptographic permission to spend from the fog
-#[account]
-#[derive(Default)]
-pub struct Authorization {
-    /// The fog pool this authorization draws from
+#[event]
+pub struct AuthorizationClosed {
+    pub authorization: Pubkey,
     pub fog_pool: Pubkey,
-    /// Who can use this authorization to spend
-    pub authorized_spender: Pubkey,
-    /// Who issued this authorization
-    pub issuer: Pubkey,
-    /// Maximum amount that can be spent
-    pub spending_cap: u64,
-    /// Amount already spent
+    pub sequence: u64,
+    pub closed_by: Pubkey,
     pub amount_spent: u64,
-    /// When the authorization was created
-    pub created_at: i64,
-    /// When the authorization expires
-    pub expires_at: i64,
-    /// Purpose description (max 64 chars)
-    pub purpose: String,
-    /// Whether the authorization is still valid
-    pub is_active: bool,
-    /// PDA bump seed
-    pub bump: u8,
 }
 
-impl Authorization {
-    pub const LEN: usize = 8 +  // discriminator
-        32 + // fog_pool
-        32 + // authorized_spender
-        32 + // issuer
-        8 +  // spending_cap
-        8 +  // amount_spent
-        8 +  // created_at
-        8 +  // expires_at
-        68 + // purpose (4 byte len + 64 chars max)
-        1 +  // is_active
-        1;   // bump
+#[event]
+pub struct RecipientAllowlistSet {
+    pub authorization: Pubkey,
+    pub allowlist_root: [u8; 32],
 }
 
-// ============================================================================
-// Context Structures (Account Validation)
-// ============================================================================
+#[event]
+pub struct UsdCapSet {
+    pub authorization: Pubkey,
+    pub cap_in_usd: bool,
+}
 
-#[derive(Accounts)]
-pub struct InitializeProtocol<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = ProtocolConfig::LEN,
-        seeds = [b"protocol_config"],
-        bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct FeeModeSet {
+    pub authorization: Pubkey,
+    pub fee_on_top: bool,
+}
 
-    /// The $SHADE token mint
-    pub shade_mint: Account<'info, token::Mint>,
+#[event]
+pub struct EscrowTermsSet {
+    pub authorization: Pubkey,
+    pub dispute_window: i64,
+    pub arbiter: Pubkey,
+}
 
-    /// Fee vault (token account for collected fees)
-    #[account(mut)]
-    pub fee_vault: Account<'info, TokenAccount>,
+#[event]
+pub struct VelocityLimitsSet {
+    pub authorization: Pubkey,
+    pub limits: Vec<VelocityLimit>,
+}
 
-    /// Staking vault (token account for staked $SHADE)
-    #[account(mut)]
-    pub staking_vault: Account<'info, TokenAccount>,
+#[event]
+pub struct LimitsReinstated {
+    pub authorization: Pubkey,
+    pub amount: u64,
+    pub spent_at: i64,
+    pub remaining: u64,
+}
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct SpendRefunded {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub debt_repaid: u64,
+    pub spend_index: u64,
+    pub remaining: u64,
+}
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct ApprovalPolicySet {
+    pub authorization: Pubkey,
+    pub approvers: Vec<Pubkey>,
+    pub threshold: u8,
+    pub min_amount: u64,
 }
 
-#[derive(Accounts)]
-pub struct UpdateProtocol<'info> {
-    #[account(
-        mut,
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump,
-        constraint = protocol_config.authority == authority.key() @ ShadeError::Unauthorized
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct ApprovalPolicyRemoved {
+    pub authorization: Pubkey,
+}
 
-    pub authority: Signer<'info>,
+#[event]
+pub struct SpendApproved {
+    pub authorization: Pubkey,
+    pub approvers: Vec<Pubkey>,
+    pub amount: u64,
+    pub approval_nonce: u64,
 }
 
-#[derive(Accounts)]
-pub struct Stake<'info> {
-    #[account(
-        mut,
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct SpendProposed {
+    pub pending_spend: Pubkey,
+    pub authorization: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+}
 
-    #[account(
-        init_if_needed,
-        payer = user,
-        space = Staker::LEN,
-        seeds = [b"staker", user.key().as_ref()],
-        bump
-    )]
-    pub staker: Account<'info, Staker>,
+#[event]
+pub struct PendingSpendApproved {
+    pub pending_spend: Pubkey,
+    pub approver: Pubkey,
+    pub approvals: u8,
+}
 
-    #[account(
-        mut,
-        constraint = staking_vault.key() == protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority,
-        constraint = staking_vault.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
-    )]
-    pub staking_vault: Account<'info, TokenAccount>,
+#[event]
+pub struct SpendProposalCancelled {
+    pub pending_spend: Pubkey,
+    pub cancelled_by: Pubkey,
+}
 
-    #[account(
-        mut,
-        constraint = user_shade_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
-    )]
-    pub user_shade_account: Account<'info, TokenAccount>,
+#[event]
+pub struct EscrowedSpendCreated {
+    pub escrowed_spend: Pubkey,
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spender: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub releases_at: i64,
+    pub remaining: u64,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct EscrowedSpendClaimed {
+    pub escrowed_spend: Pubkey,
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub net_amount: u64,
+    pub spend_index: u64,
+}
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct EscrowedSpendRefunded {
+    pub escrowed_spend: Pubkey,
+    pub authorization: Pubkey,
+    pub refunded_by: Pubkey,
+    pub amount: u64,
+    pub remaining: u64,
 }
 
-#[derive(Accounts)]
-pub struct Unstake<'info> {
-    #[account(
-        mut,
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct InvoiceCreated {
+    pub invoice: Pubkey,
+    pub recipient: Pubkey,
+    pub recipient_token_account: Pubkey,
+    pub invoice_id: [u8; 32],
+    pub amount: u64,
+    pub expires_at: i64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"staker", user.key().as_ref()],
-        bump = staker.bump,
-        constraint = staker.user == user.key() @ ShadeError::Unauthorized
-    )]
-    pub staker: Account<'info, Staker>,
+#[event]
+pub struct InvoicePaid {
+    pub invoice: Pubkey,
+    pub authorization: Pubkey,
+    pub invoice_id: [u8; 32],
+    /// Amount drawn from the authorization, including an on-top fee
+    pub amount: u64,
+}
 
-    #[account(
-        mut,
-        constraint = staking_vault.key() == protocol_config.staking_vault @ ShadeError::InvalidVaultAuthority,
-        constraint = staking_vault.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
-    )]
-    pub staking_vault: Account<'info, TokenAccount>,
+#[event]
+pub struct InvoiceClosed {
+    pub invoice: Pubkey,
+    pub invoice_id: [u8; 32],
+    pub paid: bool,
+}
 
-    #[account(
-        mut,
-        constraint = user_shade_account.mint == protocol_config.shade_mint @ ShadeError::InvalidMint
-    )]
-    pub user_shade_account: Account<'info, TokenAccount>,
+#[event]
+pub struct AuthorizationCategorySet {
+    pub authorization: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub category: PurposeCategory,
+}
 
-    pub user: Signer<'info>,
+#[event]
+pub struct CategoryBudgetUpdated {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub category: PurposeCategory,
+    /// Per-epoch cap (0 with a 0 epoch length = budget removed)
+    pub cap: u64,
+    pub epoch_length: i64,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct SplitTemplateUpdated {
+    pub split_template: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    /// Number of recipients (0 = template closed)
+    pub recipients: u8,
 }
 
-#[derive(Accounts)]
-pub struct ClaimRewards<'info> {
-    #[account(
-        mut,
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct SplitSpendExecuted {
+    pub split_template: Pubkey,
+    pub authorization: Pubkey,
+    pub amount: u64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"staker", user.key().as_ref()],
-        bump = staker.bump,
-        constraint = staker.user == user.key() @ ShadeError::Unauthorized
-    )]
-    pub staker: Account<'info, Staker>,
+#[event]
+pub struct PermitSpendExecuted {
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub spender: Pubkey,
+    pub recipient: Pubkey,
+    pub nonce: u64,
+    pub amount: u64,
+    pub fee: u64,
+    pub net_amount: u64,
+    pub spend_index: u64,
+}
 
-    #[account(
-        mut,
-        constraint = fee_vault.key() == protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority
-    )]
-    pub fee_vault: Account<'info, TokenAccount>,
+#[event]
+pub struct SpenderCompromised {
+    pub issuer: Pubkey,
+    pub spender: Pubkey,
+}
 
-    /// User's token account to receive rewards (must match fee vault mint)
-    #[account(
-        mut,
-        constraint = user_token_account.mint == fee_vault.mint @ ShadeError::InvalidMint
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
+#[event]
+pub struct SpenderCleared {
+    pub issuer: Pubkey,
+    pub spender: Pubkey,
+}
 
-    pub user: Signer<'info>,
+#[event]
+pub struct DenylistUpdated {
+    pub account: Pubkey,
+    pub denied: bool,
+}
+
+#[event]
+pub struct DenylistBlocked {
+    pub account: Pubkey,
+    pub role: DenylistRole,
+}
+
+#[event]
+pub struct VestingCreated {
+    pub schedule: Pubkey,
+    pub beneficiary: Pubkey,
+    pub total_amount: u64,
+    pub start_at: i64,
+    pub cliff_at: i64,
+    pub end_at: i64,
+    pub revocable: bool,
+}
+
+#[event]
+pub struct VestedClaimed {
+    pub schedule: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    pub total_claimed: u64,
+}
+
+#[event]
+pub struct VestingRevoked {
+    pub schedule: Pubkey,
+    pub beneficiary: Pubkey,
+    pub vested: u64,
+    pub returned: u64,
+}
+
+#[event]
+pub struct AirdropCreated {
+    pub airdrop: Pubkey,
+    pub merkle_root: [u8; 32],
+    pub amount: u64,
+    pub claim_deadline: i64,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct AirdropClaimed {
+    pub airdrop: Pubkey,
+    pub claimant: Pubkey,
+    pub index: u64,
+    pub amount: u64,
+    pub staked: bool,
 }
 
-#[derive(Accounts)]
-pub struct DistributeFees<'info> {
-    #[account(
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct AirdropSwept {
+    pub airdrop: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"staker", staker.user.as_ref()],
-        bump = staker.bump
-    )]
-    pub staker: Account<'info, Staker>,
+#[event]
+pub struct VestingTierSynced {
+    pub schedule: Pubkey,
+    pub beneficiary: Pubkey,
+    pub tier_credit: u64,
+    pub tier: u8,
 }
 
-#[derive(Accounts)]
-#[instruction(pool_seed: [u8; 32])]
-pub struct InitializeFogPool<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = FogPool::LEN,
-        seeds = [b"fog_pool", pool_seed.as_ref()],
-        bump
-    )]
-    pub fog_pool: Account<'info, FogPool>,
+#[event]
+pub struct TreasuryLockCreated {
+    pub lock: Pubkey,
+    pub lock_id: u64,
+    pub label: String,
+    pub recipient: Pubkey,
+    pub mint: Pubkey,
+    pub total_amount: u64,
+    pub cliff_at: i64,
+    pub unlock_interval: i64,
+    pub unlock_count: u16,
+}
 
-    /// Vault token account - must be owned by the fog_pool PDA
-    /// Note: We validate the vault is a token account; ownership by fog_pool PDA
-    /// is implicitly required for spend operations to work
-    #[account(mut)]
-    pub vault: Account<'info, TokenAccount>,
+#[event]
+pub struct TreasuryUnlocked {
+    pub lock: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub tranches_released: u16,
+    pub total_released: u64,
+    /// 0 once every tranche is released
+    pub next_unlock_at: i64,
+}
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct TreasuryLockExtended {
+    pub lock: Pubkey,
+    pub old_cliff_at: i64,
+    pub new_cliff_at: i64,
+}
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct BaseSpendingCapUpdated {
+    pub old_cap: u64,
+    pub new_cap: u64,
 }
 
-#[derive(Accounts)]
-pub struct DepositToFog<'info> {
-    #[account(mut)]
-    pub fog_pool: Account<'info, FogPool>,
+#[event]
+pub struct MinStakeAgeUpdated {
+    pub old_age: i64,
+    pub new_age: i64,
+}
 
-    #[account(
-        mut,
-        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
-    )]
-    pub vault: Account<'info, TokenAccount>,
+#[event]
+pub struct EarlyExitPenaltyUpdated {
+    pub old_bps: u16,
+    pub new_bps: u16,
+}
 
-    /// Depositor's token account (must match vault mint)
-    #[account(
-        mut,
-        constraint = depositor_token_account.mint == vault.mint @ ShadeError::InvalidMint
-    )]
-    pub depositor_token_account: Account<'info, TokenAccount>,
+#[event]
+pub struct UnstakeCooldownUpdated {
+    pub old_cooldown: i64,
+    pub new_cooldown: i64,
+}
 
-    #[account(mut)]
-    pub depositor: Signer<'info>,
+#[event]
+pub struct KeeperRewardUpdated {
+    pub old_reward: u64,
+    pub new_reward: u64,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct FeeSplitUpdated {
+    pub staker_share_bps: u16,
+    pub lp_share_bps: u16,
+    pub treasury_share_bps: u16,
+    pub fee_treasury: Pubkey,
 }
 
-#[derive(Accounts)]
-#[instruction(nonce: u64)]
-pub struct CreateAuthorization<'info> {
-    #[account(
-        init,
-        payer = issuer,
-        space = Authorization::LEN,
-        seeds = [
-            b"authorization",
-            fog_pool.key().as_ref(),
-            spender.key().as_ref(),
-            &nonce.to_le_bytes()
-        ],
-        bump
-    )]
-    pub authorization: Account<'info, Authorization>,
+#[event]
+pub struct FeeRebatesUpdated {
+    pub bronze_fee_rebate_bps: u16,
+    pub silver_fee_rebate_bps: u16,
+    pub gold_fee_rebate_bps: u16,
+}
 
-    #[account(mut)]
-    pub fog_pool: Account<'info, FogPool>,
+#[event]
+pub struct KeeperRewardBpsUpdated {
+    pub old_bps: u16,
+    pub new_bps: u16,
+}
 
-    #[account(
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct AuthorityProposed {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
 
-    /// Optional staker account for tier validation
-    #[account(
-        seeds = [b"staker", spender.key().as_ref()],
-        bump
-    )]
-    pub staker: Option<Account<'info, Staker>>,
+#[event]
+pub struct AuthorityTransferred {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
 
-    /// CHECK: Can be any account that will receive the authorization
-    pub spender: AccountInfo<'info>,
+#[event]
+pub struct TierSourceUpdated {
+    pub config: Pubkey,
+    pub tier_source: Pubkey,
+}
 
-    #[account(
-        mut,
-        constraint = issuer.key() == fog_pool.authority @ ShadeError::Unauthorized
-    )]
-    pub issuer: Signer<'info>,
+#[event]
+pub struct AmmProgramUpdated {
+    pub program_id: Pubkey,
+    pub whitelisted: bool,
+}
 
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct RebaseParamsUpdated {
+    pub rebase_bps: u16,
+    pub rebase_epoch_length: i64,
 }
 
-#[derive(Accounts)]
-pub struct Spend<'info> {
-    #[account(
-        mut,
-        constraint = authorization.authorized_spender == spender.key() @ ShadeError::Unauthorized
-    )]
-    pub authorization: Account<'info, Authorization>,
+#[event]
+pub struct RebaseExecuted {
+    pub epoch: u64,
+    pub amm_program: Pubkey,
+    pub fees_in: u64,
+    pub shade_out: u64,
+    pub rebase_per_share: u128,
+}
 
-    #[account(
-        mut,
-        constraint = authorization.fog_pool == fog_pool.key()
-    )]
-    pub fog_pool: Account<'info, FogPool>,
+#[event]
+pub struct RebaseCompounded {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub new_total: u64,
+    pub tier: u8,
+}
 
-    #[account(
-        mut,
-        seeds = [b"protocol_config"],
-        bump = protocol_config.bump
-    )]
-    pub protocol_config: Account<'info, ProtocolConfig>,
+#[event]
+pub struct TreasuryInitialized {
+    pub treasury: Pubkey,
+}
 
-    #[account(
-        mut,
-        constraint = vault.key() == fog_pool.vault @ ShadeError::InvalidVaultAuthority
-    )]
-    pub vault: Account<'info, TokenAccount>,
+#[event]
+pub struct TreasuryWithdrawn {
+    pub token_account: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
 
-    #[account(
-        mut,
-        constraint = fee_vault.key() == protocol_config.fee_vault @ ShadeError::InvalidVaultAuthority,
-        constraint = fee_vault.mint == vault.mint @ ShadeError::InvalidMint
-    )]
-    pub fee_vault: Account<'info, TokenAccount>,
+#[event]
+pub struct PolPositionOpened {
+    pub position: Pubkey,
+    pub amm_program: Pubkey,
+    pub amm_pool: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+}
 
-    /// Recipient's token account (must match vault mint for correct token transfer)
-    #[account(
-        mut,
-        constraint = recipient_token_account.mint == vault.mint @ ShadeError::InvalidMint
-    )]
-    pub recipient_token_account: Account<'info, TokenAccount>,
+#[event]
+pub struct PolLiquidityChanged {
+    pub position: Pubkey,
+    /// true = liquidity added, false = removed
+    pub added: bool,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    /// LP tokens minted or burned
+    pub lp_amount: u64,
+    pub lp_balance: u64,
+}
 
-    pub spender: Signer<'info>,
+#[event]
+pub struct PolFeesHarvested {
+    pub position: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub total_harvested_a: u64,
+    pub total_harvested_b: u64,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct BondMarketCreated {
+    pub market: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub terms: BondTerms,
+    pub epoch_length: i64,
+    pub vesting_duration: i64,
+    pub inventory: u64,
 }
 
-#[derive(Accounts)]
-pub struct RevokeAuthorization<'info> {
-    #[account(
-        mut,
-        constraint = authorization.issuer == issuer.key() @ ShadeError::Unauthorized
-    )]
-    pub authorization: Account<'info, Authorization>,
+#[event]
+pub struct BondMarketUpdated {
+    pub market: Pubkey,
+    pub terms: BondTerms,
+}
 
-    #[account(mut)]
-    pub fog_pool: Account<'info, FogPool>,
+#[event]
+pub struct BondPurchased {
+    pub market: Pubkey,
+    pub bond: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub payout: u64,
+    pub price: u64,
+    pub vests_at: i64,
+}
 
-    pub issuer: Signer<'info>,
+#[event]
+pub struct BondRedeemed {
+    pub bond: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub total_claimed: u64,
 }
 
-// ============================================================================
-// Events
-// ============================================================================
+#[event]
+pub struct BondMarketClosed {
+    pub market: Pubkey,
+    pub returned: u64,
+}
 
 #[event]
-pub struct ProtocolInitialized {
-    pub config: Pubkey,
-    pub authority: Pubkey,
-    pub fee_basis_points: u16,
+pub struct GaugeCreated {
+    pub gauge: Pubkey,
+    pub stake_mint: Pubkey,
+    pub fog_pool: Pubkey,
+    pub sequence: u64,
+    pub emission_rate: u64,
 }
 
 #[event]
-pub struct FeeUpdated {
-    pub old_fee: u16,
-    pub new_fee: u16,
+pub struct GaugeRateUpdated {
+    pub gauge: Pubkey,
+    /// true = operator bonus stream, false = SHADE emissions
+    pub bonus: bool,
+    pub old_rate: u64,
+    pub new_rate: u64,
 }
 
 #[event]
-pub struct TiersUpdated {
-    pub bronze: u64,
-    pub silver: u64,
-    pub gold: u64,
+pub struct GaugeFunded {
+    pub gauge: Pubkey,
+    pub funder: Pubkey,
+    pub bonus: bool,
+    pub amount: u64,
 }
 
 #[event]
-pub struct Staked {
+pub struct GaugeStaked {
+    pub gauge: Pubkey,
     pub user: Pubkey,
     pub amount: u64,
     pub new_total: u64,
-    pub tier: u8,
 }
 
 #[event]
-pub struct Unstaked {
+pub struct GaugeUnstaked {
+    pub gauge: Pubkey,
     pub user: Pubkey,
     pub amount: u64,
     pub remaining: u64,
+}
+
+#[event]
+pub struct GaugeRewardsClaimed {
+    pub gauge: Pubkey,
+    pub user: Pubkey,
+    pub bonus: bool,
+    pub amount: u64,
+}
+
+#[event]
+pub struct GaugeBonusAdded {
+    pub gauge: Pubkey,
+    pub bonus_mint: Pubkey,
+    pub rate: u64,
+}
+
+#[event]
+pub struct ClaimDelegateSet {
+    pub user: Pubkey,
+    pub delegate: Pubkey,
+    pub reward_destination: Pubkey,
+}
+
+#[event]
+pub struct TierDelegateSet {
+    pub user: Pubkey,
+    pub delegate: Pubkey,
     pub tier: u8,
 }
 
 #[event]
-pub struct RewardsClaimed {
+pub struct TierDelegateRevoked {
     pub user: Pubkey,
+    pub delegate: Pubkey,
+}
+
+#[event]
+pub struct PoolAgreementUpdated {
+    pub agreement: Pubkey,
+    pub debtor_pool: Pubkey,
+    pub creditor_pool: Pubkey,
+    pub credit_limit: u64,
+}
+
+#[event]
+pub struct SpendRouted {
+    pub authorization: Pubkey,
+    pub debtor_pool: Pubkey,
+    pub creditor_pool: Pubkey,
     pub amount: u64,
+    pub outstanding: u64,
 }
 
 #[event]
-pub struct FeesDistributed {
-    pub staker: Pubkey,
+pub struct PoolDebtSettled {
+    pub agreement: Pubkey,
     pub amount: u64,
+    pub outstanding: u64,
 }
 
 #[event]
-pub struct FogPoolCreated {
-    pub pool: Pubkey,
-    pub authority: Pubkey,
-    pub vault: Pubkey,
+pub struct FederationCreated {
+    pub federation: Pubkey,
+    pub founding_pool: Pubkey,
+    pub mint: Pubkey,
+    pub debit_limit: u64,
 }
 
 #[event]
-pub struct DepositMade {
-    pub pool: Pubkey,
-    pub depositor: Pubkey,
-    pub amount: u64,
+pub struct FederationDebitLimitUpdated {
+    pub federation: Pubkey,
+    pub debit_limit: u64,
 }
 
 #[event]
-pub struct AuthorizationCreated {
-    pub authorization: Pubkey,
+pub struct FederationMembershipChanged {
+    pub federation: Pubkey,
     pub fog_pool: Pubkey,
-    pub spender: Pubkey,
-    pub issuer: Pubkey,
-    pub spending_cap: u64,
-    pub expires_at: i64,
-    pub purpose: String,
+    pub sequence: u64,
+    pub joined: bool,
 }
 
 #[event]
-pub struct SpendExecuted {
+pub struct FederatedSpendExecuted {
+    pub federation: Pubkey,
     pub authorization: Pubkey,
-    pub fog_pool: Pubkey,
-    pub spender: Pubkey,
-    pub recipient: Pubkey,
+    pub issuing_pool: Pubkey,
+    pub paying_pool: Pubkey,
     pub amount: u64,
-    pub fee: u64,
-    pub net_amount: u64,
-    pub remaining: u64,
 }
 
 #[event]
-pub struct AuthorizationRevoked {
-    pub authorization: Pubkey,
-    pub fog_pool: Pubkey,
-    pub revoked_by: Pubkey,
+pub struct FederationSettled {
+    pub federation: Pubkey,
+    pub debtor_pool: Pubkey,
+    pub creditor_pool: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
 }
 
 // ============================================================================
@@ -1246,4 +18205,278 @@ pub enum ShadeError {
     InvalidMint,
     #[msg("Token account has incorrect owner/authority")]
     InvalidVaultAuthority,
+    #[msg("Invalid velocity limits")]
+    InvalidVelocityLimits,
+    #[msg("Spend exceeds a velocity limit window")]
+    VelocityLimitExceeded,
+    #[msg("Reinstated amount exceeds amount spent")]
+    ReinstateExceedsSpent,
+    #[msg("Invalid timestamp")]
+    InvalidTimestamp,
+    #[msg("Invalid approval policy")]
+    InvalidApprovalPolicy,
+    #[msg("Spend requires approval under the authorization's policy")]
+    ApprovalRequired,
+    #[msg("Not enough valid approver signatures")]
+    InsufficientApprovals,
+    #[msg("Malformed signature verification instruction")]
+    InvalidSignatureInstruction,
+    #[msg("Spender key has been flagged as compromised")]
+    SpenderCompromised,
+    #[msg("Account is on the protocol denylist")]
+    Denylisted,
+    #[msg("Fog pool is not accepting authorizations or spends")]
+    PoolNotActive,
+    #[msg("Not paused")]
+    NotPaused,
+    #[msg("Resume needs the guardian's co-signature or an elapsed announcement timelock")]
+    ResumeNotAuthorized,
+    #[msg("Invalid vesting schedule parameters")]
+    InvalidVestingSchedule,
+    #[msg("No vested tokens to claim")]
+    NothingVested,
+    #[msg("Vesting schedule is not revocable")]
+    VestingNotRevocable,
+    #[msg("Vesting schedule already revoked")]
+    VestingAlreadyRevoked,
+    #[msg("Invalid merkle proof")]
+    InvalidMerkleProof,
+    #[msg("Airdrop vault exhausted")]
+    AirdropExhausted,
+    #[msg("Staker account required")]
+    StakerRequired,
+    #[msg("Vesting schedule already counts toward tier")]
+    AlreadyOptedIn,
+    #[msg("Label too long (max 32 characters)")]
+    LabelTooLong,
+    #[msg("Invalid treasury lock parameters")]
+    InvalidTreasuryLock,
+    #[msg("No tranche has unlocked yet")]
+    NothingUnlocked,
+    #[msg("Treasury locks can only be extended, never unlocked early")]
+    EarlyUnlockProhibited,
+    #[msg("Invalid rebase parameters")]
+    InvalidRebaseParams,
+    #[msg("Rebase epoch has not elapsed")]
+    RebaseEpochNotElapsed,
+    #[msg("Swap drew more than the fees set aside for rebase")]
+    RebaseOverspent,
+    #[msg("Swap output below minimum")]
+    SlippageExceeded,
+    #[msg("AMM call decreased a treasury balance it must not touch")]
+    PolBalanceDecreased,
+    #[msg("Invalid bond market parameters")]
+    InvalidBondMarket,
+    #[msg("Bond market is closed")]
+    BondMarketClosed,
+    #[msg("Bond capacity exceeded")]
+    BondCapacityExceeded,
+    #[msg("Gauge already has a bonus reward token")]
+    GaugeBonusExists,
+    #[msg("Gauge has no bonus reward token")]
+    NoGaugeBonus,
+    #[msg("Spender already has the maximum number of active authorizations")]
+    SpenderIndexFull,
+    #[msg("Invalid pool agreement")]
+    InvalidPoolAgreement,
+    #[msg("Routed spend exceeds the agreement's credit limit")]
+    CreditLimitExceeded,
+    #[msg("Invalid federation operation")]
+    InvalidFederation,
+    #[msg("Federation is full")]
+    FederationFull,
+    #[msg("Pool is not a member of the federation")]
+    NotFederationMember,
+    #[msg("Pool is already a member of the federation")]
+    AlreadyFederationMember,
+    #[msg("Pool has an unsettled federation position")]
+    FederationPositionOpen,
+    #[msg("Spend would exceed the federation debit limit")]
+    FederationDebitLimitExceeded,
+    #[msg("Settlement exceeds the members' net positions")]
+    SettleExceedsPosition,
+    #[msg("Account belongs to a different protocol instance")]
+    WrongProtocolInstance,
+    #[msg("Invalid tier source")]
+    InvalidTierSource,
+    #[msg("Insufficient LP shares")]
+    InsufficientShares,
+    #[msg("Pool vault is empty; outstanding shares must be redeemed first")]
+    PoolDepleted,
+    #[msg("Protocol is paused")]
+    ProtocolPaused,
+    #[msg("Authorization is still usable and cannot be closed")]
+    AuthorizationStillUsable,
+    #[msg("No unstake has been requested")]
+    NoPendingUnstake,
+    #[msg("Unstake cooldown has not elapsed")]
+    UnstakeCooldownActive,
+    #[msg("Cap multipliers must be positive and non-decreasing by tier")]
+    InvalidCapMultipliers,
+    #[msg("Spend exceeds the streamed balance accrued so far")]
+    ExceedsStreamedBalance,
+    #[msg("Recipient is not in the authorization's allowlist")]
+    RecipientNotAllowlisted,
+    #[msg("Invalid batch payments")]
+    InvalidBatch,
+    #[msg("Authorization is frozen")]
+    AuthorizationFrozen,
+    #[msg("Authorization is not frozen")]
+    AuthorizationNotFrozen,
+    #[msg("Authorization has reached its maximum number of uses")]
+    MaxUsesReached,
+    #[msg("Minimum interval since the last spend has not elapsed")]
+    SpendTooSoon,
+    #[msg("Invalid budget window")]
+    InvalidBudgetWindow,
+    #[msg("Authorization is not active yet")]
+    AuthorizationNotStarted,
+    #[msg("Authorization has already been used")]
+    AuthorizationAlreadyUsed,
+    #[msg("Authorization budget was already reclaimed")]
+    BudgetAlreadyReclaimed,
+    #[msg("Approver has already approved this spend")]
+    AlreadyApproved,
+    #[msg("Pending spend does not match this spend")]
+    InvalidPendingSpend,
+    #[msg("Pool vault balance too low (unswept fees are reserved)")]
+    InsufficientPoolBalance,
+    #[msg("Fee split must total 10000 bps, and a treasury share needs a fee treasury")]
+    InvalidFeeSplit,
+    #[msg("Fee rebates must not decrease with tier or exceed 10000 bps")]
+    InvalidFeeRebates,
+    #[msg("Dispute window must be between 0 and 90 days")]
+    InvalidDisputeWindow,
+    #[msg("Authorization has no dispute window for escrowed spends")]
+    EscrowNotEnabled,
+    #[msg("Escrowed spend does not match these accounts")]
+    InvalidEscrowedSpend,
+    #[msg("Escrowed spend is still in its dispute window")]
+    DisputeWindowOpen,
+    #[msg("Escrowed spend's dispute window has ended")]
+    DisputeWindowClosed,
+    #[msg("No spend with this index has been made from the pool")]
+    InvalidSpendIndex,
+    #[msg("Invoice memo too long (max 64 chars)")]
+    InvoiceMemoTooLong,
+    #[msg("Invoice is not payable to this token account")]
+    InvalidInvoice,
+    #[msg("Invoice has already been paid")]
+    InvoiceAlreadyPaid,
+    #[msg("Invoice has expired")]
+    InvoiceExpired,
+    #[msg("Split shares must be 1-10 non-zero shares totalling 10000 bps")]
+    InvalidSplitShares,
+    #[msg("Split template does not belong to this pool")]
+    InvalidSplitTemplate,
+    #[msg("Permit is not signed by the pool authority")]
+    InvalidPermit,
+    #[msg("Permit has expired")]
+    PermitExpired,
+    #[msg("Permit nonce has already been used")]
+    PermitNonceUsed,
+    #[msg("Authorization campaign does not belong to this fog pool")]
+    InvalidAuthorizationCampaign,
+    #[msg("Seeds do not derive the spender from the registered program")]
+    InvalidProgramSpender,
+    #[msg("PDA spender is not bound to a registered program")]
+    ProgramSpenderNotRegistered,
+    #[msg("Account is not a SHADE account of the expected type")]
+    InvalidMigration,
+    #[msg("Account already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("Spend ring capacity must be between 1 and 60")]
+    InvalidSpendRingCapacity,
+    #[msg("Spend ring does not match the fog pool")]
+    InvalidSpendRing,
+    #[msg("The fog pool's spend ring must be passed")]
+    SpendRingRequired,
+    #[msg(
+        "Bulk revoke accounts must be writable authorization and index page pairs for the spender"
+    )]
+    InvalidBulkRevoke,
+    #[msg("Authorization template does not match the fog pool")]
+    InvalidAuthorizationTemplate,
+    #[msg("This issuer delegate may only issue from authorization templates")]
+    TemplateRequired,
+    #[msg("Category budgets need a category and a positive epoch length")]
+    InvalidCategoryBudget,
+    #[msg("The pool's budget for the authorization's category must be passed")]
+    CategoryBudgetRequired,
+    #[msg("Spend would exceed the category's budget for this epoch")]
+    CategoryBudgetExceeded,
+    #[msg("Price feed is not set or its staleness and confidence limits are invalid")]
+    InvalidPriceFeed,
+    #[msg("Price update is not a fully verified Pyth update for the pool's feed")]
+    InvalidPriceUpdate,
+    #[msg("USD-cap authorizations must spend with a Pyth price update")]
+    PriceUpdateRequired,
+    #[msg("Price update is older than the pool allows")]
+    StalePrice,
+    #[msg("Price confidence interval is wider than the pool allows")]
+    PriceConfidenceTooWide,
+    #[msg("Spending cap unit can only change before the first spend")]
+    SpendingCapUnitLocked,
+    #[msg("Price circuit breaker is disabled or its settings are invalid")]
+    InvalidCircuitBreaker,
+    #[msg("Price circuit breaker needs a reference price for the current window")]
+    PriceReferenceStale,
+    #[msg("Price moved beyond the circuit breaker threshold")]
+    PriceCircuitBreakerTripped,
+    #[msg("Utilization cap must be at most 10000 bps")]
+    InvalidUtilizationCap,
+    #[msg("Spend would push the pool past its utilization cap")]
+    UtilizationCapExceeded,
+    #[msg("Withdrawal would dip into liquidity reserved for active authorizations")]
+    LiquidityReserved,
+    #[msg("Deposit would exceed the pool's deposit cap")]
+    DepositCapExceeded,
+    #[msg("Pool only accepts deposits from allowlisted depositors")]
+    DepositorNotAllowlisted,
+    #[msg("Minimum spend exceeds maximum single spend")]
+    InvalidSpendLimits,
+    #[msg("Spend is below the pool's minimum spend")]
+    SpendBelowMinimum,
+    #[msg("Spend exceeds the pool's maximum single spend")]
+    SpendAboveMaximum,
+    #[msg("Pool requires a credential attestation")]
+    AttestationRequired,
+    #[msg("Attestation is not for this key, credential or schema")]
+    InvalidAttestation,
+    #[msg("Attestation has expired")]
+    AttestationExpired,
+    #[msg("Invalid spend schedule")]
+    InvalidSpendSchedule,
+    #[msg("Spend is outside the authorization's spend schedule")]
+    OutsideSpendSchedule,
+    #[msg("Invalid stake lock duration")]
+    InvalidLockDuration,
+    #[msg("Stake is still locked")]
+    StakeStillLocked,
+    #[msg("No active stake lock")]
+    NoActiveLock,
+    #[msg("Unstake cooldown has already elapsed")]
+    UnstakeCooldownElapsed,
+    #[msg("Early-exit penalty too high (max 50%)")]
+    InvalidExitPenalty,
+    #[msg("Stake beneficiary can't be the default pubkey")]
+    InvalidStakeBeneficiary,
+    #[msg("Tier delegation doesn't match the spender and delegator")]
+    InvalidTierDelegation,
+    #[msg("Tier is already delegated; revoke the delegation first")]
+    TierAlreadyDelegated,
+    #[msg("A pool's first deposit must exceed the locked minimum liquidity")]
+    FirstDepositTooSmall,
+    #[msg("Refund exceeds what is left of the spend")]
+    RefundExceedsSpend,
+    #[msg("A routed spend's refund needs its pool agreement, creditor pool and creditor vault")]
+    CreditorAccountsRequired,
+    #[msg("Airdrop claim deadline must be in the future")]
+    InvalidClaimDeadline,
+    #[msg("Airdrop claim window has closed")]
+    AirdropClaimWindowClosed,
+    #[msg("Airdrop claim window is still open")]
+    AirdropClaimWindowOpen,
+    #[msg("Early-exit penalties need a fee vault holding $SHADE")]
+    ExitPenaltyMintMismatch,
 }
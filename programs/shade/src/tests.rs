@@ -0,0 +1,565 @@
+use super::*;
+
+/// Little-endian field writer for building account fixtures byte by byte,
+/// independently of the program's own (de)serialization
+#[derive(Default)]
+struct Fixture(Vec<u8>);
+
+impl Fixture {
+    fn account(discriminator: &[u8]) -> Self {
+        Fixture(discriminator.to_vec())
+    }
+
+    fn key(mut self, key: &Pubkey) -> Self {
+        self.0.extend_from_slice(key.as_ref());
+        self
+    }
+
+    fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn u8(mut self, value: u8) -> Self {
+        self.0.push(value);
+        self
+    }
+
+    fn u16(mut self, value: u16) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn i64(mut self, value: i64) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+}
+
+/// Run `f` against a program-owned account holding `data`
+fn with_account<R>(data: &mut [u8], f: impl FnOnce(&AccountInfo) -> R) -> R {
+    let key = Pubkey::new_unique();
+    let mut lamports = 1_000_000;
+    let info = AccountInfo::new(&key, false, true, &mut lamports, data, &crate::ID, false, 0);
+    f(&info)
+}
+
+// ----------------------------------------------------------------------------
+// Migrations from the original single-instance layouts
+// ----------------------------------------------------------------------------
+
+fn baseline_config(total_staked: u64, total_fees_collected: u64) -> Vec<u8> {
+    Fixture::account(ProtocolConfig::DISCRIMINATOR)
+        .key(&Pubkey::new_from_array([1; 32])) // authority
+        .key(&Pubkey::new_from_array([2; 32])) // shade_mint
+        .key(&Pubkey::new_from_array([3; 32])) // fee_vault
+        .key(&Pubkey::new_from_array([4; 32])) // staking_vault
+        .u16(25) // fee_basis_points
+        .u64(total_staked)
+        .u64(total_fees_collected)
+        .u64(7) // total_fees_distributed
+        .u64(10_000_000_000) // bronze_threshold
+        .u64(100_000_000_000) // silver_threshold
+        .u64(500_000_000_000) // gold_threshold
+        .u16(100) // bronze_cap_multiplier
+        .u16(500) // silver_cap_multiplier
+        .u16(1000) // gold_cap_multiplier
+        .u8(254) // bump
+        .0
+}
+
+#[test]
+fn baseline_config_reads_as_a_prefix_of_the_current_layout() {
+    let mut data = baseline_config(1_000, 500);
+    data.resize(ProtocolConfig::LEN, 0);
+    let config = ProtocolConfig::try_deserialize(&mut &data[..]).unwrap();
+
+    assert_eq!(config.authority, Pubkey::new_from_array([1; 32]));
+    assert_eq!(config.staking_vault, Pubkey::new_from_array([4; 32]));
+    assert_eq!(config.fee_basis_points, 25);
+    assert_eq!(config.total_staked, 1_000);
+    assert_eq!(config.total_fees_collected, 500);
+    assert_eq!(config.gold_cap_multiplier, 1000);
+    assert_eq!(config.bump, 254);
+    assert_eq!(config.version, 0);
+    assert_eq!(config.instance_seed, [0; 32]);
+}
+
+#[test]
+fn legacy_config_upgrade_fills_defaults_and_freezes_fee_snapshot() {
+    let mut data = baseline_config(1_000, 500);
+    data.resize(ProtocolConfig::LEN, 0);
+    let mut config = ProtocolConfig::try_deserialize(&mut &data[..]).unwrap();
+    config.upgrade_from_legacy(253, 1_700_000_000);
+
+    assert_eq!(config.vault_authority_bump, 253);
+    assert_eq!(config.base_spending_cap, DEFAULT_BASE_SPENDING_CAP);
+    assert_eq!(config.staker_share_bps, 10_000);
+    assert_eq!(config.early_exit_penalty_bps, 0);
+    assert_eq!(config.emissions_updated_at, 1_700_000_000);
+    assert_eq!(config.legacy_fees_collected, 500);
+    assert_eq!(config.legacy_total_staked, 1_000);
+    // The original tier caps come back out of the defaults
+    assert_eq!(config.tier_max_caps()[3], 10_000_000_000);
+}
+
+#[test]
+fn default_instance_keeps_the_original_config_address() {
+    let (legacy, _) = Pubkey::find_program_address(&[b"protocol_config"], &crate::ID);
+    let (default_instance, _) = Pubkey::find_program_address(
+        &[b"protocol_config", instance_config_seed(&[0; 32])],
+        &crate::ID,
+    );
+    let (other_instance, _) = Pubkey::find_program_address(
+        &[b"protocol_config", instance_config_seed(&[9; 32])],
+        &crate::ID,
+    );
+    assert_eq!(default_instance, legacy);
+    assert_ne!(other_instance, legacy);
+}
+
+fn baseline_staker(user: &Pubkey, staked: u64, pending: u64, snapshot: u64) -> Vec<u8> {
+    Fixture::account(Staker::DISCRIMINATOR)
+        .key(user)
+        .u64(staked)
+        .u64(pending)
+        .i64(1_650_000_000) // last_claim_timestamp
+        .u64(snapshot) // last_fees_snapshot
+        .u8(1) // tier
+        .u8(255) // bump
+        .0
+}
+
+#[test]
+fn legacy_staker_decodes_the_snapshot_layout() {
+    let user = Pubkey::new_unique();
+    let mut data = baseline_staker(&user, 400, 12, 100);
+    assert_eq!(data.len(), LegacyStaker::LEN);
+
+    let legacy = with_account(&mut data, LegacyStaker::decode).unwrap();
+    assert_eq!(legacy.user, user);
+    assert_eq!(legacy.staked_amount, 400);
+    assert_eq!(legacy.pending_rewards, 12);
+    assert_eq!(legacy.last_claim_timestamp, 1_650_000_000);
+    assert_eq!(legacy.last_fees_snapshot, 100);
+    assert_eq!(legacy.bump, 255);
+}
+
+#[test]
+fn legacy_staker_rejects_current_positions() {
+    let mut data = vec![0; Staker::LEN];
+    data[..8].copy_from_slice(Staker::DISCRIMINATOR);
+    assert!(with_account(&mut data, LegacyStaker::decode).is_err());
+}
+
+#[test]
+fn legacy_staker_fee_share_uses_the_frozen_snapshot() {
+    let mut data = baseline_config(1_000, 500);
+    data.resize(ProtocolConfig::LEN, 0);
+    let mut config = ProtocolConfig::try_deserialize(&mut &data[..]).unwrap();
+    config.upgrade_from_legacy(253, 0);
+    // Fees booked after the migration don't count under the old model
+    config.total_fees_collected = 10_000;
+
+    let legacy = LegacyStaker {
+        staked_amount: 400,
+        last_fees_snapshot: 100,
+        ..Default::default()
+    };
+    // (500 - 100) * 400 / 1000
+    assert_eq!(legacy.fee_share(&config).unwrap(), 160);
+}
+
+#[test]
+fn legacy_fog_pool_decodes_the_original_layout() {
+    let authority = Pubkey::new_unique();
+    let vault = Pubkey::new_unique();
+    let mut data = Fixture::account(FogPool::DISCRIMINATOR)
+        .key(&authority)
+        .key(&vault)
+        .u64(5_000) // total_deposited
+        .u64(1_200) // total_spent
+        .u64(30) // total_fees_generated
+        .u64(2) // active_authorizations
+        .bytes(&[7; 32]) // pool_seed
+        .u8(251) // bump
+        .0;
+    assert_eq!(data.len(), LegacyFogPool::LEN);
+
+    let legacy = with_account(&mut data, LegacyFogPool::decode).unwrap();
+    assert_eq!(legacy.authority, authority);
+    assert_eq!(legacy.vault, vault);
+    assert_eq!(legacy.total_deposited, 5_000);
+    assert_eq!(legacy.total_spent, 1_200);
+    assert_eq!(legacy.active_authorizations, 2);
+    assert_eq!(legacy.pool_seed, [7; 32]);
+    assert_eq!(legacy.bump, 251);
+}
+
+#[test]
+fn legacy_fog_pool_rejects_other_accounts() {
+    let mut data = baseline_staker(&Pubkey::new_unique(), 1, 0, 0);
+    data.resize(LegacyFogPool::LEN, 0);
+    assert!(with_account(&mut data, LegacyFogPool::decode).is_err());
+}
+
+// ----------------------------------------------------------------------------
+// Fee accumulator
+// ----------------------------------------------------------------------------
+
+#[test]
+fn warming_stake_takes_no_share_of_booked_fees() {
+    let mut config = ProtocolConfig {
+        total_staked: 1_000,
+        total_warming: 400,
+        ..Default::default()
+    };
+    let active = Staker {
+        staked_amount: 600,
+        ..Default::default()
+    };
+    let warming = Staker {
+        staked_amount: 400,
+        warming_amount: 400,
+        ..Default::default()
+    };
+
+    book_protocol_fee(&mut config, 600).unwrap();
+
+    // The whole fee goes to the active stake; nothing is left unallocated
+    assert_eq!(active.fee_entitlement(config.acc_fee_per_share), 600);
+    assert_eq!(warming.fee_entitlement(config.acc_fee_per_share), 0);
+    // Emissions still count warming stake
+    assert_eq!(config.total_fee_shares(), 600);
+    assert_eq!(config.total_emission_shares(), 1_000);
+}
+
+#[test]
+fn activated_stake_only_earns_fees_booked_after_activation() {
+    let mut config = ProtocolConfig {
+        total_staked: 1_000,
+        total_warming: 400,
+        ..Default::default()
+    };
+    let mut staker = Staker {
+        staked_amount: 400,
+        warming_amount: 400,
+        ..Default::default()
+    };
+    book_protocol_fee(&mut config, 600).unwrap();
+
+    // Activation checkpoints the debt at the current index
+    let warming = staker.warming_amount;
+    drop_warming(&mut staker, &mut config, warming);
+    staker.reward_debt = staker.fee_entitlement(config.acc_fee_per_share);
+    assert_eq!(config.total_warming, 0);
+
+    book_protocol_fee(&mut config, 1_000).unwrap();
+    let earned = staker.fee_entitlement(config.acc_fee_per_share) - staker.reward_debt;
+    assert_eq!(earned, 400);
+}
+
+// ----------------------------------------------------------------------------
+// Spend ring receipts
+// ----------------------------------------------------------------------------
+
+#[test]
+fn spend_record_len_matches_its_serialized_size() {
+    let record = SpendRecord {
+        amount: 1,
+        refunded: 1,
+        ..Default::default()
+    };
+    assert_eq!(record.try_to_vec().unwrap().len(), SpendRecord::LEN);
+}
+
+#[test]
+fn largest_spend_ring_fits_one_account_creation() {
+    assert!(SpendRing::space(MAX_SPEND_RING_CAPACITY) <= 10_240);
+    assert_eq!(
+        SpendRing::slot_offset(MAX_SPEND_RING_CAPACITY as usize),
+        SpendRing::space(MAX_SPEND_RING_CAPACITY)
+    );
+}
+
+// ----------------------------------------------------------------------------
+// Refunds
+// ----------------------------------------------------------------------------
+
+#[test]
+fn restored_limits_are_reserved_again() {
+    let mut authorization = Authorization {
+        spending_cap: 1_000,
+        amount_spent: 600,
+        reserved_amount: 400,
+        ..Default::default()
+    };
+    let mut fog_pool = FogPool {
+        reserved_amount: 400,
+        ..Default::default()
+    };
+
+    restore_spend_limits(&mut authorization, &mut fog_pool, 1_000, 250, 0, 10).unwrap();
+    assert_eq!(authorization.amount_spent, 350);
+    assert_eq!(authorization.reserved_amount, 650);
+    assert_eq!(fog_pool.reserved_amount, 650);
+}
+
+#[test]
+fn restored_limits_need_free_liquidity() {
+    let mut authorization = Authorization {
+        spending_cap: 1_000,
+        amount_spent: 600,
+        reserved_amount: 400,
+        ..Default::default()
+    };
+    let mut fog_pool = FogPool {
+        reserved_amount: 400,
+        ..Default::default()
+    };
+
+    // Only 500 in the vault, so 400 + 250 can't be reserved
+    assert!(restore_spend_limits(&mut authorization, &mut fog_pool, 500, 250, 0, 10).is_err());
+}
+
+// ----------------------------------------------------------------------------
+// Tier caps
+// ----------------------------------------------------------------------------
+
+#[test]
+fn token_value_inverts_usd_value() {
+    // $1.50 with a 6-decimal mint, priced with exponent -8
+    let price = 150_000_000;
+    let usd = usd_value(2_000_000, 6, price, -8).unwrap();
+    assert_eq!(usd, 3_000_000);
+    assert_eq!(token_value(usd, 6, price, -8).unwrap(), 2_000_000);
+}
+
+#[test]
+fn spend_tier_check_compares_token_amounts() {
+    let mut fog_pool = FogPool {
+        spend_tier_check: true,
+        ..Default::default()
+    };
+    fog_pool.tier_max_caps = [100, 1_000, 10_000, 100_000];
+
+    assert!(fog_pool.check_spend_tier(Some(1), 1_000).is_ok());
+    assert!(fog_pool.check_spend_tier(Some(1), 1_001).is_err());
+    // No staker passed while the pool checks tiers
+    assert!(fog_pool.check_spend_tier(None, 1).is_err());
+    // $3 spent at $1.50 is 2 tokens (6 decimals), within a 2-token cap
+    fog_pool.tier_max_caps[0] = 2_000_000;
+    let spent = token_value(3_000_000, 6, 150_000_000, -8).unwrap();
+    assert!(fog_pool.check_spend_tier(Some(0), spent).is_ok());
+}
+
+// ----------------------------------------------------------------------------
+// Utilization
+// ----------------------------------------------------------------------------
+
+#[test]
+fn utilization_tracks_live_commitments() {
+    let fog_pool = FogPool {
+        reserved_amount: 300,
+        escrowed_amount: 100,
+        payables: 100,
+        pending_fees: 0,
+        // Lifetime totals no longer matter
+        total_spent: 1_000_000,
+        total_deposited: 1,
+        ..Default::default()
+    };
+    assert_eq!(fog_pool.utilization_bps(1_000), 5_000);
+    assert_eq!(fog_pool.utilization_bps(0), 10_000);
+}
+
+#[test]
+fn utilization_cap_counts_only_unreserved_draws() {
+    let fog_pool = FogPool {
+        reserved_amount: 400,
+        max_utilization_bps: 5_000,
+        ..Default::default()
+    };
+    // 400 committed + 100 unreserved = half of 1000
+    assert!(fog_pool.check_utilization(100, 0, 1_000).is_ok());
+    assert!(fog_pool.check_utilization(101, 0, 1_000).is_err());
+    // Drawing from the authorization's own reservation doesn't add to it
+    assert!(fog_pool.check_utilization(300, 300, 1_000).is_ok());
+}
+
+#[test]
+fn full_spender_index_pages_roll_over() {
+    let mut head = SpenderIndexHead {
+        tail_len: SPENDER_INDEX_PAGE_SIZE as u32 - 1,
+        ..Default::default()
+    };
+    assert_eq!(head.next_page(), 0);
+    head.tail_len += 1;
+    // A filled page never blocks new authorizations
+    assert_eq!(head.next_page(), 1);
+
+    let authorization = Pubkey::new_unique();
+    let mut page = SpenderIndex {
+        authorizations: vec![Pubkey::new_unique(), authorization],
+        ..Default::default()
+    };
+    assert!(page.remove(&authorization));
+    assert!(!page.remove(&authorization));
+    assert_eq!(page.authorizations.len(), 1);
+}
+
+#[test]
+fn emergency_forfeits_fail_without_touching_the_config() {
+    let staker = Staker {
+        staked_amount: 1_000,
+        pending_rewards: u64::MAX,
+        reward_debt: 0,
+        ..Default::default()
+    };
+    let mut config = ProtocolConfig {
+        total_staked: 1_000,
+        acc_fee_per_share: FEE_PRECISION,
+        ..Default::default()
+    };
+    // The caller books the forfeits on a copy and drops them on failure
+    assert!(forfeit_staker_rewards(&staker, &mut config.clone()).is_err());
+
+    let staker = Staker {
+        pending_rewards: 5,
+        ..staker
+    };
+    let (fees, rebase, emissions) = forfeit_staker_rewards(&staker, &mut config).unwrap();
+    assert_eq!((fees, rebase, emissions), (1_005, 0, 0));
+    assert!(config.acc_fee_per_share > FEE_PRECISION);
+}
+
+#[test]
+fn signed_messages_are_bound_to_this_program() {
+    let (pool, spender) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let permit = spend_permit_message(&pool, &spender, 100, 7, 1_000);
+    let domain = SPEND_PERMIT_DOMAIN.len();
+    assert_eq!(&permit[domain..domain + 32], crate::ID.as_ref());
+    assert_eq!(&permit[domain + 32..domain + 64], pool.as_ref());
+
+    let approval = spend_approval_message(&pool, &spender, 100, 7);
+    let domain = SPEND_APPROVAL_DOMAIN.len();
+    assert_eq!(&approval[domain..domain + 32], crate::ID.as_ref());
+    assert_eq!(approval.len(), domain + 32 + 32 + 32 + 8 + 8);
+}
+
+#[test]
+fn airdrop_claims_close_at_the_deadline() {
+    let claimant = Pubkey::new_unique();
+    let leaf = hashv(&[
+        &[0u8],
+        &0u64.to_le_bytes(),
+        claimant.as_ref(),
+        &10u64.to_le_bytes(),
+    ])
+    .to_bytes();
+    let mut airdrop = Airdrop {
+        merkle_root: leaf,
+        total_amount: 10,
+        claim_deadline: 100,
+        ..Default::default()
+    };
+    assert!(verify_airdrop_claim(&mut airdrop.clone(), 0, &claimant, 10, &[], 100).is_err());
+    assert!(verify_airdrop_claim(&mut airdrop, 0, &claimant, 10, &[], 99).is_ok());
+    assert_eq!(airdrop.claimed_amount, 10);
+}
+
+#[test]
+fn vesting_credit_counts_toward_tier_but_not_fees() {
+    let config = ProtocolConfig {
+        bronze_threshold: 500,
+        silver_threshold: 5_000,
+        gold_threshold: 50_000,
+        ..Default::default()
+    };
+    let staker = Staker {
+        staked_amount: 100,
+        vesting_tier_credit: 900,
+        ..Default::default()
+    };
+    assert_eq!(calculate_tier(staker.tier_weight(), &config), 1);
+    assert_eq!(staker.fee_entitlement(FEE_PRECISION), 100);
+}
+
+#[test]
+fn protocol_fees_set_aside_the_rebase_cut() {
+    let mut config = ProtocolConfig {
+        rebase_bps: 2_000,
+        total_staked: 1_000,
+        ..Default::default()
+    };
+    book_protocol_fee(&mut config, 100).unwrap();
+    assert_eq!(config.rebase_fees_pending, 20);
+    assert_eq!(config.total_fees_collected, 80);
+    assert_eq!(config.acc_fee_per_share, 80 * FEE_PRECISION / 1_000);
+}
+
+#[test]
+fn warming_stake_earns_no_fee_share() {
+    let mut config = ProtocolConfig {
+        total_staked: 1_000,
+        total_warming: 500,
+        ..Default::default()
+    };
+    book_protocol_fee(&mut config, 100).unwrap();
+    // 100 shared over the 500 active shares only
+    assert_eq!(config.acc_fee_per_share, 100 * FEE_PRECISION / 500);
+}
+
+#[test]
+fn lp_value_nets_out_fees_escrow_and_routed_debt() {
+    let fog_pool = FogPool {
+        pending_fees: 10,
+        escrowed_amount: 20,
+        receivables: 100,
+        payables: 30,
+        ..Default::default()
+    };
+    assert_eq!(fog_pool.available_balance(1_000), 970);
+    assert_eq!(fog_pool.lp_value(1_000), 1_040);
+}
+
+#[test]
+fn federation_positions_rebook_into_receivables_and_payables() {
+    let mut fog_pool = FogPool::default();
+    fog_pool.rebook_federation_position(0, 40);
+    assert_eq!((fog_pool.receivables, fog_pool.payables), (40, 0));
+    assert_eq!(fog_pool.lp_value(1_000), 1_040);
+
+    // Swinging from owed to owing moves the position across
+    fog_pool.rebook_federation_position(40, -25);
+    assert_eq!((fog_pool.receivables, fog_pool.payables), (0, 25));
+    assert_eq!(fog_pool.lp_value(1_000), 975);
+
+    // A position opened before it was tracked saturates at zero
+    fog_pool.rebook_federation_position(-60, 0);
+    assert_eq!((fog_pool.receivables, fog_pool.payables), (0, 0));
+}
+
+#[test]
+fn first_deposit_locks_the_minimum_and_donated_tokens() {
+    // 50 tokens were donated to the vault before anyone deposited
+    let (shares, locked) = lp_shares_for_deposit(MIN_LP_LIQUIDITY + 1_000, 50, 0).unwrap();
+    assert_eq!((shares, locked), (1_000, 50 + MIN_LP_LIQUIDITY));
+    assert!(lp_shares_for_deposit(MIN_LP_LIQUIDITY, 0, 0).is_err());
+
+    // Later deposits and withdrawals are priced pro-rata
+    let supply = shares + locked;
+    let value = MIN_LP_LIQUIDITY + 1_050;
+    let (more, locked) = lp_shares_for_deposit(value, value, supply).unwrap();
+    assert_eq!((more, locked), (supply, 0));
+    assert_eq!(
+        lp_amount_for_shares(more, 2 * value, 2 * supply).unwrap(),
+        value
+    );
+    assert!(lp_amount_for_shares(2 * supply + 1, 2 * value, 2 * supply).is_err());
+}
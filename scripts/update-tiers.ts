@@ -3,6 +3,7 @@ import { Program } from "@coral-xyz/anchor";
 import { Shade } from "../target/types/shade";
 import { PublicKey, Keypair } from "@solana/web3.js";
 import * as fs from "fs";
+import { instanceConfigSeed } from "../tests/instance";
 
 async function main() {
   // Set up provider
@@ -11,13 +12,16 @@ async function main() {
 
   const program = anchor.workspace.Shade as Program<Shade>;
 
+  // Default (all-zero) protocol instance
+  const instanceSeed = new Uint8Array(32);
+
   // Load deployer
   const deployerKeyfile = fs.readFileSync("D:/Dev/Keys/shade-deployer.json", "utf-8");
   const deployer = Keypair.fromSecretKey(Uint8Array.from(JSON.parse(deployerKeyfile)));
 
   // Get protocol config PDA
   const [protocolConfigPda] = PublicKey.findProgramAddressSync(
-    [Buffer.from("protocol_config")],
+    [Buffer.from("protocol_config"), instanceConfigSeed(instanceSeed)],
     program.programId
   );
 
@@ -0,0 +1,8 @@
+// Instance part of the protocol config PDA seeds, as the program's
+// instance_config_seed derives it: the default (all-zero) instance keeps the
+// original ["protocol_config"] address, other instances append their seed
+export function instanceConfigSeed(instanceSeed: Uint8Array): Buffer {
+  return instanceSeed.every((byte) => byte === 0)
+    ? Buffer.alloc(0)
+    : Buffer.from(instanceSeed);
+}
@@ -15,6 +15,7 @@ import {
 } from "@solana/spl-token";
 import { expect } from "chai";
 import * as fs from "fs";
+import { instanceConfigSeed } from "./instance";
 
 describe("SHADE Protocol - New Threshold Testing (10K/100K/500K)", () => {
   const provider = anchor.AnchorProvider.env();
@@ -22,6 +23,9 @@ describe("SHADE Protocol - New Threshold Testing (10K/100K/500K)", () => {
 
   const program = anchor.workspace.Shade as Program<Shade>;
 
+  // Default (all-zero) protocol instance
+  const instanceSeed = new Uint8Array(32);
+
   // Load pre-funded deployer
   const deployerKeyfile = fs.readFileSync("D:/Dev/Keys/shade-deployer.json", "utf-8");
   const deployer = Keypair.fromSecretKey(Uint8Array.from(JSON.parse(deployerKeyfile)));
@@ -54,7 +58,7 @@ describe("SHADE Protocol - New Threshold Testing (10K/100K/500K)", () => {
 
     // Get protocol config PDA
     [protocolConfigPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("protocol_config")],
+      [Buffer.from("protocol_config"), instanceConfigSeed(instanceSeed)],
       program.programId
     );
 
@@ -83,7 +87,7 @@ describe("SHADE Protocol - New Threshold Testing (10K/100K/500K)", () => {
   describe("Tier Assignment with 10K/100K/500K Thresholds", () => {
     it("5,000 SHADE → Tier 0 (None) - Below 10K threshold", async () => {
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), noTierUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), noTierUser.publicKey.toBuffer()],
         program.programId
       );
 
@@ -114,7 +118,7 @@ describe("SHADE Protocol - New Threshold Testing (10K/100K/500K)", () => {
 
     it("15,000 SHADE → Tier 1 (Bronze) - Above 10K threshold", async () => {
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), bronzeUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), bronzeUser.publicKey.toBuffer()],
         program.programId
       );
 
@@ -145,7 +149,7 @@ describe("SHADE Protocol - New Threshold Testing (10K/100K/500K)", () => {
 
     it("150,000 SHADE → Tier 2 (Silver) - Above 100K threshold", async () => {
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), silverUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), silverUser.publicKey.toBuffer()],
         program.programId
       );
 
@@ -176,7 +180,7 @@ describe("SHADE Protocol - New Threshold Testing (10K/100K/500K)", () => {
 
     it("600,000 SHADE → Tier 3 (Gold) - Above 500K threshold", async () => {
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), goldUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), goldUser.publicKey.toBuffer()],
         program.programId
       );
 
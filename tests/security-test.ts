@@ -16,6 +16,7 @@ import {
 } from "@solana/spl-token";
 import { expect } from "chai";
 import * as fs from "fs";
+import { instanceConfigSeed } from "./instance";
 
 describe("SHADE Protocol - Security Tests", () => {
   const provider = anchor.AnchorProvider.env();
@@ -23,6 +24,9 @@ describe("SHADE Protocol - Security Tests", () => {
 
   const program = anchor.workspace.Shade as Program<Shade>;
 
+  // Default (all-zero) protocol instance
+  const instanceSeed = new Uint8Array(32);
+
   // Load the pre-funded deployer wallet
   const deployerKeyfile = fs.readFileSync("D:/Dev/Keys/shade-deployer.json", "utf-8");
   const deployer = Keypair.fromSecretKey(Uint8Array.from(JSON.parse(deployerKeyfile)));
@@ -70,7 +74,7 @@ describe("SHADE Protocol - Security Tests", () => {
 
     // Get PDAs
     [protocolConfigPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("protocol_config")],
+      [Buffer.from("protocol_config"), instanceConfigSeed(instanceSeed)],
       program.programId
     );
 
@@ -17,6 +17,7 @@ import {
 } from "@solana/spl-token";
 import { expect } from "chai";
 import * as fs from "fs";
+import { instanceConfigSeed } from "./instance";
 
 describe("SHADE Protocol - Staking & Multi-User Tests", () => {
   const provider = anchor.AnchorProvider.env();
@@ -24,6 +25,9 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
 
   const program = anchor.workspace.Shade as Program<Shade>;
 
+  // Default (all-zero) protocol instance
+  const instanceSeed = new Uint8Array(32);
+
   // Load pre-funded deployer
   const deployerKeyfile = fs.readFileSync("D:/Dev/Keys/shade-deployer.json", "utf-8");
   const deployer = Keypair.fromSecretKey(Uint8Array.from(JSON.parse(deployerKeyfile)));
@@ -95,7 +99,7 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
 
     // Derive PDAs
     [protocolConfigPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("protocol_config")],
+      [Buffer.from("protocol_config"), instanceConfigSeed(instanceSeed)],
       program.programId
     );
 
@@ -105,17 +109,17 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
     );
 
     [aliceStakerPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("staker"), alice.publicKey.toBuffer()],
+      [Buffer.from("staker"), protocolConfigPda.toBuffer(), alice.publicKey.toBuffer()],
       program.programId
     );
 
     [bobStakerPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("staker"), bob.publicKey.toBuffer()],
+      [Buffer.from("staker"), protocolConfigPda.toBuffer(), bob.publicKey.toBuffer()],
       program.programId
     );
 
     [charlieStakerPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("staker"), charlie.publicKey.toBuffer()],
+      [Buffer.from("staker"), protocolConfigPda.toBuffer(), charlie.publicKey.toBuffer()],
       program.programId
     );
 
@@ -203,7 +207,7 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
     // Initialize protocol if needed (skip if already exists - we can't reinitialize)
     if (!protocolExists) {
       await program.methods
-        .initializeProtocol(10) // 0.1% fee
+        .initializeProtocol(Array.from(instanceSeed), 10) // 0.1% fee
         .accounts({
           protocolConfig: protocolConfigPda,
           shadeMint: shadeMint,
@@ -295,14 +299,11 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
 
       try {
         await program.methods
-          .unstake(new anchor.BN(0))
+          .requestUnstake(new anchor.BN(0))
           .accounts({
             protocolConfig: protocolConfigPda,
             staker: aliceStakerPda,
-            stakingVault: stakingVault,
-            userShadeAccount: aliceShadeAccount,
             user: alice.publicKey,
-            tokenProgram: TOKEN_PROGRAM_ID,
           })
           .signers([alice])
           .rpc();
@@ -319,14 +320,11 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
 
       try {
         await program.methods
-          .unstake(new anchor.BN(overAmount))
+          .requestUnstake(new anchor.BN(overAmount))
           .accounts({
             protocolConfig: protocolConfigPda,
             staker: aliceStakerPda,
-            stakingVault: stakingVault,
-            userShadeAccount: aliceShadeAccount,
             user: alice.publicKey,
-            tokenProgram: TOKEN_PROGRAM_ID,
           })
           .signers([alice])
           .rpc();
@@ -399,11 +397,22 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
       
       // Unstake most of it
       await program.methods
-        .unstake(new anchor.BN(350_000_000)) // Should drop below 100
+        .requestUnstake(new anchor.BN(350_000_000)) // Should drop below 100
+        .accounts({
+          protocolConfig: protocolConfigPda,
+          staker: aliceStakerPda,
+          user: alice.publicKey,
+        })
+        .signers([alice])
+        .rpc();
+
+      await program.methods
+        .completeUnstake()
         .accounts({
           protocolConfig: protocolConfigPda,
           staker: aliceStakerPda,
           stakingVault: stakingVault,
+          shadeMint: shadeMint,
           userShadeAccount: aliceShadeAccount,
           user: alice.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
@@ -505,11 +514,22 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
     it("Multiple users can unstake independently", async () => {
       // Charlie unstakes 5000, should still be Gold
       await program.methods
-        .unstake(new anchor.BN(5_000_000_000))
+        .requestUnstake(new anchor.BN(5_000_000_000))
+        .accounts({
+          protocolConfig: protocolConfigPda,
+          staker: charlieStakerPda,
+          user: charlie.publicKey,
+        })
+        .signers([charlie])
+        .rpc();
+
+      await program.methods
+        .completeUnstake()
         .accounts({
           protocolConfig: protocolConfigPda,
           staker: charlieStakerPda,
           stakingVault: stakingVault,
+          shadeMint: shadeMint,
           userShadeAccount: charlieShadeAccount,
           user: charlie.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
@@ -566,14 +586,11 @@ describe("SHADE Protocol - Staking & Multi-User Tests", () => {
     it("BLOCKS: User A cannot unstake User B's stake", async () => {
       try {
         await program.methods
-          .unstake(new anchor.BN(100_000_000))
+          .requestUnstake(new anchor.BN(100_000_000))
           .accounts({
             protocolConfig: protocolConfigPda,
             staker: bobStakerPda, // Bob's staker account
-            stakingVault: stakingVault,
-            userShadeAccount: aliceShadeAccount, // Alice trying to receive
             user: alice.publicKey, // Alice signing
-            tokenProgram: TOKEN_PROGRAM_ID,
           })
           .signers([alice])
           .rpc();
@@ -16,12 +16,16 @@ import {
   createAssociatedTokenAccountInstruction,
 } from "@solana/spl-token";
 import { expect } from "chai";
+import { instanceConfigSeed } from "./instance";
 
 describe("SHADE Protocol - Staking (No Airdrop)", () => {
   const provider = anchor.AnchorProvider.env();
   anchor.setProvider(provider);
   const program = anchor.workspace.shade as Program<Shade>;
 
+  // Default (all-zero) protocol instance
+  const instanceSeed = new Uint8Array(32);
+
   // Use the deployer wallet for everything (already funded)
   const authority = (provider.wallet as anchor.Wallet).payer;
 
@@ -73,7 +77,7 @@ describe("SHADE Protocol - Staking (No Airdrop)", () => {
 
     // Derive protocol config PDA
     [protocolConfig] = PublicKey.findProgramAddressSync(
-      [Buffer.from("protocol_config")],
+      [Buffer.from("protocol_config"), instanceConfigSeed(instanceSeed)],
       program.programId
     );
     console.log("Protocol Config:", protocolConfig.toBase58());
@@ -153,7 +157,7 @@ describe("SHADE Protocol - Staking (No Airdrop)", () => {
 
     // Derive staker PDA for authority
     [stakerAccount] = PublicKey.findProgramAddressSync(
-      [Buffer.from("staker"), authority.publicKey.toBuffer()],
+      [Buffer.from("staker"), protocolConfig.toBuffer(), authority.publicKey.toBuffer()],
       program.programId
     );
 
@@ -188,7 +192,7 @@ describe("SHADE Protocol - Staking (No Airdrop)", () => {
     it("Initializes protocol with 0.1% fee", async () => {
       try {
         await program.methods
-          .initializeProtocol(10) // 0.1% fee
+          .initializeProtocol(Array.from(instanceSeed), 10) // 0.1% fee
           .accounts({
             protocolConfig,
             shadeMint,
@@ -251,11 +255,21 @@ describe("SHADE Protocol - Staking (No Airdrop)", () => {
       const stakeBeforeUnstake = stakerBefore.stakedAmount.toNumber();
 
       await program.methods
-        .unstake(new anchor.BN(unstakeAmount))
+        .requestUnstake(new anchor.BN(unstakeAmount))
+        .accounts({
+          protocolConfig,
+          staker: stakerAccount,
+          user: authority.publicKey,
+        })
+        .rpc();
+
+      await program.methods
+        .completeUnstake()
         .accounts({
           protocolConfig,
           staker: stakerAccount,
           stakingVault,
+          shadeMint,
           userShadeAccount: authorityShadeAccount,
           user: authority.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
@@ -372,38 +386,46 @@ describe("SHADE Protocol - Staking (No Airdrop)", () => {
   });
 
   describe("Fee Distribution", () => {
-    it("Distributes and claims fees", async () => {
-      // Distribute fees to staker
+    it("Sweeps and claims fees", async () => {
+      // Sweep the pool's pending fees into the staker fee vault
       await program.methods
-        .distributeFees()
+        .sweepFees()
         .accounts({
+          fogPool,
           protocolConfig,
-          staker: stakerAccount,
+          vault: fogPoolVault,
+          mint: spendMint,
+          feeVault,
+          poolFeeVault: null,
+          feeTreasury: null,
+          keeperTokenAccount: authoritySpendAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
 
-      const staker = await program.account.staker.fetch(stakerAccount);
-      const pending = staker.pendingRewards.toNumber();
-      console.log("Pending rewards:", pending / 1_000_000, "tokens");
+      const before = await getAccount(provider.connection, authoritySpendAccount);
 
-      if (pending > 0) {
-        // Claim rewards
-        await program.methods
-          .claimRewards()
-          .accounts({
-            protocolConfig,
-            staker: stakerAccount,
-            feeVault,
-            userTokenAccount: authoritySpendAccount,
-            user: authority.publicKey,
-            tokenProgram: TOKEN_PROGRAM_ID,
-          })
-          .rpc();
+      // Claim rewards
+      await program.methods
+        .claimRewards()
+        .accounts({
+          protocolConfig,
+          staker: stakerAccount,
+          feeVault,
+          feeMint: spendMint,
+          userTokenAccount: authoritySpendAccount,
+          user: authority.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
 
-        const stakerAfter = await program.account.staker.fetch(stakerAccount);
-        expect(stakerAfter.pendingRewards.toNumber()).to.equal(0);
-        console.log("Claimed", pending / 1_000_000, "tokens in rewards");
-      }
+      const after = await getAccount(provider.connection, authoritySpendAccount);
+      const claimed = Number(after.amount) - Number(before.amount);
+      const stakerAfter = await program.account.staker.fetch(stakerAccount);
+
+      expect(claimed).to.be.greaterThan(0);
+      expect(stakerAfter.pendingRewards.toNumber()).to.equal(0);
+      console.log("Claimed", claimed / 1_000_000, "tokens in rewards");
     });
   });
 
@@ -17,12 +17,16 @@ import {
   createAssociatedTokenAccountInstruction,
 } from "@solana/spl-token";
 import { expect } from "chai";
+import { instanceConfigSeed } from "./instance";
 
 describe("SHADE Protocol - Staking & Fee Tests", () => {
   const provider = anchor.AnchorProvider.env();
   anchor.setProvider(provider);
   const program = anchor.workspace.shade as Program<Shade>;
 
+  // Default (all-zero) protocol instance
+  const instanceSeed = new Uint8Array(32);
+
   // Keypairs
   const authority = (provider.wallet as anchor.Wallet).payer;
   const staker1 = Keypair.generate();
@@ -97,7 +101,7 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
 
     // Derive protocol config PDA
     [protocolConfig, protocolConfigBump] = PublicKey.findProgramAddressSync(
-      [Buffer.from("protocol_config")],
+      [Buffer.from("protocol_config"), instanceConfigSeed(instanceSeed)],
       program.programId
     );
     console.log("Protocol Config:", protocolConfig.toBase58());
@@ -170,11 +174,11 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
 
     // Derive staker PDAs
     [staker1Account] = PublicKey.findProgramAddressSync(
-      [Buffer.from("staker"), staker1.publicKey.toBuffer()],
+      [Buffer.from("staker"), protocolConfig.toBuffer(), staker1.publicKey.toBuffer()],
       program.programId
     );
     [staker2Account] = PublicKey.findProgramAddressSync(
-      [Buffer.from("staker"), staker2.publicKey.toBuffer()],
+      [Buffer.from("staker"), protocolConfig.toBuffer(), staker2.publicKey.toBuffer()],
       program.programId
     );
 
@@ -220,7 +224,7 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
   describe("Protocol Initialization", () => {
     it("Initializes protocol with 0.1% fee (10 basis points)", async () => {
       await program.methods
-        .initializeProtocol(10) // 0.1% fee
+        .initializeProtocol(Array.from(instanceSeed), 10) // 0.1% fee
         .accounts({
           protocolConfig,
           shadeMint,
@@ -248,7 +252,7 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
     it("Rejects fee higher than 10%", async () => {
       try {
         await program.methods
-          .initializeProtocol(1001) // 10.01% - too high
+          .initializeProtocol(Array.from(instanceSeed), 1001) // 10.01% - too high
           .accounts({
             protocolConfig,
             shadeMint,
@@ -327,11 +331,22 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
       const unstakeAmount = 5_000_000_000;
 
       await program.methods
-        .unstake(new anchor.BN(unstakeAmount))
+        .requestUnstake(new anchor.BN(unstakeAmount))
+        .accounts({
+          protocolConfig,
+          staker: staker1Account,
+          user: staker1.publicKey,
+        })
+        .signers([staker1])
+        .rpc();
+
+      await program.methods
+        .completeUnstake()
         .accounts({
           protocolConfig,
           staker: staker1Account,
           stakingVault,
+          shadeMint,
           userShadeAccount: staker1ShadeAccount,
           user: staker1.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
@@ -348,14 +363,11 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
     it("Cannot unstake more than staked", async () => {
       try {
         await program.methods
-          .unstake(new anchor.BN(100_000_000_000)) // Way more than staked
+          .requestUnstake(new anchor.BN(100_000_000_000)) // Way more than staked
           .accounts({
             protocolConfig,
             staker: staker1Account,
-            stakingVault,
-            userShadeAccount: staker1ShadeAccount,
             user: staker1.publicKey,
-            tokenProgram: TOKEN_PROGRAM_ID,
           })
           .signers([staker1])
           .rpc();
@@ -510,23 +522,32 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
   // =========================================================================
 
   describe("Fee Distribution", () => {
-    it("Distributes fees to staker1", async () => {
-      const stakerBefore = await program.account.staker.fetch(staker1Account);
-      const pendingBefore = stakerBefore.pendingRewards.toNumber();
+    it("Sweeps pool fees to stakers", async () => {
+      const configBefore = await program.account.protocolConfig.fetch(protocolConfig);
+      const keeperSpendAccount = getAssociatedTokenAddressSync(spendMint, authority.publicKey);
 
       await program.methods
-        .distributeFees()
+        .sweepFees()
         .accounts({
+          fogPool,
           protocolConfig,
-          staker: staker1Account,
+          vault: fogPoolVault,
+          mint: spendMint,
+          feeVault,
+          poolFeeVault: null,
+          feeTreasury: null,
+          keeperTokenAccount: keeperSpendAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
 
-      const stakerAfter = await program.account.staker.fetch(staker1Account);
-      const pendingAfter = stakerAfter.pendingRewards.toNumber();
-      
-      expect(pendingAfter).to.be.greaterThan(pendingBefore);
-      console.log(`Staker1 pending rewards: ${pendingAfter / 1_000_000} tokens`);
+      const configAfter = await program.account.protocolConfig.fetch(protocolConfig);
+      const booked =
+        configAfter.totalFeesCollected.toNumber() - configBefore.totalFeesCollected.toNumber();
+
+      expect(booked).to.be.greaterThan(0);
+      expect(configAfter.accFeePerShare.gt(configBefore.accFeePerShare)).to.be.true;
+      console.log(`Fees booked for stakers: ${booked / 1_000_000} tokens`);
     });
 
     it("Claims rewards", async () => {
@@ -537,30 +558,29 @@ describe("SHADE Protocol - Staking & Fee Tests", () => {
         spendMint,
         staker1.publicKey
       );
+      const before = await getAccount(provider.connection, staker1SpendAccount);
 
-      const staker = await program.account.staker.fetch(staker1Account);
-      const pendingRewards = staker.pendingRewards.toNumber();
+      await program.methods
+        .claimRewards()
+        .accounts({
+          protocolConfig,
+          staker: staker1Account,
+          feeVault,
+          feeMint: spendMint,
+          userTokenAccount: staker1SpendAccount,
+          user: staker1.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([staker1])
+        .rpc();
 
-      if (pendingRewards > 0) {
-        await program.methods
-          .claimRewards()
-          .accounts({
-            protocolConfig,
-            staker: staker1Account,
-            feeVault,
-            userTokenAccount: staker1SpendAccount,
-            user: staker1.publicKey,
-            tokenProgram: TOKEN_PROGRAM_ID,
-          })
-          .signers([staker1])
-          .rpc();
+      const after = await getAccount(provider.connection, staker1SpendAccount);
+      const claimed = Number(after.amount) - Number(before.amount);
+      const stakerAfter = await program.account.staker.fetch(staker1Account);
 
-        const stakerAfter = await program.account.staker.fetch(staker1Account);
-        expect(stakerAfter.pendingRewards.toNumber()).to.equal(0);
-        console.log(`Claimed ${pendingRewards / 1_000_000} tokens in rewards`);
-      } else {
-        console.log("No pending rewards to claim");
-      }
+      expect(claimed).to.be.greaterThan(0);
+      expect(stakerAfter.pendingRewards.toNumber()).to.equal(0);
+      console.log(`Claimed ${claimed / 1_000_000} tokens in rewards`);
     });
   });
 
@@ -15,6 +15,7 @@ import {
 } from "@solana/spl-token";
 import { expect } from "chai";
 import * as fs from "fs";
+import { instanceConfigSeed } from "./instance";
 
 describe("SHADE Protocol - Tier Threshold Tests", () => {
   const provider = anchor.AnchorProvider.env();
@@ -22,6 +23,9 @@ describe("SHADE Protocol - Tier Threshold Tests", () => {
 
   const program = anchor.workspace.Shade as Program<Shade>;
 
+  // Default (all-zero) protocol instance
+  const instanceSeed = new Uint8Array(32);
+
   // Load pre-funded deployer
   const deployerKeyfile = fs.readFileSync("D:/Dev/Keys/shade-deployer.json", "utf-8");
   const deployer = Keypair.fromSecretKey(Uint8Array.from(JSON.parse(deployerKeyfile)));
@@ -52,7 +56,7 @@ describe("SHADE Protocol - Tier Threshold Tests", () => {
 
     // Get protocol config
     [protocolConfigPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("protocol_config")],
+      [Buffer.from("protocol_config"), instanceConfigSeed(instanceSeed)],
       program.programId
     );
 
@@ -79,7 +83,7 @@ describe("SHADE Protocol - Tier Threshold Tests", () => {
       await fundAccount(testUser.publicKey, 0.05 * LAMPORTS_PER_SOL);
 
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), testUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), testUser.publicKey.toBuffer()],
         program.programId
       );
 
@@ -115,7 +119,7 @@ describe("SHADE Protocol - Tier Threshold Tests", () => {
       await fundAccount(testUser.publicKey, 0.05 * LAMPORTS_PER_SOL);
 
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), testUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), testUser.publicKey.toBuffer()],
         program.programId
       );
 
@@ -150,7 +154,7 @@ describe("SHADE Protocol - Tier Threshold Tests", () => {
       await fundAccount(testUser.publicKey, 0.05 * LAMPORTS_PER_SOL);
 
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), testUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), testUser.publicKey.toBuffer()],
         program.programId
       );
 
@@ -185,7 +189,7 @@ describe("SHADE Protocol - Tier Threshold Tests", () => {
       await fundAccount(testUser.publicKey, 0.05 * LAMPORTS_PER_SOL);
 
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), testUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), testUser.publicKey.toBuffer()],
         program.programId
       );
 
@@ -220,7 +224,7 @@ describe("SHADE Protocol - Tier Threshold Tests", () => {
       await fundAccount(testUser.publicKey, 0.05 * LAMPORTS_PER_SOL);
 
       const [stakerPda] = PublicKey.findProgramAddressSync(
-        [Buffer.from("staker"), testUser.publicKey.toBuffer()],
+        [Buffer.from("staker"), protocolConfigPda.toBuffer(), testUser.publicKey.toBuffer()],
         program.programId
       );
 